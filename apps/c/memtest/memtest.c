@@ -1,6 +1,44 @@
+#include <assert.h>
+#include <fcntl.h>
 #include <stdint.h>
 #include <stdio.h>
 #include <stdlib.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <unistd.h>
+
+// Read a file into a private mapping of itself
+static int test_mmap_file(void)
+{
+    const char *path = "/memtest.tmp";
+    size_t page = 4096;
+    char data[4096];
+    int fd = open(path, O_RDWR | O_CREAT | O_TRUNC, 0644);
+    if (fd < 0) {
+        puts("open failed!");
+        return -1;
+    }
+    memset(data, 'a', page);
+    write(fd, data, page);
+    memset(data, 'b', page);
+    write(fd, data, page);
+
+    char *buf = mmap(NULL, 2 * page, PROT_READ | PROT_WRITE, MAP_PRIVATE, fd, 0);
+    if (buf == MAP_FAILED) {
+        puts("mmap file failed!");
+        return -1;
+    }
+    assert(buf[0] == 'a' && buf[page] == 'b');
+    lseek(fd, 0, SEEK_SET);
+    assert(read(fd, buf + page, page) == (ssize_t)page);
+    assert(buf[page] == 'a' && buf[2 * page - 1] == 'a');
+    // the file is not changed
+    assert(read(fd, data, page) == (ssize_t)page && data[0] == 'b');
+    munmap(buf, 2 * page);
+    close(fd);
+    puts("mmap file OK!");
+    return 0;
+}
 
 int main()
 {
@@ -22,6 +60,20 @@ int main()
         free(p[i]);
     }
     free(p);
+
+    size_t len = 16 << 20;
+    char *buf = mmap(NULL, len, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    if (buf == MAP_FAILED) {
+        puts("mmap failed!");
+        return -1;
+    }
+    printf("mmap %ldByte: addr=%p\n", len, buf);
+    for (i = 0; i < len; i += 4096) buf[i] = i >> 12;
+    for (i = 0; i < len; i += 4096) assert(buf[i] == (char)(i >> 12));
+    munmap(buf, len);
+
+    if (test_mmap_file() < 0) return -1;
+
     puts("Memory tests run OK!");
     return 0;
 }
@@ -0,0 +1 @@
+app-objs := socket.o
@@ -0,0 +1,90 @@
+#include <arpa/inet.h>
+#include <assert.h>
+#include <netinet/in.h>
+#include <poll.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/select.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+#define PORT 5555
+
+// Poll a single fd, return the returned events or -1 if timed out
+static int poll_one(int fd, short events, int timeout)
+{
+    struct pollfd pfd = {.fd = fd, .events = events};
+    int n = poll(&pfd, 1, timeout);
+    assert(n >= 0);
+    return n == 0 ? -1 : pfd.revents;
+}
+
+int main()
+{
+    puts("Running socket tests...");
+    char buf[16];
+    struct sockaddr_in addr;
+    socklen_t addrlen = sizeof(addr);
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_port = htons(PORT);
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+
+    int listener = socket(AF_INET, SOCK_STREAM, 0);
+    assert(listener >= 3);
+    assert(bind(listener, (struct sockaddr *)&addr, sizeof(addr)) == 0);
+    assert(listen(listener, 1) == 0);
+    // no connection to accept yet
+    assert(poll_one(listener, POLLIN, 0) == -1);
+
+    int client = socket(AF_INET, SOCK_STREAM, 0);
+    assert(client > listener);
+    assert(connect(client, (struct sockaddr *)&addr, sizeof(addr)) == 0);
+    assert(poll_one(listener, POLLIN, 1000) == POLLIN);
+
+    memset(&addr, 0, sizeof(addr));
+    int server = accept(listener, (struct sockaddr *)&addr, &addrlen);
+    assert(server > client);
+    assert(addrlen == sizeof(addr) && addr.sin_family == AF_INET);
+    assert(addr.sin_addr.s_addr == htonl(INADDR_LOOPBACK));
+    puts("accept OK!");
+
+    // nothing to read on the server, the client can write
+    fd_set readfds, writefds;
+    FD_ZERO(&readfds);
+    FD_ZERO(&writefds);
+    FD_SET(server, &readfds);
+    FD_SET(client, &writefds);
+    struct timeval tv = {.tv_sec = 0, .tv_usec = 0};
+    assert(select(server + 1, &readfds, &writefds, NULL, &tv) == 1);
+    assert(!FD_ISSET(server, &readfds) && FD_ISSET(client, &writefds));
+
+    // wait for the data on the server
+    assert(send(client, "hello", 5, 0) == 5);
+    FD_ZERO(&readfds);
+    FD_SET(server, &readfds);
+    tv.tv_sec = 1;
+    assert(select(server + 1, &readfds, NULL, NULL, &tv) == 1);
+    assert(FD_ISSET(server, &readfds));
+    assert(recv(server, buf, sizeof(buf), 0) == 5 && strncmp(buf, "hello", 5) == 0);
+    puts("select OK!");
+
+    // read() and write() also work on the sockets
+    assert(write(server, "world", 5) == 5);
+    assert(poll_one(client, POLLIN, 1000) == POLLIN);
+    assert(poll_one(client, POLLIN | POLLOUT, 0) == (POLLIN | POLLOUT));
+    assert(read(client, buf, sizeof(buf)) == 5 && strncmp(buf, "world", 5) == 0);
+    assert(poll_one(client, POLLIN, 0) == -1);
+
+    // the end of stream is readable
+    assert(shutdown(client, SHUT_RDWR) == 0);
+    assert(poll_one(server, POLLIN, 1000) & POLLIN);
+    assert(recv(server, buf, sizeof(buf), 0) == 0);
+    puts("poll OK!");
+
+    close(server);
+    close(client);
+    close(listener);
+    puts("Socket tests run OK!");
+    return 0;
+}
@@ -2,9 +2,6 @@
 #![no_main]
 
 extern crate libax;
-mod display;
-
-use display::*;
 
 use embedded_graphics::{
     mono_font::{ascii::FONT_10X20, MonoTextStyle},
@@ -13,13 +10,14 @@ use embedded_graphics::{
     primitives::{Circle, PrimitiveStyle, Rectangle, Triangle},
     text::{Alignment, Text},
 };
+use libax::display::{framebuffer, framebuffer_flush_rect, Framebuffer};
 
 const INIT_X: i32 = 80;
 const INIT_Y: i32 = 400;
 const RECT_SIZE: u32 = 150;
 
 pub struct DrawingBoard {
-    disp: Display,
+    disp: Framebuffer<'static>,
     latest_pos: Point,
 }
 
@@ -32,11 +30,17 @@ impl Default for DrawingBoard {
 impl DrawingBoard {
     pub fn new() -> Self {
         Self {
-            disp: Display::new(),
+            disp: framebuffer().expect("no display device"),
             latest_pos: Point::new(INIT_X, INIT_Y),
         }
     }
 
+    fn flush(&mut self) {
+        if let Some(damage) = self.disp.take_damage() {
+            framebuffer_flush_rect(damage).expect("failed to flush the framebuffer");
+        }
+    }
+
     fn paint(&mut self) {
         Rectangle::with_center(self.latest_pos, Size::new(RECT_SIZE, RECT_SIZE))
             .into_styled(PrimitiveStyle::with_stroke(Rgb888::RED, 10))
@@ -72,7 +76,7 @@ fn test_gpu() -> i32 {
     for _ in 0..5 {
         board.latest_pos.x += RECT_SIZE as i32 + 20;
         board.paint();
-        framebuffer_flush();
+        board.flush();
     }
     0
 }
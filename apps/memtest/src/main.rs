@@ -6,7 +6,9 @@ extern crate libax;
 extern crate alloc;
 
 use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
+use libax::mem::{self, Backend, GlobalPage, MappingFlags};
 use libax::rand;
 
 fn test_vec() {
@@ -38,10 +40,196 @@ fn test_btree_map() {
     println!("test_btree_map() OK!");
 }
 
+fn test_mmap() {
+    const LEN: usize = 16 * 1024 * 1024;
+    const PAGE_SIZE: usize = 0x1000;
+    let flags = MappingFlags::READ | MappingFlags::WRITE;
+    let ptr = mem::mmap(core::ptr::null_mut(), LEN, flags, false, Backend::Alloc).unwrap();
+    let buf = unsafe { core::slice::from_raw_parts_mut(ptr, LEN) };
+    assert!(buf.iter().all(|&b| b == 0));
+    for (i, b) in buf.iter_mut().enumerate() {
+        *b = (i / PAGE_SIZE) as u8;
+    }
+    mem::mprotect(ptr, LEN, MappingFlags::READ).unwrap();
+    for (i, b) in buf.iter().enumerate() {
+        assert_eq!(*b, (i / PAGE_SIZE) as u8);
+    }
+    // the pages around the hole are not affected
+    mem::munmap(ptr.wrapping_add(PAGE_SIZE), PAGE_SIZE).unwrap();
+    assert_eq!(buf[0], 0);
+    assert_eq!(buf[PAGE_SIZE * 2], 2);
+    mem::munmap(ptr, LEN).unwrap();
+    println!("test_mmap() OK!");
+}
+
+fn test_snapshot() {
+    const LEN: usize = 4 * 1024 * 1024;
+    let flags = MappingFlags::READ | MappingFlags::WRITE;
+    let ptr = mem::mmap(core::ptr::null_mut(), LEN, flags, false, Backend::Alloc).unwrap();
+    let buf = unsafe { core::slice::from_raw_parts_mut(ptr, LEN) };
+    // leave the second half not allocated yet
+    buf[..LEN / 2].fill(0xaa);
+    let snap_ptr = mem::snapshot(ptr, LEN).unwrap();
+    let snap = unsafe { core::slice::from_raw_parts_mut(snap_ptr, LEN) };
+    buf.fill(0x55);
+    assert!(snap[..LEN / 2].iter().all(|&b| b == 0xaa));
+    assert!(snap[LEN / 2..].iter().all(|&b| b == 0));
+    snap.fill(0x11);
+    assert!(buf.iter().all(|&b| b == 0x55));
+    mem::munmap(ptr, LEN).unwrap();
+    assert!(snap.iter().all(|&b| b == 0x11));
+    mem::munmap(snap_ptr, LEN).unwrap();
+    println!("test_snapshot() OK!");
+}
+
+fn test_huge_mmap() {
+    const LEN: usize = 4 * 1024 * 1024;
+    const HUGE_PAGE_SIZE: usize = 2 * 1024 * 1024;
+    let pages = GlobalPage::alloc_huge(LEN, HUGE_PAGE_SIZE).unwrap();
+    assert_eq!(pages.start_vaddr().as_usize() % HUGE_PAGE_SIZE, 0);
+    let backend = Backend::Contiguous {
+        pages: Arc::new(pages),
+        offset: 0,
+    };
+    let flags = MappingFlags::READ | MappingFlags::WRITE;
+    let ptr = mem::mmap(core::ptr::null_mut(), LEN, flags, false, backend).unwrap();
+    assert_eq!(ptr as usize % HUGE_PAGE_SIZE, 0);
+    let buf = unsafe { core::slice::from_raw_parts_mut(ptr as *mut u32, LEN / 4) };
+    for (i, x) in buf.iter_mut().enumerate() {
+        *x = i as u32;
+    }
+    // split the huge pages, the rest are mapped again on demand
+    mem::munmap(ptr.wrapping_add(HUGE_PAGE_SIZE / 2), HUGE_PAGE_SIZE).unwrap();
+    assert_eq!(buf[0], 0);
+    assert_eq!(buf[LEN / 4 - 1], (LEN / 4 - 1) as u32);
+    mem::munmap(ptr, LEN).unwrap();
+    println!("test_huge_mmap() OK!");
+}
+
+fn test_stats() {
+    const N: usize = 1000;
+    let before = mem::stats();
+    let small: Vec<Vec<u8>> = (0..N).map(|_| Vec::with_capacity(24)).collect();
+    let large = Vec::<u8>::with_capacity(0x10000);
+    let stats = mem::stats();
+    println!(
+        "allocator: {}, heap: {:#x}, used: {:#x}, peak: {:#x}",
+        stats.backend, stats.heap_bytes, stats.used_bytes, stats.peak_used_bytes
+    );
+    // the 24-byte vectors are in the 32-byte class
+    assert_eq!(stats.size_classes[2].max_size, 32);
+    assert!(stats.size_classes[2].live_count >= before.size_classes[2].live_count + N);
+    assert!(stats.size_classes[10].live_bytes >= 0x10000);
+    assert!(stats.requested_bytes <= stats.used_bytes);
+    assert!(stats.peak_used_bytes >= stats.used_bytes);
+    assert_eq!(
+        stats.used_bytes + stats.external_fragmentation(),
+        stats.heap_bytes
+    );
+    drop(small);
+    drop(large);
+    let after = mem::stats();
+    assert_eq!(
+        after.size_classes[2].live_count,
+        before.size_classes[2].live_count
+    );
+    assert!(after.size_classes[2].total_count >= stats.size_classes[2].total_count);
+    assert!(after.peak_used_bytes >= stats.peak_used_bytes);
+    println!("test_stats() OK!");
+}
+
+/// The freed heap memory is returned to the page allocator, only the TLSF
+/// backend supports it. The other ones keep it in the heap for reuse.
+#[cfg(not(feature = "alloc-debug"))]
+fn test_heap_reclaim() {
+    let before = mem::stats();
+    // larger than the free heap, so the heap must grow
+    let size = before.heap_bytes - before.used_bytes + 0x10_0000;
+    let large = Vec::<u8>::with_capacity(size);
+    let stats = mem::stats();
+    assert!(stats.heap_bytes > before.heap_bytes);
+    drop(large);
+    let after = mem::stats();
+    if cfg!(feature = "alloc-tlsf") {
+        assert!(after.heap_bytes < stats.heap_bytes);
+        assert!(after.used_pages < stats.used_pages);
+    } else {
+        assert_eq!(after.heap_bytes, stats.heap_bytes);
+        assert_eq!(after.used_pages, stats.used_pages);
+        // the freed memory is reused without growing the heap again
+        let large = Vec::<u8>::with_capacity(size);
+        assert_eq!(mem::stats().heap_bytes, stats.heap_bytes);
+        drop(large);
+    }
+    println!("test_heap_reclaim() OK!");
+}
+
+#[cfg(feature = "alloc-debug")]
+fn test_alloc_debug() {
+    use alloc::alloc::Layout;
+
+    const LEN: usize = 100;
+    let layout = Layout::from_size_align(LEN, 8).unwrap();
+    let ptr = unsafe { alloc::alloc::alloc(layout) };
+    for i in 0..LEN {
+        assert_eq!(unsafe { ptr.add(i).read_volatile() }, mem::ALLOC_POISON);
+    }
+    unsafe { alloc::alloc::dealloc(ptr, layout) };
+    // the freed memory is kept poisoned for a while
+    for i in 0..LEN {
+        assert_eq!(unsafe { ptr.add(i).read_volatile() }, mem::FREE_POISON);
+    }
+
+    // leak one, which is reported at exit
+    let (ptr, start, end) = leak_one();
+    let caller = mem::allocation_caller(ptr).unwrap();
+    assert!((start..=end).contains(&caller));
+    println!("leaked at {:#x}, in leak_one()", caller);
+    assert!(mem::dump_live_allocations() > 0);
+    println!("test_alloc_debug() OK!");
+}
+
+/// Leaks an allocation, and returns it with the range of the code which
+/// allocates it.
+#[cfg(feature = "alloc-debug")]
+#[inline(never)]
+fn leak_one() -> (*const u8, usize, usize) {
+    use alloc::alloc::Layout;
+
+    let layout = Layout::from_size_align(4000, 8).unwrap();
+    let ptr = unsafe { alloc::alloc::alloc(layout) };
+    let end = current_pc();
+    unsafe { ptr.add(3999).write(1) };
+    (ptr, leak_one as fn() -> _ as usize, end)
+}
+
+#[cfg(feature = "alloc-debug")]
+#[inline(always)]
+fn current_pc() -> usize {
+    let pc;
+    #[cfg(target_arch = "riscv64")]
+    unsafe {
+        core::arch::asm!("auipc {}, 0", out(reg) pc)
+    };
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        core::arch::asm!("adr {}, .", out(reg) pc)
+    };
+    pc
+}
+
 #[no_mangle]
 fn main() {
     println!("Running memory tests...");
     test_vec();
     test_btree_map();
+    test_mmap();
+    test_snapshot();
+    test_huge_mmap();
+    test_stats();
+    #[cfg(not(feature = "alloc-debug"))]
+    test_heap_reclaim();
+    #[cfg(feature = "alloc-debug")]
+    test_alloc_debug();
     println!("Memory tests run OK!");
 }
@@ -0,0 +1,132 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libax;
+
+use core::str::FromStr;
+
+use libax::io;
+use libax::net::{IcmpSocket, IpAddr};
+use libax::task;
+use libax::time::{Duration, Instant};
+
+const DEST_IP: &str = "10.0.2.2"; // QEMU user networking gateway
+const COUNT: u16 = 4;
+const INTERVAL: Duration = Duration::from_secs(1);
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+const IDENT: u16 = 0xA2CE;
+const DATA_LEN: usize = 56;
+
+const ICMP_HEADER_LEN: usize = 8;
+const ICMP_ECHO_REPLY: u8 = 0;
+const ICMP_ECHO_REQUEST: u8 = 8;
+
+/// Builds an ICMP echo request, the checksum is filled by the stack.
+fn echo_request(seq: u16) -> [u8; ICMP_HEADER_LEN + DATA_LEN] {
+    let mut packet = [0; ICMP_HEADER_LEN + DATA_LEN];
+    packet[0] = ICMP_ECHO_REQUEST;
+    packet[4..6].copy_from_slice(&IDENT.to_be_bytes());
+    packet[6..8].copy_from_slice(&seq.to_be_bytes());
+    for (i, b) in packet[ICMP_HEADER_LEN..].iter_mut().enumerate() {
+        *b = i as u8;
+    }
+    packet
+}
+
+/// Returns the sequence number if `packet` is an echo reply to us.
+fn echo_reply_seq(packet: &[u8]) -> Option<u16> {
+    if packet.len() < ICMP_HEADER_LEN
+        || packet[0] != ICMP_ECHO_REPLY
+        || packet[4..6] != IDENT.to_be_bytes()
+    {
+        return None;
+    }
+    Some(u16::from_be_bytes([packet[6], packet[7]]))
+}
+
+/// Waits for the echo reply of `seq`, returns the number of bytes received.
+fn wait_reply(socket: &IcmpSocket, seq: u16, start: &Instant) -> io::Result<Option<usize>> {
+    let mut buf = [0; 1024];
+    loop {
+        let elapsed = start.elapsed();
+        if elapsed >= TIMEOUT {
+            return Ok(None);
+        }
+        socket.set_read_timeout(Some(TIMEOUT - elapsed))?;
+        match socket.recv_from(&mut buf) {
+            Ok((n, _)) if echo_reply_seq(&buf[..n]) == Some(seq) => return Ok(Some(n)),
+            Ok(_) => continue, // not for this request
+            Err(io::Error::TimedOut) => return Ok(None),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn fmt_ms(dur: Duration) -> impl core::fmt::Display {
+    struct Ms(u128);
+    impl core::fmt::Display for Ms {
+        fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+            write!(f, "{}.{:03}", self.0 / 1000, self.0 % 1000)
+        }
+    }
+    Ms(dur.as_micros())
+}
+
+fn ping(addr: IpAddr) -> io::Result {
+    let socket = IcmpSocket::bind(IDENT)?;
+    println!("PING {}: {} data bytes", addr, DATA_LEN);
+
+    let mut received = 0;
+    let (mut min, mut max, mut total) = (Duration::MAX, Duration::ZERO, Duration::ZERO);
+    for seq in 0..COUNT {
+        let start = Instant::now();
+        socket.send_to(&echo_request(seq), addr)?;
+        match wait_reply(&socket, seq, &start)? {
+            Some(n) => {
+                let rtt = start.elapsed();
+                println!(
+                    "{} bytes from {}: icmp_seq={} time={} ms",
+                    n,
+                    addr,
+                    seq,
+                    fmt_ms(rtt)
+                );
+                received += 1;
+                min = min.min(rtt);
+                max = max.max(rtt);
+                total += rtt;
+            }
+            None => println!("Request timeout for icmp_seq {}", seq),
+        }
+        let elapsed = start.elapsed();
+        if seq + 1 < COUNT && elapsed < INTERVAL {
+            task::sleep(INTERVAL - elapsed);
+        }
+    }
+
+    println!("--- {} ping statistics ---", addr);
+    println!(
+        "{} packets transmitted, {} received, {}% packet loss",
+        COUNT,
+        received,
+        (COUNT - received) as u32 * 100 / COUNT as u32
+    );
+    if received > 0 {
+        println!(
+            "rtt min/avg/max = {}/{}/{} ms",
+            fmt_ms(min),
+            fmt_ms(total / received as u32),
+            fmt_ms(max)
+        );
+    }
+    Ok(())
+}
+
+#[no_mangle]
+fn main() {
+    println!("Hello, ping!");
+    let addr = IpAddr::from_str(DEST_IP).unwrap();
+    ping(addr).expect("ping failed");
+}
@@ -7,14 +7,25 @@ use bitmap_allocator::BitAlloc;
 use crate::{AllocError, AllocResult, BaseAllocator, PageAllocator};
 
 // Support max 1M * 4096 = 4GB memory.
+#[cfg(not(feature = "page-alloc-64g"))]
 type BitAllocUsed = bitmap_allocator::BitAlloc1M;
 
+// Support max 16M * 4096 = 64GB memory.
+#[cfg(feature = "page-alloc-64g")]
+type BitAllocUsed = bitmap_allocator::BitAlloc16M;
+
+/// The maximum alignment of the allocated pages, which is the size of the
+/// largest huge page (1GB).
+const MAX_ALIGN_1GB: usize = 0x4000_0000;
+
 /// A page-granularity memory allocator based on the [bitmap_allocator].
 ///
 /// It internally uses a bitmap, each bit indicates whether a page has been
 /// allocated.
 ///
-/// The `PAGE_SIZE` must be a power of two.
+/// The `PAGE_SIZE` must be a power of two. The alignment of the allocated
+/// pages is relative to address `0` rather than the start of the memory, so
+/// they can be used as huge pages, up to 1GB.
 ///
 /// [bitmap_allocator]: https://github.com/rcore-os/bitmap-allocator
 pub struct BitmapPageAllocator<const PAGE_SIZE: usize> {
@@ -41,9 +52,12 @@ impl<const PAGE_SIZE: usize> BaseAllocator for BitmapPageAllocator<PAGE_SIZE> {
         assert!(PAGE_SIZE.is_power_of_two());
         let end = super::align_down(start + size, PAGE_SIZE);
         let start = super::align_up(start, PAGE_SIZE);
-        self.base = start;
-        self.total_pages = (end - start) / PAGE_SIZE;
-        self.inner.insert(0..self.total_pages);
+        // the page indices in the bitmap are aligned as the addresses
+        self.base = super::align_down(start, MAX_ALIGN_1GB);
+        let start_idx = (start - self.base) / PAGE_SIZE;
+        let end_idx = ((end - self.base) / PAGE_SIZE).min(BitAllocUsed::CAP);
+        self.total_pages = end_idx - start_idx;
+        self.inner.insert(start_idx..end_idx);
     }
 
     fn add_memory(&mut self, _start: usize, _size: usize) -> AllocResult {
@@ -55,14 +69,20 @@ impl<const PAGE_SIZE: usize> PageAllocator for BitmapPageAllocator<PAGE_SIZE> {
     const PAGE_SIZE: usize = PAGE_SIZE;
 
     fn alloc_pages(&mut self, num_pages: usize, align_pow2: usize) -> AllocResult<usize> {
-        if align_pow2 % PAGE_SIZE != 0 || !align_pow2.is_power_of_two() {
+        if align_pow2 % PAGE_SIZE != 0
+            || !align_pow2.is_power_of_two()
+            || align_pow2 > MAX_ALIGN_1GB
+        {
             return Err(AllocError::InvalidParam);
         }
+        let align_log2 = (align_pow2 / PAGE_SIZE).trailing_zeros() as usize;
         match num_pages.cmp(&1) {
-            core::cmp::Ordering::Equal => self.inner.alloc().map(|idx| idx * PAGE_SIZE + self.base),
-            core::cmp::Ordering::Greater => self
+            core::cmp::Ordering::Equal if align_log2 == 0 => {
+                self.inner.alloc().map(|idx| idx * PAGE_SIZE + self.base)
+            }
+            core::cmp::Ordering::Equal | core::cmp::Ordering::Greater => self
                 .inner
-                .alloc_contiguous(num_pages, align_pow2 / PAGE_SIZE)
+                .alloc_contiguous(num_pages, align_log2)
                 .map(|idx| idx * PAGE_SIZE + self.base),
             _ => return Err(AllocError::InvalidParam),
         }
@@ -73,7 +93,10 @@ impl<const PAGE_SIZE: usize> PageAllocator for BitmapPageAllocator<PAGE_SIZE> {
     fn dealloc_pages(&mut self, pos: usize, num_pages: usize) {
         // TODO: not decrease `used_pages` if deallocation failed
         self.used_pages -= num_pages;
-        self.inner.dealloc((pos - self.base) / PAGE_SIZE)
+        let start_idx = (pos - self.base) / PAGE_SIZE;
+        for idx in start_idx..start_idx + num_pages {
+            self.inner.dealloc(idx)
+        }
     }
 
     fn total_pages(&self) -> usize {
@@ -0,0 +1,189 @@
+//! Bitmap allocation of IDs.
+
+use crate::{AllocError, AllocResult, BaseAllocator, IdAllocator};
+
+const BITS_PER_WORD: usize = u64::BITS as usize;
+
+/// An ID allocator based on a flat bitmap, each bit indicates whether an ID
+/// has been allocated.
+///
+/// It manages up to `WORDS * 64` IDs, starting with the `start` given to
+/// [`BaseAllocator::init`]. [`IdAllocator::alloc_id`] always allocates the
+/// lowest available IDs, like the file descriptors in POSIX, so the IDs are
+/// reused as soon as they are deallocated. [`alloc_next_id`] delays the reuse
+/// instead. The alignment of the allocated IDs is relative to ID `0`.
+///
+/// [`alloc_next_id`]: BitmapIdAllocator::alloc_next_id
+pub struct BitmapIdAllocator<const WORDS: usize> {
+    start: usize,
+    size: usize,
+    used: usize,
+    /// Where [`BitmapIdAllocator::alloc_next_id`] starts searching.
+    cursor: usize,
+    bitmap: [u64; WORDS],
+}
+
+impl<const WORDS: usize> BitmapIdAllocator<WORDS> {
+    /// The maximum number of IDs.
+    pub const CAP: usize = WORDS * BITS_PER_WORD;
+
+    /// Creates a new empty `BitmapIdAllocator`.
+    pub const fn new() -> Self {
+        Self {
+            start: 0,
+            size: 0,
+            used: 0,
+            cursor: 0,
+            bitmap: [0; WORDS],
+        }
+    }
+
+    /// Allocates one ID, searching from the one after the last ID allocated
+    /// by this method and wrapping around at the end.
+    ///
+    /// A deallocated ID is not reused until the search passes it again, so a
+    /// stale ID (e.g. of an exited task) is unlikely to refer to a new owner
+    /// soon.
+    pub fn alloc_next_id(&mut self) -> AllocResult<usize> {
+        let idx = self
+            .find_free_from(self.cursor)
+            .or_else(|| self.find_free_from(0))
+            .ok_or(AllocError::NoMemory)?;
+        self.set(idx);
+        self.used += 1;
+        self.cursor = if idx + 1 < self.size { idx + 1 } else { 0 };
+        Ok(self.start + idx)
+    }
+
+    fn test(&self, idx: usize) -> bool {
+        self.bitmap[idx / BITS_PER_WORD] & (1 << (idx % BITS_PER_WORD)) != 0
+    }
+
+    fn set(&mut self, idx: usize) {
+        self.bitmap[idx / BITS_PER_WORD] |= 1 << (idx % BITS_PER_WORD);
+    }
+
+    fn clear(&mut self, idx: usize) {
+        self.bitmap[idx / BITS_PER_WORD] &= !(1 << (idx % BITS_PER_WORD));
+    }
+
+    /// Finds the lowest available ID at or after index `from` by skipping the
+    /// full words.
+    fn find_free_from(&self, from: usize) -> Option<usize> {
+        let first = from / BITS_PER_WORD;
+        // treat the IDs before `from` in the first word as allocated
+        let below_from = (1 << (from % BITS_PER_WORD)) - 1;
+        self.bitmap
+            .get(first..)?
+            .iter()
+            .enumerate()
+            .map(|(i, &word)| (first + i, if i == 0 { word | below_from } else { word }))
+            .find(|&(_, word)| word != u64::MAX)
+            .map(|(i, word)| i * BITS_PER_WORD + (!word).trailing_zeros() as usize)
+            .filter(|&idx| idx < self.size)
+    }
+}
+
+impl<const WORDS: usize> Default for BitmapIdAllocator<WORDS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const WORDS: usize> BaseAllocator for BitmapIdAllocator<WORDS> {
+    fn init(&mut self, start: usize, size: usize) {
+        assert!(size <= Self::CAP);
+        self.start = start;
+        self.size = size;
+        self.used = 0;
+        self.cursor = 0;
+        self.bitmap = [0; WORDS];
+    }
+
+    fn add_memory(&mut self, start: usize, size: usize) -> AllocResult {
+        // only the IDs right after the existing ones can be added
+        let end = self.start + self.size;
+        if start < end && self.start < start + size {
+            return Err(AllocError::MemoryOverlap);
+        }
+        if start != end {
+            return Err(AllocError::InvalidParam);
+        }
+        if self.size + size > Self::CAP {
+            return Err(AllocError::NoMemory);
+        }
+        self.size += size;
+        Ok(())
+    }
+}
+
+impl<const WORDS: usize> IdAllocator for BitmapIdAllocator<WORDS> {
+    fn alloc_id(&mut self, count: usize, align_pow2: usize) -> AllocResult<usize> {
+        if count == 0 || !align_pow2.is_power_of_two() {
+            return Err(AllocError::InvalidParam);
+        }
+        if count == 1 && align_pow2 == 1 {
+            let idx = self.find_free_from(0).ok_or(AllocError::NoMemory)?;
+            self.set(idx);
+            self.used += 1;
+            return Ok(self.start + idx);
+        }
+
+        let end = self.start + self.size;
+        let mut id = super::align_up(self.start, align_pow2);
+        while id + count <= end {
+            let idx = id - self.start;
+            // restart after the last allocated ID in the range
+            match (idx..idx + count).rev().find(|&i| self.test(i)) {
+                Some(i) => id = super::align_up(self.start + i + 1, align_pow2),
+                None => {
+                    (idx..idx + count).for_each(|i| self.set(i));
+                    self.used += count;
+                    return Ok(id);
+                }
+            }
+        }
+        Err(AllocError::NoMemory)
+    }
+
+    fn dealloc_id(&mut self, start_id: usize, count: usize) {
+        for id in start_id..start_id + count {
+            if self.is_allocated(id) {
+                self.clear(id - self.start);
+                self.used -= 1;
+            }
+        }
+    }
+
+    fn is_allocated(&self, id: usize) -> bool {
+        id >= self.start && id - self.start < self.size && self.test(id - self.start)
+    }
+
+    /// Mark the given `id` has been allocated and cannot be reallocated.
+    ///
+    /// Returns [`AllocError::InvalidParam`] if the `id` is out of range, or
+    /// [`AllocError::NoMemory`] if it has been allocated.
+    fn alloc_fixed_id(&mut self, id: usize) -> AllocResult {
+        if id < self.start || id - self.start >= self.size {
+            return Err(AllocError::InvalidParam);
+        }
+        if self.test(id - self.start) {
+            return Err(AllocError::NoMemory);
+        }
+        self.set(id - self.start);
+        self.used += 1;
+        Ok(())
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn used(&self) -> usize {
+        self.used
+    }
+
+    fn available(&self) -> usize {
+        self.size - self.used
+    }
+}
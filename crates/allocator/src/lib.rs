@@ -3,21 +3,42 @@
 //! There are three types of allocators:
 //!
 //! - [`ByteAllocator`]: Byte-granularity memory allocator. (e.g.,
-//!   [`BuddyByteAllocator`], [`SlabByteAllocator`])
+//!   [`BuddyByteAllocator`], [`SlabByteAllocator`], [`TlsfByteAllocator`])
 //! - [`PageAllocator`]: Page-granularity memory allocator. (e.g.,
-//!   [`BitmapPageAllocator`])
-//! - [`IdAllocator`]: Used to allocate unique IDs.
+//!   [`BitmapPageAllocator`], [`RegionPageAllocator`])
+//! - [`IdAllocator`]: Used to allocate unique IDs. (e.g.,
+//!   [`BitmapIdAllocator`])
+//!
+//! The byte allocators can be selected by the cargo features `slab`, `buddy`
+//! and `tlsf`, all of them are enabled by default.
 
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 #![feature(result_option_inspect)]
 
 mod bitmap;
+mod bitmap_id;
+mod region;
+
+#[cfg(feature = "buddy")]
 mod buddy;
+#[cfg(feature = "slab")]
 mod slab;
+#[cfg(feature = "tlsf")]
+mod tlsf;
+
+#[cfg(test)]
+mod tests;
 
 pub use bitmap::BitmapPageAllocator;
+pub use bitmap_id::BitmapIdAllocator;
+pub use region::RegionPageAllocator;
+
+#[cfg(feature = "buddy")]
 pub use buddy::BuddyByteAllocator;
+#[cfg(feature = "slab")]
 pub use slab::SlabByteAllocator;
+#[cfg(feature = "tlsf")]
+pub use tlsf::TlsfByteAllocator;
 
 /// The error type used for allocation.
 #[derive(Debug)]
@@ -60,6 +81,13 @@ pub trait ByteAllocator: BaseAllocator {
 
     /// Returns available memory size in bytes.
     fn available_bytes(&self) -> usize;
+
+    /// Remove a memory region added by `add_memory`, so that it can be
+    /// reused by others. It fails if any part of the region is allocated, or
+    /// the allocator does not support it.
+    fn remove_memory(&mut self, _start: usize, _size: usize) -> AllocResult {
+        Err(AllocError::InvalidParam)
+    }
 }
 
 /// Page-granularity allocator.
@@ -0,0 +1,278 @@
+//! Page allocation in multiple disjoint memory regions.
+
+use core::ptr::null_mut;
+
+use crate::{AllocError, AllocResult, BaseAllocator, PageAllocator};
+
+const BITS_PER_WORD: usize = u64::BITS as usize;
+
+#[derive(Clone, Copy)]
+struct Region {
+    /// The address of the first usable page.
+    start: usize,
+    num_pages: usize,
+    used_pages: usize,
+    /// Each bit indicates whether a page has been allocated. The bits after
+    /// `num_pages` in the last word are always set.
+    bitmap: *mut u64,
+    /// The lowest word which may have free pages.
+    hint: usize,
+}
+
+impl Region {
+    const EMPTY: Self = Self {
+        start: 0,
+        num_pages: 0,
+        used_pages: 0,
+        bitmap: null_mut(),
+        hint: 0,
+    };
+
+    fn end(&self, page_size: usize) -> usize {
+        self.start + self.num_pages * page_size
+    }
+
+    fn num_words(&self) -> usize {
+        (self.num_pages + BITS_PER_WORD - 1) / BITS_PER_WORD
+    }
+
+    fn words(&self) -> &[u64] {
+        unsafe { core::slice::from_raw_parts(self.bitmap, self.num_words()) }
+    }
+
+    fn words_mut(&mut self) -> &mut [u64] {
+        unsafe { core::slice::from_raw_parts_mut(self.bitmap, self.num_words()) }
+    }
+
+    fn test(&self, idx: usize) -> bool {
+        self.words()[idx / BITS_PER_WORD] & (1 << (idx % BITS_PER_WORD)) != 0
+    }
+
+    fn set(&mut self, range: core::ops::Range<usize>, allocated: bool) {
+        let words = self.words_mut();
+        for idx in range {
+            let bit = 1 << (idx % BITS_PER_WORD);
+            if allocated {
+                words[idx / BITS_PER_WORD] |= bit;
+            } else {
+                words[idx / BITS_PER_WORD] &= !bit;
+            }
+        }
+    }
+
+    /// Allocates one page with the fast path, which skips the full words.
+    fn alloc_one(&mut self) -> Option<usize> {
+        let (i, word) = self
+            .words()
+            .iter()
+            .enumerate()
+            .skip(self.hint)
+            .find(|&(_, &word)| word != u64::MAX)
+            .map(|(i, &word)| (i, word))?;
+        let idx = i * BITS_PER_WORD + (!word).trailing_zeros() as usize;
+        self.words_mut()[i] |= 1 << (idx % BITS_PER_WORD);
+        self.hint = i;
+        Some(idx)
+    }
+
+    /// Allocates `num_pages` contiguous pages aligned to `align_pow2`, and
+    /// ending before `limit`.
+    fn alloc(
+        &mut self,
+        page_size: usize,
+        num_pages: usize,
+        align_pow2: usize,
+        limit: usize,
+    ) -> Option<usize> {
+        if num_pages == 1 && align_pow2 == page_size && self.end(page_size) <= limit {
+            return self
+                .alloc_one()
+                .map(|idx| self.start + idx * page_size)
+                .inspect(|_| self.used_pages += 1);
+        }
+        let end = self.end(page_size).min(limit);
+        let mut pos = super::align_up(self.start, align_pow2);
+        while pos + num_pages * page_size <= end {
+            let idx = (pos - self.start) / page_size;
+            // restart after the last allocated page in the range
+            match (idx..idx + num_pages).rev().find(|&i| self.test(i)) {
+                Some(i) => pos = super::align_up(self.start + (i + 1) * page_size, align_pow2),
+                None => {
+                    self.set(idx..idx + num_pages, true);
+                    self.used_pages += num_pages;
+                    return Some(pos);
+                }
+            }
+        }
+        None
+    }
+
+    fn dealloc(&mut self, page_size: usize, pos: usize, num_pages: usize) -> usize {
+        let start_idx = (pos - self.start) / page_size;
+        let end_idx = (start_idx + num_pages).min(self.num_pages);
+        let freed = (start_idx..end_idx).filter(|&i| self.test(i)).count();
+        self.set(start_idx..end_idx, false);
+        self.hint = self.hint.min(start_idx / BITS_PER_WORD);
+        self.used_pages -= freed;
+        freed
+    }
+}
+
+/// A page-granularity memory allocator that manages up to `MAX_REGIONS`
+/// disjoint memory regions, e.g. the DRAM banks of a board.
+///
+/// Each region has its own bitmap, which is stored in the first pages of
+/// the region, so there is no limit on the addresses or the sizes of the
+/// regions. The alignment of the allocated pages is relative to address `0`,
+/// so they can be used as huge pages.
+///
+/// The regions are searched from the lowest address. Use
+/// [`alloc_pages_below`](Self::alloc_pages_below) to allocate from a zone
+/// with limited addresses, e.g. for the devices with 32-bit DMA.
+pub struct RegionPageAllocator<const PAGE_SIZE: usize, const MAX_REGIONS: usize = 16> {
+    /// The regions sorted by the addresses.
+    regions: [Region; MAX_REGIONS],
+    num_regions: usize,
+    total_pages: usize,
+    used_pages: usize,
+}
+
+unsafe impl<const PAGE_SIZE: usize, const MAX_REGIONS: usize> Send
+    for RegionPageAllocator<PAGE_SIZE, MAX_REGIONS>
+{
+}
+
+impl<const PAGE_SIZE: usize, const MAX_REGIONS: usize> RegionPageAllocator<PAGE_SIZE, MAX_REGIONS> {
+    /// Creates a new empty `RegionPageAllocator`.
+    pub const fn new() -> Self {
+        Self {
+            regions: [Region::EMPTY; MAX_REGIONS],
+            num_regions: 0,
+            total_pages: 0,
+            used_pages: 0,
+        }
+    }
+
+    /// Returns the number of the memory regions.
+    pub fn num_regions(&self) -> usize {
+        self.num_regions
+    }
+
+    /// Returns the range, and the number of the allocated pages of the
+    /// `idx`-th region, sorted by the addresses.
+    pub fn region(&self, idx: usize) -> Option<(core::ops::Range<usize>, usize)> {
+        self.regions[..self.num_regions]
+            .get(idx)
+            .map(|r| (r.start..r.end(PAGE_SIZE), r.used_pages))
+    }
+
+    /// Allocates contiguous pages that end before the address `limit`.
+    pub fn alloc_pages_below(
+        &mut self,
+        num_pages: usize,
+        align_pow2: usize,
+        limit: usize,
+    ) -> AllocResult<usize> {
+        if num_pages == 0 || align_pow2 % PAGE_SIZE != 0 || !align_pow2.is_power_of_two() {
+            return Err(AllocError::InvalidParam);
+        }
+        let pos = self.regions[..self.num_regions]
+            .iter_mut()
+            .take_while(|r| r.start < limit)
+            .find_map(|r| r.alloc(PAGE_SIZE, num_pages, align_pow2, limit))
+            .ok_or(AllocError::NoMemory)?;
+        self.used_pages += num_pages;
+        Ok(pos)
+    }
+}
+
+impl<const PAGE_SIZE: usize, const MAX_REGIONS: usize> Default
+    for RegionPageAllocator<PAGE_SIZE, MAX_REGIONS>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const PAGE_SIZE: usize, const MAX_REGIONS: usize> BaseAllocator
+    for RegionPageAllocator<PAGE_SIZE, MAX_REGIONS>
+{
+    fn init(&mut self, start: usize, size: usize) {
+        *self = Self::new();
+        self.add_memory(start, size).unwrap();
+    }
+
+    fn add_memory(&mut self, start: usize, size: usize) -> AllocResult {
+        assert!(PAGE_SIZE.is_power_of_two());
+        let end = super::align_down(start + size, PAGE_SIZE);
+        let start = super::align_up(start, PAGE_SIZE);
+        if end <= start {
+            return Err(AllocError::InvalidParam);
+        }
+        let regions = &self.regions[..self.num_regions];
+        if regions
+            .iter()
+            .any(|r| start < r.end(PAGE_SIZE) && (r.bitmap as usize) < end)
+        {
+            return Err(AllocError::MemoryOverlap);
+        }
+        if self.num_regions == MAX_REGIONS {
+            return Err(AllocError::NoMemory);
+        }
+
+        // the bitmap takes `n * 8 / 64` bytes for `n` pages
+        let pages = (end - start) / PAGE_SIZE;
+        let bitmap_pages = (pages + PAGE_SIZE * 8) / (PAGE_SIZE * 8 + 1);
+        if bitmap_pages >= pages {
+            return Err(AllocError::InvalidParam);
+        }
+        let num_pages = pages - bitmap_pages;
+        let mut region = Region {
+            start: start + bitmap_pages * PAGE_SIZE,
+            num_pages,
+            used_pages: 0,
+            bitmap: start as *mut u64,
+            hint: 0,
+        };
+        region.words_mut().fill(0);
+        let padded = region.num_words() * BITS_PER_WORD;
+        region.set(num_pages..padded, true);
+
+        let idx = regions.partition_point(|r| r.start < region.start);
+        self.regions.copy_within(idx..self.num_regions, idx + 1);
+        self.regions[idx] = region;
+        self.num_regions += 1;
+        self.total_pages += num_pages;
+        Ok(())
+    }
+}
+
+impl<const PAGE_SIZE: usize, const MAX_REGIONS: usize> PageAllocator
+    for RegionPageAllocator<PAGE_SIZE, MAX_REGIONS>
+{
+    const PAGE_SIZE: usize = PAGE_SIZE;
+
+    fn alloc_pages(&mut self, num_pages: usize, align_pow2: usize) -> AllocResult<usize> {
+        self.alloc_pages_below(num_pages, align_pow2, usize::MAX)
+    }
+
+    fn dealloc_pages(&mut self, pos: usize, num_pages: usize) {
+        let regions = &mut self.regions[..self.num_regions];
+        let idx = regions.partition_point(|r| r.start <= pos);
+        if idx > 0 && pos < regions[idx - 1].end(PAGE_SIZE) {
+            self.used_pages -= regions[idx - 1].dealloc(PAGE_SIZE, pos, num_pages);
+        }
+    }
+
+    fn total_pages(&self) -> usize {
+        self.total_pages
+    }
+
+    fn used_pages(&self) -> usize {
+        self.used_pages
+    }
+
+    fn available_pages(&self) -> usize {
+        self.total_pages - self.used_pages
+    }
+}
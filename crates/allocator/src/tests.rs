@@ -0,0 +1,435 @@
+use crate::*;
+
+type IdAlloc = BitmapIdAllocator<4>; // 256 IDs
+
+#[test]
+fn test_id_alloc_reuse() {
+    let mut ids = IdAlloc::new();
+    ids.init(1, 100);
+    assert_eq!((ids.size(), ids.used(), ids.available()), (100, 0, 100));
+
+    for i in 1..=100 {
+        assert_eq!(ids.alloc_id(1, 1).unwrap(), i);
+        assert!(ids.is_allocated(i));
+    }
+    assert!(matches!(ids.alloc_id(1, 1), Err(AllocError::NoMemory)));
+    assert_eq!(ids.available(), 0);
+
+    // the lowest available IDs are reused first
+    ids.dealloc_id(70, 1);
+    ids.dealloc_id(5, 1);
+    assert!(!ids.is_allocated(5));
+    assert_eq!(ids.alloc_id(1, 1).unwrap(), 5);
+    assert_eq!(ids.alloc_id(1, 1).unwrap(), 70);
+    assert_eq!(ids.used(), 100);
+
+    // out of range or not allocated
+    ids.dealloc_id(0, 1);
+    ids.dealloc_id(101, 1);
+    ids.dealloc_id(20, 2);
+    ids.dealloc_id(20, 2);
+    assert_eq!(ids.used(), 98);
+    assert!(!ids.is_allocated(0) && !ids.is_allocated(101));
+}
+
+#[test]
+fn test_id_alloc_next() {
+    let mut ids = IdAlloc::new();
+    ids.init(1, 100);
+    for i in 1..=10 {
+        assert_eq!(ids.alloc_next_id().unwrap(), i);
+    }
+
+    // the deallocated IDs are not reused until the search wraps around
+    ids.dealloc_id(5, 1);
+    assert_eq!(ids.alloc_next_id().unwrap(), 11);
+    ids.alloc_fixed_id(12).unwrap();
+    ids.alloc_fixed_id(65).unwrap();
+    assert_eq!(ids.alloc_next_id().unwrap(), 13);
+    while ids.alloc_next_id().unwrap() != 100 {}
+    assert_eq!(ids.alloc_next_id().unwrap(), 5);
+    assert_eq!(ids.available(), 0);
+    assert!(matches!(ids.alloc_next_id(), Err(AllocError::NoMemory)));
+
+    ids.dealloc_id(30, 1);
+    ids.dealloc_id(7, 1);
+    assert_eq!(ids.alloc_next_id().unwrap(), 7);
+    assert_eq!(ids.alloc_next_id().unwrap(), 30);
+}
+
+#[test]
+fn test_id_alloc_fixed() {
+    let mut ids = IdAlloc::new();
+    ids.init(0, 256);
+    for fd in 0..3 {
+        ids.alloc_fixed_id(fd).unwrap();
+    }
+    assert!(matches!(ids.alloc_fixed_id(1), Err(AllocError::NoMemory)));
+    assert!(matches!(
+        ids.alloc_fixed_id(256),
+        Err(AllocError::InvalidParam)
+    ));
+    assert_eq!(ids.alloc_id(1, 1).unwrap(), 3);
+    ids.alloc_fixed_id(200).unwrap();
+    assert_eq!(ids.used(), 5);
+}
+
+#[test]
+fn test_id_alloc_contiguous() {
+    let mut ids = IdAlloc::new();
+    ids.init(3, 253);
+    assert!(matches!(ids.alloc_id(0, 1), Err(AllocError::InvalidParam)));
+    assert!(matches!(ids.alloc_id(1, 3), Err(AllocError::InvalidParam)));
+
+    // the alignment is relative to ID 0
+    assert_eq!(ids.alloc_id(4, 8).unwrap(), 8);
+    assert_eq!(ids.alloc_id(5, 1).unwrap(), 3);
+    assert_eq!(ids.alloc_id(1, 1).unwrap(), 12);
+    assert_eq!(ids.alloc_id(8, 8).unwrap(), 16);
+    // skips the allocated IDs across the words
+    ids.alloc_fixed_id(70).unwrap();
+    assert_eq!(ids.alloc_id(64, 1).unwrap(), 71);
+    assert_eq!(ids.alloc_id(64, 64).unwrap(), 192);
+    assert!(matches!(ids.alloc_id(64, 64), Err(AllocError::NoMemory)));
+    assert_eq!(ids.alloc_id(30, 1).unwrap(), 24);
+    assert_eq!(ids.used(), 4 + 5 + 1 + 8 + 1 + 64 + 64 + 30);
+
+    ids.dealloc_id(192, 64);
+    assert_eq!(ids.alloc_id(64, 64).unwrap(), 192);
+}
+
+#[test]
+fn test_id_alloc_add() {
+    let mut ids = IdAlloc::new();
+    ids.init(10, 10);
+    assert!(matches!(
+        ids.add_memory(15, 10),
+        Err(AllocError::MemoryOverlap)
+    ));
+    assert!(matches!(
+        ids.add_memory(30, 10),
+        Err(AllocError::InvalidParam)
+    ));
+    assert!(matches!(ids.add_memory(20, 250), Err(AllocError::NoMemory)));
+    for _ in 0..10 {
+        ids.alloc_id(1, 1).unwrap();
+    }
+    ids.add_memory(20, 246).unwrap();
+    assert_eq!(ids.size(), IdAlloc::CAP);
+    assert_eq!(ids.alloc_id(1, 1).unwrap(), 20);
+    assert_eq!(ids.alloc_id(16, 16).unwrap(), 32);
+}
+
+#[cfg(feature = "tlsf")]
+mod tlsf {
+    use std::alloc::{alloc, dealloc, Layout};
+
+    use crate::*;
+
+    const POOL_SIZE: usize = 0x10_0000;
+    const POOL_LAYOUT: Layout = match Layout::from_size_align(POOL_SIZE, 4096) {
+        Ok(layout) => layout,
+        Err(_) => panic!(),
+    };
+
+    fn with_pool(f: impl FnOnce(&mut TlsfByteAllocator, usize)) {
+        let pool = unsafe { alloc(POOL_LAYOUT) } as usize;
+        let mut tlsf = TlsfByteAllocator::new();
+        tlsf.init(pool, POOL_SIZE);
+        f(&mut tlsf, pool);
+        unsafe { dealloc(pool as *mut u8, POOL_LAYOUT) };
+    }
+
+    /// A simple xorshift generator for the reproducible sizes.
+    fn rand(seed: &mut u64) -> usize {
+        *seed ^= *seed << 13;
+        *seed ^= *seed >> 7;
+        *seed ^= *seed << 17;
+        *seed as usize
+    }
+
+    #[test]
+    fn test_tlsf_alloc_align() {
+        with_pool(|tlsf, pool| {
+            let total = tlsf.total_bytes();
+            assert!(total < POOL_SIZE && total > POOL_SIZE - 64);
+            assert_eq!(tlsf.used_bytes(), 0);
+
+            for align in [1, 8, 16, 64, 256, 4096, 0x1_0000] {
+                let pos = tlsf.alloc(100, align).unwrap();
+                assert_eq!(pos % align, 0);
+                assert!(pos >= pool && pos + 100 <= pool + POOL_SIZE);
+            }
+            assert!(matches!(tlsf.alloc(8, 3), Err(AllocError::InvalidParam)));
+            assert!(matches!(
+                tlsf.alloc(POOL_SIZE, 8),
+                Err(AllocError::NoMemory)
+            ));
+            assert!(matches!(
+                tlsf.alloc(usize::MAX - 4096, 8),
+                Err(AllocError::NoMemory)
+            ));
+        });
+    }
+
+    #[test]
+    fn test_tlsf_random() {
+        with_pool(|tlsf, _| {
+            let total = tlsf.total_bytes();
+            let mut seed = 0x2545_f491_4f6c_dd1d;
+            let mut blocks: Vec<(usize, usize, usize)> = Vec::new();
+            for round in 0..20000 {
+                if blocks.len() < 200 && rand(&mut seed) % 3 != 0 {
+                    let size = 1 + rand(&mut seed) % if round % 16 == 0 { 8192 } else { 256 };
+                    let align = 1 << (rand(&mut seed) % 8);
+                    let pos = tlsf.alloc(size, align).unwrap();
+                    assert_eq!(pos % align, 0);
+                    unsafe { core::ptr::write_bytes(pos as *mut u8, blocks.len() as u8, size) };
+                    blocks.push((pos, size, align));
+                } else if !blocks.is_empty() {
+                    let idx = rand(&mut seed) % blocks.len();
+                    let (pos, size, align) = blocks.swap_remove(idx);
+                    tlsf.dealloc(pos, size, align);
+                }
+            }
+
+            // the live blocks never overlap
+            blocks.sort();
+            for pair in blocks.windows(2) {
+                assert!(pair[0].0 + pair[0].1 <= pair[1].0);
+            }
+            for (pos, size, align) in blocks.drain(..) {
+                tlsf.dealloc(pos, size, align);
+            }
+
+            // all the blocks are merged back, the searched size class is
+            // rounded up so leave some room
+            assert_eq!(tlsf.used_bytes(), 0);
+            let size = total - total / 8;
+            let pos = tlsf.alloc(size, 8).unwrap();
+            tlsf.dealloc(pos, size, 8);
+        });
+    }
+
+    #[test]
+    fn test_tlsf_add_memory() {
+        with_pool(|tlsf, pool| {
+            let half = POOL_SIZE / 2;
+            tlsf.init(pool, half);
+            tlsf.add_memory(pool + half, half).unwrap();
+            assert!(matches!(
+                tlsf.add_memory(pool, 8),
+                Err(AllocError::InvalidParam)
+            ));
+            // the regions are not merged
+            assert!(tlsf.alloc(half, 8).is_err());
+            let size = half - half / 8;
+            let a = tlsf.alloc(size, 8).unwrap();
+            let b = tlsf.alloc(size, 8).unwrap();
+            assert_ne!((a - pool) / half, (b - pool) / half);
+            assert!(tlsf.alloc(size, 8).is_err());
+            tlsf.dealloc(a, size, 8);
+            tlsf.dealloc(b, size, 8);
+            assert_eq!(tlsf.used_bytes(), 0);
+        });
+    }
+
+    #[test]
+    fn test_tlsf_remove_memory() {
+        with_pool(|tlsf, pool| {
+            let half = POOL_SIZE / 2;
+            tlsf.init(pool, half);
+            tlsf.add_memory(pool + half, half).unwrap();
+            let total = tlsf.total_bytes();
+
+            let a = tlsf.alloc(half - half / 8, 8).unwrap();
+            let region = if a < pool + half { pool } else { pool + half };
+            assert!(matches!(
+                tlsf.remove_memory(region, half),
+                Err(AllocError::InvalidParam)
+            ));
+            // only a whole region can be removed
+            let other = pool + half - (region - pool);
+            assert!(matches!(
+                tlsf.remove_memory(other, half / 2),
+                Err(AllocError::InvalidParam)
+            ));
+            tlsf.remove_memory(other, half).unwrap();
+            assert_eq!(tlsf.total_bytes(), total / 2);
+            assert!(tlsf.alloc(half / 2, 8).is_err());
+
+            tlsf.dealloc(a, half - half / 8, 8);
+            tlsf.remove_memory(region, half).unwrap();
+            assert_eq!(tlsf.total_bytes(), 0);
+            assert!(tlsf.alloc(8, 8).is_err());
+        });
+    }
+}
+
+mod region {
+    use std::alloc::{alloc, dealloc, Layout};
+
+    use crate::*;
+
+    const PAGE_SIZE: usize = 0x1000;
+    const POOL_SIZE: usize = 0x40_0000;
+    const POOL_LAYOUT: Layout = match Layout::from_size_align(POOL_SIZE, 0x20_0000) {
+        Ok(layout) => layout,
+        Err(_) => panic!(),
+    };
+
+    type RegionAlloc = RegionPageAllocator<PAGE_SIZE, 4>;
+
+    fn with_pool(f: impl FnOnce(usize)) {
+        let pool = unsafe { alloc(POOL_LAYOUT) } as usize;
+        f(pool);
+        unsafe { dealloc(pool as *mut u8, POOL_LAYOUT) };
+    }
+
+    #[test]
+    fn test_region_alloc_pages() {
+        with_pool(|pool| {
+            let mut pages = RegionAlloc::new();
+            pages.init(pool, 0x10_0000);
+            // one page is taken by the bitmap
+            assert_eq!(pages.total_pages(), 255);
+            assert_eq!(
+                pages.region(0),
+                Some((pool + PAGE_SIZE..pool + 0x10_0000, 0))
+            );
+
+            assert_eq!(pages.alloc_pages(1, PAGE_SIZE).unwrap(), pool + PAGE_SIZE);
+            assert_eq!(
+                pages.alloc_pages(2, PAGE_SIZE).unwrap(),
+                pool + 2 * PAGE_SIZE
+            );
+            assert_eq!(pages.alloc_pages(4, 0x4000).unwrap(), pool + 0x4000);
+            assert_eq!(
+                pages.alloc_pages(1, PAGE_SIZE).unwrap(),
+                pool + 8 * PAGE_SIZE
+            );
+            assert_eq!(pages.used_pages(), 8);
+            assert!(matches!(
+                pages.alloc_pages(0, PAGE_SIZE),
+                Err(AllocError::InvalidParam)
+            ));
+            assert!(matches!(
+                pages.alloc_pages(1, 0x800),
+                Err(AllocError::InvalidParam)
+            ));
+            assert!(matches!(
+                pages.alloc_pages(256, PAGE_SIZE),
+                Err(AllocError::NoMemory)
+            ));
+
+            // the freed pages are reused, double frees are ignored
+            pages.dealloc_pages(pool + 2 * PAGE_SIZE, 2);
+            pages.dealloc_pages(pool + 2 * PAGE_SIZE, 2);
+            assert_eq!(pages.used_pages(), 6);
+            assert_eq!(
+                pages.alloc_pages(2, PAGE_SIZE).unwrap(),
+                pool + 2 * PAGE_SIZE
+            );
+
+            let rest = pages.available_pages();
+            let pos = pages.alloc_pages(rest, PAGE_SIZE).unwrap();
+            assert_eq!(pos, pool + 9 * PAGE_SIZE);
+            assert_eq!(pages.available_pages(), 0);
+            assert!(pages.alloc_pages(1, PAGE_SIZE).is_err());
+            pages.dealloc_pages(pos, rest);
+            assert_eq!(pages.region(0).unwrap().1, 8);
+        });
+    }
+
+    #[test]
+    fn test_region_add_memory() {
+        with_pool(|pool| {
+            let mut pages = RegionAlloc::new();
+            let mb = 0x10_0000;
+            pages.init(pool + 2 * mb, mb);
+            pages.add_memory(pool, mb).unwrap();
+            assert!(matches!(
+                pages.add_memory(pool + mb / 2, mb),
+                Err(AllocError::MemoryOverlap)
+            ));
+            assert!(matches!(
+                pages.add_memory(pool + 2 * mb + PAGE_SIZE, PAGE_SIZE),
+                Err(AllocError::MemoryOverlap)
+            ));
+            // too small to hold the bitmap and a page
+            assert!(matches!(
+                pages.add_memory(pool + mb, PAGE_SIZE + 1),
+                Err(AllocError::InvalidParam)
+            ));
+            pages.add_memory(pool + mb + 1, 3 * PAGE_SIZE).unwrap();
+            pages.add_memory(pool + 3 * mb, PAGE_SIZE * 2).unwrap();
+            assert!(matches!(
+                pages.add_memory(pool + 3 * mb + 0x10_000, mb / 2),
+                Err(AllocError::NoMemory)
+            ));
+
+            // sorted by the addresses
+            assert_eq!(pages.num_regions(), 4);
+            let starts: Vec<_> = (0..4).map(|i| pages.region(i).unwrap().0.start).collect();
+            assert_eq!(
+                starts,
+                [pool, pool + mb + PAGE_SIZE, pool + 2 * mb, pool + 3 * mb]
+                    .map(|start| start + PAGE_SIZE)
+            );
+            assert_eq!(pages.total_pages(), 255 * 2 + 1 + 1);
+
+            // the lower regions are used first, and the allocations never
+            // span the regions
+            let a = pages.alloc_pages(200, PAGE_SIZE).unwrap();
+            assert_eq!(a, pool + PAGE_SIZE);
+            let b = pages.alloc_pages(100, PAGE_SIZE).unwrap();
+            assert_eq!(b, pool + 2 * mb + PAGE_SIZE);
+            let c = pages.alloc_pages(1, PAGE_SIZE).unwrap();
+            assert_eq!(c, pool + 201 * PAGE_SIZE);
+            assert_eq!(pages.region(2).unwrap().1, 100);
+
+            pages.dealloc_pages(b, 100);
+            pages.dealloc_pages(a, 200);
+            pages.dealloc_pages(c, 1);
+            assert_eq!(pages.used_pages(), 0);
+        });
+    }
+
+    #[test]
+    fn test_region_alloc_below() {
+        with_pool(|pool| {
+            let mut pages = RegionAlloc::new();
+            let mb = 0x10_0000;
+            pages.init(pool + mb, 3 * mb);
+            pages.add_memory(pool, mb).unwrap();
+
+            let limit = pool + mb;
+            let mut low = Vec::new();
+            while let Ok(pos) = pages.alloc_pages_below(1, PAGE_SIZE, limit) {
+                assert!(pos + PAGE_SIZE <= limit);
+                low.push(pos);
+            }
+            assert_eq!(low.len(), 255);
+            assert!(matches!(
+                pages.alloc_pages_below(1, PAGE_SIZE, pool),
+                Err(AllocError::NoMemory)
+            ));
+
+            // only one huge page fits in the second region
+            let huge = pages.alloc_pages(0x200, 0x20_0000).unwrap();
+            assert_eq!(huge, pool + 2 * mb);
+            assert!(pages.alloc_pages(0x200, 0x20_0000).is_err());
+            let below = pages.alloc_pages_below(255, PAGE_SIZE, huge).unwrap();
+            assert_eq!(below, pool + mb + PAGE_SIZE);
+            assert!(pages.alloc_pages_below(1, PAGE_SIZE, huge).is_err());
+            assert_eq!(pages.available_pages(), 0);
+
+            pages.dealloc_pages(below, 255);
+            for pos in low {
+                pages.dealloc_pages(pos, 1);
+            }
+            pages.dealloc_pages(huge, 0x200);
+            assert_eq!(pages.used_pages(), 0);
+        });
+    }
+}
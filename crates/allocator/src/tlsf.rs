@@ -0,0 +1,337 @@
+//! TLSF (Two-Level Segregated Fit) memory allocation.
+//!
+//! The free blocks are kept in segregated lists indexed by two levels of size
+//! classes, and two levels of bitmaps show which lists are not empty, so both
+//! allocation and deallocation take `O(1)` time. See "TLSF: a New Dynamic
+//! Memory Allocator for Real-Time Systems" (Masmano et al., ECRTS 2004).
+
+use core::mem::size_of;
+use core::ptr::null_mut;
+
+use crate::{AllocError, AllocResult, BaseAllocator, ByteAllocator};
+
+/// The granularity of the block sizes, and the alignment of the blocks.
+const GRANULARITY: usize = 2 * size_of::<usize>();
+/// The size of the block header: `prev_phys` and `size`.
+const HEADER_SIZE: usize = 2 * size_of::<usize>();
+/// The minimum size of a block, with room for the free list links.
+const MIN_BLOCK_SIZE: usize = size_of::<Block>();
+
+/// Each first-level class is divided into `2^SL_LOG2` second-level classes.
+const SL_LOG2: usize = 4;
+const SL_COUNT: usize = 1 << SL_LOG2;
+/// The blocks smaller than it are in the first-level class `0`, which is
+/// divided linearly by the granularity.
+const SMALL_BLOCK_SIZE: usize = GRANULARITY << SL_LOG2;
+const SMALL_BLOCK_LOG2: usize = SMALL_BLOCK_SIZE.trailing_zeros() as usize;
+const FL_COUNT: usize = usize::BITS as usize - SMALL_BLOCK_LOG2 + 1;
+/// The maximum size of a request, so the size classes do not overflow.
+const MAX_REQUEST_SIZE: usize = 1 << (usize::BITS - 2);
+
+/// The block is free.
+const FREE: usize = 0b01;
+/// The physically previous block is free.
+const PREV_FREE: usize = 0b10;
+const FLAGS_MASK: usize = FREE | PREV_FREE;
+
+/// The header of a memory block, followed by the payload.
+///
+/// The free list links overlap the payload, and are only valid if the block
+/// is free. The last block of a memory region is a sentinel with only the
+/// header, which is always used and never merged.
+#[repr(C)]
+struct Block {
+    /// The physically previous block.
+    prev_phys: *mut Block,
+    /// The size of the whole block, with the flags in the low bits.
+    size: usize,
+    next_free: *mut Block,
+    prev_free: *mut Block,
+}
+
+impl Block {
+    unsafe fn size(this: *mut Self) -> usize {
+        (*this).size & !FLAGS_MASK
+    }
+
+    unsafe fn set_size(this: *mut Self, size: usize) {
+        (*this).size = size | ((*this).size & FLAGS_MASK);
+    }
+
+    unsafe fn is_free(this: *mut Self) -> bool {
+        (*this).size & FREE != 0
+    }
+
+    unsafe fn is_prev_free(this: *mut Self) -> bool {
+        (*this).size & PREV_FREE != 0
+    }
+
+    unsafe fn set_flag(this: *mut Self, flag: usize, set: bool) {
+        if set {
+            (*this).size |= flag;
+        } else {
+            (*this).size &= !flag;
+        }
+    }
+
+    unsafe fn next_phys(this: *mut Self) -> *mut Self {
+        (this as usize + Self::size(this)) as *mut Self
+    }
+
+    fn payload(this: *mut Self) -> usize {
+        this as usize + HEADER_SIZE
+    }
+
+    fn from_payload(pos: usize) -> *mut Self {
+        (pos - HEADER_SIZE) as *mut Self
+    }
+}
+
+/// Returns the indices of the free list containing blocks of `size`.
+const fn mapping_insert(size: usize) -> (usize, usize) {
+    if size < SMALL_BLOCK_SIZE {
+        (0, size / GRANULARITY)
+    } else {
+        let fl = (usize::BITS - 1 - size.leading_zeros()) as usize;
+        let sl = (size >> (fl - SL_LOG2)) & (SL_COUNT - 1);
+        (fl - SMALL_BLOCK_LOG2 + 1, sl)
+    }
+}
+
+/// Returns the indices of the first free list whose blocks are all large
+/// enough for `size`.
+const fn mapping_search(size: usize) -> (usize, usize) {
+    if size < SMALL_BLOCK_SIZE {
+        mapping_insert(size)
+    } else {
+        let fl = (usize::BITS - 1 - size.leading_zeros()) as usize;
+        mapping_insert(size + (1 << (fl - SL_LOG2)) - 1)
+    }
+}
+
+/// A byte-granularity memory allocator with the TLSF algorithm, which
+/// allocates and deallocates in bounded time for real-time systems.
+///
+/// Each block has a header of two words, and the block sizes are rounded up
+/// to two words.
+pub struct TlsfByteAllocator {
+    fl_bitmap: usize,
+    sl_bitmaps: [u32; FL_COUNT],
+    free_lists: [[*mut Block; SL_COUNT]; FL_COUNT],
+    total_bytes: usize,
+    used_bytes: usize,
+}
+
+unsafe impl Send for TlsfByteAllocator {}
+
+impl TlsfByteAllocator {
+    /// Creates a new empty `TlsfByteAllocator`.
+    pub const fn new() -> Self {
+        Self {
+            fl_bitmap: 0,
+            sl_bitmaps: [0; FL_COUNT],
+            free_lists: [[null_mut(); SL_COUNT]; FL_COUNT],
+            total_bytes: 0,
+            used_bytes: 0,
+        }
+    }
+
+    unsafe fn insert_free(&mut self, block: *mut Block) {
+        let (fl, sl) = mapping_insert(Block::size(block));
+        let head = self.free_lists[fl][sl];
+        (*block).next_free = head;
+        (*block).prev_free = null_mut();
+        if !head.is_null() {
+            (*head).prev_free = block;
+        }
+        self.free_lists[fl][sl] = block;
+        self.fl_bitmap |= 1 << fl;
+        self.sl_bitmaps[fl] |= 1 << sl;
+    }
+
+    unsafe fn remove_free(&mut self, block: *mut Block) {
+        let (fl, sl) = mapping_insert(Block::size(block));
+        let (prev, next) = ((*block).prev_free, (*block).next_free);
+        if !next.is_null() {
+            (*next).prev_free = prev;
+        }
+        if !prev.is_null() {
+            (*prev).next_free = next;
+        } else {
+            self.free_lists[fl][sl] = next;
+            if next.is_null() {
+                self.sl_bitmaps[fl] &= !(1 << sl);
+                if self.sl_bitmaps[fl] == 0 {
+                    self.fl_bitmap &= !(1 << fl);
+                }
+            }
+        }
+    }
+
+    /// Finds a free block of at least `size` bytes in the non-empty lists.
+    fn find_suitable(&self, size: usize) -> Option<*mut Block> {
+        let (mut fl, sl) = mapping_search(size);
+        if fl >= FL_COUNT {
+            return None;
+        }
+        let mut sl_map = self.sl_bitmaps[fl] & (u32::MAX << sl);
+        if sl_map == 0 {
+            let fl_map = self.fl_bitmap & (usize::MAX << (fl + 1));
+            if fl_map == 0 {
+                return None;
+            }
+            fl = fl_map.trailing_zeros() as usize;
+            sl_map = self.sl_bitmaps[fl];
+        }
+        Some(self.free_lists[fl][sl_map.trailing_zeros() as usize])
+    }
+
+    /// Splits the free block at `offset` bytes, and returns the second part.
+    unsafe fn split(block: *mut Block, offset: usize) -> *mut Block {
+        let rest = (block as usize + offset) as *mut Block;
+        (*rest).prev_phys = block;
+        (*rest).size = (Block::size(block) - offset) | FREE | PREV_FREE;
+        (*Block::next_phys(rest)).prev_phys = rest;
+        Block::set_size(block, offset);
+        rest
+    }
+}
+
+impl Default for TlsfByteAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BaseAllocator for TlsfByteAllocator {
+    fn init(&mut self, start: usize, size: usize) {
+        *self = Self::new();
+        self.add_memory(start, size).unwrap();
+    }
+
+    fn add_memory(&mut self, start: usize, size: usize) -> AllocResult {
+        let end = super::align_down(start + size, GRANULARITY);
+        let start = super::align_up(start, GRANULARITY);
+        if end < start || end - start < MIN_BLOCK_SIZE + HEADER_SIZE {
+            return Err(AllocError::InvalidParam);
+        }
+        let block_size = end - start - HEADER_SIZE;
+        let block = start as *mut Block;
+        let sentinel = (start + block_size) as *mut Block;
+        unsafe {
+            (*block).prev_phys = null_mut();
+            (*block).size = block_size | FREE;
+            (*sentinel).prev_phys = block;
+            (*sentinel).size = PREV_FREE;
+            self.insert_free(block);
+        }
+        self.total_bytes += block_size;
+        Ok(())
+    }
+}
+
+impl ByteAllocator for TlsfByteAllocator {
+    fn alloc(&mut self, size: usize, align_pow2: usize) -> AllocResult<usize> {
+        if !align_pow2.is_power_of_two() {
+            return Err(AllocError::InvalidParam);
+        }
+        if size > MAX_REQUEST_SIZE || align_pow2 > MAX_REQUEST_SIZE {
+            return Err(AllocError::NoMemory);
+        }
+        let block_size =
+            super::align_up(size, GRANULARITY).max(MIN_BLOCK_SIZE - HEADER_SIZE) + HEADER_SIZE;
+        // leave room to split the front part for the alignment
+        let search_size = if align_pow2 > GRANULARITY {
+            block_size + align_pow2 + MIN_BLOCK_SIZE
+        } else {
+            block_size
+        };
+        let mut block = self
+            .find_suitable(search_size)
+            .ok_or(AllocError::NoMemory)?;
+        unsafe {
+            self.remove_free(block);
+            let payload = Block::payload(block);
+            let aligned = super::align_up(payload, align_pow2);
+            if aligned != payload {
+                let aligned = if aligned - payload < MIN_BLOCK_SIZE {
+                    super::align_up(payload + MIN_BLOCK_SIZE, align_pow2)
+                } else {
+                    aligned
+                };
+                let front = block;
+                block = Self::split(front, aligned - payload);
+                self.insert_free(front);
+            }
+            if Block::size(block) >= block_size + MIN_BLOCK_SIZE {
+                let rest = Self::split(block, block_size);
+                Block::set_flag(rest, PREV_FREE, false);
+                self.insert_free(rest);
+            } else {
+                Block::set_flag(Block::next_phys(block), PREV_FREE, false);
+            }
+            Block::set_flag(block, FREE, false);
+            self.used_bytes += Block::size(block);
+            Ok(Block::payload(block))
+        }
+    }
+
+    fn dealloc(&mut self, pos: usize, _size: usize, _align_pow2: usize) {
+        let mut block = Block::from_payload(pos);
+        unsafe {
+            debug_assert!(!Block::is_free(block), "double free at {:#x}", pos);
+            self.used_bytes -= Block::size(block);
+            Block::set_flag(block, FREE, true);
+
+            let next = Block::next_phys(block);
+            if Block::is_free(next) {
+                self.remove_free(next);
+                Block::set_size(block, Block::size(block) + Block::size(next));
+            }
+            if Block::is_prev_free(block) {
+                let prev = (*block).prev_phys;
+                self.remove_free(prev);
+                Block::set_size(prev, Block::size(prev) + Block::size(block));
+                block = prev;
+            }
+            let next = Block::next_phys(block);
+            (*next).prev_phys = block;
+            Block::set_flag(next, PREV_FREE, true);
+            self.insert_free(block);
+        }
+    }
+
+    fn total_bytes(&self) -> usize {
+        self.total_bytes
+    }
+
+    fn used_bytes(&self) -> usize {
+        self.used_bytes
+    }
+
+    fn available_bytes(&self) -> usize {
+        self.total_bytes - self.used_bytes
+    }
+
+    fn remove_memory(&mut self, start: usize, size: usize) -> AllocResult {
+        let end = super::align_down(start + size, GRANULARITY);
+        let start = super::align_up(start, GRANULARITY);
+        if end < start || end - start < MIN_BLOCK_SIZE + HEADER_SIZE {
+            return Err(AllocError::InvalidParam);
+        }
+        // the region is unused iff it is a single free block before the sentinel
+        let block_size = end - start - HEADER_SIZE;
+        let block = start as *mut Block;
+        unsafe {
+            if !(*block).prev_phys.is_null()
+                || !Block::is_free(block)
+                || Block::size(block) != block_size
+            {
+                return Err(AllocError::InvalidParam);
+            }
+            self.remove_free(block);
+        }
+        self.total_bytes -= block_size;
+        Ok(())
+    }
+}
@@ -54,7 +54,11 @@
 #define	ENOSYS		38	/* Invalid system call number */
 #define ENOTEMPTY	39	/* Directory not empty */
 
+#define	ENOTSOCK	88	/* Socket operation on non-socket */
+#define	EPROTONOSUPPORT	93	/* Protocol not supported */
+#define	EAFNOSUPPORT	97	/* Address family not supported by protocol */
 #define	ENOTCONN	107	/* Transport endpoint is not connected */
+#define	ETIMEDOUT	110	/* Connection timed out */
 #define	ECONNREFUSED	111	/* Connection refused */
 
 #endif
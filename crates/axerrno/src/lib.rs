@@ -70,6 +70,8 @@ pub enum AxError {
     ResourceBusy,
     /// The underlying storage (typically, a filesystem) is full.
     StorageFull,
+    /// The I/O operation's timeout expired, causing it to be canceled.
+    TimedOut,
     /// An error returned when an operation could not be completed because an
     /// "end of file" was reached prematurely.
     UnexpectedEof,
@@ -186,6 +188,7 @@ impl const From<AxError> for LinuxError {
             PermissionDenied => LinuxError::EACCES,
             ResourceBusy => LinuxError::EBUSY,
             StorageFull => LinuxError::ENOSPC,
+            TimedOut => LinuxError::ETIMEDOUT,
             Unsupported => LinuxError::ENOSYS,
             UnexpectedEof | WriteZero => LinuxError::EIO,
         }
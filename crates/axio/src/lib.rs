@@ -181,6 +181,18 @@ pub enum SeekFrom {
     Current(i64),
 }
 
+/// The readiness of an I/O object, returned by polling it.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PollState {
+    /// Data can be read without blocking, or the end of the stream is
+    /// reached (the read returns zero).
+    pub readable: bool,
+    /// Data can be written without blocking.
+    pub writable: bool,
+    /// The peer has closed the connection.
+    pub hangup: bool,
+}
+
 /// A `BufRead` is a type of `Read`er which has an internal buffer, allowing it
 /// to perform extra ways of reading.
 pub trait BufRead: Read {
@@ -3,10 +3,18 @@
 #![no_std]
 #![feature(doc_auto_cfg)]
 
+#[cfg(feature = "nvme")]
+pub mod nvme;
 #[cfg(feature = "ramdisk")]
 pub mod ramdisk;
 
-use driver_common::{BaseDriverOps, DevResult};
+use driver_common::{BaseDriverOps, DevError, DevResult};
+
+/// The identifier of a request submitted to the queue of a block device.
+///
+/// It is unique among the in-flight requests of the device, but may be
+/// reused once the request is completed.
+pub type RequestToken = u16;
 
 /// Operations that require a block storage device driver to implement.
 pub trait BlockDriverOps: BaseDriverOps {
@@ -31,4 +39,77 @@ pub trait BlockDriverOps: BaseDriverOps {
 
     /// Flushes the device to write all pending data to the storage.
     fn flush(&mut self) -> DevResult;
+
+    /// Reads a batch of requests, each of which is `(block_id, buf)` as in
+    /// [`read_block`](Self::read_block).
+    ///
+    /// Drivers with a request queue submit them all before waiting for the
+    /// completions. The default implementation reads them one by one. The
+    /// first error is returned after all requests are finished.
+    fn read_blocks(&mut self, requests: &mut [(u64, &mut [u8])]) -> DevResult {
+        requests
+            .iter_mut()
+            .map(|(block_id, buf)| self.read_block(*block_id, buf))
+            .fold(Ok(()), Result::and)
+    }
+
+    /// Writes a batch of requests, each of which is `(block_id, buf)` as in
+    /// [`write_block`](Self::write_block).
+    ///
+    /// Drivers with a request queue submit them all before waiting for the
+    /// completions. The default implementation writes them one by one. The
+    /// first error is returned after all requests are finished.
+    fn write_blocks(&mut self, requests: &[(u64, &[u8])]) -> DevResult {
+        requests
+            .iter()
+            .map(|(block_id, buf)| self.write_block(*block_id, buf))
+            .fold(Ok(()), Result::and)
+    }
+
+    /// Submits a request to read blocks into `buf` as in
+    /// [`read_block`](Self::read_block), without waiting for it to complete.
+    ///
+    /// Returns the token of the request, which is later returned by
+    /// [`poll_completion`](Self::poll_completion). Returns
+    /// [`DevError::Again`] if the queue is full, or [`DevError::Unsupported`]
+    /// if the driver does not have a request queue (the default).
+    ///
+    /// # Safety
+    ///
+    /// `buf` must be valid and must not be accessed until the request is
+    /// completed, and the driver must not be moved in the meantime.
+    unsafe fn submit_read(&mut self, _block_id: u64, _buf: *mut [u8]) -> DevResult<RequestToken> {
+        Err(DevError::Unsupported)
+    }
+
+    /// Submits a request to write blocks from `buf` as in
+    /// [`write_block`](Self::write_block), without waiting for it to
+    /// complete.
+    ///
+    /// See [`submit_read`](Self::submit_read) for the return value.
+    ///
+    /// # Safety
+    ///
+    /// `buf` must be valid and must not be modified until the request is
+    /// completed, and the driver must not be moved in the meantime.
+    unsafe fn submit_write(
+        &mut self,
+        _block_id: u64,
+        _buf: *const [u8],
+    ) -> DevResult<RequestToken> {
+        Err(DevError::Unsupported)
+    }
+
+    /// Takes a completed request from the queue, returns its token and
+    /// result, or `None` if no more requests are completed.
+    fn poll_completion(&mut self) -> Option<(RequestToken, DevResult)> {
+        None
+    }
+
+    /// Acknowledges the interrupt of the device, returns whether the device
+    /// raised it. Completed requests should be taken by
+    /// [`poll_completion`](Self::poll_completion) afterwards.
+    fn ack_interrupt(&mut self) -> bool {
+        false
+    }
 }
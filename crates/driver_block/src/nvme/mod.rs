@@ -0,0 +1,377 @@
+//! Driver for NVMe (NVM Express) controllers.
+//!
+//! The controller is used with an admin queue pair and a single I/O queue
+//! pair. Commands are executed synchronously, and completions are polled
+//! (interrupts of the controller are masked). Data is transferred through a
+//! DMA bounce buffer, so the buffers of callers need not be physically
+//! contiguous.
+
+mod queue;
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::ptr::NonNull;
+
+use crate::BlockDriverOps;
+use driver_common::{BaseDriverOps, DevError, DevResult, DeviceType};
+
+use self::queue::{Command, Dma, QueuePair, PAGE_SIZE};
+
+/// The PCI class, subclass and programming interface of NVMe controllers.
+pub const NVME_PCI_CLASS: (u8, u8, u8) = (0x01, 0x08, 0x02);
+
+// Controller registers
+const REG_CAP: usize = 0x00;
+const REG_INTMS: usize = 0x0c;
+const REG_CC: usize = 0x14;
+const REG_CSTS: usize = 0x1c;
+const REG_AQA: usize = 0x24;
+const REG_ASQ: usize = 0x28;
+const REG_ACQ: usize = 0x30;
+const REG_DOORBELL_BASE: usize = 0x1000;
+
+const CC_ENABLE: u32 = 1 << 0;
+const CC_IOSQES: u32 = 6 << 16; // 64-byte submission queue entries
+const CC_IOCQES: u32 = 4 << 20; // 16-byte completion queue entries
+const CSTS_READY: u32 = 1 << 0;
+const CSTS_FATAL: u32 = 1 << 1;
+
+// Admin commands
+const ADMIN_CREATE_IO_SQ: u8 = 0x01;
+const ADMIN_CREATE_IO_CQ: u8 = 0x05;
+const ADMIN_IDENTIFY: u8 = 0x06;
+
+const IDENTIFY_NAMESPACE: u32 = 0x00;
+const IDENTIFY_CONTROLLER: u32 = 0x01;
+const IDENTIFY_ACTIVE_NAMESPACES: u32 = 0x02;
+
+// I/O commands
+const IO_FLUSH: u8 = 0x00;
+const IO_WRITE: u8 = 0x01;
+const IO_READ: u8 = 0x02;
+
+const ADMIN_QUEUE_SIZE: u16 = 32;
+const IO_QUEUE_SIZE: u16 = 64;
+const IO_QUEUE_ID: u16 = 1;
+/// The size of the bounce buffer, which can be described by the two PRP
+/// entries of a command without a PRP list.
+const BOUNCE_PAGES: usize = 2;
+
+/// The hardware abstraction that the NVMe driver needs.
+///
+/// # Safety
+///
+/// The memory returned by [`dma_alloc`](Self::dma_alloc) must be physically
+/// contiguous, mapped at the returned virtual address, and accessible by the
+/// device at the returned physical address.
+pub unsafe trait NvmeHal {
+    /// Allocates `pages` contiguous pages for DMA, returns their physical and
+    /// virtual addresses, or `None` if out of memory.
+    fn dma_alloc(pages: usize) -> Option<(usize, NonNull<u8>)>;
+
+    /// Deallocates the pages allocated by [`dma_alloc`](Self::dma_alloc).
+    ///
+    /// # Safety
+    ///
+    /// The pages must not be accessed by the device anymore.
+    unsafe fn dma_dealloc(paddr: usize, vaddr: NonNull<u8>, pages: usize);
+}
+
+/// The information of an active namespace.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct NamespaceInfo {
+    /// The namespace ID.
+    pub nsid: u32,
+    /// The number of logical blocks.
+    pub num_blocks: u64,
+    /// The size of each logical block in bytes.
+    pub block_size: usize,
+}
+
+/// The NVMe controller driver.
+///
+/// All active namespaces are enumerated when the controller is initialized,
+/// and block operations go to one of them (the first one by default, see
+/// [`NvmeDev::select_namespace`]).
+pub struct NvmeDev<H: NvmeHal> {
+    mmio_base: usize,
+    admin_queue: QueuePair<H>,
+    io_queue: QueuePair<H>,
+    bounce: Dma<H>,
+    /// The maximum number of bytes transferred by one command.
+    max_transfer: usize,
+    namespaces: Vec<NamespaceInfo>,
+    current: NamespaceInfo,
+}
+
+unsafe impl<H: NvmeHal> Send for NvmeDev<H> {}
+unsafe impl<H: NvmeHal> Sync for NvmeDev<H> {}
+
+impl<H: NvmeHal> NvmeDev<H> {
+    /// Creates a new driver instance for the controller whose registers (BAR
+    /// 0) are mapped at `mmio_base`, and initializes it.
+    ///
+    /// Returns an error if any step fails, or the controller has no active
+    /// namespace.
+    ///
+    /// # Safety
+    ///
+    /// `mmio_base` must be the virtual address of the mapped registers of an
+    /// NVMe controller, which is not used by others.
+    pub unsafe fn try_new(mmio_base: usize) -> DevResult<Self> {
+        let cap = ((mmio_base + REG_CAP) as *const u64).read_volatile();
+        let max_entries = (cap & 0xffff) as u16 + 1;
+        let doorbell_stride = 4usize << ((cap >> 32) & 0xf);
+        let doorbell = |qid: u16, is_cq: bool| {
+            let idx = 2 * qid as usize + is_cq as usize;
+            (mmio_base + REG_DOORBELL_BASE + idx * doorbell_stride) as *mut u32
+        };
+
+        let admin_queue = QueuePair::new(
+            ADMIN_QUEUE_SIZE.min(max_entries),
+            doorbell(0, false),
+            doorbell(0, true),
+        )?;
+        let io_queue = QueuePair::new(
+            IO_QUEUE_SIZE.min(max_entries),
+            doorbell(IO_QUEUE_ID, false),
+            doorbell(IO_QUEUE_ID, true),
+        )?;
+        let mut dev = Self {
+            mmio_base,
+            admin_queue,
+            io_queue,
+            bounce: Dma::new(BOUNCE_PAGES)?,
+            max_transfer: BOUNCE_PAGES * PAGE_SIZE,
+            namespaces: Vec::new(),
+            current: NamespaceInfo {
+                nsid: 0,
+                num_blocks: 0,
+                block_size: 0,
+            },
+        };
+        dev.reset()?;
+        dev.create_io_queues()?;
+        dev.identify()?;
+        dev.current = *dev.namespaces.first().ok_or(DevError::BadState)?;
+        Ok(dev)
+    }
+
+    /// The active namespaces of the controller.
+    pub fn namespaces(&self) -> &[NamespaceInfo] {
+        &self.namespaces
+    }
+
+    /// The namespace which block operations go to.
+    pub fn current_namespace(&self) -> &NamespaceInfo {
+        &self.current
+    }
+
+    /// Directs the following block operations to the namespace `nsid`.
+    pub fn select_namespace(&mut self, nsid: u32) -> DevResult {
+        self.current = *self
+            .namespaces
+            .iter()
+            .find(|ns| ns.nsid == nsid)
+            .ok_or(DevError::InvalidParam)?;
+        Ok(())
+    }
+
+    fn read_reg(&self, offset: usize) -> u32 {
+        unsafe { ((self.mmio_base + offset) as *const u32).read_volatile() }
+    }
+
+    fn write_reg(&mut self, offset: usize, value: u32) {
+        unsafe { ((self.mmio_base + offset) as *mut u32).write_volatile(value) }
+    }
+
+    fn write_reg64(&mut self, offset: usize, value: u64) {
+        self.write_reg(offset, value as u32);
+        self.write_reg(offset + 4, (value >> 32) as u32);
+    }
+
+    /// Waits for the controller to become ready or not ready. A fatal status
+    /// is only meaningful after the controller is enabled.
+    fn wait_ready(&self, ready: bool) -> DevResult {
+        loop {
+            let csts = self.read_reg(REG_CSTS);
+            if ready && csts & CSTS_FATAL != 0 {
+                return Err(DevError::BadState);
+            }
+            if (csts & CSTS_READY != 0) == ready {
+                return Ok(());
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Disables the controller, sets up the admin queues, and enables it
+    /// again.
+    fn reset(&mut self) -> DevResult {
+        let cc = self.read_reg(REG_CC);
+        if cc & CC_ENABLE != 0 {
+            self.write_reg(REG_CC, cc & !CC_ENABLE);
+        }
+        self.wait_ready(false)?;
+
+        let size = self.admin_queue.size() as u32 - 1;
+        self.write_reg(REG_AQA, size << 16 | size);
+        self.write_reg64(REG_ASQ, self.admin_queue.sq_paddr() as u64);
+        self.write_reg64(REG_ACQ, self.admin_queue.cq_paddr() as u64);
+        // NVM command set, 4K memory pages, round robin arbitration
+        self.write_reg(REG_CC, CC_ENABLE | CC_IOSQES | CC_IOCQES);
+        self.wait_ready(true)?;
+
+        // completions are polled, mask all pin-based interrupts
+        self.write_reg(REG_INTMS, u32::MAX);
+        Ok(())
+    }
+
+    fn admin(&mut self, cmd: Command) -> DevResult<u32> {
+        let csts = (self.mmio_base + REG_CSTS) as *const u32;
+        let is_fatal = || unsafe { csts.read_volatile() } & CSTS_FATAL != 0;
+        Ok(self.admin_queue.execute(cmd, is_fatal)?.result)
+    }
+
+    fn io(&mut self, cmd: Command) -> DevResult {
+        let csts = (self.mmio_base + REG_CSTS) as *const u32;
+        let is_fatal = || unsafe { csts.read_volatile() } & CSTS_FATAL != 0;
+        self.io_queue.execute(cmd, is_fatal).map(|_| ())
+    }
+
+    fn create_io_queues(&mut self) -> DevResult {
+        let size = self.io_queue.size() as u32 - 1;
+
+        let mut cmd = Command::new(ADMIN_CREATE_IO_CQ, 0);
+        cmd.prp1 = self.io_queue.cq_paddr() as u64;
+        cmd.cdw10 = size << 16 | IO_QUEUE_ID as u32;
+        cmd.cdw11 = 1; // physically contiguous, interrupts disabled
+        self.admin(cmd)?;
+
+        let mut cmd = Command::new(ADMIN_CREATE_IO_SQ, 0);
+        cmd.prp1 = self.io_queue.sq_paddr() as u64;
+        cmd.cdw10 = size << 16 | IO_QUEUE_ID as u32;
+        cmd.cdw11 = (IO_QUEUE_ID as u32) << 16 | 1; // completion queue, physically contiguous
+        self.admin(cmd)?;
+        Ok(())
+    }
+
+    /// Runs an identify command, whose 4K result is left in the bounce
+    /// buffer.
+    fn identify_cmd(&mut self, cns: u32, nsid: u32) -> DevResult<&[u8]> {
+        let mut cmd = Command::new(ADMIN_IDENTIFY, nsid);
+        cmd.prp1 = self.bounce.paddr() as u64;
+        cmd.cdw10 = cns;
+        self.admin(cmd)?;
+        Ok(&self.bounce.as_slice()[..PAGE_SIZE])
+    }
+
+    /// Identifies the controller and enumerates the active namespaces.
+    fn identify(&mut self) -> DevResult {
+        let ctrl = self.identify_cmd(IDENTIFY_CONTROLLER, 0)?;
+        // maximum data transfer size, in units of the minimum page size
+        let mdts = ctrl[77];
+        if mdts != 0 {
+            self.max_transfer = self.max_transfer.min(PAGE_SIZE << mdts);
+        }
+
+        let list = self.identify_cmd(IDENTIFY_ACTIVE_NAMESPACES, 0)?;
+        let nsids: Vec<u32> = list
+            .chunks_exact(4)
+            .map(|id| u32::from_le_bytes([id[0], id[1], id[2], id[3]]))
+            .take_while(|&nsid| nsid != 0)
+            .collect();
+
+        for nsid in nsids {
+            let ns = self.identify_cmd(IDENTIFY_NAMESPACE, nsid)?;
+            let num_blocks = u64::from_le_bytes(ns[0..8].try_into().unwrap());
+            // the current LBA format, each format descriptor is 4 bytes
+            let format = (ns[26] & 0xf) as usize;
+            let lba_shift = ns[128 + format * 4 + 2];
+            if num_blocks == 0 || !(9..=12).contains(&lba_shift) {
+                continue; // inactive or unsupported block size
+            }
+            self.namespaces.push(NamespaceInfo {
+                nsid,
+                num_blocks,
+                block_size: 1 << lba_shift,
+            });
+        }
+        Ok(())
+    }
+
+    /// Reads or writes the blocks starting from `block_id` from/to the bounce
+    /// buffer, `len` must not exceed `max_transfer`.
+    fn transfer(&mut self, opcode: u8, block_id: u64, len: usize) -> DevResult {
+        let ns = self.current;
+        let num = (len / ns.block_size) as u32;
+        let mut cmd = Command::new(opcode, ns.nsid);
+        cmd.prp1 = self.bounce.paddr() as u64;
+        if len > PAGE_SIZE {
+            cmd.prp2 = (self.bounce.paddr() + PAGE_SIZE) as u64;
+        }
+        cmd.cdw10 = block_id as u32;
+        cmd.cdw11 = (block_id >> 32) as u32;
+        cmd.cdw12 = num - 1;
+        self.io(cmd)
+    }
+
+    /// Checks that `len` bytes starting from `block_id` are whole blocks in
+    /// the current namespace.
+    fn check_range(&self, block_id: u64, len: usize) -> DevResult {
+        let ns = &self.current;
+        let end = block_id.checked_add((len / ns.block_size) as u64);
+        match end {
+            Some(end) if len % ns.block_size == 0 && end <= ns.num_blocks => Ok(()),
+            _ => Err(DevError::InvalidParam),
+        }
+    }
+}
+
+impl<H: NvmeHal> BaseDriverOps for NvmeDev<H> {
+    fn device_name(&self) -> &str {
+        "nvme"
+    }
+
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Block
+    }
+}
+
+impl<H: NvmeHal> BlockDriverOps for NvmeDev<H> {
+    #[inline]
+    fn num_blocks(&self) -> u64 {
+        self.current.num_blocks
+    }
+
+    #[inline]
+    fn block_size(&self) -> usize {
+        self.current.block_size
+    }
+
+    fn read_block(&mut self, mut block_id: u64, buf: &mut [u8]) -> DevResult {
+        self.check_range(block_id, buf.len())?;
+        for chunk in buf.chunks_mut(self.max_transfer) {
+            self.transfer(IO_READ, block_id, chunk.len())?;
+            chunk.copy_from_slice(&self.bounce.as_slice()[..chunk.len()]);
+            block_id += (chunk.len() / self.current.block_size) as u64;
+        }
+        Ok(())
+    }
+
+    fn write_block(&mut self, mut block_id: u64, buf: &[u8]) -> DevResult {
+        self.check_range(block_id, buf.len())?;
+        for chunk in buf.chunks(self.max_transfer) {
+            self.bounce.as_mut_slice()[..chunk.len()].copy_from_slice(chunk);
+            self.transfer(IO_WRITE, block_id, chunk.len())?;
+            block_id += (chunk.len() / self.current.block_size) as u64;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> DevResult {
+        let cmd = Command::new(IO_FLUSH, self.current.nsid);
+        self.io(cmd)
+    }
+}
@@ -0,0 +1,201 @@
+//! DMA buffers, commands and the submission/completion queue pairs.
+
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+use core::sync::atomic::{fence, Ordering};
+
+use driver_common::{DevError, DevResult};
+
+use super::NvmeHal;
+
+pub(super) const PAGE_SIZE: usize = 0x1000;
+
+/// A physically contiguous, zero-initialized DMA region.
+pub(super) struct Dma<H: NvmeHal> {
+    paddr: usize,
+    vaddr: NonNull<u8>,
+    pages: usize,
+    _hal: PhantomData<H>,
+}
+
+impl<H: NvmeHal> Dma<H> {
+    pub fn new(pages: usize) -> DevResult<Self> {
+        let (paddr, vaddr) = H::dma_alloc(pages).ok_or(DevError::NoMemory)?;
+        unsafe { core::ptr::write_bytes(vaddr.as_ptr(), 0, pages * PAGE_SIZE) };
+        Ok(Self {
+            paddr,
+            vaddr,
+            pages,
+            _hal: PhantomData,
+        })
+    }
+
+    pub const fn paddr(&self) -> usize {
+        self.paddr
+    }
+
+    pub fn as_ptr<T>(&self) -> *mut T {
+        self.vaddr.as_ptr() as *mut T
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.vaddr.as_ptr(), self.pages * PAGE_SIZE) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.vaddr.as_ptr(), self.pages * PAGE_SIZE) }
+    }
+}
+
+impl<H: NvmeHal> Drop for Dma<H> {
+    fn drop(&mut self) {
+        unsafe { H::dma_dealloc(self.paddr, self.vaddr, self.pages) };
+    }
+}
+
+/// A submission queue entry.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct Command {
+    pub opcode: u8,
+    pub flags: u8,
+    pub cid: u16,
+    pub nsid: u32,
+    _rsvd: u64,
+    pub mptr: u64,
+    pub prp1: u64,
+    pub prp2: u64,
+    pub cdw10: u32,
+    pub cdw11: u32,
+    pub cdw12: u32,
+    pub cdw13: u32,
+    pub cdw14: u32,
+    pub cdw15: u32,
+}
+
+impl Command {
+    pub fn new(opcode: u8, nsid: u32) -> Self {
+        Self {
+            opcode,
+            nsid,
+            ..Default::default()
+        }
+    }
+}
+
+/// A completion queue entry.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Completion {
+    pub result: u32,
+    _rsvd: u32,
+    pub sq_head: u16,
+    pub sq_id: u16,
+    pub cid: u16,
+    /// The phase tag (bit 0) and the status field.
+    pub status: u16,
+}
+
+impl Completion {
+    const fn phase(&self) -> bool {
+        self.status & 1 != 0
+    }
+
+    /// The status code type and the status code, both 0 on success.
+    pub const fn status_code(&self) -> u16 {
+        (self.status >> 1) & 0x7ff
+    }
+}
+
+/// A submission queue and its completion queue, used synchronously: each
+/// command is submitted and then polled until it completes.
+pub(super) struct QueuePair<H: NvmeHal> {
+    sq: Dma<H>,
+    cq: Dma<H>,
+    size: u16,
+    sq_tail: u16,
+    cq_head: u16,
+    /// The expected phase tag of the next new completion.
+    phase: bool,
+    next_cid: u16,
+    sq_doorbell: *mut u32,
+    cq_doorbell: *mut u32,
+}
+
+impl<H: NvmeHal> QueuePair<H> {
+    /// Allocates the queues of `size` entries, which must fit in a page.
+    pub fn new(size: u16, sq_doorbell: *mut u32, cq_doorbell: *mut u32) -> DevResult<Self> {
+        Ok(Self {
+            sq: Dma::new(1)?,
+            cq: Dma::new(1)?,
+            size,
+            sq_tail: 0,
+            cq_head: 0,
+            phase: true,
+            next_cid: 0,
+            sq_doorbell,
+            cq_doorbell,
+        })
+    }
+
+    pub const fn size(&self) -> u16 {
+        self.size
+    }
+
+    pub const fn sq_paddr(&self) -> usize {
+        self.sq.paddr()
+    }
+
+    pub const fn cq_paddr(&self) -> usize {
+        self.cq.paddr()
+    }
+
+    /// Submits `cmd` and busy-waits for its completion.
+    ///
+    /// `is_fatal` is checked while waiting, to give up if the controller
+    /// fails.
+    pub fn execute(
+        &mut self,
+        mut cmd: Command,
+        is_fatal: impl Fn() -> bool,
+    ) -> DevResult<Completion> {
+        cmd.cid = self.next_cid;
+        self.next_cid = self.next_cid.wrapping_add(1);
+        unsafe {
+            self.sq
+                .as_ptr::<Command>()
+                .add(self.sq_tail as usize)
+                .write_volatile(cmd);
+        }
+        self.sq_tail = (self.sq_tail + 1) % self.size;
+        fence(Ordering::SeqCst);
+        unsafe { self.sq_doorbell.write_volatile(self.sq_tail as u32) };
+
+        let entry = unsafe { self.cq.as_ptr::<Completion>().add(self.cq_head as usize) };
+        let completion = loop {
+            let completion = unsafe { entry.read_volatile() };
+            if completion.phase() == self.phase {
+                fence(Ordering::SeqCst);
+                break completion;
+            }
+            if is_fatal() {
+                return Err(DevError::BadState);
+            }
+            core::hint::spin_loop();
+        };
+        self.cq_head += 1;
+        if self.cq_head == self.size {
+            self.cq_head = 0;
+            self.phase = !self.phase;
+        }
+        unsafe { self.cq_doorbell.write_volatile(self.cq_head as u32) };
+
+        if completion.cid != cmd.cid {
+            return Err(DevError::BadState);
+        }
+        match completion.status_code() {
+            0 => Ok(completion),
+            _ => Err(DevError::Io),
+        }
+    }
+}
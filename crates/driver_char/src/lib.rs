@@ -0,0 +1,26 @@
+//! Common traits for character device drivers (e.g., consoles).
+
+#![no_std]
+
+use driver_common::{BaseDriverOps, DevResult};
+
+/// Operations that require a character device driver to implement.
+pub trait CharDriverOps: BaseDriverOps {
+    /// Reads the bytes already received into `buf` without blocking.
+    ///
+    /// Returns the number of bytes read, which is 0 if nothing has been
+    /// received.
+    fn read_bytes(&mut self, buf: &mut [u8]) -> DevResult<usize>;
+
+    /// Writes bytes from `buf` to the device.
+    ///
+    /// Returns the number of bytes written, which may be less than the length
+    /// of `buf` if the device is busy.
+    fn write_bytes(&mut self, buf: &[u8]) -> DevResult<usize>;
+
+    /// Acknowledges the interrupt of the device, returns whether the device
+    /// raised it.
+    fn ack_interrupt(&mut self) -> bool {
+        false
+    }
+}
@@ -5,13 +5,21 @@
 //! device types:
 //!
 //! - [`driver_block`][2]: Common traits for block storage drivers.
+//! - [`driver_char`][5]: Common traits for character device drivers.
 //! - [`driver_display`][3]: Common traits and types for graphics display drivers.
+//! - [`driver_input`][6]: Common traits and types for input device drivers.
 //! - [`driver_net`][4]: Common traits and types for network (NIC) drivers.
+//! - [`driver_rng`][7]: Common traits for random number generator drivers.
+//! - [`driver_serial`][8]: Common traits and types for serial port (UART) drivers.
 //!
 //! [1]: https://github.com/rcore-os/arceos
 //! [2]: ../driver_block/index.html
 //! [3]: ../driver_display/index.html
 //! [4]: ../driver_net/index.html
+//! [5]: ../driver_char/index.html
+//! [6]: ../driver_input/index.html
+//! [7]: ../driver_rng/index.html
+//! [8]: ../driver_serial/index.html
 
 #![no_std]
 #![feature(const_trait_impl)]
@@ -27,6 +35,10 @@ pub enum DeviceType {
     Net,
     /// Graphic display device (e.g., GPU)
     Display,
+    /// Random number generator (entropy source).
+    Rng,
+    /// Input device (e.g., keyboard, mouse).
+    Input,
 }
 
 /// The error type for device operation failures.
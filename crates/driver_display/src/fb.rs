@@ -0,0 +1,203 @@
+use crate::{Color, DisplayInfo, PixelFormat, Rect};
+
+/// The framebuffer.
+///
+/// It's a special memory buffer that mapped from the device memory. The rows
+/// of pixels are [`stride`](Self::stride) bytes apart, which may be more than
+/// the bytes of the visible pixels of a row.
+///
+/// All the drawing operations are clipped to the visible area, and the
+/// pixels written are accumulated in the damaged rectangle, which can be
+/// taken by [`take_damage`](Self::take_damage) to flush only the changed part
+/// to the screen.
+pub struct Framebuffer<'a> {
+    raw: &'a mut [u8],
+    width: u32,
+    height: u32,
+    stride: usize,
+    format: PixelFormat,
+    damage: Rect,
+}
+
+impl<'a> Framebuffer<'a> {
+    /// Use the memory region described by `info` as the framebuffer.
+    ///
+    /// # Safety
+    ///
+    /// Caller must insure that the memory region at `info.fb_base_vaddr` of
+    /// `info.fb_size` bytes is valid and accessible, and not aliased during
+    /// `'a`.
+    pub unsafe fn from_info(info: &DisplayInfo) -> Self {
+        let raw = core::slice::from_raw_parts_mut(info.fb_base_vaddr as *mut u8, info.fb_size);
+        Self::from_slice(raw, info.width, info.height, info.stride, info.format)
+    }
+
+    /// Use the given slice as the framebuffer of `width` x `height` pixels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `stride` is less than a row of pixels, or `slice` is too
+    /// small.
+    pub fn from_slice(
+        slice: &'a mut [u8],
+        width: u32,
+        height: u32,
+        stride: u32,
+        format: PixelFormat,
+    ) -> Self {
+        let stride = stride as usize;
+        assert!(stride >= width as usize * format.bytes_per_pixel());
+        assert!(slice.len() >= stride * height as usize);
+        Self {
+            raw: slice,
+            width,
+            height,
+            stride,
+            format,
+            damage: Rect::default(),
+        }
+    }
+
+    /// The visible width in pixels.
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The visible height in pixels.
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The number of bytes between the starts of two rows.
+    pub const fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// The layout of the pixels.
+    pub const fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    /// The visible area.
+    pub const fn bounds(&self) -> Rect {
+        Rect::new(0, 0, self.width, self.height)
+    }
+
+    /// The raw bytes of the framebuffer.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.raw
+    }
+
+    /// The raw bytes of the framebuffer, for writing.
+    ///
+    /// The whole framebuffer is marked as damaged.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        self.add_damage(self.bounds());
+        self.raw
+    }
+
+    /// The bytes of the visible pixels of row `y`.
+    pub fn row(&self, y: u32) -> Option<&[u8]> {
+        let range = self.row_range(y)?;
+        Some(&self.raw[range])
+    }
+
+    /// The bytes of the visible pixels of row `y`, for writing.
+    ///
+    /// The row is marked as damaged.
+    pub fn row_mut(&mut self, y: u32) -> Option<&mut [u8]> {
+        let range = self.row_range(y)?;
+        self.add_damage(Rect::new(0, y, self.width, 1));
+        Some(&mut self.raw[range])
+    }
+
+    /// Returns the color of the pixel at (`x`, `y`).
+    pub fn pixel(&self, x: u32, y: u32) -> Option<Color> {
+        let offset = self.pixel_offset(x, y)?;
+        Some(self.format.decode(&self.raw[offset..]))
+    }
+
+    /// Sets the color of the pixel at (`x`, `y`), it's ignored if the pixel
+    /// is not visible.
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: Color) {
+        if let Some(offset) = self.pixel_offset(x, y) {
+            self.format.encode(color, &mut self.raw[offset..]);
+            self.add_damage(Rect::new(x, y, 1, 1));
+        }
+    }
+
+    /// Fills the pixels in `rect` with `color`.
+    pub fn fill(&mut self, rect: Rect, color: Color) {
+        let rect = rect.intersection(&self.bounds());
+        if rect.is_empty() {
+            return;
+        }
+        let bpp = self.format.bytes_per_pixel();
+        let mut pixel = [0; 4];
+        self.format.encode(color, &mut pixel);
+        for y in rect.y..rect.y + rect.height {
+            let start = y as usize * self.stride + rect.x as usize * bpp;
+            let end = start + rect.width as usize * bpp;
+            for dst in self.raw[start..end].chunks_exact_mut(bpp) {
+                dst.copy_from_slice(&pixel[..bpp]);
+            }
+        }
+        self.add_damage(rect);
+    }
+
+    /// Copies an image of `width` x `height` pixels, whose rows are
+    /// `src_stride` bytes apart in `src`, to (`x`, `y`).
+    ///
+    /// The image must be in the same pixel format as the framebuffer. The part
+    /// out of the visible area is not copied.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `src` is too small for the image.
+    pub fn blit(&mut self, x: u32, y: u32, width: u32, height: u32, src: &[u8], src_stride: usize) {
+        let bpp = self.format.bytes_per_pixel();
+        if height > 0 {
+            assert!(src.len() >= (height as usize - 1) * src_stride + width as usize * bpp);
+        }
+        let rect = Rect::new(x, y, width, height).intersection(&self.bounds());
+        if rect.is_empty() {
+            return;
+        }
+        let row_len = rect.width as usize * bpp;
+        for row in 0..rect.height as usize {
+            let src_start = row * src_stride;
+            let dst_start = (rect.y as usize + row) * self.stride + rect.x as usize * bpp;
+            self.raw[dst_start..dst_start + row_len]
+                .copy_from_slice(&src[src_start..src_start + row_len]);
+        }
+        self.add_damage(rect);
+    }
+
+    /// Marks `rect` as damaged, i.e., to be flushed to the screen.
+    pub fn add_damage(&mut self, rect: Rect) {
+        let rect = rect.intersection(&self.bounds());
+        self.damage = self.damage.union(&rect);
+    }
+
+    /// Returns the smallest rectangle that contains all the pixels written
+    /// since the last call, or `None` if nothing is written.
+    pub fn take_damage(&mut self) -> Option<Rect> {
+        let damage = core::mem::take(&mut self.damage);
+        (!damage.is_empty()).then_some(damage)
+    }
+
+    fn row_range(&self, y: u32) -> Option<core::ops::Range<usize>> {
+        if y >= self.height {
+            return None;
+        }
+        let start = y as usize * self.stride;
+        Some(start..start + self.width as usize * self.format.bytes_per_pixel())
+    }
+
+    fn pixel_offset(&self, x: u32, y: u32) -> Option<usize> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(y as usize * self.stride + x as usize * self.format.bytes_per_pixel())
+    }
+}
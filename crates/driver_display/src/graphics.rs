@@ -0,0 +1,64 @@
+//! [`embedded_graphics_core`] support of the framebuffer.
+
+use core::convert::Infallible;
+
+use embedded_graphics_core::pixelcolor::{Rgb888, RgbColor};
+use embedded_graphics_core::prelude::{DrawTarget, OriginDimensions, Pixel, Size};
+use embedded_graphics_core::primitives::Rectangle;
+
+use crate::{Color, Framebuffer, Rect};
+
+impl From<Rgb888> for Color {
+    fn from(color: Rgb888) -> Self {
+        Self::new(color.r(), color.g(), color.b())
+    }
+}
+
+impl From<Color> for Rgb888 {
+    fn from(color: Color) -> Self {
+        Self::new(color.r, color.g, color.b)
+    }
+}
+
+/// Converts `area` to a [`Rect`], clipping the part with negative
+/// coordinates.
+fn to_rect(area: &Rectangle) -> Rect {
+    let (x, y) = (area.top_left.x, area.top_left.y);
+    let width = (x as i64 + area.size.width as i64).max(0) as u32;
+    let height = (y as i64 + area.size.height as i64).max(0) as u32;
+    let (x, y) = (x.max(0) as u32, y.max(0) as u32);
+    Rect::new(x, y, width.saturating_sub(x), height.saturating_sub(y))
+}
+
+impl OriginDimensions for Framebuffer<'_> {
+    fn size(&self) -> Size {
+        Size::new(self.width(), self.height())
+    }
+}
+
+impl DrawTarget for Framebuffer<'_> {
+    type Color = Rgb888;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if let (Ok(x), Ok(y)) = (u32::try_from(point.x), u32::try_from(point.y)) {
+                self.set_pixel(x, y, color.into());
+            }
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill(to_rect(area), color.into());
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill(self.bounds(), color.into());
+        Ok(())
+    }
+}
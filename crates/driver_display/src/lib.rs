@@ -1,9 +1,164 @@
 //! Common traits and types for graphics display device drivers.
 
 #![no_std]
+#![feature(doc_auto_cfg)]
+
+mod fb;
+#[cfg(feature = "embedded-graphics")]
+mod graphics;
+
+pub use self::fb::Framebuffer;
 
 use driver_common::{BaseDriverOps, DevResult};
 
+/// The layout of a pixel in the framebuffer.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PixelFormat {
+    /// 3 bytes per pixel, red, green and blue in the order of addresses.
+    Rgb888,
+    /// 4 bytes per pixel, blue, green, red and alpha in the order of
+    /// addresses.
+    Bgra8888,
+    /// 2 bytes per pixel, a little-endian `u16` with red in the 5 most
+    /// significant bits, green in the middle 6 bits and blue in the 5 least
+    /// significant bits.
+    Rgb565,
+}
+
+impl PixelFormat {
+    /// The number of bytes of a pixel.
+    pub const fn bytes_per_pixel(self) -> usize {
+        match self {
+            Self::Rgb888 => 3,
+            Self::Bgra8888 => 4,
+            Self::Rgb565 => 2,
+        }
+    }
+
+    /// Encodes `color` to the first [`bytes_per_pixel`] bytes of `pixel`.
+    ///
+    /// [`bytes_per_pixel`]: Self::bytes_per_pixel
+    pub fn encode(self, color: Color, pixel: &mut [u8]) {
+        match self {
+            Self::Rgb888 => pixel[..3].copy_from_slice(&[color.r, color.g, color.b]),
+            Self::Bgra8888 => pixel[..4].copy_from_slice(&[color.b, color.g, color.r, 0xff]),
+            Self::Rgb565 => {
+                let value =
+                    (color.r as u16 >> 3) << 11 | (color.g as u16 >> 2) << 5 | color.b as u16 >> 3;
+                pixel[..2].copy_from_slice(&value.to_le_bytes());
+            }
+        }
+    }
+
+    /// Decodes the color from the first [`bytes_per_pixel`] bytes of
+    /// `pixel`.
+    ///
+    /// [`bytes_per_pixel`]: Self::bytes_per_pixel
+    pub fn decode(self, pixel: &[u8]) -> Color {
+        match self {
+            Self::Rgb888 => Color::new(pixel[0], pixel[1], pixel[2]),
+            Self::Bgra8888 => Color::new(pixel[2], pixel[1], pixel[0]),
+            Self::Rgb565 => {
+                let value = u16::from_le_bytes([pixel[0], pixel[1]]);
+                let (r, g, b) = (value >> 11, (value >> 5) & 0x3f, value & 0x1f);
+                // scale to 8 bits, replicating the high bits to the low bits
+                Color::new(
+                    (r << 3 | r >> 2) as u8,
+                    (g << 2 | g >> 4) as u8,
+                    (b << 3 | b >> 2) as u8,
+                )
+            }
+        }
+    }
+}
+
+/// A color with 8 bits per channel.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct Color {
+    /// The red channel.
+    pub r: u8,
+    /// The green channel.
+    pub g: u8,
+    /// The blue channel.
+    pub b: u8,
+}
+
+impl Color {
+    /// Black.
+    pub const BLACK: Self = Self::new(0, 0, 0);
+    /// White.
+    pub const WHITE: Self = Self::new(0xff, 0xff, 0xff);
+
+    /// Creates a color from its channels.
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+}
+
+/// A rectangle in the framebuffer, in pixels.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct Rect {
+    /// The left edge.
+    pub x: u32,
+    /// The top edge.
+    pub y: u32,
+    /// The width.
+    pub width: u32,
+    /// The height.
+    pub height: u32,
+}
+
+impl Rect {
+    /// Creates a rectangle from its top-left corner and size.
+    pub const fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Whether the rectangle contains no pixel.
+    pub const fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// The pixels in both `self` and `other`, which may be empty.
+    pub fn intersection(&self, other: &Self) -> Self {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+        Self::new(x, y, right.saturating_sub(x), bottom.saturating_sub(y))
+    }
+
+    /// The smallest rectangle that contains both `self` and `other`.
+    ///
+    /// Empty rectangles are ignored.
+    pub fn union(&self, other: &Self) -> Self {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = self.right().max(other.right());
+        let bottom = self.bottom().max(other.bottom());
+        Self::new(x, y, right - x, bottom - y)
+    }
+
+    fn right(&self) -> u32 {
+        self.x.saturating_add(self.width)
+    }
+
+    fn bottom(&self) -> u32 {
+        self.y.saturating_add(self.height)
+    }
+}
+
 /// The information of the graphics device.
 #[derive(Debug, Clone, Copy)]
 pub struct DisplayInfo {
@@ -11,48 +166,35 @@ pub struct DisplayInfo {
     pub width: u32,
     /// The visible height.
     pub height: u32,
+    /// The number of bytes between the starts of two rows.
+    pub stride: u32,
+    /// The layout of the pixels.
+    pub format: PixelFormat,
     /// The base virtual address of the framebuffer.
     pub fb_base_vaddr: usize,
     /// The size of the framebuffer in bytes.
     pub fb_size: usize,
 }
 
-/// The framebuffer.
-///
-/// It's a special memory buffer that mapped from the device memory.
-pub struct FrameBuffer<'a> {
-    _raw: &'a mut [u8],
-}
-
-impl<'a> FrameBuffer<'a> {
-    /// Use the given raw pointer and size as the framebuffer.
-    ///
-    /// # Safety
-    ///
-    /// Caller must insure that the given memory region is valid and accessible.
-    pub unsafe fn from_raw_parts_mut(ptr: *mut u8, len: usize) -> Self {
-        Self {
-            _raw: core::slice::from_raw_parts_mut(ptr, len),
-        }
-    }
-
-    /// Use the given slice as the framebuffer.
-    pub fn from_slice(slice: &'a mut [u8]) -> Self {
-        Self { _raw: slice }
-    }
-}
-
 /// Operations that require a graphics device driver to implement.
 pub trait DisplayDriverOps: BaseDriverOps {
     /// Get the display information.
     fn info(&self) -> DisplayInfo;
 
     /// Get the framebuffer.
-    fn fb(&self) -> FrameBuffer;
+    fn fb(&self) -> Framebuffer;
 
     /// Whether need to flush the framebuffer to the screen.
     fn need_flush(&self) -> bool;
 
     /// Flush framebuffer to the screen.
     fn flush(&mut self) -> DevResult;
+
+    /// Flush the pixels in `rect` of the framebuffer to the screen.
+    ///
+    /// The default implementation flushes the whole framebuffer.
+    fn flush_rect(&mut self, rect: Rect) -> DevResult {
+        let _ = rect;
+        self.flush()
+    }
 }
@@ -0,0 +1,74 @@
+//! Common traits and types for input device drivers (e.g., keyboards and
+//! mice).
+//!
+//! The events follow the Linux input event model: each event has a type, a
+//! code and a value, and a group of events is terminated by
+//! [`EventType::Syn`].
+
+#![no_std]
+
+use driver_common::{BaseDriverOps, DevResult};
+
+/// The type of an input event.
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum EventType {
+    /// Separates groups of events.
+    Syn = 0x00,
+    /// Key or button state changes, the value is 1 for press, 0 for release
+    /// and 2 for auto-repeat.
+    Key = 0x01,
+    /// Relative axis changes (e.g., mouse movement).
+    Relative = 0x02,
+    /// Absolute axis changes (e.g., tablet position).
+    Absolute = 0x03,
+    /// Miscellaneous events.
+    Misc = 0x04,
+}
+
+impl EventType {
+    /// Converts from the raw event type, or returns `None` if it is unknown.
+    pub const fn from_raw(raw: u16) -> Option<Self> {
+        match raw {
+            0x00 => Some(Self::Syn),
+            0x01 => Some(Self::Key),
+            0x02 => Some(Self::Relative),
+            0x03 => Some(Self::Absolute),
+            0x04 => Some(Self::Misc),
+            _ => None,
+        }
+    }
+}
+
+/// An input event.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct InputEvent {
+    /// The raw event type, see [`EventType`].
+    pub event_type: u16,
+    /// The key code or axis, depending on the event type.
+    pub code: u16,
+    /// The key state or axis value, depending on the event type.
+    pub value: u32,
+}
+
+impl InputEvent {
+    /// The type of the event, or `None` if it is unknown.
+    pub const fn event_type(&self) -> Option<EventType> {
+        EventType::from_raw(self.event_type)
+    }
+}
+
+/// Operations that require an input device driver to implement.
+pub trait InputDriverOps: BaseDriverOps {
+    /// Takes the next pending event, or returns `None` if there is none.
+    fn read_event(&mut self) -> Option<InputEvent>;
+
+    /// Returns whether the device may generate events of `event_type`.
+    fn has_event_type(&mut self, event_type: EventType) -> DevResult<bool>;
+
+    /// Acknowledges the interrupt of the device, returns whether the device
+    /// raised it.
+    fn ack_interrupt(&mut self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,499 @@
+//! Driver for Intel 8254x (e1000) and 82574 (e1000e) gigabit ethernet
+//! controllers.
+//!
+//! Only the legacy descriptor formats are used, which are supported by the
+//! whole family. Packets are copied between the DMA buffers of the rings and
+//! the heap buffers, so the receive descriptors are given back to the NIC as
+//! soon as the packets are taken. The tail registers are only written on
+//! flushing, once for a batch of packets.
+
+extern crate alloc;
+
+use alloc::{vec, vec::Vec};
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+use core::sync::atomic::{fence, Ordering};
+
+use crate::{EthernetAddress, NetBuffer, NetCapabilities, NetDriverOps, DEFAULT_MTU};
+use driver_common::{BaseDriverOps, DevError, DevResult, DeviceType};
+
+/// The PCI vendor ID of Intel.
+pub const INTEL_VENDOR_ID: u16 = 0x8086;
+
+/// The PCI device IDs supported by the driver.
+pub const E1000_DEVICE_IDS: &[u16] = &[
+    0x100e, // 82540EM (QEMU `e1000`)
+    0x100f, // 82545EM
+    0x1004, // 82543GC
+    0x10d3, // 82574L (QEMU `e1000e`)
+];
+
+const PAGE_SIZE: usize = 0x1000;
+
+// Registers
+const REG_CTRL: usize = 0x0000;
+const REG_STATUS: usize = 0x0008;
+const REG_ICR: usize = 0x00c0;
+const REG_IMS: usize = 0x00d0;
+const REG_IMC: usize = 0x00d8;
+const REG_RCTL: usize = 0x0100;
+const REG_TCTL: usize = 0x0400;
+const REG_TIPG: usize = 0x0410;
+const REG_RDBAL: usize = 0x2800;
+const REG_RDBAH: usize = 0x2804;
+const REG_RDLEN: usize = 0x2808;
+const REG_RDH: usize = 0x2810;
+const REG_RDT: usize = 0x2818;
+const REG_TDBAL: usize = 0x3800;
+const REG_TDBAH: usize = 0x3804;
+const REG_TDLEN: usize = 0x3808;
+const REG_TDH: usize = 0x3810;
+const REG_TDT: usize = 0x3818;
+const REG_MTA: usize = 0x5200;
+const REG_RAL0: usize = 0x5400;
+const REG_RAH0: usize = 0x5404;
+
+const CTRL_ASDE: u32 = 1 << 5;
+const CTRL_SLU: u32 = 1 << 6;
+const CTRL_RST: u32 = 1 << 26;
+const STATUS_LU: u32 = 1 << 1;
+
+const RCTL_EN: u32 = 1 << 1;
+const RCTL_BAM: u32 = 1 << 15;
+const RCTL_SECRC: u32 = 1 << 26; // strip the ethernet CRC, buffer size is 2048 by default
+const TCTL_EN: u32 = 1 << 1;
+const TCTL_PSP: u32 = 1 << 3;
+const TCTL_CT: u32 = 0x10 << 4;
+const TCTL_COLD: u32 = 0x40 << 12;
+const TIPG_DEFAULT: u32 = 10 | 8 << 10 | 6 << 20;
+
+const INT_TXDW: u32 = 1 << 0;
+const INT_LSC: u32 = 1 << 2;
+const INT_RXDMT0: u32 = 1 << 4;
+const INT_RXO: u32 = 1 << 6;
+const INT_RXT0: u32 = 1 << 7;
+
+const DESC_STATUS_DD: u8 = 1 << 0;
+const DESC_STATUS_EOP: u8 = 1 << 1;
+const TX_CMD_EOP: u8 = 1 << 0;
+const TX_CMD_IFCS: u8 = 1 << 1;
+const TX_CMD_RS: u8 = 1 << 3;
+
+/// The number of descriptors of each ring.
+const QUEUE_SIZE: usize = 64;
+/// The maximum number of the packets in flight. One TX descriptor is always
+/// kept free, since the ring is seen as empty by the device if `TDT == TDH`.
+const TX_MAX_INFLIGHT: usize = QUEUE_SIZE - 1;
+/// The size of each packet buffer.
+const BUF_SIZE: usize = 2048;
+
+/// The hardware abstraction that the e1000 driver needs.
+///
+/// # Safety
+///
+/// The memory returned by [`dma_alloc`](Self::dma_alloc) must be physically
+/// contiguous, mapped at the returned virtual address, and accessible by the
+/// device at the returned physical address.
+pub unsafe trait E1000Hal {
+    /// Allocates `pages` contiguous pages for DMA, returns their physical and
+    /// virtual addresses, or `None` if out of memory.
+    fn dma_alloc(pages: usize) -> Option<(usize, NonNull<u8>)>;
+
+    /// Deallocates the pages allocated by [`dma_alloc`](Self::dma_alloc).
+    ///
+    /// # Safety
+    ///
+    /// The pages must not be accessed by the device anymore.
+    unsafe fn dma_dealloc(paddr: usize, vaddr: NonNull<u8>, pages: usize);
+}
+
+/// A legacy receive descriptor.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct RxDesc {
+    addr: u64,
+    length: u16,
+    checksum: u16,
+    status: u8,
+    errors: u8,
+    special: u16,
+}
+
+/// A legacy transmit descriptor.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct TxDesc {
+    addr: u64,
+    length: u16,
+    cso: u8,
+    cmd: u8,
+    status: u8,
+    css: u8,
+    special: u16,
+}
+
+/// A descriptor ring and the packet buffers of its descriptors.
+struct Ring<H: E1000Hal> {
+    paddr: usize,
+    vaddr: NonNull<u8>,
+    _hal: PhantomData<H>,
+}
+
+impl<H: E1000Hal> Ring<H> {
+    /// The descriptors take the first page, followed by the buffers.
+    const PAGES: usize = 1 + QUEUE_SIZE * BUF_SIZE / PAGE_SIZE;
+
+    fn new() -> DevResult<Self> {
+        let (paddr, vaddr) = H::dma_alloc(Self::PAGES).ok_or(DevError::NoMemory)?;
+        unsafe { core::ptr::write_bytes(vaddr.as_ptr(), 0, Self::PAGES * PAGE_SIZE) };
+        Ok(Self {
+            paddr,
+            vaddr,
+            _hal: PhantomData,
+        })
+    }
+
+    fn desc<D>(&self, idx: usize) -> *mut D {
+        unsafe { (self.vaddr.as_ptr() as *mut D).add(idx) }
+    }
+
+    fn buf_paddr(&self, idx: usize) -> u64 {
+        (self.paddr + PAGE_SIZE + idx * BUF_SIZE) as u64
+    }
+
+    fn buf(&self, idx: usize) -> *mut u8 {
+        unsafe { self.vaddr.as_ptr().add(PAGE_SIZE + idx * BUF_SIZE) }
+    }
+}
+
+impl<H: E1000Hal> Drop for Ring<H> {
+    fn drop(&mut self) {
+        unsafe { H::dma_dealloc(self.paddr, self.vaddr, Self::PAGES) };
+    }
+}
+
+/// The packet buffer used by [`E1000Dev`].
+pub struct E1000Buffer(Vec<u8>);
+
+impl NetBuffer for E1000Buffer {
+    fn packet_len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn packet(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+
+    fn packet_mut(&mut self) -> &mut [u8] {
+        self.0.as_mut_slice()
+    }
+}
+
+/// The e1000 NIC driver.
+pub struct E1000Dev<H: E1000Hal> {
+    mmio_base: usize,
+    mac: [u8; 6],
+    link_up: bool,
+    rx_ring: Ring<H>,
+    tx_ring: Ring<H>,
+    /// The next receive descriptor to be checked.
+    rx_next: usize,
+    /// The receive tail to be written on flushing, if any descriptors were
+    /// given back since the last flush.
+    rx_tail: Option<usize>,
+    /// The next transmit descriptor to be used.
+    tx_next: usize,
+    /// The oldest transmit descriptor that is not reclaimed.
+    tx_clean: usize,
+    /// The number of transmit descriptors that are not reclaimed.
+    tx_inflight: usize,
+    /// Whether packets were submitted since the last flush.
+    tx_pending: bool,
+}
+
+unsafe impl<H: E1000Hal> Send for E1000Dev<H> {}
+unsafe impl<H: E1000Hal> Sync for E1000Dev<H> {}
+
+impl<H: E1000Hal> E1000Dev<H> {
+    /// Creates a new driver instance for the NIC whose registers (BAR 0) are
+    /// mapped at `mmio_base`, and initializes it.
+    ///
+    /// # Safety
+    ///
+    /// `mmio_base` must be the virtual address of the mapped registers of an
+    /// e1000 NIC, which is not used by others.
+    pub unsafe fn try_new(mmio_base: usize) -> DevResult<Self> {
+        let mut dev = Self {
+            mmio_base,
+            mac: [0; 6],
+            link_up: false,
+            rx_ring: Ring::new()?,
+            tx_ring: Ring::new()?,
+            rx_next: 0,
+            rx_tail: None,
+            tx_next: 0,
+            tx_clean: 0,
+            tx_inflight: 0,
+            tx_pending: false,
+        };
+        dev.reset();
+        dev.read_mac_address()?;
+        dev.init_rx();
+        dev.init_tx();
+        dev.link_up = dev.read_reg(REG_STATUS) & STATUS_LU != 0;
+        dev.write_reg(
+            REG_IMS,
+            INT_TXDW | INT_LSC | INT_RXDMT0 | INT_RXO | INT_RXT0,
+        );
+        Ok(dev)
+    }
+
+    /// Whether the link is up, updated on link status change interrupts.
+    pub fn link_up(&self) -> bool {
+        self.link_up
+    }
+
+    fn read_reg(&self, offset: usize) -> u32 {
+        unsafe { ((self.mmio_base + offset) as *const u32).read_volatile() }
+    }
+
+    fn write_reg(&mut self, offset: usize, value: u32) {
+        unsafe { ((self.mmio_base + offset) as *mut u32).write_volatile(value) }
+    }
+
+    fn reset(&mut self) {
+        self.write_reg(REG_IMC, u32::MAX);
+        let ctrl = self.read_reg(REG_CTRL);
+        self.write_reg(REG_CTRL, ctrl | CTRL_RST);
+        while self.read_reg(REG_CTRL) & CTRL_RST != 0 {
+            core::hint::spin_loop();
+        }
+        // the interrupts are enabled again after reset
+        self.write_reg(REG_IMC, u32::MAX);
+        self.read_reg(REG_ICR);
+
+        let ctrl = self.read_reg(REG_CTRL);
+        self.write_reg(REG_CTRL, ctrl | CTRL_SLU | CTRL_ASDE);
+        for i in 0..128 {
+            self.write_reg(REG_MTA + i * 4, 0);
+        }
+    }
+
+    /// Reads the MAC address from the receive address registers, which are
+    /// loaded from the EEPROM on reset.
+    fn read_mac_address(&mut self) -> DevResult {
+        let low = self.read_reg(REG_RAL0);
+        let high = self.read_reg(REG_RAH0);
+        let bytes = (low as u64 | (high as u64 & 0xffff) << 32).to_le_bytes();
+        self.mac.copy_from_slice(&bytes[..6]);
+        if self.mac == [0; 6] {
+            return Err(DevError::BadState);
+        }
+        // mark the address valid, in case it was not
+        self.write_reg(REG_RAH0, high | 1 << 31);
+        Ok(())
+    }
+
+    fn init_rx(&mut self) {
+        for i in 0..QUEUE_SIZE {
+            let desc = RxDesc {
+                addr: self.rx_ring.buf_paddr(i),
+                length: 0,
+                checksum: 0,
+                status: 0,
+                errors: 0,
+                special: 0,
+            };
+            unsafe { self.rx_ring.desc::<RxDesc>(i).write_volatile(desc) };
+        }
+        let paddr = self.rx_ring.paddr as u64;
+        self.write_reg(REG_RDBAL, paddr as u32);
+        self.write_reg(REG_RDBAH, (paddr >> 32) as u32);
+        self.write_reg(
+            REG_RDLEN,
+            (QUEUE_SIZE * core::mem::size_of::<RxDesc>()) as u32,
+        );
+        self.write_reg(REG_RDH, 0);
+        // all descriptors except the one at the tail belong to the NIC
+        self.write_reg(REG_RDT, QUEUE_SIZE as u32 - 1);
+        self.write_reg(REG_RCTL, RCTL_EN | RCTL_BAM | RCTL_SECRC);
+    }
+
+    fn init_tx(&mut self) {
+        for i in 0..QUEUE_SIZE {
+            let desc = TxDesc {
+                addr: self.tx_ring.buf_paddr(i),
+                length: 0,
+                cso: 0,
+                cmd: 0,
+                status: 0,
+                css: 0,
+                special: 0,
+            };
+            unsafe { self.tx_ring.desc::<TxDesc>(i).write_volatile(desc) };
+        }
+        let paddr = self.tx_ring.paddr as u64;
+        self.write_reg(REG_TDBAL, paddr as u32);
+        self.write_reg(REG_TDBAH, (paddr >> 32) as u32);
+        self.write_reg(
+            REG_TDLEN,
+            (QUEUE_SIZE * core::mem::size_of::<TxDesc>()) as u32,
+        );
+        self.write_reg(REG_TDH, 0);
+        self.write_reg(REG_TDT, 0);
+        self.write_reg(REG_TIPG, TIPG_DEFAULT);
+        self.write_reg(REG_TCTL, TCTL_EN | TCTL_PSP | TCTL_CT | TCTL_COLD);
+    }
+
+    fn rx_desc(&self) -> RxDesc {
+        unsafe { self.rx_ring.desc::<RxDesc>(self.rx_next).read_volatile() }
+    }
+
+    /// Gives the current receive descriptor back to the NIC, the tail register
+    /// is updated on flushing.
+    fn rx_rearm(&mut self) {
+        let idx = self.rx_next;
+        unsafe {
+            self.rx_ring.desc::<RxDesc>(idx).write_volatile(RxDesc {
+                addr: self.rx_ring.buf_paddr(idx),
+                length: 0,
+                checksum: 0,
+                status: 0,
+                errors: 0,
+                special: 0,
+            });
+        }
+        self.rx_tail = Some(idx);
+        self.rx_next = (idx + 1) % QUEUE_SIZE;
+    }
+
+    fn tx_desc_status(&self, idx: usize) -> u8 {
+        unsafe { core::ptr::addr_of!((*self.tx_ring.desc::<TxDesc>(idx)).status).read_volatile() }
+    }
+}
+
+impl<H: E1000Hal> BaseDriverOps for E1000Dev<H> {
+    fn device_name(&self) -> &str {
+        "e1000"
+    }
+
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Net
+    }
+}
+
+impl<H: E1000Hal> NetDriverOps for E1000Dev<H> {
+    type RxBuffer = E1000Buffer;
+    type TxBuffer = E1000Buffer;
+
+    #[inline]
+    fn mac_address(&self) -> EthernetAddress {
+        EthernetAddress(self.mac)
+    }
+
+    fn capabilities(&self) -> NetCapabilities {
+        NetCapabilities {
+            mtu: DEFAULT_MTU,
+            max_burst: Some(TX_MAX_INFLIGHT),
+            ..Default::default()
+        }
+    }
+
+    fn can_send(&self) -> bool {
+        self.link_up && self.tx_inflight < TX_MAX_INFLIGHT
+    }
+
+    fn can_recv(&self) -> bool {
+        self.rx_desc().status & DESC_STATUS_DD != 0
+    }
+
+    fn ack_interrupt(&mut self) -> bool {
+        let icr = self.read_reg(REG_ICR); // cleared on read
+        if icr & INT_LSC != 0 {
+            self.link_up = self.read_reg(REG_STATUS) & STATUS_LU != 0;
+        }
+        icr != 0
+    }
+
+    fn new_tx_buffer(&mut self, buf_len: usize) -> DevResult<Self::TxBuffer> {
+        if buf_len > BUF_SIZE {
+            return Err(DevError::InvalidParam);
+        }
+        Ok(E1000Buffer(vec![0; buf_len]))
+    }
+
+    fn recycle_rx_buffer(&mut self, _rx_buf: Self::RxBuffer) -> DevResult {
+        Ok(())
+    }
+
+    fn transmit(&mut self, tx_buf: Self::TxBuffer) -> DevResult {
+        if !self.can_send() {
+            return Err(DevError::Again);
+        }
+        let idx = self.tx_next;
+        let len = tx_buf.packet_len();
+        unsafe {
+            core::ptr::copy_nonoverlapping(tx_buf.packet().as_ptr(), self.tx_ring.buf(idx), len);
+            self.tx_ring.desc::<TxDesc>(idx).write_volatile(TxDesc {
+                addr: self.tx_ring.buf_paddr(idx),
+                length: len as u16,
+                cso: 0,
+                cmd: TX_CMD_EOP | TX_CMD_IFCS | TX_CMD_RS,
+                status: 0,
+                css: 0,
+                special: 0,
+            });
+        }
+        self.tx_next = (idx + 1) % QUEUE_SIZE;
+        self.tx_inflight += 1;
+        self.tx_pending = true;
+        Ok(())
+    }
+
+    fn reclaim_tx_buffers(&mut self) -> usize {
+        let mut count = 0;
+        while self.tx_inflight > 0 && self.tx_desc_status(self.tx_clean) & DESC_STATUS_DD != 0 {
+            self.tx_clean = (self.tx_clean + 1) % QUEUE_SIZE;
+            self.tx_inflight -= 1;
+            count += 1;
+        }
+        count
+    }
+
+    fn flush(&mut self) -> DevResult {
+        fence(Ordering::SeqCst);
+        if let Some(tail) = self.rx_tail.take() {
+            self.write_reg(REG_RDT, tail as u32);
+        }
+        if self.tx_pending {
+            self.tx_pending = false;
+            self.write_reg(REG_TDT, self.tx_next as u32);
+        }
+        Ok(())
+    }
+
+    fn receive(&mut self) -> DevResult<Self::RxBuffer> {
+        loop {
+            let desc = self.rx_desc();
+            if desc.status & DESC_STATUS_DD == 0 {
+                return Err(DevError::Again);
+            }
+            fence(Ordering::SeqCst);
+            // packets larger than a buffer are not expected, as long packets
+            // are not enabled, and the bad ones are dropped
+            let packet = if desc.status & DESC_STATUS_EOP != 0 && desc.errors == 0 {
+                let len = (desc.length as usize).min(BUF_SIZE);
+                let mut packet = vec![0; len];
+                let src = self.rx_ring.buf(self.rx_next);
+                unsafe { core::ptr::copy_nonoverlapping(src, packet.as_mut_ptr(), len) };
+                Some(E1000Buffer(packet))
+            } else {
+                None
+            };
+            self.rx_rearm();
+            if let Some(packet) = packet {
+                return Ok(packet);
+            }
+        }
+    }
+}
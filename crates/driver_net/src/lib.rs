@@ -1,12 +1,80 @@
 //! Common traits and types for network device (NIC) drivers.
 
 #![no_std]
+#![feature(doc_auto_cfg)]
+
+#[cfg(feature = "e1000")]
+pub mod e1000;
+#[cfg(feature = "loopback")]
+pub mod loopback;
 
 use driver_common::{BaseDriverOps, DevResult};
 
 /// The ethernet address of the NIC (MAC address).
 pub struct EthernetAddress(pub [u8; 6]);
 
+/// The default maximum transmission unit of ethernet.
+pub const DEFAULT_MTU: usize = 1500;
+
+/// The protocols whose checksums are offloaded to the NIC.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct ChecksumOffload {
+    /// The IPv4 header checksum.
+    pub ipv4: bool,
+    /// The TCP checksum.
+    pub tcp: bool,
+    /// The UDP checksum.
+    pub udp: bool,
+    /// The ICMP checksum.
+    pub icmp: bool,
+}
+
+impl ChecksumOffload {
+    /// No checksum is offloaded.
+    pub const NONE: Self = Self {
+        ipv4: false,
+        tcp: false,
+        udp: false,
+        icmp: false,
+    };
+
+    /// All checksums are offloaded.
+    pub const ALL: Self = Self {
+        ipv4: true,
+        tcp: true,
+        udp: true,
+        icmp: true,
+    };
+}
+
+/// The capabilities of a NIC.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct NetCapabilities {
+    /// The maximum transmission unit, i.e., the max size of an IP packet,
+    /// excluding the link layer header.
+    pub mtu: usize,
+    /// The max number of packets that can be transmitted before any of them
+    /// are reclaimed, or `None` if unlimited.
+    pub max_burst: Option<usize>,
+    /// The checksums verified by the NIC on receiving. Packets with bad
+    /// checksums are dropped and never returned by the driver.
+    pub rx_checksum: ChecksumOffload,
+    /// The checksums computed by the NIC on transmitting, which should be left
+    /// as zeros in the packets.
+    pub tx_checksum: ChecksumOffload,
+}
+
+impl Default for NetCapabilities {
+    fn default() -> Self {
+        Self {
+            mtu: DEFAULT_MTU,
+            max_burst: None,
+            rx_checksum: ChecksumOffload::NONE,
+            tx_checksum: ChecksumOffload::NONE,
+        }
+    }
+}
+
 /// The abstract buffer for transmitting or receiving data.
 pub trait NetBuffer {
     /// The length of the packet.
@@ -18,6 +86,21 @@ pub trait NetBuffer {
 }
 
 /// Operations that require a network device (NIC) driver to implement.
+///
+/// All operations are non-blocking. Packets are submitted by [`transmit`][1]
+/// in batches: the driver may not notify the device until [`flush`][2] is
+/// called, and the transmitted buffers are reclaimed later by
+/// [`reclaim_tx_buffers`][3], after the device has sent them. Received buffers
+/// given back by [`recycle_rx_buffer`][4] are also re-posted on flushing.
+///
+/// When the NIC runs out of buffers or queue entries, the operations fail with
+/// [`DevError::Again`][5] rather than waiting.
+///
+/// [1]: NetDriverOps::transmit
+/// [2]: NetDriverOps::flush
+/// [3]: NetDriverOps::reclaim_tx_buffers
+/// [4]: NetDriverOps::recycle_rx_buffer
+/// [5]: driver_common::DevError::Again
 pub trait NetDriverOps: BaseDriverOps {
     /// The type of the receive buffer.
     type RxBuffer: NetBuffer;
@@ -27,21 +110,51 @@ pub trait NetDriverOps: BaseDriverOps {
     /// The ethernet address of the NIC.
     fn mac_address(&self) -> EthernetAddress;
 
-    /// Whether can send data.
+    /// The capabilities of the NIC.
+    fn capabilities(&self) -> NetCapabilities {
+        NetCapabilities::default()
+    }
+
+    /// Whether a packet can be transmitted now, without reclaiming the
+    /// transmitted buffers first.
     fn can_send(&self) -> bool;
 
     /// Whether can receive data.
     fn can_recv(&self) -> bool;
 
+    /// Acknowledges the interrupt of the device, returns whether there was an
+    /// interrupt pending.
+    fn ack_interrupt(&mut self) -> bool;
+
     /// Allocates a new buffer for transmitting.
+    ///
+    /// Returns [`DevError::Again`][1] if all buffers are in use, even after
+    /// reclaiming the transmitted ones.
+    ///
+    /// [1]: driver_common::DevError::Again
     fn new_tx_buffer(&mut self, buf_len: usize) -> DevResult<Self::TxBuffer>;
 
     /// Gives back the ownership of `rx_buf`, and recycles it for later receiving.
     fn recycle_rx_buffer(&mut self, rx_buf: Self::RxBuffer) -> DevResult;
 
-    /// Sends data in the buffer to the network, and blocks until the request
-    /// completed.
-    fn send(&mut self, tx_buf: Self::TxBuffer) -> DevResult;
+    /// Submits the packet in the buffer for transmitting, without waiting for
+    /// it to be sent.
+    ///
+    /// Returns [`DevError::Again`][1] if the transmit queue is full, and the
+    /// packet is dropped.
+    ///
+    /// [1]: driver_common::DevError::Again
+    fn transmit(&mut self, tx_buf: Self::TxBuffer) -> DevResult;
+
+    /// Reclaims the buffers of the packets that have been sent by the device,
+    /// returns the number of them.
+    fn reclaim_tx_buffers(&mut self) -> usize;
+
+    /// Notifies the device of the packets submitted and the receive buffers
+    /// recycled since the last flush.
+    fn flush(&mut self) -> DevResult {
+        Ok(())
+    }
 
     /// Receives data from the network and store it in the [`RxBuffer`][1],
     /// returns the buffer.
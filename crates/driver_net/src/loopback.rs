@@ -0,0 +1,115 @@
+//! Loopback network device that delivers every transmitted packet back to
+//! itself.
+
+extern crate alloc;
+
+use crate::{ChecksumOffload, EthernetAddress, NetBuffer, NetCapabilities, NetDriverOps};
+use alloc::{collections::VecDeque, vec, vec::Vec};
+use driver_common::{BaseDriverOps, DevError, DevResult, DeviceType};
+
+/// The packet buffer used by [`LoopbackDev`].
+pub struct LoopbackBuffer(Vec<u8>);
+
+impl NetBuffer for LoopbackBuffer {
+    fn packet_len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn packet(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+
+    fn packet_mut(&mut self) -> &mut [u8] {
+        self.0.as_mut_slice()
+    }
+}
+
+/// A loopback device that queues the sent packets in memory, and returns them
+/// on the later receiving in FIFO order.
+///
+/// Packets are raw IP packets without any link layer headers. As they never
+/// leave the memory, checksums are neither computed nor verified.
+pub struct LoopbackDev {
+    queue: VecDeque<LoopbackBuffer>,
+}
+
+impl LoopbackDev {
+    /// Creates a new loopback device with an empty packet queue.
+    pub const fn new() -> Self {
+        Self {
+            queue: VecDeque::new(),
+        }
+    }
+}
+
+impl Default for LoopbackDev {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BaseDriverOps for LoopbackDev {
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Net
+    }
+
+    fn device_name(&self) -> &str {
+        "loopback"
+    }
+}
+
+impl NetDriverOps for LoopbackDev {
+    type RxBuffer = LoopbackBuffer;
+    type TxBuffer = LoopbackBuffer;
+
+    #[inline]
+    fn mac_address(&self) -> EthernetAddress {
+        EthernetAddress([0; 6])
+    }
+
+    fn capabilities(&self) -> NetCapabilities {
+        NetCapabilities {
+            mtu: 65535,
+            max_burst: None,
+            rx_checksum: ChecksumOffload::ALL,
+            tx_checksum: ChecksumOffload::ALL,
+        }
+    }
+
+    #[inline]
+    fn can_send(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn can_recv(&self) -> bool {
+        !self.queue.is_empty()
+    }
+
+    #[inline]
+    fn ack_interrupt(&mut self) -> bool {
+        false
+    }
+
+    fn new_tx_buffer(&mut self, buf_len: usize) -> DevResult<Self::TxBuffer> {
+        Ok(LoopbackBuffer(vec![0; buf_len]))
+    }
+
+    fn recycle_rx_buffer(&mut self, _rx_buf: Self::RxBuffer) -> DevResult {
+        Ok(())
+    }
+
+    fn transmit(&mut self, tx_buf: Self::TxBuffer) -> DevResult {
+        self.queue.push_back(tx_buf);
+        Ok(())
+    }
+
+    #[inline]
+    fn reclaim_tx_buffers(&mut self) -> usize {
+        0
+    }
+
+    fn receive(&mut self) -> DevResult<Self::RxBuffer> {
+        self.queue.pop_front().ok_or(DevError::Again)
+    }
+}
@@ -0,0 +1,243 @@
+use core::fmt;
+
+use crate::{BusDeviceFunction, Command, ConfigAccess, PciError, PciRoot, REG_BAR0};
+
+/// The address width of a memory BAR.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum MemoryBarType {
+    /// A 32-bit address.
+    Width32,
+    /// A 32-bit address below 1 MiB (legacy).
+    Below1MiB,
+    /// A 64-bit address, occupies two BAR slots.
+    Width64,
+}
+
+/// The information of a base address register (BAR).
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BarInfo {
+    /// The BAR maps a memory region.
+    Memory {
+        /// The address width.
+        address_type: MemoryBarType,
+        /// Whether the region is prefetchable (no side effect on reads).
+        prefetchable: bool,
+        /// The address of the region on the PCI bus.
+        address: u64,
+        /// The size of the region, 0 if the BAR is not implemented.
+        size: u64,
+    },
+    /// The BAR maps an I/O port region.
+    Io {
+        /// The starting port number.
+        address: u32,
+        /// The number of ports.
+        size: u32,
+    },
+}
+
+impl BarInfo {
+    /// Whether the BAR takes two slots (a 64-bit memory BAR).
+    pub const fn takes_two_entries(&self) -> bool {
+        matches!(
+            self,
+            Self::Memory {
+                address_type: MemoryBarType::Width64,
+                ..
+            }
+        )
+    }
+
+    /// The address and size of the memory region, or `None` if it is an I/O
+    /// BAR.
+    pub const fn memory_address_size(&self) -> Option<(u64, u64)> {
+        match self {
+            Self::Memory { address, size, .. } => Some((*address, *size)),
+            Self::Io { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for BarInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Memory {
+                address_type,
+                prefetchable,
+                address,
+                size,
+            } => write!(
+                f,
+                "MEM [{:#x}, {:#x}) {:?}{}",
+                address,
+                address + size,
+                address_type,
+                if *prefetchable { " pref" } else { "" },
+            ),
+            Self::Io { address, size } => {
+                write!(f, "IO [{:#x}, {:#x})", address, address + size)
+            }
+        }
+    }
+}
+
+/// A bump allocator of the PCI bus addresses, for assigning BARs.
+///
+/// Each region is aligned to its size, as BARs require.
+pub struct PciRangeAllocator {
+    current: u64,
+    end: u64,
+}
+
+impl PciRangeAllocator {
+    /// Creates a new allocator of the addresses in `[base, base + size)`.
+    pub const fn new(base: u64, size: u64) -> Self {
+        Self {
+            current: base,
+            end: base + size,
+        }
+    }
+
+    /// Allocates a region of `size` bytes, which must be a power of two.
+    ///
+    /// Returns the starting address, or `None` if there is no space left.
+    pub fn alloc(&mut self, size: u64) -> Option<u64> {
+        if !size.is_power_of_two() {
+            return None;
+        }
+        let start = self.current.checked_add(size - 1)? & !(size - 1);
+        let end = start.checked_add(size)?;
+        if end > self.end {
+            return None;
+        }
+        self.current = end;
+        Some(start)
+    }
+}
+
+impl<C: ConfigAccess> PciRoot<C> {
+    /// Returns the information of the BAR at `index` of `bdf`.
+    ///
+    /// The size is probed by writing all ones to the BAR, so the memory and
+    /// I/O decoding of the function is disabled in the meantime.
+    pub fn bar_info(&mut self, bdf: BusDeviceFunction, index: u8) -> Result<BarInfo, PciError> {
+        if index >= 6 {
+            return Err(PciError::InvalidBarIndex);
+        }
+        let offset = REG_BAR0 + index as u16 * 4;
+        let bar = self.config_read(bdf, offset);
+
+        let command = self.command(bdf);
+        self.set_command(bdf, command - (Command::IO_SPACE | Command::MEMORY_SPACE));
+        let size_mask = self.probe_bar(bdf, offset, bar);
+
+        let info = if bar & 1 == 0 {
+            let address_type = match (bar >> 1) & 0b11 {
+                0 => MemoryBarType::Width32,
+                1 => MemoryBarType::Below1MiB,
+                2 if index < 5 => MemoryBarType::Width64,
+                _ => {
+                    self.set_command(bdf, command);
+                    return Err(PciError::InvalidBarType);
+                }
+            };
+            let mut address = (bar & !0xf) as u64;
+            let size = if address_type == MemoryBarType::Width64 {
+                let bar_high = self.config_read(bdf, offset + 4);
+                let size_mask_high = self.probe_bar(bdf, offset + 4, bar_high);
+                address |= (bar_high as u64) << 32;
+                let mask = (size_mask_high as u64) << 32 | (size_mask & !0xf) as u64;
+                (!mask).wrapping_add(1)
+            } else {
+                match size_mask & !0xf {
+                    0 => 0,
+                    mask => (!mask).wrapping_add(1) as u64,
+                }
+            };
+            BarInfo::Memory {
+                address_type,
+                prefetchable: bar & 0b1000 != 0,
+                address,
+                size,
+            }
+        } else {
+            let mut mask = size_mask & !0b11;
+            if mask & 0xffff_0000 == 0 {
+                // 16-bit I/O decoder
+                mask |= 0xffff_0000;
+            }
+            BarInfo::Io {
+                address: bar & !0b11,
+                size: if mask == 0xffff_0000 {
+                    0
+                } else {
+                    (!mask).wrapping_add(1)
+                },
+            }
+        };
+        self.set_command(bdf, command);
+        Ok(info)
+    }
+
+    /// Writes all ones to the BAR register at `offset`, returns the value read
+    /// back, and restores the original value `bar`.
+    fn probe_bar(&mut self, bdf: BusDeviceFunction, offset: u16, bar: u32) -> u32 {
+        self.config_write(bdf, offset, 0xffff_ffff);
+        let size_mask = self.config_read(bdf, offset);
+        self.config_write(bdf, offset, bar);
+        size_mask
+    }
+
+    /// Sets the address of the 32-bit memory or I/O BAR at `index`.
+    pub fn set_bar_32(&mut self, bdf: BusDeviceFunction, index: u8, address: u32) {
+        self.config_write(bdf, REG_BAR0 + index as u16 * 4, address);
+    }
+
+    /// Sets the address of the 64-bit memory BAR at `index` (and `index + 1`).
+    pub fn set_bar_64(&mut self, bdf: BusDeviceFunction, index: u8, address: u64) {
+        let offset = REG_BAR0 + index as u16 * 4;
+        self.config_write(bdf, offset, address as u32);
+        self.config_write(bdf, offset + 4, (address >> 32) as u32);
+    }
+
+    /// Assigns addresses from `allocator` to all implemented memory BARs of
+    /// `bdf`, then enables the memory decoding and bus mastering of it.
+    ///
+    /// I/O BARs are left untouched, as there is no I/O space on most
+    /// non-x86 platforms.
+    pub fn assign_bars(
+        &mut self,
+        bdf: BusDeviceFunction,
+        allocator: &mut PciRangeAllocator,
+    ) -> Result<(), PciError> {
+        let bar_count = match self.function_info(bdf) {
+            Some(info) => info.header_type.bar_count(),
+            None => return Ok(()),
+        };
+
+        let mut index = 0;
+        while index < bar_count {
+            let info = self.bar_info(bdf, index)?;
+            if let BarInfo::Memory {
+                address_type, size, ..
+            } = info
+            {
+                if size > 0 {
+                    let address = allocator.alloc(size).ok_or(PciError::NoSpace)?;
+                    if address_type == MemoryBarType::Width64 {
+                        self.set_bar_64(bdf, index, address);
+                    } else if address + size <= 1 << 32 {
+                        self.set_bar_32(bdf, index, address as u32);
+                    } else {
+                        return Err(PciError::InvalidAddress);
+                    }
+                }
+            }
+            index += if info.takes_two_entries() { 2 } else { 1 };
+        }
+
+        let command = self.command(bdf);
+        self.set_command(bdf, command | Command::MEMORY_SPACE | Command::BUS_MASTER);
+        Ok(())
+    }
+}
@@ -0,0 +1,211 @@
+use crate::REG_CAPABILITIES_POINTER;
+use crate::{BusDeviceFunction, Command, ConfigAccess, PciError, PciRoot, Status};
+
+const CAP_ID_MSI: u8 = 0x05;
+const CAP_ID_MSIX: u8 = 0x11;
+
+/// The maximum number of capabilities to walk, to avoid looping forever on a
+/// corrupted list.
+const MAX_CAPABILITIES: usize = 48;
+
+const MSI_CTRL_ENABLE: u16 = 1 << 0;
+const MSI_CTRL_MULTI_MESSAGE_ENABLE: u16 = 0b111 << 4;
+const MSI_CTRL_64BIT: u16 = 1 << 7;
+const MSIX_CTRL_TABLE_SIZE: u16 = 0x7ff;
+const MSIX_CTRL_FUNCTION_MASK: u16 = 1 << 14;
+const MSIX_CTRL_ENABLE: u16 = 1 << 15;
+
+/// An entry in the capability list of a PCI function.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct CapabilityInfo {
+    /// The offset of the capability in the configuration space.
+    pub offset: u16,
+    /// The capability ID.
+    pub id: u8,
+    /// The third and fourth bytes of the capability, whose meaning depends on
+    /// the capability.
+    pub private_header: u16,
+}
+
+/// An iterator over the capabilities of a PCI function, created by
+/// [`PciRoot::capabilities`].
+pub struct CapabilityIter<'a, C: ConfigAccess> {
+    root: &'a PciRoot<C>,
+    bdf: BusDeviceFunction,
+    next_offset: u16,
+    remaining: usize,
+}
+
+impl<'a, C: ConfigAccess> Iterator for CapabilityIter<'a, C> {
+    type Item = CapabilityInfo;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // the lowest 2 bits are reserved, and the list is after the header
+        let offset = self.next_offset & !0b11;
+        if offset < 0x40 || self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let header = self.root.config_read(self.bdf, offset);
+        self.next_offset = ((header >> 8) & 0xff) as u16;
+        Some(CapabilityInfo {
+            offset,
+            id: header as u8,
+            private_header: (header >> 16) as u16,
+        })
+    }
+}
+
+/// The location and size of the MSI-X table of a PCI function.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct MsixInfo {
+    /// The offset of the MSI-X capability in the configuration space.
+    pub cap_offset: u16,
+    /// The number of entries in the table.
+    pub table_size: u16,
+    /// The index of the BAR where the table resides.
+    pub table_bar: u8,
+    /// The offset of the table in the BAR.
+    pub table_offset: u32,
+    /// The index of the BAR where the pending bit array resides.
+    pub pba_bar: u8,
+    /// The offset of the pending bit array in the BAR.
+    pub pba_offset: u32,
+}
+
+impl<C: ConfigAccess> PciRoot<C> {
+    /// Returns an iterator over the capabilities of `bdf`.
+    pub fn capabilities(&self, bdf: BusDeviceFunction) -> CapabilityIter<'_, C> {
+        let next_offset = if self.status(bdf).contains(Status::CAPABILITIES_LIST) {
+            (self.config_read(bdf, REG_CAPABILITIES_POINTER) & 0xff) as u16
+        } else {
+            0
+        };
+        CapabilityIter {
+            root: self,
+            bdf,
+            next_offset,
+            remaining: MAX_CAPABILITIES,
+        }
+    }
+
+    /// Returns the first capability of `bdf` with the given `id`.
+    pub fn find_capability(&self, bdf: BusDeviceFunction, id: u8) -> Option<CapabilityInfo> {
+        self.capabilities(bdf).find(|cap| cap.id == id)
+    }
+
+    fn write_cap_control(&mut self, bdf: BusDeviceFunction, cap_offset: u16, control: u16) {
+        let header = self.config_read(bdf, cap_offset);
+        let value = (header & 0xffff) | (control as u32) << 16;
+        self.config_write(bdf, cap_offset, value);
+    }
+
+    fn set_intx_disable(&mut self, bdf: BusDeviceFunction, disable: bool) {
+        let mut command = self.command(bdf);
+        command.set(Command::INTERRUPT_DISABLE, disable);
+        self.set_command(bdf, command);
+    }
+
+    /// Enables MSI of `bdf` with a single vector, which writes `data` to
+    /// `address` to raise an interrupt. The legacy INTx is disabled.
+    ///
+    /// Returns [`PciError::NoCapability`] if MSI is not supported, or
+    /// [`PciError::InvalidAddress`] if `address` is above 4 GiB but the
+    /// function only supports 32-bit addresses.
+    pub fn enable_msi(
+        &mut self,
+        bdf: BusDeviceFunction,
+        address: u64,
+        data: u16,
+    ) -> Result<(), PciError> {
+        let cap = self
+            .find_capability(bdf, CAP_ID_MSI)
+            .ok_or(PciError::NoCapability)?;
+        let mut control = cap.private_header;
+        let is_64bit = control & MSI_CTRL_64BIT != 0;
+        if !is_64bit && address >> 32 != 0 {
+            return Err(PciError::InvalidAddress);
+        }
+
+        self.config_write(bdf, cap.offset + 4, address as u32);
+        let data_offset = if is_64bit {
+            self.config_write(bdf, cap.offset + 8, (address >> 32) as u32);
+            cap.offset + 12
+        } else {
+            cap.offset + 8
+        };
+        let old_data = self.config_read(bdf, data_offset);
+        self.config_write(bdf, data_offset, (old_data & !0xffff) | data as u32);
+
+        control &= !MSI_CTRL_MULTI_MESSAGE_ENABLE;
+        control |= MSI_CTRL_ENABLE;
+        self.write_cap_control(bdf, cap.offset, control);
+        self.set_intx_disable(bdf, true);
+        Ok(())
+    }
+
+    /// Disables MSI of `bdf`, and enables the legacy INTx again.
+    pub fn disable_msi(&mut self, bdf: BusDeviceFunction) -> Result<(), PciError> {
+        let cap = self
+            .find_capability(bdf, CAP_ID_MSI)
+            .ok_or(PciError::NoCapability)?;
+        self.write_cap_control(bdf, cap.offset, cap.private_header & !MSI_CTRL_ENABLE);
+        self.set_intx_disable(bdf, false);
+        Ok(())
+    }
+
+    /// Returns the location of the MSI-X table of `bdf`, or `None` if MSI-X
+    /// is not supported.
+    pub fn msix_info(&self, bdf: BusDeviceFunction) -> Option<MsixInfo> {
+        let cap = self.find_capability(bdf, CAP_ID_MSIX)?;
+        let table = self.config_read(bdf, cap.offset + 4);
+        let pba = self.config_read(bdf, cap.offset + 8);
+        Some(MsixInfo {
+            cap_offset: cap.offset,
+            table_size: (cap.private_header & MSIX_CTRL_TABLE_SIZE) + 1,
+            table_bar: (table & 0b111) as u8,
+            table_offset: table & !0b111,
+            pba_bar: (pba & 0b111) as u8,
+            pba_offset: pba & !0b111,
+        })
+    }
+
+    /// Enables or disables MSI-X of `bdf`. The legacy INTx is disabled while
+    /// MSI-X is enabled.
+    ///
+    /// The table entries should be set up by [`write_msix_entry`] before
+    /// enabling.
+    pub fn set_msix_enable(
+        &mut self,
+        bdf: BusDeviceFunction,
+        enable: bool,
+    ) -> Result<(), PciError> {
+        let cap = self
+            .find_capability(bdf, CAP_ID_MSIX)
+            .ok_or(PciError::NoCapability)?;
+        let mut control = cap.private_header & !MSIX_CTRL_FUNCTION_MASK;
+        if enable {
+            control |= MSIX_CTRL_ENABLE;
+        } else {
+            control &= !MSIX_CTRL_ENABLE;
+        }
+        self.write_cap_control(bdf, cap.offset, control);
+        self.set_intx_disable(bdf, enable);
+        Ok(())
+    }
+}
+
+/// Sets the entry at `index` of the MSI-X table at `table` (virtual address),
+/// so that the vector writes `data` to `address`, and unmasks it.
+///
+/// # Safety
+///
+/// `table` must point to the mapped MSI-X table described by [`MsixInfo`],
+/// and `index` must be less than [`MsixInfo::table_size`].
+pub unsafe fn write_msix_entry(table: *mut u32, index: usize, address: u64, data: u32) {
+    let entry = table.add(index * 4);
+    entry.write_volatile(address as u32);
+    entry.add(1).write_volatile((address >> 32) as u32);
+    entry.add(2).write_volatile(data);
+    entry.add(3).write_volatile(0); // vector control: unmasked
+}
@@ -0,0 +1,457 @@
+//! Structures and functions for PCI bus operations.
+//!
+//! The configuration space of the functions is accessed through the
+//! [`ConfigAccess`] trait, and [`Ecam`] is the memory-mapped implementation
+//! (PCIe enhanced configuration access mechanism) used by most platforms.
+//!
+//! On top of it, [`PciRoot`] provides:
+//!
+//! - enumeration of the functions on a bus ([`PciRoot::enumerate_bus`]);
+//! - sizing and assignment of the base address registers (BARs), see
+//!   [`PciRoot::bar_info`] and [`PciRoot::assign_bars`];
+//! - interrupt configuration: the legacy INTx pin, MSI, and MSI-X.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use driver_pci::{Ecam, PciRangeAllocator, PciRoot};
+//!
+//! # fn example(ecam_vaddr: *mut u8) {
+//! let mut root = PciRoot::new(unsafe { Ecam::new(ecam_vaddr, 0) });
+//! let mut allocator = PciRangeAllocator::new(0x1000_0000, 0x1000_0000);
+//! let functions: Vec<_> = root.enumerate_bus(0).collect();
+//! for (bdf, info) in functions {
+//!     println!("{}: {}", bdf, info);
+//!     root.assign_bars(bdf, &mut allocator).unwrap();
+//! }
+//! # }
+//! ```
+
+#![cfg_attr(not(test), no_std)]
+
+mod bar;
+mod capability;
+
+#[cfg(test)]
+mod tests;
+
+use core::fmt;
+
+pub use self::bar::{BarInfo, MemoryBarType, PciRangeAllocator};
+pub use self::capability::{write_msix_entry, CapabilityInfo, CapabilityIter, MsixInfo};
+
+/// The maximum number of devices on a bus.
+pub const MAX_DEVICES: u8 = 32;
+/// The maximum number of functions of a device.
+pub const MAX_FUNCTIONS: u8 = 8;
+
+const INVALID_READ: u32 = 0xffff_ffff;
+
+// offsets of the registers in the common header
+const REG_ID: u16 = 0x00;
+const REG_STATUS_COMMAND: u16 = 0x04;
+const REG_CLASS_REVISION: u16 = 0x08;
+const REG_BIST_HEADER_TYPE: u16 = 0x0c;
+const REG_BAR0: u16 = 0x10;
+const REG_CAPABILITIES_POINTER: u16 = 0x34;
+const REG_INTERRUPT: u16 = 0x3c;
+
+/// The address of a PCI function.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+pub struct BusDeviceFunction {
+    /// The bus number, `0..=255`.
+    pub bus: u8,
+    /// The device number on the bus, `0..32`.
+    pub device: u8,
+    /// The function number of the device, `0..8`.
+    pub function: u8,
+}
+
+impl BusDeviceFunction {
+    /// Creates a new function address.
+    pub const fn new(bus: u8, device: u8, function: u8) -> Self {
+        Self {
+            bus,
+            device,
+            function,
+        }
+    }
+
+    /// Whether the device and function numbers are in the valid range.
+    pub const fn is_valid(&self) -> bool {
+        self.device < MAX_DEVICES && self.function < MAX_FUNCTIONS
+    }
+}
+
+impl fmt::Display for BusDeviceFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02x}:{:02x}.{}", self.bus, self.device, self.function)
+    }
+}
+
+/// The mechanism to access the configuration space of PCI functions.
+pub trait ConfigAccess {
+    /// Reads the 32-bit register at `offset` of function `bdf`.
+    ///
+    /// `offset` is 4-byte aligned. If the function does not exist, returns
+    /// `0xffff_ffff`.
+    fn read(&self, bdf: BusDeviceFunction, offset: u16) -> u32;
+
+    /// Writes the 32-bit register at `offset` of function `bdf`.
+    ///
+    /// `offset` is 4-byte aligned.
+    fn write(&mut self, bdf: BusDeviceFunction, offset: u16, value: u32);
+}
+
+/// The PCIe enhanced configuration access mechanism (ECAM).
+///
+/// The configuration space of each function is mapped to a 4 KiB region of
+/// memory, at offset `(bus << 20) | (device << 15) | (function << 12)`
+/// relative to the first bus of the region.
+pub struct Ecam {
+    base: *mut u8,
+    bus_start: u8,
+}
+
+unsafe impl Send for Ecam {}
+unsafe impl Sync for Ecam {}
+
+impl Ecam {
+    /// The size of the configuration space region of each bus.
+    pub const BUS_SIZE: usize = 1 << 20;
+
+    /// Creates a new ECAM accessor, the region at `base` (virtual address)
+    /// starts at bus number `bus_start`.
+    ///
+    /// # Safety
+    ///
+    /// `base` must point to the mapped ECAM region, which must cover all
+    /// buses to be accessed.
+    pub const unsafe fn new(base: *mut u8, bus_start: u8) -> Self {
+        Self { base, bus_start }
+    }
+
+    /// The virtual address of the region.
+    pub const fn base(&self) -> *mut u8 {
+        self.base
+    }
+
+    /// The first bus number of the region.
+    pub const fn bus_start(&self) -> u8 {
+        self.bus_start
+    }
+
+    fn reg_ptr(&self, bdf: BusDeviceFunction, offset: u16) -> Option<*mut u32> {
+        if bdf.bus < self.bus_start || !bdf.is_valid() || offset >= 0x1000 || offset & 0b11 != 0 {
+            return None;
+        }
+        let offset = ((bdf.bus - self.bus_start) as usize) << 20
+            | (bdf.device as usize) << 15
+            | (bdf.function as usize) << 12
+            | offset as usize;
+        Some(unsafe { self.base.add(offset) } as *mut u32)
+    }
+}
+
+impl ConfigAccess for Ecam {
+    fn read(&self, bdf: BusDeviceFunction, offset: u16) -> u32 {
+        match self.reg_ptr(bdf, offset) {
+            Some(ptr) => unsafe { ptr.read_volatile() },
+            None => INVALID_READ,
+        }
+    }
+
+    fn write(&mut self, bdf: BusDeviceFunction, offset: u16, value: u32) {
+        if let Some(ptr) = self.reg_ptr(bdf, offset) {
+            unsafe { ptr.write_volatile(value) }
+        }
+    }
+}
+
+bitflags::bitflags! {
+    /// The command register in the configuration space.
+    #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+    pub struct Command: u16 {
+        /// The function responds to the I/O space accesses.
+        const IO_SPACE = 1 << 0;
+        /// The function responds to the memory space accesses.
+        const MEMORY_SPACE = 1 << 1;
+        /// The function can initiate DMA (act as a bus master).
+        const BUS_MASTER = 1 << 2;
+        /// The function can generate the special cycles.
+        const SPECIAL_CYCLES = 1 << 3;
+        /// The function can generate the memory write and invalidate command.
+        const MEMORY_WRITE_AND_INVALIDATE_ENABLE = 1 << 4;
+        /// The function snoops the palette register writes.
+        const VGA_PALETTE_SNOOP = 1 << 5;
+        /// The function takes its normal action on a parity error.
+        const PARITY_ERROR_RESPONSE = 1 << 6;
+        /// The SERR# driver is enabled.
+        const SERR_ENABLE = 1 << 8;
+        /// The function can generate fast back-to-back transactions.
+        const FAST_BACK_TO_BACK_ENABLE = 1 << 9;
+        /// The INTx# signal of the function is disabled.
+        const INTERRUPT_DISABLE = 1 << 10;
+    }
+
+    /// The status register in the configuration space.
+    #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+    pub struct Status: u16 {
+        /// An INTx interrupt is pending.
+        const INTERRUPT_STATUS = 1 << 3;
+        /// The function has a list of capabilities.
+        const CAPABILITIES_LIST = 1 << 4;
+        /// The function is capable of running at 66 MHz.
+        const MHZ_66_CAPABLE = 1 << 5;
+        /// The function can accept fast back-to-back transactions.
+        const FAST_BACK_TO_BACK_CAPABLE = 1 << 7;
+        /// A data parity error is detected as a bus master.
+        const MASTER_DATA_PARITY_ERROR = 1 << 8;
+        /// The function has terminated a transaction with target-abort.
+        const SIGNALED_TARGET_ABORT = 1 << 11;
+        /// A transaction of the function is terminated with target-abort.
+        const RECEIVED_TARGET_ABORT = 1 << 12;
+        /// A transaction of the function is terminated with master-abort.
+        const RECEIVED_MASTER_ABORT = 1 << 13;
+        /// The function has asserted SERR#.
+        const SIGNALED_SYSTEM_ERROR = 1 << 14;
+        /// The function has detected a parity error.
+        const DETECTED_PARITY_ERROR = 1 << 15;
+    }
+}
+
+/// The type of the configuration space header.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum HeaderType {
+    /// A general device.
+    Standard,
+    /// A PCI-to-PCI bridge.
+    PciPciBridge,
+    /// A PCI-to-CardBus bridge.
+    PciCardbusBridge,
+    /// An unrecognised header type.
+    Unrecognised(u8),
+}
+
+impl From<u8> for HeaderType {
+    fn from(value: u8) -> Self {
+        match value & 0x7f {
+            0x00 => Self::Standard,
+            0x01 => Self::PciPciBridge,
+            0x02 => Self::PciCardbusBridge,
+            _ => Self::Unrecognised(value & 0x7f),
+        }
+    }
+}
+
+impl HeaderType {
+    /// The number of BARs in the header.
+    pub const fn bar_count(&self) -> u8 {
+        match self {
+            Self::Standard => 6,
+            Self::PciPciBridge => 2,
+            _ => 0,
+        }
+    }
+}
+
+/// The identification information of a PCI function.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct DeviceFunctionInfo {
+    /// The vendor ID.
+    pub vendor_id: u16,
+    /// The device ID.
+    pub device_id: u16,
+    /// The base class code.
+    pub class: u8,
+    /// The subclass code.
+    pub subclass: u8,
+    /// The programming interface.
+    pub prog_if: u8,
+    /// The revision ID.
+    pub revision: u8,
+    /// The type of the configuration space header.
+    pub header_type: HeaderType,
+}
+
+impl fmt::Display for DeviceFunctionInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04x}:{:04x} (class {:02x}.{:02x}, rev {:02x}) {:?}",
+            self.vendor_id,
+            self.device_id,
+            self.class,
+            self.subclass,
+            self.revision,
+            self.header_type,
+        )
+    }
+}
+
+/// The error type for PCI operation failures.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PciError {
+    /// The BAR index is out of range.
+    InvalidBarIndex,
+    /// The BAR has a reserved type, or is the upper half of a 64-bit BAR.
+    InvalidBarType,
+    /// The address cannot be used (e.g., a 64-bit address for a 32-bit BAR).
+    InvalidAddress,
+    /// The function does not have the required capability.
+    NoCapability,
+    /// Not enough address space to allocate.
+    NoSpace,
+}
+
+impl fmt::Display for PciError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidBarIndex => write!(f, "invalid BAR index"),
+            Self::InvalidBarType => write!(f, "invalid BAR type"),
+            Self::InvalidAddress => write!(f, "invalid address"),
+            Self::NoCapability => write!(f, "capability not supported"),
+            Self::NoSpace => write!(f, "no address space left"),
+        }
+    }
+}
+
+/// The root complex of a PCI bus hierarchy.
+pub struct PciRoot<C: ConfigAccess> {
+    access: C,
+}
+
+impl<C: ConfigAccess> PciRoot<C> {
+    /// Creates a new root complex which accesses the configuration space by
+    /// `access`.
+    pub const fn new(access: C) -> Self {
+        Self { access }
+    }
+
+    /// Returns the mechanism to access the configuration space.
+    pub const fn access(&self) -> &C {
+        &self.access
+    }
+
+    /// Reads the 32-bit register at `offset` in the configuration space of
+    /// `bdf`.
+    pub fn config_read(&self, bdf: BusDeviceFunction, offset: u16) -> u32 {
+        self.access.read(bdf, offset)
+    }
+
+    /// Writes the 32-bit register at `offset` in the configuration space of
+    /// `bdf`.
+    pub fn config_write(&mut self, bdf: BusDeviceFunction, offset: u16, value: u32) {
+        self.access.write(bdf, offset, value)
+    }
+
+    /// Returns the identification information of `bdf`, or `None` if the
+    /// function does not exist.
+    pub fn function_info(&self, bdf: BusDeviceFunction) -> Option<DeviceFunctionInfo> {
+        let id = self.config_read(bdf, REG_ID);
+        let vendor_id = id as u16;
+        if vendor_id == 0xffff {
+            return None;
+        }
+        let class_revision = self.config_read(bdf, REG_CLASS_REVISION);
+        let header_type = (self.config_read(bdf, REG_BIST_HEADER_TYPE) >> 16) as u8;
+        Some(DeviceFunctionInfo {
+            vendor_id,
+            device_id: (id >> 16) as u16,
+            class: (class_revision >> 24) as u8,
+            subclass: (class_revision >> 16) as u8,
+            prog_if: (class_revision >> 8) as u8,
+            revision: class_revision as u8,
+            header_type: header_type.into(),
+        })
+    }
+
+    /// Whether `bdf` is a function of a multi-function device.
+    fn is_multi_function(&self, bdf: BusDeviceFunction) -> bool {
+        self.config_read(bdf, REG_BIST_HEADER_TYPE) & (0x80 << 16) != 0
+    }
+
+    /// Enumerates all functions on `bus`.
+    ///
+    /// Bridges are reported like other functions, but the buses behind them
+    /// are not scanned, which should be done by calling this function for
+    /// each bus number.
+    pub fn enumerate_bus(&self, bus: u8) -> BusIter<'_, C> {
+        BusIter {
+            root: self,
+            next: BusDeviceFunction::new(bus, 0, 0),
+        }
+    }
+
+    /// Returns the command register of `bdf`.
+    pub fn command(&self, bdf: BusDeviceFunction) -> Command {
+        Command::from_bits_retain(self.config_read(bdf, REG_STATUS_COMMAND) as u16)
+    }
+
+    /// Sets the command register of `bdf`.
+    pub fn set_command(&mut self, bdf: BusDeviceFunction, command: Command) {
+        // writing 1 to the status bits clears them, keep them untouched
+        self.config_write(bdf, REG_STATUS_COMMAND, command.bits() as u32);
+    }
+
+    /// Returns the status register of `bdf`.
+    pub fn status(&self, bdf: BusDeviceFunction) -> Status {
+        Status::from_bits_retain((self.config_read(bdf, REG_STATUS_COMMAND) >> 16) as u16)
+    }
+
+    /// Returns the legacy interrupt pin used by `bdf`, `1..=4` for INTA# to
+    /// INTD#, or `None` if it does not use one.
+    pub fn interrupt_pin(&self, bdf: BusDeviceFunction) -> Option<u8> {
+        match (self.config_read(bdf, REG_INTERRUPT) >> 8) as u8 {
+            pin @ 1..=4 => Some(pin),
+            _ => None,
+        }
+    }
+
+    /// Sets the interrupt line register of `bdf`.
+    ///
+    /// It does not affect the routing, but is informative for the drivers.
+    pub fn set_interrupt_line(&mut self, bdf: BusDeviceFunction, line: u8) {
+        let value = self.config_read(bdf, REG_INTERRUPT);
+        self.config_write(bdf, REG_INTERRUPT, (value & !0xff) | line as u32);
+    }
+}
+
+/// An iterator over the functions on a bus, created by
+/// [`PciRoot::enumerate_bus`].
+pub struct BusIter<'a, C: ConfigAccess> {
+    root: &'a PciRoot<C>,
+    next: BusDeviceFunction,
+}
+
+impl<'a, C: ConfigAccess> BusIter<'a, C> {
+    fn advance(&mut self, next_device: bool) {
+        if next_device || self.next.function + 1 >= MAX_FUNCTIONS {
+            self.next.device += 1;
+            self.next.function = 0;
+        } else {
+            self.next.function += 1;
+        }
+    }
+}
+
+impl<'a, C: ConfigAccess> Iterator for BusIter<'a, C> {
+    type Item = (BusDeviceFunction, DeviceFunctionInfo);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.next.device < MAX_DEVICES {
+            let bdf = self.next;
+            match self.root.function_info(bdf) {
+                Some(info) => {
+                    // scan other functions only for multi-function devices
+                    let single = bdf.function == 0 && !self.root.is_multi_function(bdf);
+                    self.advance(single);
+                    return Some((bdf, info));
+                }
+                // no function 0 means no device
+                None => self.advance(bdf.function == 0),
+            }
+        }
+        None
+    }
+}
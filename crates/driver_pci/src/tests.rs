@@ -0,0 +1,297 @@
+use std::collections::BTreeMap;
+
+use crate::*;
+
+/// A function whose configuration space is emulated in memory.
+struct MockFunction {
+    regs: [u32; 1024],
+    /// The writable bits of each BAR, the others are read-only.
+    bar_masks: [u32; 6],
+}
+
+impl MockFunction {
+    fn new(vendor_id: u16, device_id: u16, class: u8, subclass: u8) -> Self {
+        let mut regs = [0; 1024];
+        regs[0] = (device_id as u32) << 16 | vendor_id as u32;
+        regs[2] = (class as u32) << 24 | (subclass as u32) << 16 | 0x01;
+        Self {
+            regs,
+            bar_masks: [0; 6],
+        }
+    }
+
+    fn multi_function(mut self) -> Self {
+        self.regs[3] |= 0x80 << 16;
+        self
+    }
+
+    /// Adds a memory BAR of `size` bytes, `flags` is the lowest 4 bits.
+    fn mem_bar(mut self, index: usize, size: u64, flags: u32) -> Self {
+        let mask = !(size - 1);
+        self.regs[4 + index] = flags;
+        self.bar_masks[index] = mask as u32 & !0xf;
+        if flags & 0b110 == 0b100 {
+            self.bar_masks[index + 1] = (mask >> 32) as u32;
+        }
+        self
+    }
+
+    fn io_bar(mut self, index: usize, size: u32) -> Self {
+        self.regs[4 + index] = 1;
+        self.bar_masks[index] = !(size - 1) & 0xffff & !0b11;
+        self
+    }
+
+    fn interrupt_pin(mut self, pin: u8) -> Self {
+        self.regs[15] = (pin as u32) << 8;
+        self
+    }
+
+    /// Appends a capability at `offset`, with the given header and body.
+    fn capability(mut self, offset: u16, id: u8, header: u16, body: &[u32]) -> Self {
+        let idx = offset as usize / 4;
+        self.regs[idx] = (header as u32) << 16 | id as u32;
+        self.regs[idx + 1..idx + 1 + body.len()].copy_from_slice(body);
+        // link it to the end of the list
+        self.regs[1] |= (Status::CAPABILITIES_LIST.bits() as u32) << 16;
+        let mut ptr_reg = 13;
+        let mut shift = 0;
+        loop {
+            let next = (self.regs[ptr_reg] >> shift) & 0xff;
+            if next == 0 {
+                self.regs[ptr_reg] |= (offset as u32) << shift;
+                break;
+            }
+            ptr_reg = next as usize / 4;
+            shift = 8;
+        }
+        self
+    }
+}
+
+#[derive(Default)]
+struct MockBus {
+    functions: BTreeMap<BusDeviceFunction, MockFunction>,
+}
+
+impl MockBus {
+    fn add(&mut self, bdf: BusDeviceFunction, function: MockFunction) {
+        self.functions.insert(bdf, function);
+    }
+}
+
+impl ConfigAccess for MockBus {
+    fn read(&self, bdf: BusDeviceFunction, offset: u16) -> u32 {
+        self.functions
+            .get(&bdf)
+            .map_or(0xffff_ffff, |f| f.regs[offset as usize / 4])
+    }
+
+    fn write(&mut self, bdf: BusDeviceFunction, offset: u16, value: u32) {
+        let Some(f) = self.functions.get_mut(&bdf) else {
+            return;
+        };
+        let idx = offset as usize / 4;
+        match idx {
+            0 | 2 | 3 => {} // read-only
+            // the status bits are write-1-to-clear, ignore them
+            1 => f.regs[1] = (f.regs[1] & 0xffff_0000) | (value & 0xffff),
+            4..=9 => {
+                let mask = f.bar_masks[idx - 4];
+                f.regs[idx] = (value & mask) | (f.regs[idx] & !mask & 0xf);
+            }
+            _ => f.regs[idx] = value,
+        }
+    }
+}
+
+fn bdf(bus: u8, device: u8, function: u8) -> BusDeviceFunction {
+    BusDeviceFunction::new(bus, device, function)
+}
+
+#[test]
+fn test_enumerate() {
+    let mut bus = MockBus::default();
+    bus.add(bdf(0, 0, 0), MockFunction::new(0x1b36, 0x0008, 0x06, 0x00));
+    bus.add(bdf(0, 1, 0), MockFunction::new(0x1af4, 0x1041, 0x02, 0x00));
+    // not a multi-function device, function 1 must be ignored
+    bus.add(bdf(0, 1, 1), MockFunction::new(0x1af4, 0x1042, 0x01, 0x00));
+    bus.add(
+        bdf(0, 3, 0),
+        MockFunction::new(0x8086, 0x2922, 0x01, 0x06).multi_function(),
+    );
+    bus.add(bdf(0, 3, 2), MockFunction::new(0x8086, 0x2930, 0x0c, 0x05));
+    bus.add(bdf(0, 31, 0), MockFunction::new(0x1af4, 0x1050, 0x03, 0x80));
+    bus.add(bdf(1, 0, 0), MockFunction::new(0x1af4, 0x1042, 0x01, 0x00));
+
+    let root = PciRoot::new(bus);
+    let found: Vec<_> = root.enumerate_bus(0).collect();
+    let bdfs: Vec<_> = found.iter().map(|(bdf, _)| bdf.to_string()).collect();
+    assert_eq!(
+        bdfs,
+        ["00:00.0", "00:01.0", "00:03.0", "00:03.2", "00:1f.0"]
+    );
+
+    let info = found[1].1;
+    assert_eq!(info.vendor_id, 0x1af4);
+    assert_eq!(info.device_id, 0x1041);
+    assert_eq!(
+        (info.class, info.subclass, info.revision),
+        (0x02, 0x00, 0x01)
+    );
+    assert_eq!(info.header_type, HeaderType::Standard);
+    assert_eq!(root.enumerate_bus(1).count(), 1);
+    assert_eq!(root.enumerate_bus(2).count(), 0);
+}
+
+#[test]
+fn test_bar_info() {
+    let mut bus = MockBus::default();
+    let f = MockFunction::new(0x1af4, 0x1041, 0x02, 0x00)
+        .mem_bar(0, 0x1000, 0b0000)
+        .io_bar(1, 0x20)
+        .mem_bar(4, 0x4000, 0b1100);
+    bus.add(bdf(0, 1, 0), f);
+    let mut root = PciRoot::new(bus);
+    let dev = bdf(0, 1, 0);
+
+    assert_eq!(
+        root.bar_info(dev, 0),
+        Ok(BarInfo::Memory {
+            address_type: MemoryBarType::Width32,
+            prefetchable: false,
+            address: 0,
+            size: 0x1000,
+        })
+    );
+    assert_eq!(
+        root.bar_info(dev, 1),
+        Ok(BarInfo::Io {
+            address: 0,
+            size: 0x20
+        })
+    );
+    // not implemented
+    assert_eq!(
+        root.bar_info(dev, 2).unwrap().memory_address_size(),
+        Some((0, 0))
+    );
+    let bar4 = root.bar_info(dev, 4).unwrap();
+    assert!(bar4.takes_two_entries());
+    assert_eq!(
+        bar4,
+        BarInfo::Memory {
+            address_type: MemoryBarType::Width64,
+            prefetchable: true,
+            address: 0,
+            size: 0x4000,
+        }
+    );
+    assert_eq!(root.bar_info(dev, 6), Err(PciError::InvalidBarIndex));
+
+    // sizing must not change the BARs
+    root.set_bar_64(dev, 4, 0x80_0000_8000);
+    assert_eq!(
+        root.bar_info(dev, 4).unwrap().memory_address_size(),
+        Some((0x80_0000_8000, 0x4000))
+    );
+}
+
+#[test]
+fn test_assign_bars() {
+    let mut bus = MockBus::default();
+    bus.add(
+        bdf(0, 1, 0),
+        MockFunction::new(0x1af4, 0x1041, 0x02, 0x00)
+            .mem_bar(1, 0x1000, 0b0000)
+            .mem_bar(4, 0x4000, 0b1100),
+    );
+    bus.add(
+        bdf(0, 2, 0),
+        MockFunction::new(0x1af4, 0x1042, 0x01, 0x00).mem_bar(0, 0x10_0000, 0b0000),
+    );
+    let mut root = PciRoot::new(bus);
+    let mut allocator = PciRangeAllocator::new(0x1000_0000, 0x20_0000);
+
+    root.assign_bars(bdf(0, 1, 0), &mut allocator).unwrap();
+    let bar1 = root.bar_info(bdf(0, 1, 0), 1).unwrap();
+    let bar4 = root.bar_info(bdf(0, 1, 0), 4).unwrap();
+    assert_eq!(bar1.memory_address_size(), Some((0x1000_0000, 0x1000)));
+    assert_eq!(bar4.memory_address_size(), Some((0x1000_4000, 0x4000)));
+    let command = root.command(bdf(0, 1, 0));
+    assert!(command.contains(Command::MEMORY_SPACE | Command::BUS_MASTER));
+
+    root.assign_bars(bdf(0, 2, 0), &mut allocator).unwrap();
+    let bar0 = root.bar_info(bdf(0, 2, 0), 0).unwrap();
+    assert_eq!(bar0.memory_address_size(), Some((0x1010_0000, 0x10_0000)));
+
+    // out of space
+    let mut small = PciRangeAllocator::new(0x1000_0000, 0x8_0000);
+    assert_eq!(
+        root.assign_bars(bdf(0, 2, 0), &mut small),
+        Err(PciError::NoSpace)
+    );
+}
+
+#[test]
+fn test_range_allocator() {
+    let mut allocator = PciRangeAllocator::new(0x1000, 0x1_0000);
+    assert_eq!(allocator.alloc(0x100), Some(0x1000));
+    assert_eq!(allocator.alloc(0x1000), Some(0x2000));
+    assert_eq!(allocator.alloc(0x10), Some(0x3000));
+    assert_eq!(allocator.alloc(0x300), None); // not a power of two
+    assert_eq!(allocator.alloc(0x8000), Some(0x8000));
+    assert_eq!(allocator.alloc(0x1000), Some(0x1_0000));
+    assert_eq!(allocator.alloc(0x1000), None);
+}
+
+#[test]
+fn test_interrupts() {
+    let mut bus = MockBus::default();
+    let msi_body = [0, 0, 0]; // address low, address high, data
+    let msix_body = [0x0000_3001, 0x0000_3801]; // table in BAR 1, PBA in BAR 1
+    bus.add(
+        bdf(0, 1, 0),
+        MockFunction::new(0x8086, 0x10d3, 0x02, 0x00)
+            .interrupt_pin(1)
+            .capability(0x50, 0x01, 0, &[0])
+            .capability(0x60, 0x05, 1 << 7, &msi_body)
+            .capability(0xa0, 0x11, 4, &msix_body),
+    );
+    bus.add(bdf(0, 2, 0), MockFunction::new(0x1af4, 0x1042, 0x01, 0x00));
+    let mut root = PciRoot::new(bus);
+    let dev = bdf(0, 1, 0);
+
+    assert_eq!(root.interrupt_pin(dev), Some(1));
+    assert_eq!(root.interrupt_pin(bdf(0, 2, 0)), None);
+    root.set_interrupt_line(dev, 33);
+    assert_eq!(root.config_read(dev, 0x3c), 0x0121);
+
+    let caps: Vec<_> = root.capabilities(dev).map(|c| (c.offset, c.id)).collect();
+    assert_eq!(caps, [(0x50, 0x01), (0x60, 0x05), (0xa0, 0x11)]);
+    assert_eq!(root.capabilities(bdf(0, 2, 0)).count(), 0);
+
+    root.enable_msi(dev, 0x1_0802_0040, 0x51).unwrap();
+    assert_eq!(root.config_read(dev, 0x64), 0x0802_0040);
+    assert_eq!(root.config_read(dev, 0x68), 0x1);
+    assert_eq!(root.config_read(dev, 0x6c), 0x51);
+    assert_eq!(root.config_read(dev, 0x60) >> 16, (1 << 7) | 1);
+    assert!(root.command(dev).contains(Command::INTERRUPT_DISABLE));
+    root.disable_msi(dev).unwrap();
+    assert!(!root.command(dev).contains(Command::INTERRUPT_DISABLE));
+    assert_eq!(
+        root.enable_msi(bdf(0, 2, 0), 0x1000, 0),
+        Err(PciError::NoCapability)
+    );
+
+    let msix = root.msix_info(dev).unwrap();
+    assert_eq!(msix.table_size, 5);
+    assert_eq!((msix.table_bar, msix.table_offset), (1, 0x3000));
+    assert_eq!((msix.pba_bar, msix.pba_offset), (1, 0x3800));
+    root.set_msix_enable(dev, true).unwrap();
+    assert_eq!(root.config_read(dev, 0xa0) >> 16, (1 << 15) | 4);
+
+    let mut table = [0xffff_ffffu32; 8];
+    unsafe { write_msix_entry(table.as_mut_ptr(), 1, 0x0802_0040, 0x52) };
+    assert_eq!(table[4..], [0x0802_0040, 0, 0x52, 0]);
+}
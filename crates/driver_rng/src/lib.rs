@@ -0,0 +1,14 @@
+//! Common traits for random number generator (entropy source) drivers.
+
+#![no_std]
+
+use driver_common::{BaseDriverOps, DevResult};
+
+/// Operations that require a random number generator driver to implement.
+pub trait RngDriverOps: BaseDriverOps {
+    /// Fills `buf` with random bytes from the device.
+    ///
+    /// Returns the number of bytes filled, which may be less than the length
+    /// of `buf`.
+    fn fill_bytes(&mut self, buf: &mut [u8]) -> DevResult<usize>;
+}
@@ -0,0 +1,48 @@
+//! Common traits and types for serial port (UART) drivers.
+//!
+//! The drivers do not allocate, and can be constructed in `const` contexts,
+//! so that they can be used as the early console before the heap is ready.
+
+#![no_std]
+#![feature(doc_auto_cfg)]
+
+#[cfg(feature = "ns16550")]
+pub mod ns16550;
+#[cfg(feature = "pl011")]
+pub mod pl011;
+
+mod ring;
+
+pub use self::ring::RxRingBuffer;
+
+use driver_common::BaseDriverOps;
+
+/// Operations that require a serial port (UART) driver to implement.
+///
+/// All the methods are non-blocking, except [`putchar`] which waits for room
+/// in the transmit FIFO.
+///
+/// [`putchar`]: SerialDriverOps::putchar
+pub trait SerialDriverOps: BaseDriverOps {
+    /// Initializes the UART, with 8 data bits, no parity, 1 stop bit, the
+    /// FIFOs enabled and all the interrupts masked.
+    ///
+    /// The baud rate is left as configured by the firmware.
+    fn init(&mut self);
+
+    /// Writes a byte, spinning while the transmit FIFO is full.
+    fn putchar(&mut self, c: u8);
+
+    /// Reads a received byte, or returns `None` if the receive FIFO is empty.
+    fn getchar(&mut self) -> Option<u8>;
+
+    /// Enables or disables the interrupt on received data.
+    fn set_rx_interrupt(&mut self, enabled: bool);
+
+    /// Acknowledges the interrupt of the UART, returns whether the UART raised
+    /// it.
+    ///
+    /// The receive interrupt keeps pending until the receive FIFO is drained
+    /// by [`getchar`](SerialDriverOps::getchar).
+    fn ack_interrupt(&mut self) -> bool;
+}
@@ -0,0 +1,123 @@
+//! Driver for the NS16550(A) compatible UARTs.
+//!
+//! The registers are spaced by `1 << reg_shift` bytes, and accessed by 8-bit
+//! or 32-bit loads and stores, as described by the `reg-shift` and
+//! `reg-io-width` properties in the device tree.
+
+use crate::SerialDriverOps;
+use driver_common::{BaseDriverOps, DeviceType};
+
+// Registers
+const REG_RBR: usize = 0; // Receiver Buffer Register (read)
+const REG_THR: usize = 0; // Transmitter Holding Register (write)
+const REG_IER: usize = 1; // Interrupt Enable Register
+const REG_IIR: usize = 2; // Interrupt Identification Register (read)
+const REG_FCR: usize = 2; // FIFO Control Register (write)
+const REG_LCR: usize = 3; // Line Control Register
+const REG_MCR: usize = 4; // Modem Control Register
+const REG_LSR: usize = 5; // Line Status Register
+
+const IER_RDA: u8 = 1 << 0; // received data available
+const IER_RLS: u8 = 1 << 2; // receiver line status
+const IIR_NO_INT: u8 = 1 << 0;
+const FCR_ENABLE: u8 = 1 << 0;
+const FCR_CLEAR_RX: u8 = 1 << 1;
+const FCR_CLEAR_TX: u8 = 1 << 2;
+const LCR_8N1: u8 = 0b11;
+const MCR_DTR: u8 = 1 << 0;
+const MCR_RTS: u8 = 1 << 1;
+const MCR_OUT2: u8 = 1 << 3; // gates the interrupt output on PC-style boards
+const LSR_DR: u8 = 1 << 0; // data ready
+const LSR_THRE: u8 = 1 << 5; // transmitter holding register empty
+
+/// The NS16550(A) UART.
+pub struct Ns16550 {
+    base_vaddr: usize,
+    reg_shift: u8,
+    io_width32: bool,
+}
+
+impl Ns16550 {
+    /// Creates a driver for the UART whose registers are mapped at
+    /// `base_vaddr`.
+    ///
+    /// `reg_shift` is the log2 of the register spacing, and `reg_io_width` is
+    /// the width in bytes of the accesses (1 or 4). The UART is not touched
+    /// until [`SerialDriverOps::init`] is called.
+    pub const fn new(base_vaddr: usize, reg_shift: u8, reg_io_width: u8) -> Self {
+        Self {
+            base_vaddr,
+            reg_shift,
+            io_width32: reg_io_width == 4,
+        }
+    }
+
+    fn read_reg(&self, reg: usize) -> u8 {
+        let addr = self.base_vaddr + (reg << self.reg_shift);
+        unsafe {
+            if self.io_width32 {
+                (addr as *const u32).read_volatile() as u8
+            } else {
+                (addr as *const u8).read_volatile()
+            }
+        }
+    }
+
+    fn write_reg(&mut self, reg: usize, value: u8) {
+        let addr = self.base_vaddr + (reg << self.reg_shift);
+        unsafe {
+            if self.io_width32 {
+                (addr as *mut u32).write_volatile(value as u32);
+            } else {
+                (addr as *mut u8).write_volatile(value);
+            }
+        }
+    }
+}
+
+impl BaseDriverOps for Ns16550 {
+    fn device_name(&self) -> &str {
+        "ns16550a"
+    }
+
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Char
+    }
+}
+
+impl SerialDriverOps for Ns16550 {
+    fn init(&mut self) {
+        self.write_reg(REG_IER, 0);
+        self.write_reg(REG_LCR, LCR_8N1);
+        self.write_reg(REG_FCR, FCR_ENABLE | FCR_CLEAR_RX | FCR_CLEAR_TX);
+        self.write_reg(REG_MCR, MCR_DTR | MCR_RTS | MCR_OUT2);
+    }
+
+    fn putchar(&mut self, c: u8) {
+        while self.read_reg(REG_LSR) & LSR_THRE == 0 {}
+        self.write_reg(REG_THR, c);
+    }
+
+    fn getchar(&mut self) -> Option<u8> {
+        if self.read_reg(REG_LSR) & LSR_DR != 0 {
+            Some(self.read_reg(REG_RBR))
+        } else {
+            None
+        }
+    }
+
+    fn set_rx_interrupt(&mut self, enabled: bool) {
+        let ier = if enabled { IER_RDA | IER_RLS } else { 0 };
+        self.write_reg(REG_IER, ier);
+    }
+
+    fn ack_interrupt(&mut self) -> bool {
+        // reading IIR clears the THRE interrupt, the others are cleared by
+        // reading RBR or LSR
+        let pending = self.read_reg(REG_IIR) & IIR_NO_INT == 0;
+        if pending {
+            self.read_reg(REG_LSR);
+        }
+        pending
+    }
+}
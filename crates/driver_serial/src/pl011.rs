@@ -0,0 +1,112 @@
+//! Driver for the ARM PrimeCell UART (PL011).
+
+use tock_registers::interfaces::{Readable, Writeable};
+use tock_registers::register_structs;
+use tock_registers::registers::{ReadOnly, ReadWrite, WriteOnly};
+
+use crate::SerialDriverOps;
+use driver_common::{BaseDriverOps, DeviceType};
+
+register_structs! {
+    Pl011UartRegs {
+        /// Data Register.
+        (0x00 => dr: ReadWrite<u32>),
+        (0x04 => _reserved0),
+        /// Flag Register.
+        (0x18 => fr: ReadOnly<u32>),
+        (0x1c => _reserved1),
+        /// Line Control Register.
+        (0x2c => lcr_h: ReadWrite<u32>),
+        /// Control register.
+        (0x30 => cr: ReadWrite<u32>),
+        /// Interrupt FIFO Level Select Register.
+        (0x34 => ifls: ReadWrite<u32>),
+        /// Interrupt Mask Set Clear Register.
+        (0x38 => imsc: ReadWrite<u32>),
+        /// Raw Interrupt Status Register.
+        (0x3c => ris: ReadOnly<u32>),
+        /// Masked Interrupt Status Register.
+        (0x40 => mis: ReadOnly<u32>),
+        /// Interrupt Clear Register.
+        (0x44 => icr: WriteOnly<u32>),
+        (0x48 => @END),
+    }
+}
+
+const FR_RXFE: u32 = 1 << 4; // receive FIFO empty
+const FR_TXFF: u32 = 1 << 5; // transmit FIFO full
+const LCR_H_FEN: u32 = 1 << 4; // enable FIFOs
+const LCR_H_WLEN_8: u32 = 0b11 << 5;
+const CR_UARTEN: u32 = 1 << 0;
+const CR_TXE: u32 = 1 << 8;
+const CR_RXE: u32 = 1 << 9;
+const INT_RX: u32 = 1 << 4;
+const INT_RT: u32 = 1 << 6; // receive timeout
+const INT_ALL: u32 = 0x7ff;
+
+/// The PL011 UART.
+pub struct Pl011 {
+    base_vaddr: usize,
+}
+
+impl Pl011 {
+    /// Creates a driver for the UART whose registers are mapped at
+    /// `base_vaddr`.
+    ///
+    /// The UART is not touched until [`SerialDriverOps::init`] is called.
+    pub const fn new(base_vaddr: usize) -> Self {
+        Self { base_vaddr }
+    }
+
+    const fn regs(&self) -> &Pl011UartRegs {
+        unsafe { &*(self.base_vaddr as *const _) }
+    }
+}
+
+impl BaseDriverOps for Pl011 {
+    fn device_name(&self) -> &str {
+        "pl011"
+    }
+
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Char
+    }
+}
+
+impl SerialDriverOps for Pl011 {
+    fn init(&mut self) {
+        // mask and clear all irqs
+        self.regs().imsc.set(0);
+        self.regs().icr.set(INT_ALL);
+
+        // 8N1, and interrupt when the rx FIFO is 1/8 full or idle for a while
+        self.regs().lcr_h.set(LCR_H_WLEN_8 | LCR_H_FEN);
+        self.regs().ifls.set(0);
+
+        self.regs().cr.set(CR_UARTEN | CR_TXE | CR_RXE);
+    }
+
+    fn putchar(&mut self, c: u8) {
+        while self.regs().fr.get() & FR_TXFF != 0 {}
+        self.regs().dr.set(c as u32);
+    }
+
+    fn getchar(&mut self) -> Option<u8> {
+        if self.regs().fr.get() & FR_RXFE == 0 {
+            Some(self.regs().dr.get() as u8)
+        } else {
+            None
+        }
+    }
+
+    fn set_rx_interrupt(&mut self, enabled: bool) {
+        let imsc = if enabled { INT_RX | INT_RT } else { 0 };
+        self.regs().imsc.set(imsc);
+    }
+
+    fn ack_interrupt(&mut self) -> bool {
+        let mis = self.regs().mis.get();
+        self.regs().icr.set(mis);
+        mis != 0
+    }
+}
@@ -0,0 +1,76 @@
+/// A fixed-size ring buffer of received bytes.
+///
+/// It is filled by the interrupt handler, and drained by the readers. New
+/// bytes are dropped if it is full.
+pub struct RxRingBuffer<const N: usize> {
+    buf: [u8; N],
+    head: usize,
+    len: usize,
+}
+
+impl<const N: usize> RxRingBuffer<N> {
+    /// Creates an empty ring buffer.
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// The number of bytes in the buffer.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the buffer is empty.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether the buffer is full.
+    pub const fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// Appends a byte, returns `false` if the buffer is full and the byte is
+    /// dropped.
+    pub fn push(&mut self, c: u8) -> bool {
+        if self.is_full() {
+            return false;
+        }
+        self.buf[(self.head + self.len) % N] = c;
+        self.len += 1;
+        true
+    }
+
+    /// Removes the oldest byte.
+    pub fn pop(&mut self) -> Option<u8> {
+        if self.is_empty() {
+            return None;
+        }
+        let c = self.buf[self.head];
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        Some(c)
+    }
+
+    /// Moves the oldest bytes to `buf`, returns the number of bytes moved.
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        let mut n = 0;
+        while n < buf.len() {
+            match self.pop() {
+                Some(c) => buf[n] = c,
+                None => break,
+            }
+            n += 1;
+        }
+        n
+    }
+}
+
+impl<const N: usize> Default for RxRingBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
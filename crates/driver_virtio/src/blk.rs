@@ -1,11 +1,31 @@
+use alloc::{boxed::Box, vec::Vec};
+
 use crate::as_dev_err;
-use driver_block::BlockDriverOps;
-use driver_common::{BaseDriverOps, DevResult, DeviceType};
-use virtio_drivers::{device::blk::VirtIOBlk as InnerDev, transport::Transport, Hal};
+use driver_block::{BlockDriverOps, RequestToken};
+use driver_common::{BaseDriverOps, DevError, DevResult, DeviceType};
+use virtio_drivers::device::blk::{BlkReq, BlkResp, VirtIOBlk as InnerDev};
+use virtio_drivers::{transport::Transport, Error, Hal};
+
+/// An in-flight request. The header and the response are accessed by the
+/// device, so they are boxed to stay in place until the completion.
+struct Request {
+    req: BlkReq,
+    resp: BlkResp,
+    buf: *mut [u8],
+    is_write: bool,
+}
 
 /// The VirtIO block device driver.
+///
+/// Besides the blocking operations, multiple requests can be submitted to the
+/// device queue at the same time by [`BlockDriverOps::submit_read`] and
+/// [`BlockDriverOps::submit_write`]. The blocking operations are not allowed
+/// while there are in-flight requests submitted that way.
 pub struct VirtIoBlkDev<H: Hal, T: Transport> {
     inner: InnerDev<H, T>,
+    /// The in-flight requests, indexed by their tokens.
+    requests: Vec<Option<Box<Request>>>,
+    inflight: usize,
 }
 
 unsafe impl<H: Hal, T: Transport> Send for VirtIoBlkDev<H, T> {}
@@ -15,10 +35,88 @@ impl<H: Hal, T: Transport> VirtIoBlkDev<H, T> {
     /// Creates a new driver instance and initializes the device, or returns
     /// an error if any step fails.
     pub fn try_new(transport: T) -> DevResult<Self> {
+        let inner = InnerDev::new(transport).map_err(as_dev_err)?;
+        let mut requests = Vec::new();
+        requests.resize_with(inner.virt_queue_size() as usize, || None);
         Ok(Self {
-            inner: InnerDev::new(transport).map_err(as_dev_err)?,
+            inner,
+            requests,
+            inflight: 0,
         })
     }
+
+    unsafe fn submit(
+        &mut self,
+        block_id: u64,
+        buf: *mut [u8],
+        is_write: bool,
+    ) -> DevResult<RequestToken> {
+        let mut request = Box::new(Request {
+            req: BlkReq::default(),
+            resp: BlkResp::default(),
+            buf,
+            is_write,
+        });
+        let Request { req, resp, .. } = &mut *request;
+        let res = if is_write {
+            self.inner.write_block_nb(block_id as _, req, &*buf, resp)
+        } else {
+            self.inner
+                .read_block_nb(block_id as _, req, &mut *buf, resp)
+        };
+        let token = match res {
+            Ok(token) => token,
+            Err(Error::QueueFull) => return Err(DevError::Again),
+            Err(e) => return Err(as_dev_err(e)),
+        };
+        match self.requests.get_mut(token as usize) {
+            Some(slot @ None) => {
+                *slot = Some(request);
+                self.inflight += 1;
+                Ok(token)
+            }
+            _ => Err(DevError::BadState),
+        }
+    }
+
+    /// Submits all `requests`, then busy-waits for them to complete. Returns
+    /// the first error.
+    fn batch<I>(&mut self, requests: I, is_write: bool) -> DevResult
+    where
+        I: Iterator<Item = (u64, *mut [u8])>,
+    {
+        if self.inflight > 0 {
+            return Err(DevError::ResourceBusy);
+        }
+        let mut result = Ok(());
+        for (block_id, buf) in requests {
+            loop {
+                match unsafe { self.submit(block_id, buf, is_write) } {
+                    Err(DevError::Again) if self.inflight > 0 => self.wait_one(&mut result),
+                    res => {
+                        result = result.and(res.map(|_| ()));
+                        break;
+                    }
+                }
+            }
+        }
+        while self.inflight > 0 {
+            self.wait_one(&mut result);
+        }
+        result
+    }
+
+    /// Busy-waits for an in-flight request to complete, and merges its result
+    /// into `result`.
+    fn wait_one(&mut self, result: &mut DevResult) {
+        loop {
+            if let Some((_, res)) = self.poll_completion() {
+                *result = core::mem::replace(result, Ok(())).and(res);
+                return;
+            }
+            core::hint::spin_loop();
+        }
+    }
 }
 
 impl<H: Hal, T: Transport> const BaseDriverOps for VirtIoBlkDev<H, T> {
@@ -43,12 +141,18 @@ impl<H: Hal, T: Transport> BlockDriverOps for VirtIoBlkDev<H, T> {
     }
 
     fn read_block(&mut self, block_id: u64, buf: &mut [u8]) -> DevResult {
+        if self.inflight > 0 {
+            return Err(DevError::ResourceBusy);
+        }
         self.inner
             .read_block(block_id as _, buf)
             .map_err(as_dev_err)
     }
 
     fn write_block(&mut self, block_id: u64, buf: &[u8]) -> DevResult {
+        if self.inflight > 0 {
+            return Err(DevError::ResourceBusy);
+        }
         self.inner
             .write_block(block_id as _, buf)
             .map_err(as_dev_err)
@@ -57,4 +161,51 @@ impl<H: Hal, T: Transport> BlockDriverOps for VirtIoBlkDev<H, T> {
     fn flush(&mut self) -> DevResult {
         Ok(())
     }
+
+    fn read_blocks(&mut self, requests: &mut [(u64, &mut [u8])]) -> DevResult {
+        let requests = requests
+            .iter_mut()
+            .map(|(block_id, buf)| (*block_id, &mut **buf as *mut [u8]));
+        self.batch(requests, false)
+    }
+
+    fn write_blocks(&mut self, requests: &[(u64, &[u8])]) -> DevResult {
+        // the buffers are only read by the device
+        let requests = requests
+            .iter()
+            .map(|(block_id, buf)| (*block_id, *buf as *const [u8] as *mut [u8]));
+        self.batch(requests, true)
+    }
+
+    unsafe fn submit_read(&mut self, block_id: u64, buf: *mut [u8]) -> DevResult<RequestToken> {
+        self.submit(block_id, buf, false)
+    }
+
+    unsafe fn submit_write(&mut self, block_id: u64, buf: *const [u8]) -> DevResult<RequestToken> {
+        self.submit(block_id, buf as *mut [u8], true)
+    }
+
+    fn poll_completion(&mut self) -> Option<(RequestToken, DevResult)> {
+        let token = self.inner.peek_used()?;
+        let mut request = self.requests.get_mut(token as usize)?.take()?;
+        self.inflight -= 1;
+        let Request {
+            req,
+            resp,
+            buf,
+            is_write,
+        } = &mut *request;
+        let res = unsafe {
+            if *is_write {
+                self.inner.complete_write_block(token, req, &**buf, resp)
+            } else {
+                self.inner.complete_read_block(token, req, &mut **buf, resp)
+            }
+        };
+        Some((token, res.map_err(as_dev_err)))
+    }
+
+    fn ack_interrupt(&mut self) -> bool {
+        self.inner.ack_interrupt()
+    }
 }
@@ -0,0 +1,57 @@
+use crate::as_dev_err;
+use driver_char::CharDriverOps;
+use driver_common::{BaseDriverOps, DevResult, DeviceType};
+use virtio_drivers::{device::console::VirtIOConsole as InnerDev, transport::Transport, Hal};
+
+/// The VirtIO console device driver.
+pub struct VirtIoConsoleDev<H: Hal, T: Transport> {
+    inner: InnerDev<'static, H, T>,
+}
+
+unsafe impl<H: Hal, T: Transport> Send for VirtIoConsoleDev<H, T> {}
+unsafe impl<H: Hal, T: Transport> Sync for VirtIoConsoleDev<H, T> {}
+
+impl<H: Hal, T: Transport> VirtIoConsoleDev<H, T> {
+    /// Creates a new driver instance and initializes the device, or returns
+    /// an error if any step fails.
+    pub fn try_new(transport: T) -> DevResult<Self> {
+        Ok(Self {
+            inner: InnerDev::new(transport).map_err(as_dev_err)?,
+        })
+    }
+}
+
+impl<H: Hal, T: Transport> const BaseDriverOps for VirtIoConsoleDev<H, T> {
+    fn device_name(&self) -> &str {
+        "virtio-console"
+    }
+
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Char
+    }
+}
+
+impl<H: Hal, T: Transport> CharDriverOps for VirtIoConsoleDev<H, T> {
+    fn read_bytes(&mut self, buf: &mut [u8]) -> DevResult<usize> {
+        let mut read_len = 0;
+        while read_len < buf.len() {
+            match self.inner.recv(true).map_err(as_dev_err)? {
+                Some(c) => buf[read_len] = c,
+                None => break,
+            }
+            read_len += 1;
+        }
+        Ok(read_len)
+    }
+
+    fn write_bytes(&mut self, buf: &[u8]) -> DevResult<usize> {
+        for &c in buf {
+            self.inner.send(c).map_err(as_dev_err)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn ack_interrupt(&mut self) -> bool {
+        self.inner.ack_interrupt().unwrap_or(false)
+    }
+}
@@ -2,7 +2,7 @@ extern crate alloc;
 use crate::as_dev_err;
 
 use driver_common::{BaseDriverOps, DevResult, DeviceType};
-use driver_display::{DisplayDriverOps, DisplayInfo, FrameBuffer};
+use driver_display::{DisplayDriverOps, DisplayInfo, Framebuffer, PixelFormat};
 use virtio_drivers::{device::gpu::VirtIOGpu as InnerDev, transport::Transport, Hal};
 
 /// The VirtIO GPU device driver.
@@ -25,9 +25,12 @@ impl<H: Hal, T: Transport> VirtIoGpuDev<H, T> {
         let fb_base_vaddr = fbuffer.as_mut_ptr() as usize;
         let fb_size = fbuffer.len();
         let (width, height) = virtio.resolution().unwrap();
+        // the framebuffer is a resource of format `B8G8R8A8_UNORM`
         let info = DisplayInfo {
             width,
             height,
+            stride: width * 4,
+            format: PixelFormat::Bgra8888,
             fb_base_vaddr,
             fb_size,
         };
@@ -54,10 +57,8 @@ impl<H: Hal, T: Transport> DisplayDriverOps for VirtIoGpuDev<H, T> {
         self.info
     }
 
-    fn fb(&self) -> FrameBuffer {
-        unsafe {
-            FrameBuffer::from_raw_parts_mut(self.info.fb_base_vaddr as *mut u8, self.info.fb_size)
-        }
+    fn fb(&self) -> Framebuffer {
+        unsafe { Framebuffer::from_info(&self.info) }
     }
 
     fn need_flush(&self) -> bool {
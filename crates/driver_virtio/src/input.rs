@@ -0,0 +1,59 @@
+use driver_common::{BaseDriverOps, DevResult, DeviceType};
+use driver_input::{EventType, InputDriverOps, InputEvent};
+use virtio_drivers::device::input::{InputConfigSelect, VirtIOInput as InnerDev};
+use virtio_drivers::{transport::Transport, Hal};
+
+use crate::as_dev_err;
+
+/// The VirtIO input device driver, for keyboards, mice and tablets.
+pub struct VirtIoInputDev<H: Hal, T: Transport> {
+    inner: InnerDev<H, T>,
+}
+
+unsafe impl<H: Hal, T: Transport> Send for VirtIoInputDev<H, T> {}
+unsafe impl<H: Hal, T: Transport> Sync for VirtIoInputDev<H, T> {}
+
+impl<H: Hal, T: Transport> VirtIoInputDev<H, T> {
+    /// Creates a new driver instance and initializes the device, or returns
+    /// an error if any step fails.
+    pub fn try_new(transport: T) -> DevResult<Self> {
+        Ok(Self {
+            inner: InnerDev::new(transport).map_err(as_dev_err)?,
+        })
+    }
+}
+
+impl<H: Hal, T: Transport> const BaseDriverOps for VirtIoInputDev<H, T> {
+    fn device_name(&self) -> &str {
+        "virtio-input"
+    }
+
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Input
+    }
+}
+
+impl<H: Hal, T: Transport> InputDriverOps for VirtIoInputDev<H, T> {
+    fn read_event(&mut self) -> Option<InputEvent> {
+        self.inner.pop_pending_event().map(|e| InputEvent {
+            event_type: e.event_type,
+            code: e.code,
+            value: e.value,
+        })
+    }
+
+    fn has_event_type(&mut self, event_type: EventType) -> DevResult<bool> {
+        // the bitmap of the supported codes, empty if the type is unsupported
+        let mut bitmap = [0u8; 128];
+        let size = self.inner.query_config_select(
+            InputConfigSelect::EvBits,
+            event_type as u8,
+            &mut bitmap,
+        );
+        Ok(size > 0)
+    }
+
+    fn ack_interrupt(&mut self) -> bool {
+        self.inner.ack_interrupt()
+    }
+}
@@ -16,20 +16,33 @@
 
 #[macro_use]
 extern crate log;
+extern crate alloc;
 
 #[cfg(feature = "block")]
 mod blk;
+#[cfg(feature = "console")]
+mod console;
 #[cfg(feature = "gpu")]
 mod gpu;
+#[cfg(feature = "input")]
+mod input;
 #[cfg(feature = "net")]
 mod net;
+#[cfg(feature = "rng")]
+mod rng;
 
 #[cfg(feature = "block")]
 pub use self::blk::VirtIoBlkDev;
+#[cfg(feature = "console")]
+pub use self::console::VirtIoConsoleDev;
 #[cfg(feature = "gpu")]
 pub use self::gpu::VirtIoGpuDev;
+#[cfg(feature = "input")]
+pub use self::input::VirtIoInputDev;
 #[cfg(feature = "net")]
 pub use self::net::VirtIoNetDev;
+#[cfg(feature = "rng")]
+pub use self::rng::VirtIoRngDev;
 
 use driver_common::{DevError, DeviceType};
 use virtio_drivers::transport;
@@ -75,12 +88,88 @@ pub fn probe_mmio_device(
     }
 }
 
+/// Try to probe a VirtIO PCI device at the given PCI function.
+///
+/// `ecam_base` is the virtual address of the ECAM configuration space, which
+/// starts at bus 0. The memory BARs of the function must have been assigned
+/// with addresses equal to their physical addresses, and the memory decoding
+/// must have been enabled.
+///
+/// If the function is a VirtIO device, [`Some(PciTransport)`][PciTransport]
+/// is returned. Otherwise, [`None`] is returned.
+///
+/// If `type_match` is [`None`], the device type is not considered. Otherwise,
+/// [`Some`] is returned only if the device type also matches.
+#[cfg(feature = "bus-pci")]
+pub fn probe_pci_device<H: VirtIoHal>(
+    ecam_base: *mut u8,
+    (bus, device, function): (u8, u8, u8),
+    (vendor_id, device_id): (u16, u16),
+    type_match: Option<DeviceType>,
+) -> Option<PciTransport> {
+    use transport::pci::bus::{Cam, DeviceFunction, PciRoot};
+
+    let dev_type = pci_dev_type(vendor_id, device_id)?;
+    if type_match.is_some() && Some(dev_type) != type_match {
+        return None;
+    }
+    let mut root = unsafe { PciRoot::new(ecam_base, Cam::Ecam) };
+    let bdf = DeviceFunction {
+        bus,
+        device,
+        function,
+    };
+    match PciTransport::new::<H>(&mut root, bdf) {
+        Ok(transport) => {
+            debug!(
+                "Detected virtio PCI device at {:02x}:{:02x}.{}, device type: {:?}",
+                bus,
+                device,
+                function,
+                transport.device_type(),
+            );
+            Some(transport)
+        }
+        Err(e) => {
+            warn!(
+                "failed to create virtio PCI transport at {:02x}:{:02x}.{}: {:?}",
+                bus, device, function, e
+            );
+            None
+        }
+    }
+}
+
+/// The device type of a VirtIO PCI function, from its vendor and device IDs.
+///
+/// Both the transitional (`0x1000..0x1040`) and the modern (`0x1040 + type`)
+/// device IDs are recognized.
+#[cfg(feature = "bus-pci")]
+const fn pci_dev_type(vendor_id: u16, device_id: u16) -> Option<DeviceType> {
+    const VIRTIO_VENDOR_ID: u16 = 0x1af4;
+    if vendor_id != VIRTIO_VENDOR_ID {
+        return None;
+    }
+    match device_id {
+        0x1000 | 0x1041 => Some(DeviceType::Net),
+        0x1001 | 0x1042 => Some(DeviceType::Block),
+        0x1003 | 0x1043 => Some(DeviceType::Char),
+        0x1005 | 0x1044 => Some(DeviceType::Rng),
+        0x1050 => Some(DeviceType::Display),
+        0x1052 => Some(DeviceType::Input),
+        _ => None,
+    }
+}
+
 const fn as_dev_type(t: transport::DeviceType) -> Option<DeviceType> {
     use transport::DeviceType::*;
     match t {
         Block => Some(DeviceType::Block),
         Network => Some(DeviceType::Net),
         GPU => Some(DeviceType::Display),
+        Console => Some(DeviceType::Char),
+        EntropySource => Some(DeviceType::Rng),
+        Input => Some(DeviceType::Input),
         _ => None,
     }
 }
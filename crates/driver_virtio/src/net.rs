@@ -1,65 +1,430 @@
+use alloc::sync::Arc;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+use core::sync::atomic::{fence, AtomicU64, Ordering};
+
 use crate::as_dev_err;
-use driver_common::{BaseDriverOps, DevResult, DeviceType};
-use driver_net::{EthernetAddress, NetBuffer, NetDriverOps};
-use virtio_drivers::device::net::{self, VirtIONet as InnerDev};
-use virtio_drivers::{transport::Transport, Hal};
+use driver_common::{BaseDriverOps, DevError, DevResult, DeviceType};
+use driver_net::{ChecksumOffload, EthernetAddress, NetBuffer, NetCapabilities, NetDriverOps};
+use virtio_drivers::{transport::Transport, BufferDirection, Hal, PhysAddr};
 
-/// The VirtIO network device driver.
-///
-/// `QS` is the VirtIO queue size.
-pub struct VirtIoNetDev<H: Hal, T: Transport, const QS: usize> {
-    inner: InnerDev<H, T, QS>,
+const PAGE_SIZE: usize = 0x1000;
+const QUEUE_RECEIVE: u16 = 0;
+const QUEUE_TRANSMIT: u16 = 1;
+
+// Feature bits
+const F_CSUM: u64 = 1 << 0;
+const F_MTU: u64 = 1 << 3;
+const F_MAC: u64 = 1 << 5;
+const F_MRG_RXBUF: u64 = 1 << 15;
+const F_VERSION_1: u64 = 1 << 32;
+
+const DESC_F_NEXT: u16 = 1;
+const DESC_F_WRITE: u16 = 2;
+const USED_F_NO_NOTIFY: u16 = 1;
+const HDR_F_NEEDS_CSUM: u8 = 1;
+
+/// The room before the packet in each buffer, where the header is placed.
+const HDR_ROOM: usize = 16;
+const ETH_HDR_LEN: usize = 14;
+
+#[repr(C)]
+struct NetConfig {
+    mac: [u8; 6],
+    status: u16,
+    max_virtqueue_pairs: u16,
+    mtu: u16,
 }
 
-unsafe impl<H: Hal, T: Transport, const QS: usize> Send for VirtIoNetDev<H, T, QS> {}
-unsafe impl<H: Hal, T: Transport, const QS: usize> Sync for VirtIoNetDev<H, T, QS> {}
+/// The header before each packet. The last field only exists with
+/// `VIRTIO_F_VERSION_1` or `VIRTIO_NET_F_MRG_RXBUF`.
+#[repr(C)]
+#[derive(Default)]
+struct NetHeader {
+    flags: u8,
+    gso_type: u8,
+    hdr_len: u16,
+    gso_size: u16,
+    csum_start: u16,
+    csum_offset: u16,
+    num_buffers: u16,
+}
 
-pub struct RxBufferWrapper(net::RxBuffer);
-pub struct TxBufferWrapper(net::TxBuffer);
+#[repr(C)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
 
-impl NetBuffer for RxBufferWrapper {
-    #[inline]
-    fn packet_len(&self) -> usize {
-        self.0.packet_len()
+#[repr(C)]
+struct UsedElem {
+    id: u32,
+    len: u32,
+}
+
+/// A pool of DMA buffers of the same size, shared by the driver and the
+/// buffers lent out, so it outlives both of them.
+struct BufPool<H: Hal> {
+    paddr: PhysAddr,
+    vaddr: NonNull<u8>,
+    pages: usize,
+    buf_size: usize,
+    /// The bitmap of idle buffers, which are neither lent out nor in the queue.
+    idle: AtomicU64,
+    _hal: PhantomData<H>,
+}
+
+unsafe impl<H: Hal> Send for BufPool<H> {}
+unsafe impl<H: Hal> Sync for BufPool<H> {}
+
+impl<H: Hal> BufPool<H> {
+    fn new(count: usize, buf_size: usize) -> DevResult<Self> {
+        let pages = align_up(count * buf_size) / PAGE_SIZE;
+        let (paddr, vaddr) = H::dma_alloc(pages, BufferDirection::Both);
+        if paddr == 0 {
+            return Err(DevError::NoMemory);
+        }
+        Ok(Self {
+            paddr,
+            vaddr,
+            pages,
+            buf_size,
+            idle: AtomicU64::new(u64::MAX >> (64 - count)),
+            _hal: PhantomData,
+        })
     }
 
-    #[inline]
-    fn packet(&self) -> &[u8] {
-        self.0.packet()
+    fn buf_paddr(&self, idx: usize) -> u64 {
+        (self.paddr + idx * self.buf_size) as u64
     }
 
-    #[inline]
-    fn packet_mut(&mut self) -> &mut [u8] {
-        self.0.packet_mut()
+    fn buf_ptr(&self, idx: usize) -> *mut u8 {
+        unsafe { self.vaddr.as_ptr().add(idx * self.buf_size) }
+    }
+
+    /// Takes an idle buffer out.
+    fn take_idle(&self) -> Option<usize> {
+        let idle = self.idle.load(Ordering::Acquire);
+        if idle == 0 {
+            return None;
+        }
+        // only the driver takes buffers, others can only set the bits
+        let idx = idle.trailing_zeros() as usize;
+        self.idle.fetch_and(!(1 << idx), Ordering::AcqRel);
+        Some(idx)
+    }
+
+    fn put_idle(&self, idx: usize) {
+        self.idle.fetch_or(1 << idx, Ordering::AcqRel);
+    }
+}
+
+impl<H: Hal> Drop for BufPool<H> {
+    fn drop(&mut self) {
+        unsafe { H::dma_dealloc(self.paddr, self.vaddr, self.pages) };
+    }
+}
+
+/// A split virtqueue of `2 * bufs` descriptors, where the buffer `i` of the
+/// pool always uses the descriptor chain `(2i, 2i + 1)`, one for the header
+/// and one for the packet.
+struct NetQueue<H: Hal> {
+    idx: u16,
+    size: u16,
+    paddr: PhysAddr,
+    vaddr: NonNull<u8>,
+    pages: usize,
+    avail_offset: usize,
+    used_offset: usize,
+    avail_idx: u16,
+    last_used_idx: u16,
+    /// Whether buffers were added since the last notification.
+    need_notify: bool,
+    _hal: PhantomData<H>,
+}
+
+impl<H: Hal> NetQueue<H> {
+    /// Allocates the rings in the legacy layout, where the used ring starts
+    /// at the next page after the available ring, and sets up the queue.
+    fn new<T: Transport>(
+        transport: &mut T,
+        idx: u16,
+        size: u16,
+        pool: &BufPool<H>,
+        hdr_len: usize,
+    ) -> DevResult<Self> {
+        if transport.queue_used(idx) || transport.max_queue_size() < size as u32 {
+            return Err(DevError::BadState);
+        }
+        let qs = size as usize;
+        let avail_offset = core::mem::size_of::<Descriptor>() * qs;
+        let used_offset = align_up(avail_offset + 2 * (qs + 3));
+        let pages = align_up(used_offset + 6 + 8 * qs) / PAGE_SIZE;
+        let (paddr, vaddr) = H::dma_alloc(pages, BufferDirection::Both);
+        if paddr == 0 {
+            return Err(DevError::NoMemory);
+        }
+        unsafe { core::ptr::write_bytes(vaddr.as_ptr(), 0, pages * PAGE_SIZE) };
+
+        let queue = Self {
+            idx,
+            size,
+            paddr,
+            vaddr,
+            pages,
+            avail_offset,
+            used_offset,
+            avail_idx: 0,
+            last_used_idx: 0,
+            need_notify: false,
+            _hal: PhantomData,
+        };
+        for i in 0..qs / 2 {
+            let buf = pool.buf_paddr(i);
+            unsafe {
+                queue.desc(2 * i).write_volatile(Descriptor {
+                    addr: buf,
+                    len: hdr_len as u32,
+                    flags: DESC_F_NEXT,
+                    next: 2 * i as u16 + 1,
+                });
+                queue.desc(2 * i + 1).write_volatile(Descriptor {
+                    addr: buf + HDR_ROOM as u64,
+                    len: 0,
+                    flags: 0,
+                    next: 0,
+                });
+            }
+        }
+        transport.queue_set(
+            idx,
+            size as u32,
+            paddr,
+            paddr + avail_offset,
+            paddr + used_offset,
+        );
+        Ok(queue)
+    }
+
+    fn ptr<U>(&self, offset: usize) -> *mut U {
+        unsafe { self.vaddr.as_ptr().add(offset) as *mut U }
+    }
+
+    fn desc(&self, idx: usize) -> *mut Descriptor {
+        unsafe { self.ptr::<Descriptor>(0).add(idx) }
+    }
+
+    /// Makes the buffer `buf` available to the device, with `len` bytes of the
+    /// packet. For receive queues, the packet part is writable.
+    fn push(&mut self, buf: usize, len: usize, writable: bool) {
+        let flags = if writable { DESC_F_WRITE } else { 0 };
+        unsafe {
+            let head = self.desc(2 * buf);
+            let data = self.desc(2 * buf + 1);
+            core::ptr::addr_of_mut!((*head).flags).write_volatile(flags | DESC_F_NEXT);
+            core::ptr::addr_of_mut!((*data).len).write_volatile(len as u32);
+            core::ptr::addr_of_mut!((*data).flags).write_volatile(flags);
+
+            let slot = (self.avail_idx % self.size) as usize;
+            self.ptr::<u16>(self.avail_offset + 4)
+                .add(slot)
+                .write_volatile(2 * buf as u16);
+            fence(Ordering::SeqCst);
+            self.avail_idx = self.avail_idx.wrapping_add(1);
+            self.ptr::<u16>(self.avail_offset + 2)
+                .write_volatile(self.avail_idx);
+        }
+        self.need_notify = true;
+    }
+
+    fn used_idx(&self) -> u16 {
+        unsafe { self.ptr::<u16>(self.used_offset + 2).read_volatile() }
+    }
+
+    fn has_used(&self) -> bool {
+        self.used_idx() != self.last_used_idx
+    }
+
+    /// Takes a buffer used by the device, returns its index and the number of
+    /// bytes written by the device.
+    fn pop_used(&mut self) -> Option<(usize, usize)> {
+        if !self.has_used() {
+            return None;
+        }
+        fence(Ordering::SeqCst);
+        let slot = (self.last_used_idx % self.size) as usize;
+        let elem = unsafe {
+            self.ptr::<UsedElem>(self.used_offset + 4)
+                .add(slot)
+                .read_volatile()
+        };
+        self.last_used_idx = self.last_used_idx.wrapping_add(1);
+        Some((elem.id as usize / 2, elem.len as usize))
+    }
+
+    /// Notifies the device if buffers were added, unless the device does not
+    /// want to be notified.
+    fn kick<T: Transport>(&mut self, transport: &mut T) {
+        if !self.need_notify {
+            return;
+        }
+        self.need_notify = false;
+        fence(Ordering::SeqCst);
+        let flags = unsafe { self.ptr::<u16>(self.used_offset).read_volatile() };
+        if flags & USED_F_NO_NOTIFY == 0 {
+            transport.notify(self.idx);
+        }
     }
 }
 
-impl NetBuffer for TxBufferWrapper {
+impl<H: Hal> Drop for NetQueue<H> {
+    fn drop(&mut self) {
+        unsafe { H::dma_dealloc(self.paddr, self.vaddr, self.pages) };
+    }
+}
+
+/// The buffer of [`VirtIoNetDev`], which is a DMA buffer of the device, so
+/// packets are never copied.
+///
+/// A buffer dropped without being transmitted or recycled is given back to
+/// the device automatically.
+pub struct VirtIoNetBuffer<H: Hal> {
+    pool: Arc<BufPool<H>>,
+    idx: usize,
+    len: usize,
+    /// Whether the buffer has been put into a queue.
+    queued: bool,
+}
+
+impl<H: Hal> NetBuffer for VirtIoNetBuffer<H> {
     #[inline]
     fn packet_len(&self) -> usize {
-        self.0.packet_len()
+        self.len
     }
 
     #[inline]
     fn packet(&self) -> &[u8] {
-        self.0.packet()
+        let ptr = unsafe { self.pool.buf_ptr(self.idx).add(HDR_ROOM) };
+        unsafe { core::slice::from_raw_parts(ptr, self.len) }
     }
 
     #[inline]
     fn packet_mut(&mut self) -> &mut [u8] {
-        self.0.packet_mut()
+        let ptr = unsafe { self.pool.buf_ptr(self.idx).add(HDR_ROOM) };
+        unsafe { core::slice::from_raw_parts_mut(ptr, self.len) }
+    }
+}
+
+impl<H: Hal> Drop for VirtIoNetBuffer<H> {
+    fn drop(&mut self) {
+        if !self.queued {
+            self.pool.put_idle(self.idx);
+        }
     }
 }
 
+/// The VirtIO network device driver.
+///
+/// `QS` is the VirtIO queue size, which must be a power of two no more than
+/// 128. Each packet takes two descriptors, so at most `QS / 2` packets can be
+/// in flight for each direction.
+///
+/// The queues are managed here rather than by the [`virtio-drivers`] crate,
+/// whose driver waits for each packet to be sent. Packets are submitted in
+/// batches, and the device is notified once for a batch on flushing.
+///
+/// [`virtio-drivers`]: https://docs.rs/virtio-drivers/latest/virtio_drivers/
+pub struct VirtIoNetDev<H: Hal, T: Transport, const QS: usize> {
+    transport: T,
+    mac: [u8; 6],
+    mtu: usize,
+    hdr_len: usize,
+    tx_csum: bool,
+    rx_queue: NetQueue<H>,
+    tx_queue: NetQueue<H>,
+    /// The idle receive buffers are recycled ones to be posted again.
+    rx_pool: Arc<BufPool<H>>,
+    /// The idle transmit buffers are free to allocate.
+    tx_pool: Arc<BufPool<H>>,
+}
+
+unsafe impl<H: Hal, T: Transport, const QS: usize> Send for VirtIoNetDev<H, T, QS> {}
+unsafe impl<H: Hal, T: Transport, const QS: usize> Sync for VirtIoNetDev<H, T, QS> {}
+
 impl<H: Hal, T: Transport, const QS: usize> VirtIoNetDev<H, T, QS> {
     /// Creates a new driver instance and initializes the device, or returns
     /// an error if any step fails.
     ///
     /// `buf_len` is the length of the transmit and receive buffer.
-    pub fn try_new(transport: T, buf_len: usize) -> DevResult<Self> {
-        Ok(Self {
-            inner: InnerDev::new(transport, buf_len).map_err(as_dev_err)?,
-        })
+    pub fn try_new(mut transport: T, buf_len: usize) -> DevResult<Self> {
+        if !QS.is_power_of_two() || !(2..=128).contains(&QS) {
+            return Err(DevError::InvalidParam);
+        }
+        if buf_len < HDR_ROOM + ETH_HDR_LEN + driver_net::DEFAULT_MTU {
+            return Err(DevError::InvalidParam);
+        }
+
+        let mut features = 0;
+        transport.begin_init(|device_features| {
+            features = device_features & (F_CSUM | F_MTU | F_MAC | F_VERSION_1);
+            features
+        });
+        let hdr_len = if features & (F_VERSION_1 | F_MRG_RXBUF) != 0 {
+            core::mem::size_of::<NetHeader>()
+        } else {
+            core::mem::size_of::<NetHeader>() - 2
+        };
+
+        let config = transport
+            .config_space::<NetConfig>()
+            .map_err(as_dev_err)?
+            .as_ptr();
+        let mac = unsafe { core::ptr::addr_of!((*config).mac).read_volatile() };
+        let max_mtu = buf_len - HDR_ROOM - ETH_HDR_LEN;
+        let mtu = if features & F_MTU != 0 {
+            let mtu = unsafe { core::ptr::addr_of!((*config).mtu).read_volatile() };
+            (mtu as usize).min(max_mtu)
+        } else {
+            driver_net::DEFAULT_MTU
+        };
+
+        let rx_pool = Arc::new(BufPool::new(QS / 2, buf_len)?);
+        let tx_pool = Arc::new(BufPool::new(QS / 2, buf_len)?);
+        let rx_queue = NetQueue::new(&mut transport, QUEUE_RECEIVE, QS as u16, &rx_pool, hdr_len)?;
+        let tx_queue = NetQueue::new(&mut transport, QUEUE_TRANSMIT, QS as u16, &tx_pool, hdr_len)?;
+        transport.finish_init();
+
+        let mut dev = Self {
+            transport,
+            mac,
+            mtu,
+            hdr_len,
+            tx_csum: features & F_CSUM != 0,
+            rx_queue,
+            tx_queue,
+            rx_pool,
+            tx_pool,
+        };
+        dev.flush()?; // post all receive buffers
+        Ok(dev)
+    }
+
+    fn new_buffer(pool: &Arc<BufPool<H>>, idx: usize, len: usize) -> VirtIoNetBuffer<H> {
+        VirtIoNetBuffer {
+            pool: pool.clone(),
+            idx,
+            len,
+            queued: false,
+        }
+    }
+}
+
+impl<H: Hal, T: Transport, const QS: usize> Drop for VirtIoNetDev<H, T, QS> {
+    fn drop(&mut self) {
+        self.transport.queue_unset(QUEUE_RECEIVE);
+        self.transport.queue_unset(QUEUE_TRANSMIT);
     }
 }
 
@@ -74,36 +439,150 @@ impl<H: Hal, T: Transport, const QS: usize> const BaseDriverOps for VirtIoNetDev
 }
 
 impl<H: Hal, T: Transport, const QS: usize> NetDriverOps for VirtIoNetDev<H, T, QS> {
-    type RxBuffer = RxBufferWrapper;
-    type TxBuffer = TxBufferWrapper;
+    type RxBuffer = VirtIoNetBuffer<H>;
+    type TxBuffer = VirtIoNetBuffer<H>;
 
     fn mac_address(&self) -> EthernetAddress {
-        EthernetAddress(self.inner.mac_address())
+        EthernetAddress(self.mac)
+    }
+
+    fn capabilities(&self) -> NetCapabilities {
+        let tx_checksum = ChecksumOffload {
+            tcp: self.tx_csum,
+            udp: self.tx_csum,
+            ..ChecksumOffload::NONE
+        };
+        NetCapabilities {
+            mtu: self.mtu,
+            max_burst: Some(QS / 2),
+            rx_checksum: ChecksumOffload::NONE,
+            tx_checksum,
+        }
     }
 
     fn can_send(&self) -> bool {
-        self.inner.can_send()
+        self.tx_pool.idle.load(Ordering::Acquire) != 0
     }
 
     fn can_recv(&self) -> bool {
-        self.inner.can_recv()
+        self.rx_queue.has_used()
+    }
+
+    fn ack_interrupt(&mut self) -> bool {
+        self.transport.ack_interrupt()
     }
 
     fn new_tx_buffer(&mut self, buf_len: usize) -> DevResult<Self::TxBuffer> {
-        Ok(TxBufferWrapper(self.inner.new_tx_buffer(buf_len)))
+        if buf_len > self.tx_pool.buf_size - HDR_ROOM {
+            return Err(DevError::InvalidParam);
+        }
+        let idx = match self.tx_pool.take_idle() {
+            Some(idx) => idx,
+            None => {
+                self.reclaim_tx_buffers();
+                self.tx_pool.take_idle().ok_or(DevError::Again)?
+            }
+        };
+        Ok(Self::new_buffer(&self.tx_pool, idx, buf_len))
     }
 
     fn recycle_rx_buffer(&mut self, rx_buf: Self::RxBuffer) -> DevResult {
-        self.inner.recycle_rx_buffer(rx_buf.0).map_err(as_dev_err)?;
+        if !Arc::ptr_eq(&rx_buf.pool, &self.rx_pool) {
+            return Err(DevError::InvalidParam);
+        }
+        drop(rx_buf); // it becomes idle, and will be posted on flushing
         Ok(())
     }
 
-    fn send(&mut self, tx_buf: Self::TxBuffer) -> DevResult {
-        self.inner.send(tx_buf.0).map_err(as_dev_err)?;
+    fn transmit(&mut self, mut tx_buf: Self::TxBuffer) -> DevResult {
+        if !Arc::ptr_eq(&tx_buf.pool, &self.tx_pool) {
+            return Err(DevError::InvalidParam);
+        }
+        let mut header = NetHeader::default();
+        if self.tx_csum {
+            if let Some((start, offset)) = prepare_partial_checksum(tx_buf.packet_mut()) {
+                header.flags = HDR_F_NEEDS_CSUM;
+                header.csum_start = start;
+                header.csum_offset = offset;
+            }
+        }
+        let ptr = self.tx_pool.buf_ptr(tx_buf.idx) as *mut NetHeader;
+        unsafe { ptr.write_volatile(header) };
+        self.tx_queue.push(tx_buf.idx, tx_buf.len, false);
+        tx_buf.queued = true;
+        Ok(())
+    }
+
+    fn reclaim_tx_buffers(&mut self) -> usize {
+        let mut count = 0;
+        while let Some((idx, _)) = self.tx_queue.pop_used() {
+            self.tx_pool.put_idle(idx);
+            count += 1;
+        }
+        count
+    }
+
+    fn flush(&mut self) -> DevResult {
+        while let Some(idx) = self.rx_pool.take_idle() {
+            let len = self.rx_pool.buf_size - HDR_ROOM;
+            self.rx_queue.push(idx, len, true);
+        }
+        self.rx_queue.kick(&mut self.transport);
+        self.tx_queue.kick(&mut self.transport);
         Ok(())
     }
 
     fn receive(&mut self) -> DevResult<Self::RxBuffer> {
-        Ok(RxBufferWrapper(self.inner.receive().map_err(as_dev_err)?))
+        loop {
+            let (idx, len) = self.rx_queue.pop_used().ok_or(DevError::Again)?;
+            let buf = Self::new_buffer(&self.rx_pool, idx, len.saturating_sub(self.hdr_len));
+            if buf.len > 0 {
+                return Ok(buf);
+            }
+            // drop the empty packet, which makes the buffer idle again
+        }
+    }
+}
+
+/// Prepares the checksum offloading of a TCP or UDP packet over IPv4 in the
+/// ethernet `frame`: stores the checksum of the pseudo header in the checksum
+/// field, and returns where the device should start to checksum and the
+/// offset of the field from there.
+fn prepare_partial_checksum(frame: &mut [u8]) -> Option<(u16, u16)> {
+    const ETH_TYPE_IPV4: [u8; 2] = [0x08, 0x00];
+    if frame.len() < ETH_HDR_LEN + 20 || frame[12..14] != ETH_TYPE_IPV4 {
+        return None;
+    }
+    let ip = &frame[ETH_HDR_LEN..];
+    let ihl = (ip[0] & 0xf) as usize * 4;
+    let total_len = u16::from_be_bytes([ip[2], ip[3]]) as usize;
+    let fragment = u16::from_be_bytes([ip[6], ip[7]]) & 0x3fff; // MF and offset
+    if ip[0] >> 4 != 4 || ihl < 20 || total_len < ihl || total_len > ip.len() || fragment != 0 {
+        return None;
     }
+    let csum_offset = match ip[9] {
+        6 => 16, // TCP
+        17 => 6, // UDP
+        _ => return None,
+    };
+    let l4_len = total_len - ihl;
+    if l4_len < csum_offset + 2 {
+        return None;
+    }
+
+    let mut sum = ip[9] as u32 + l4_len as u32;
+    for word in ip[12..20].chunks_exact(2) {
+        sum += u16::from_be_bytes([word[0], word[1]]) as u32;
+    }
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    let csum_start = ETH_HDR_LEN + ihl;
+    let field = csum_start + csum_offset;
+    frame[field..field + 2].copy_from_slice(&(sum as u16).to_be_bytes());
+    Some((csum_start as u16, csum_offset as u16))
+}
+
+const fn align_up(val: usize) -> usize {
+    (val + PAGE_SIZE - 1) & !(PAGE_SIZE - 1)
 }
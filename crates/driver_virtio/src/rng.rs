@@ -0,0 +1,173 @@
+use core::ptr::NonNull;
+use core::sync::atomic::{fence, Ordering};
+
+use driver_common::{BaseDriverOps, DevError, DevResult, DeviceType};
+use driver_rng::RngDriverOps;
+use virtio_drivers::{transport::Transport, BufferDirection, Hal, PhysAddr};
+
+const PAGE_SIZE: usize = 0x1000;
+const QUEUE_IDX: u16 = 0;
+/// Only one request is in flight at a time.
+const QUEUE_SIZE: u16 = 1;
+const DESC_F_WRITE: u16 = 2;
+
+// The layout of the DMA region (two pages). It also meets the legacy layout
+// where the used ring must start at the next page after the available ring.
+const DESC_OFFSET: usize = 0;
+const AVAIL_OFFSET: usize = 16;
+const BUF_OFFSET: usize = PAGE_SIZE / 2;
+const BUF_SIZE: usize = PAGE_SIZE / 2;
+const USED_OFFSET: usize = PAGE_SIZE;
+const DMA_PAGES: usize = 2;
+
+#[repr(C)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+#[repr(C)]
+struct AvailRing {
+    flags: u16,
+    idx: u16,
+    ring: [u16; QUEUE_SIZE as usize],
+    used_event: u16,
+}
+
+#[repr(C)]
+struct UsedElem {
+    id: u32,
+    len: u32,
+}
+
+#[repr(C)]
+struct UsedRing {
+    flags: u16,
+    idx: u16,
+    ring: [UsedElem; QUEUE_SIZE as usize],
+    avail_event: u16,
+}
+
+/// The VirtIO entropy device (virtio-rng) driver.
+///
+/// The device has a single request queue, where the driver posts buffers and
+/// the device fills them with random bytes. The [`virtio-drivers`] crate does
+/// not support it, so a minimal queue of one descriptor is managed here.
+///
+/// [`virtio-drivers`]: https://docs.rs/virtio-drivers/latest/virtio_drivers/
+pub struct VirtIoRngDev<H: Hal, T: Transport> {
+    transport: T,
+    dma_paddr: PhysAddr,
+    dma_vaddr: NonNull<u8>,
+    avail_idx: u16,
+    last_used_idx: u16,
+    _hal: core::marker::PhantomData<H>,
+}
+
+unsafe impl<H: Hal, T: Transport> Send for VirtIoRngDev<H, T> {}
+unsafe impl<H: Hal, T: Transport> Sync for VirtIoRngDev<H, T> {}
+
+impl<H: Hal, T: Transport> VirtIoRngDev<H, T> {
+    /// Creates a new driver instance and initializes the device, or returns
+    /// an error if any step fails.
+    pub fn try_new(mut transport: T) -> DevResult<Self> {
+        transport.begin_init(|_| 0); // no feature bits are defined
+        if transport.max_queue_size() < QUEUE_SIZE as u32 || transport.queue_used(QUEUE_IDX) {
+            return Err(DevError::BadState);
+        }
+        let (dma_paddr, dma_vaddr) = H::dma_alloc(DMA_PAGES, BufferDirection::Both);
+        if dma_paddr == 0 {
+            return Err(DevError::NoMemory);
+        }
+        unsafe { core::ptr::write_bytes(dma_vaddr.as_ptr(), 0, DMA_PAGES * PAGE_SIZE) };
+        transport.queue_set(
+            QUEUE_IDX,
+            QUEUE_SIZE as u32,
+            dma_paddr + DESC_OFFSET,
+            dma_paddr + AVAIL_OFFSET,
+            dma_paddr + USED_OFFSET,
+        );
+        transport.finish_init();
+        Ok(Self {
+            transport,
+            dma_paddr,
+            dma_vaddr,
+            avail_idx: 0,
+            last_used_idx: 0,
+            _hal: core::marker::PhantomData,
+        })
+    }
+
+    fn dma_ptr<U>(&self, offset: usize) -> *mut U {
+        unsafe { self.dma_vaddr.as_ptr().add(offset) as *mut U }
+    }
+
+    /// Posts the buffer of `len` bytes, and busy-waits for the device to fill
+    /// it. Returns the number of bytes filled.
+    fn request(&mut self, len: usize) -> usize {
+        let desc = self.dma_ptr::<Descriptor>(DESC_OFFSET);
+        let avail = self.dma_ptr::<AvailRing>(AVAIL_OFFSET);
+        let used = self.dma_ptr::<UsedRing>(USED_OFFSET);
+        unsafe {
+            desc.write_volatile(Descriptor {
+                addr: (self.dma_paddr + BUF_OFFSET) as u64,
+                len: len as u32,
+                flags: DESC_F_WRITE,
+                next: 0,
+            });
+            // there is only one slot in the rings, which is always used
+            (*avail).ring[0] = 0;
+            fence(Ordering::SeqCst);
+            self.avail_idx = self.avail_idx.wrapping_add(1);
+            core::ptr::addr_of_mut!((*avail).idx).write_volatile(self.avail_idx);
+            fence(Ordering::SeqCst);
+        }
+        self.transport.notify(QUEUE_IDX);
+
+        while unsafe { core::ptr::addr_of!((*used).idx).read_volatile() } == self.last_used_idx {
+            core::hint::spin_loop();
+        }
+        fence(Ordering::SeqCst);
+        let elem = unsafe { core::ptr::addr_of!((*used).ring[0]).read_volatile() };
+        self.last_used_idx = self.last_used_idx.wrapping_add(1);
+        (elem.len as usize).min(len)
+    }
+}
+
+impl<H: Hal, T: Transport> Drop for VirtIoRngDev<H, T> {
+    fn drop(&mut self) {
+        self.transport.queue_unset(QUEUE_IDX);
+        unsafe { H::dma_dealloc(self.dma_paddr, self.dma_vaddr, DMA_PAGES) };
+    }
+}
+
+impl<H: Hal, T: Transport> const BaseDriverOps for VirtIoRngDev<H, T> {
+    fn device_name(&self) -> &str {
+        "virtio-rng"
+    }
+
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Rng
+    }
+}
+
+impl<H: Hal, T: Transport> RngDriverOps for VirtIoRngDev<H, T> {
+    fn fill_bytes(&mut self, buf: &mut [u8]) -> DevResult<usize> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            let len = (buf.len() - filled).min(BUF_SIZE);
+            let n = self.request(len);
+            if n == 0 {
+                break; // no entropy is available for now
+            }
+            let src = self.dma_ptr::<u8>(BUF_OFFSET);
+            unsafe {
+                core::ptr::copy_nonoverlapping(src, buf[filled..].as_mut_ptr(), n);
+            }
+            filled += n;
+        }
+        Ok(filled)
+    }
+}
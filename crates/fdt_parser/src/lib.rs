@@ -0,0 +1,704 @@
+//! A `no_std` parser of the flattened device tree (FDT), also known as the
+//! device tree blob (DTB).
+//!
+//! The blob is parsed in place, no allocation is needed. The structure block
+//! is validated once in [`Fdt::new`], so the tree can be walked afterwards
+//! without error handling.
+//!
+//! # Examples
+//!
+//! ```
+//! # fn example(dtb: &[u8]) -> Result<(), fdt_parser::FdtError> {
+//! let fdt = fdt_parser::Fdt::new(dtb)?;
+//! for region in fdt.memory_regions() {
+//!     println!("RAM: {:#x} + {:?}", region.address, region.size);
+//! }
+//! if let Some(uart) = fdt.find_compatible(&["ns16550a", "arm,pl011"]) {
+//!     println!("UART: {}", uart.name());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+#![cfg_attr(not(test), no_std)]
+
+#[cfg(test)]
+mod tests;
+
+use core::fmt;
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+const HEADER_SIZE: usize = 40;
+/// The earliest version whose layout is compatible with this parser.
+const LAST_COMP_VERSION: u32 = 16;
+/// The maximum nesting depth of nodes.
+const MAX_DEPTH: usize = 16;
+
+/// The default `#address-cells` and `#size-cells` if not specified.
+const DEFAULT_CELLS: (u32, u32) = (2, 1);
+
+/// Errors when parsing a device tree blob.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FdtError {
+    /// The pointer is null or not 4-byte aligned.
+    BadPtr,
+    /// The magic number is not `0xd00dfeed`.
+    BadMagic,
+    /// The blob is not compatible with version 16.
+    BadVersion,
+    /// The buffer is smaller than the size in the header, or a block lies
+    /// outside of the blob.
+    Truncated,
+    /// The structure block is malformed or nested too deep.
+    BadStructure,
+}
+
+impl fmt::Display for FdtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            Self::BadPtr => "bad pointer",
+            Self::BadMagic => "bad magic number",
+            Self::BadVersion => "unsupported version",
+            Self::Truncated => "truncated blob",
+            Self::BadStructure => "malformed structure block",
+        };
+        f.write_str(msg)
+    }
+}
+
+fn be32(data: &[u8], off: usize) -> Option<u32> {
+    let bytes = data.get(off..off.checked_add(4)?)?;
+    Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+fn be64(data: &[u8], off: usize) -> Option<u64> {
+    let bytes = data.get(off..off.checked_add(8)?)?;
+    Some(u64::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+const fn align4(off: usize) -> usize {
+    (off + 3) & !3
+}
+
+/// Reads a number of `cells` 32-bit big-endian cells. Only the lowest 64
+/// bits are kept if there are more than 2 cells.
+fn read_cells(data: &[u8], cells: u32) -> u64 {
+    (0..cells as usize)
+        .map_while(|i| be32(data, i * 4))
+        .fold(0, |acc, c| (acc << 32) | c as u64)
+}
+
+/// Reads a NUL-terminated string from the beginning of `data`.
+fn read_str(data: &[u8]) -> Option<&str> {
+    let len = data.iter().position(|&b| b == 0)?;
+    core::str::from_utf8(&data[..len]).ok()
+}
+
+enum Token<'a> {
+    BeginNode(&'a str),
+    EndNode,
+    Prop(Property<'a>),
+    End,
+}
+
+/// A parsed flattened device tree.
+#[derive(Clone, Copy)]
+pub struct Fdt<'a> {
+    data: &'a [u8],
+    structs: &'a [u8],
+    strings: &'a [u8],
+    mem_rsvmap: &'a [u8],
+    boot_cpuid: u32,
+}
+
+impl<'a> Fdt<'a> {
+    /// Parses the device tree blob in `data`.
+    pub fn new(data: &'a [u8]) -> Result<Self, FdtError> {
+        let header = |idx: usize| be32(data, idx * 4).ok_or(FdtError::Truncated);
+        if header(0)? != FDT_MAGIC {
+            return Err(FdtError::BadMagic);
+        }
+        let total_size = header(1)? as usize;
+        if data.len() < total_size || total_size < HEADER_SIZE {
+            return Err(FdtError::Truncated);
+        }
+        if header(6)? < LAST_COMP_VERSION {
+            return Err(FdtError::BadVersion);
+        }
+        let data = &data[..total_size];
+        let block = |off: usize, size: usize| {
+            let end = off.checked_add(size).ok_or(FdtError::Truncated)?;
+            data.get(off..end).ok_or(FdtError::Truncated)
+        };
+        let off_rsvmap = header(4)? as usize;
+        let fdt = Self {
+            data,
+            structs: block(header(2)? as usize, header(9)? as usize)?,
+            strings: block(header(3)? as usize, header(8)? as usize)?,
+            mem_rsvmap: data.get(off_rsvmap..).ok_or(FdtError::Truncated)?,
+            boot_cpuid: header(7)?,
+        };
+        fdt.validate()?;
+        Ok(fdt)
+    }
+
+    /// Parses the device tree blob at `ptr`, reading its size from the header.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a device tree blob that is valid for reads, and
+    /// lives as long as `'a`.
+    pub unsafe fn from_ptr(ptr: *const u8) -> Result<Self, FdtError> {
+        if ptr.is_null() || ptr as usize % 4 != 0 {
+            return Err(FdtError::BadPtr);
+        }
+        let header = core::slice::from_raw_parts(ptr, 8);
+        if be32(header, 0) != Some(FDT_MAGIC) {
+            return Err(FdtError::BadMagic);
+        }
+        let total_size = be32(header, 4).unwrap() as usize;
+        Self::new(core::slice::from_raw_parts(ptr, total_size))
+    }
+
+    /// The size of the whole blob in bytes.
+    pub fn total_size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// The physical ID of the boot CPU.
+    pub fn boot_cpuid(&self) -> u32 {
+        self.boot_cpuid
+    }
+
+    fn read_token(&self, off: &mut usize) -> Option<Token<'a>> {
+        let structs = self.structs;
+        loop {
+            let token = be32(structs, *off)?;
+            *off += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    let name = read_str(&structs[*off..])?;
+                    *off = align4(*off + name.len() + 1);
+                    return Some(Token::BeginNode(name));
+                }
+                FDT_END_NODE => return Some(Token::EndNode),
+                FDT_PROP => {
+                    let len = be32(structs, *off)? as usize;
+                    let name_off = be32(structs, *off + 4)? as usize;
+                    let start = *off + 8;
+                    let value = structs.get(start..start.checked_add(len)?)?;
+                    let name = read_str(self.strings.get(name_off..)?)?;
+                    *off = align4(start + len);
+                    return Some(Token::Prop(Property { name, value }));
+                }
+                FDT_NOP => continue,
+                FDT_END => return Some(Token::End),
+                _ => return None,
+            }
+        }
+    }
+
+    /// Checks that the structure block is a single well-formed tree.
+    fn validate(&self) -> Result<(), FdtError> {
+        let mut off = 0;
+        let mut depth = 0;
+        let mut has_root = false;
+        loop {
+            match self.read_token(&mut off).ok_or(FdtError::BadStructure)? {
+                Token::BeginNode(_) if depth > 0 || !has_root => {
+                    has_root = true;
+                    depth += 1;
+                    if depth > MAX_DEPTH {
+                        return Err(FdtError::BadStructure);
+                    }
+                }
+                Token::EndNode if depth > 0 => depth -= 1,
+                Token::Prop(_) if depth > 0 => {}
+                Token::End if depth == 0 && has_root => return Ok(()),
+                _ => return Err(FdtError::BadStructure),
+            }
+        }
+    }
+
+    /// The root node.
+    pub fn root(&self) -> Node<'a> {
+        self.all_nodes().next().unwrap()
+    }
+
+    /// Iterates over all nodes in the tree in depth-first order, starting
+    /// with the root node.
+    pub fn all_nodes(&self) -> NodeIter<'a> {
+        NodeIter {
+            fdt: *self,
+            off: 0,
+            depth: 0,
+            target: None,
+            cells: [DEFAULT_CELLS; MAX_DEPTH],
+        }
+    }
+
+    /// Finds a node by its full path, e.g. `/soc/uart@10000000`.
+    ///
+    /// A path component without a unit address (`@...`) matches a node with
+    /// any unit address.
+    pub fn find_node(&self, path: &str) -> Option<Node<'a>> {
+        let mut node = self.root();
+        for comp in path.split('/').filter(|c| !c.is_empty()) {
+            node = node.children().find(|child| child.name_matches(comp))?;
+        }
+        Some(node)
+    }
+
+    /// Finds the node with the given `phandle`.
+    pub fn find_phandle(&self, phandle: u32) -> Option<Node<'a>> {
+        self.all_nodes().find(|n| n.phandle() == Some(phandle))
+    }
+
+    /// Finds the first node compatible with any of `compatible`.
+    pub fn find_compatible(&self, compatible: &[&str]) -> Option<Node<'a>> {
+        self.all_nodes().find(|n| n.is_compatible(compatible))
+    }
+
+    /// Iterates over all nodes compatible with any of `compatible`.
+    pub fn all_compatible<'b>(
+        &self,
+        compatible: &'b [&'b str],
+    ) -> impl Iterator<Item = Node<'a>> + 'b
+    where
+        'a: 'b,
+    {
+        self.all_nodes()
+            .filter(move |n| n.is_compatible(compatible))
+    }
+
+    /// The `/chosen` node.
+    pub fn chosen(&self) -> Option<Node<'a>> {
+        self.find_node("/chosen")
+    }
+
+    /// Resolves an alias in `/aliases` to a node.
+    pub fn find_alias(&self, alias: &str) -> Option<Node<'a>> {
+        let path = self.find_node("/aliases")?.property(alias)?.as_str()?;
+        self.find_node(path)
+    }
+
+    /// The node of the console, given by `stdout-path` in `/chosen`.
+    pub fn stdout(&self) -> Option<Node<'a>> {
+        let chosen = self.chosen()?;
+        let path = chosen
+            .property("stdout-path")
+            .or_else(|| chosen.property("linux,stdout-path"))?
+            .as_str()?;
+        // strip options like `:115200n8`
+        let path = path.split(':').next().unwrap();
+        if path.starts_with('/') {
+            self.find_node(path)
+        } else {
+            self.find_alias(path)
+        }
+    }
+
+    /// The kernel command line, given by `bootargs` in `/chosen`.
+    pub fn bootargs(&self) -> Option<&'a str> {
+        self.chosen()?.property("bootargs")?.as_str()
+    }
+
+    /// Iterates over all CPU nodes under `/cpus`.
+    pub fn cpus(&self) -> impl Iterator<Item = Node<'a>> {
+        self.find_node("/cpus")
+            .into_iter()
+            .flat_map(|cpus| cpus.children())
+            .filter(|n| n.device_type() == Some("cpu"))
+    }
+
+    /// Iterates over the physical memory regions of all memory nodes.
+    pub fn memory_regions(&self) -> impl Iterator<Item = Reg> + 'a {
+        self.all_nodes()
+            .filter(|n| n.device_type() == Some("memory"))
+            .flat_map(|n| n.reg())
+    }
+
+    /// Iterates over the memory reservation block, which lists the regions
+    /// that must not be used as normal RAM (e.g., the firmware).
+    pub fn memory_reservations(&self) -> MemReservations<'a> {
+        MemReservations {
+            data: self.mem_rsvmap,
+            off: 0,
+        }
+    }
+}
+
+impl fmt::Debug for Fdt<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Fdt")
+            .field("total_size", &self.total_size())
+            .field("boot_cpuid", &self.boot_cpuid)
+            .finish()
+    }
+}
+
+/// A node of the device tree.
+#[derive(Clone, Copy)]
+pub struct Node<'a> {
+    fdt: Fdt<'a>,
+    name: &'a str,
+    /// Offset of the first token after `FDT_BEGIN_NODE` and its name.
+    off: usize,
+    /// `#address-cells` and `#size-cells` of the parent node.
+    parent_cells: (u32, u32),
+}
+
+impl<'a> Node<'a> {
+    /// The full name of the node, including the unit address, e.g.
+    /// `uart@10000000`. The root node has an empty name.
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// The name of the node without the unit address.
+    pub fn base_name(&self) -> &'a str {
+        self.name.split('@').next().unwrap()
+    }
+
+    /// The unit address of the node, the part after `@` in the name.
+    pub fn unit_address(&self) -> Option<&'a str> {
+        self.name.split_once('@').map(|(_, addr)| addr)
+    }
+
+    fn name_matches(&self, comp: &str) -> bool {
+        self.name == comp || (!comp.contains('@') && self.base_name() == comp)
+    }
+
+    /// Iterates over the properties of the node.
+    pub fn properties(&self) -> Properties<'a> {
+        Properties {
+            fdt: self.fdt,
+            off: self.off,
+        }
+    }
+
+    /// Finds a property by name.
+    pub fn property(&self, name: &str) -> Option<Property<'a>> {
+        self.properties().find(|p| p.name == name)
+    }
+
+    /// Iterates over the direct children of the node.
+    pub fn children(&self) -> NodeIter<'a> {
+        let mut cells = [DEFAULT_CELLS; MAX_DEPTH];
+        cells[0] = (self.address_cells(), self.size_cells());
+        NodeIter {
+            fdt: self.fdt,
+            off: self.off,
+            depth: 1,
+            target: Some(1),
+            cells,
+        }
+    }
+
+    /// Iterates over the strings in the `compatible` property.
+    pub fn compatible(&self) -> StrList<'a> {
+        self.property("compatible")
+            .map_or(StrList { data: &[] }, |p| p.as_str_list())
+    }
+
+    /// Whether the node is compatible with any of `compatible`.
+    pub fn is_compatible(&self, compatible: &[&str]) -> bool {
+        self.compatible().any(|c| compatible.contains(&c))
+    }
+
+    /// The `device_type` property.
+    pub fn device_type(&self) -> Option<&'a str> {
+        self.property("device_type")?.as_str()
+    }
+
+    /// The `phandle` property, which is referenced by other nodes.
+    pub fn phandle(&self) -> Option<u32> {
+        self.property("phandle")
+            .or_else(|| self.property("linux,phandle"))?
+            .as_u32()
+    }
+
+    /// The `interrupt-parent` property, the phandle of the interrupt
+    /// controller. It is inherited from the ancestors if absent, which is not
+    /// resolved here.
+    pub fn interrupt_parent(&self) -> Option<u32> {
+        self.property("interrupt-parent")?.as_u32()
+    }
+
+    /// The `#address-cells` of the children.
+    pub fn address_cells(&self) -> u32 {
+        self.property("#address-cells")
+            .and_then(|p| p.as_u32())
+            .unwrap_or(DEFAULT_CELLS.0)
+    }
+
+    /// The `#size-cells` of the children.
+    pub fn size_cells(&self) -> u32 {
+        self.property("#size-cells")
+            .and_then(|p| p.as_u32())
+            .unwrap_or(DEFAULT_CELLS.1)
+    }
+
+    /// The `#address-cells` and `#size-cells` of the parent node, which are
+    /// used to decode `reg`, and the parent side of `ranges`.
+    pub fn parent_cells(&self) -> (u32, u32) {
+        self.parent_cells
+    }
+
+    /// Iterates over the regions in the `reg` property, decoded with the
+    /// `#address-cells` and `#size-cells` of the parent node.
+    ///
+    /// The addresses are in the parent bus address space, `ranges` are not
+    /// translated.
+    pub fn reg(&self) -> RegIter<'a> {
+        let (address_cells, size_cells) = self.parent_cells;
+        RegIter {
+            data: self.property("reg").map_or(&[], |p| p.value),
+            address_cells,
+            size_cells,
+        }
+    }
+
+    /// Iterates over the raw cells of the `interrupts` property.
+    ///
+    /// How many cells make up one interrupt specifier is determined by the
+    /// `#interrupt-cells` of the interrupt controller.
+    pub fn interrupts(&self) -> U32List<'a> {
+        self.property("interrupts")
+            .map_or(U32List { data: &[] }, |p| p.as_u32_list())
+    }
+}
+
+impl fmt::Debug for Node<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Node").field("name", &self.name).finish()
+    }
+}
+
+/// A property of a node.
+#[derive(Debug, Clone, Copy)]
+pub struct Property<'a> {
+    name: &'a str,
+    value: &'a [u8],
+}
+
+impl<'a> Property<'a> {
+    /// The name of the property.
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// The raw value of the property.
+    pub fn value(&self) -> &'a [u8] {
+        self.value
+    }
+
+    /// Interprets the value as a single 32-bit cell.
+    pub fn as_u32(&self) -> Option<u32> {
+        match self.value.len() {
+            4 => be32(self.value, 0),
+            _ => None,
+        }
+    }
+
+    /// Interprets the value as one or two 32-bit cells.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self.value.len() {
+            4 => be32(self.value, 0).map(|v| v as u64),
+            8 => be64(self.value, 0),
+            _ => None,
+        }
+    }
+
+    /// Interprets the value as a NUL-terminated string.
+    pub fn as_str(&self) -> Option<&'a str> {
+        read_str(self.value)
+    }
+
+    /// Interprets the value as a list of NUL-terminated strings.
+    pub fn as_str_list(&self) -> StrList<'a> {
+        StrList { data: self.value }
+    }
+
+    /// Interprets the value as a list of 32-bit cells.
+    pub fn as_u32_list(&self) -> U32List<'a> {
+        U32List { data: self.value }
+    }
+}
+
+/// A region in a `reg` property.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reg {
+    /// The start address.
+    pub address: u64,
+    /// The size in bytes, `None` if `#size-cells` of the parent is 0.
+    pub size: Option<u64>,
+}
+
+/// An entry of the memory reservation block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemReservation {
+    /// The start physical address.
+    pub address: u64,
+    /// The size in bytes.
+    pub size: u64,
+}
+
+/// An iterator over nodes, created by [`Fdt::all_nodes`] or
+/// [`Node::children`].
+pub struct NodeIter<'a> {
+    fdt: Fdt<'a>,
+    off: usize,
+    /// Number of open nodes since the start of iteration.
+    depth: usize,
+    /// Only yields nodes at this depth if specified.
+    target: Option<usize>,
+    /// `#address-cells` and `#size-cells` of the open node at each depth.
+    cells: [(u32, u32); MAX_DEPTH],
+}
+
+impl<'a> Iterator for NodeIter<'a> {
+    type Item = Node<'a>;
+
+    fn next(&mut self) -> Option<Node<'a>> {
+        loop {
+            match self.fdt.read_token(&mut self.off)? {
+                Token::BeginNode(name) => {
+                    let depth = self.depth;
+                    let parent_cells = match depth {
+                        0 => DEFAULT_CELLS,
+                        _ => self.cells[depth - 1],
+                    };
+                    let node = Node {
+                        fdt: self.fdt,
+                        name,
+                        off: self.off,
+                        parent_cells,
+                    };
+                    if depth < MAX_DEPTH {
+                        self.cells[depth] = (node.address_cells(), node.size_cells());
+                    }
+                    self.depth += 1;
+                    if self.target.is_none() || self.target == Some(depth) {
+                        return Some(node);
+                    }
+                }
+                Token::Prop(_) => {}
+                Token::EndNode => {
+                    self.depth = self.depth.checked_sub(1)?;
+                    if self.depth == 0 && self.target.is_some() {
+                        // the end of the parent node
+                        self.off = self.fdt.structs.len();
+                        return None;
+                    }
+                }
+                Token::End => return None,
+            }
+        }
+    }
+}
+
+/// An iterator over the properties of a node.
+pub struct Properties<'a> {
+    fdt: Fdt<'a>,
+    off: usize,
+}
+
+impl<'a> Iterator for Properties<'a> {
+    type Item = Property<'a>;
+
+    fn next(&mut self) -> Option<Property<'a>> {
+        match self.fdt.read_token(&mut self.off)? {
+            Token::Prop(prop) => Some(prop),
+            _ => {
+                // properties always precede child nodes
+                self.off = self.fdt.structs.len();
+                None
+            }
+        }
+    }
+}
+
+/// An iterator over a list of NUL-terminated strings.
+pub struct StrList<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for StrList<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let s = read_str(self.data)?;
+        self.data = &self.data[s.len() + 1..];
+        Some(s)
+    }
+}
+
+/// An iterator over a list of 32-bit big-endian cells.
+pub struct U32List<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for U32List<'a> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        let v = be32(self.data, 0)?;
+        self.data = &self.data[4..];
+        Some(v)
+    }
+}
+
+/// An iterator over the regions in a `reg` property.
+pub struct RegIter<'a> {
+    data: &'a [u8],
+    address_cells: u32,
+    size_cells: u32,
+}
+
+impl<'a> Iterator for RegIter<'a> {
+    type Item = Reg;
+
+    fn next(&mut self) -> Option<Reg> {
+        let addr_len = self.address_cells as usize * 4;
+        let entry_len = addr_len + self.size_cells as usize * 4;
+        if entry_len == 0 || self.data.len() < entry_len {
+            return None;
+        }
+        let (entry, rest) = self.data.split_at(entry_len);
+        self.data = rest;
+        Some(Reg {
+            address: read_cells(entry, self.address_cells),
+            size: match self.size_cells {
+                0 => None,
+                n => Some(read_cells(&entry[addr_len..], n)),
+            },
+        })
+    }
+}
+
+/// An iterator over the memory reservation block.
+pub struct MemReservations<'a> {
+    data: &'a [u8],
+    off: usize,
+}
+
+impl<'a> Iterator for MemReservations<'a> {
+    type Item = MemReservation;
+
+    fn next(&mut self) -> Option<MemReservation> {
+        let address = be64(self.data, self.off)?;
+        let size = be64(self.data, self.off + 8)?;
+        if address == 0 && size == 0 {
+            return None; // terminator
+        }
+        self.off += 16;
+        Some(MemReservation { address, size })
+    }
+}
@@ -0,0 +1,378 @@
+use super::*;
+
+/// Builds a device tree blob for testing.
+#[derive(Default)]
+struct DtbBuilder {
+    structs: Vec<u8>,
+    strings: Vec<u8>,
+    rsvmap: Vec<(u64, u64)>,
+}
+
+impl DtbBuilder {
+    fn token(&mut self, token: u32) -> &mut Self {
+        self.structs.extend_from_slice(&token.to_be_bytes());
+        self
+    }
+
+    fn pad(&mut self) {
+        self.structs.resize(align4(self.structs.len()), 0);
+    }
+
+    fn begin(&mut self, name: &str) -> &mut Self {
+        self.token(FDT_BEGIN_NODE);
+        self.structs.extend_from_slice(name.as_bytes());
+        self.structs.push(0);
+        self.pad();
+        self
+    }
+
+    fn end(&mut self) -> &mut Self {
+        self.token(FDT_END_NODE)
+    }
+
+    fn prop(&mut self, name: &str, value: &[u8]) -> &mut Self {
+        let name_off = self.strings.len() as u32;
+        self.strings.extend_from_slice(name.as_bytes());
+        self.strings.push(0);
+        self.token(FDT_PROP)
+            .token(value.len() as u32)
+            .token(name_off);
+        self.structs.extend_from_slice(value);
+        self.pad();
+        self
+    }
+
+    fn prop_cells(&mut self, name: &str, cells: &[u32]) -> &mut Self {
+        let value: Vec<u8> = cells.iter().flat_map(|c| c.to_be_bytes()).collect();
+        self.prop(name, &value)
+    }
+
+    fn prop_str(&mut self, name: &str, value: &str) -> &mut Self {
+        let mut bytes = value.replace('\n', "\0").into_bytes();
+        bytes.push(0);
+        self.prop(name, &bytes)
+    }
+
+    fn build(&mut self) -> Vec<u8> {
+        self.token(FDT_END);
+        let off_rsvmap = HEADER_SIZE;
+        let off_structs = off_rsvmap + (self.rsvmap.len() + 1) * 16;
+        let off_strings = off_structs + self.structs.len();
+        let total_size = off_strings + self.strings.len();
+        let header = [
+            FDT_MAGIC,
+            total_size as u32,
+            off_structs as u32,
+            off_strings as u32,
+            off_rsvmap as u32,
+            17, // version
+            16, // last_comp_version
+            0,  // boot_cpuid_phys
+            self.strings.len() as u32,
+            self.structs.len() as u32,
+        ];
+        let mut dtb: Vec<u8> = header.iter().flat_map(|h| h.to_be_bytes()).collect();
+        for &(addr, size) in self.rsvmap.iter().chain([(0, 0)].iter()) {
+            dtb.extend_from_slice(&addr.to_be_bytes());
+            dtb.extend_from_slice(&size.to_be_bytes());
+        }
+        dtb.extend_from_slice(&self.structs);
+        dtb.extend_from_slice(&self.strings);
+        dtb
+    }
+}
+
+/// A tree similar to the one of QEMU `virt` (RISC-V).
+fn qemu_virt() -> Vec<u8> {
+    let mut b = DtbBuilder::default();
+    b.rsvmap.push((0x8000_0000, 0x20_0000));
+    b.begin("")
+        .prop_cells("#address-cells", &[2])
+        .prop_cells("#size-cells", &[2])
+        .prop_str("compatible", "riscv-virtio")
+        .begin("chosen")
+        .prop_str("stdout-path", "serial0:115200n8")
+        .prop_str("bootargs", "console=ttyS0")
+        .end()
+        .begin("aliases")
+        .prop_str("serial0", "/soc/serial@10000000")
+        .end()
+        .begin("memory@80000000")
+        .prop_str("device_type", "memory")
+        .prop_cells("reg", &[0, 0x8000_0000, 0, 0x2000_0000])
+        .end()
+        .begin("cpus")
+        .prop_cells("#address-cells", &[1])
+        .prop_cells("#size-cells", &[0])
+        .prop_cells("timebase-frequency", &[10_000_000]);
+    for i in 0..4 {
+        b.begin(&format!("cpu@{i}"))
+            .prop_str("device_type", "cpu")
+            .prop_cells("reg", &[i])
+            .end();
+    }
+    b.begin("cpu-map").end().end();
+    b.begin("soc")
+        .prop_cells("#address-cells", &[2])
+        .prop_cells("#size-cells", &[2])
+        .prop_str("compatible", "simple-bus")
+        .begin("virtio_mmio@10001000")
+        .prop_cells("interrupts", &[1])
+        .prop_cells("interrupt-parent", &[3])
+        .prop_cells("reg", &[0, 0x1000_1000, 0, 0x1000])
+        .prop_str("compatible", "virtio,mmio")
+        .end()
+        .begin("virtio_mmio@10002000")
+        .prop_cells("interrupts", &[2])
+        .prop_cells("interrupt-parent", &[3])
+        .prop_cells("reg", &[0, 0x1000_2000, 0, 0x1000])
+        .prop_str("compatible", "virtio,mmio")
+        .end()
+        .begin("serial@10000000")
+        .prop_cells("interrupts", &[10])
+        .prop_cells("interrupt-parent", &[3])
+        .prop_cells("clock-frequency", &[0x0038_4000])
+        .prop_cells("reg", &[0, 0x1000_0000, 0, 0x100])
+        .prop_str("compatible", "ns16550a")
+        .end()
+        .begin("plic@c000000")
+        .prop_cells("phandle", &[3])
+        .prop_cells("#interrupt-cells", &[1])
+        .prop_cells("reg", &[0, 0x0c00_0000, 0, 0x60_0000])
+        .prop_str("compatible", "sifive,plic-1.0.0\nriscv,plic0")
+        .prop("interrupt-controller", &[])
+        .end()
+        .end()
+        .end();
+    b.build()
+}
+
+#[test]
+fn test_header() {
+    let dtb = qemu_virt();
+    let fdt = Fdt::new(&dtb).unwrap();
+    assert_eq!(fdt.total_size(), dtb.len());
+    assert_eq!(fdt.boot_cpuid(), 0);
+
+    let mut bad = dtb.clone();
+    bad[0] = 0;
+    assert_eq!(Fdt::new(&bad).unwrap_err(), FdtError::BadMagic);
+    assert_eq!(
+        Fdt::new(&dtb[..dtb.len() - 1]).unwrap_err(),
+        FdtError::Truncated
+    );
+    assert_eq!(Fdt::new(&dtb[..8]).unwrap_err(), FdtError::Truncated);
+
+    let mut bad = dtb.clone();
+    bad[27] = 15; // last_comp_version
+    assert_eq!(Fdt::new(&bad).unwrap_err(), FdtError::BadVersion);
+
+    let ptr = dtb.as_ptr();
+    let fdt = unsafe { Fdt::from_ptr(ptr) }.unwrap();
+    assert_eq!(fdt.total_size(), dtb.len());
+    assert_eq!(
+        unsafe { Fdt::from_ptr(core::ptr::null()) }.unwrap_err(),
+        FdtError::BadPtr
+    );
+}
+
+#[test]
+fn test_bad_structure() {
+    // unbalanced
+    let dtb = DtbBuilder::default().begin("").begin("a").end().build();
+    assert_eq!(Fdt::new(&dtb).unwrap_err(), FdtError::BadStructure);
+    // two roots
+    let dtb = DtbBuilder::default()
+        .begin("")
+        .end()
+        .begin("")
+        .end()
+        .build();
+    assert_eq!(Fdt::new(&dtb).unwrap_err(), FdtError::BadStructure);
+    // property outside of nodes
+    let dtb = DtbBuilder::default()
+        .prop_cells("a", &[1])
+        .begin("")
+        .end()
+        .build();
+    assert_eq!(Fdt::new(&dtb).unwrap_err(), FdtError::BadStructure);
+    // no root
+    let dtb = DtbBuilder::default().build();
+    assert_eq!(Fdt::new(&dtb).unwrap_err(), FdtError::BadStructure);
+    // too deep
+    let mut b = DtbBuilder::default();
+    for _ in 0..=MAX_DEPTH {
+        b.begin("n");
+    }
+    for _ in 0..=MAX_DEPTH {
+        b.end();
+    }
+    assert_eq!(Fdt::new(&b.build()).unwrap_err(), FdtError::BadStructure);
+    // NOPs are skipped
+    let dtb = DtbBuilder::default()
+        .token(FDT_NOP)
+        .begin("")
+        .token(FDT_NOP)
+        .end()
+        .build();
+    assert_eq!(Fdt::new(&dtb).unwrap().root().name(), "");
+}
+
+#[test]
+fn test_nodes() {
+    let dtb = qemu_virt();
+    let fdt = Fdt::new(&dtb).unwrap();
+    let root = fdt.root();
+    assert_eq!(root.name(), "");
+    assert_eq!(root.compatible().collect::<Vec<_>>(), ["riscv-virtio"]);
+    assert_eq!(
+        root.children().map(|n| n.name()).collect::<Vec<_>>(),
+        ["chosen", "aliases", "memory@80000000", "cpus", "soc"]
+    );
+    assert_eq!(fdt.all_nodes().count(), 15);
+
+    let uart = fdt.find_node("/soc/serial@10000000").unwrap();
+    assert_eq!(uart.base_name(), "serial");
+    assert_eq!(uart.unit_address(), Some("10000000"));
+    assert_eq!(fdt.find_node("/soc/serial").unwrap().name(), uart.name());
+    assert!(fdt.find_node("/soc/serial@0").is_none());
+    assert!(fdt.find_node("/cpu-map").is_none());
+    assert_eq!(fdt.find_node("/").unwrap().name(), "");
+    assert_eq!(
+        fdt.find_node("/cpus/cpu-map").unwrap().children().count(),
+        0
+    );
+
+    let plic = fdt.find_phandle(uart.interrupt_parent().unwrap()).unwrap();
+    assert_eq!(plic.name(), "plic@c000000");
+    assert!(plic.is_compatible(&["riscv,plic0"]));
+    assert!(!plic.is_compatible(&["arm,gic-400"]));
+    assert!(plic
+        .property("interrupt-controller")
+        .unwrap()
+        .value()
+        .is_empty());
+}
+
+#[test]
+fn test_properties() {
+    let dtb = qemu_virt();
+    let fdt = Fdt::new(&dtb).unwrap();
+    let uart = fdt.find_compatible(&["arm,pl011", "ns16550a"]).unwrap();
+    assert_eq!(
+        uart.properties().map(|p| p.name()).collect::<Vec<_>>(),
+        [
+            "interrupts",
+            "interrupt-parent",
+            "clock-frequency",
+            "reg",
+            "compatible"
+        ]
+    );
+    assert_eq!(
+        uart.property("clock-frequency").unwrap().as_u32(),
+        Some(0x0038_4000)
+    );
+    assert_eq!(
+        uart.property("clock-frequency").unwrap().as_u64(),
+        Some(0x0038_4000)
+    );
+    assert_eq!(uart.property("reg").unwrap().as_u32(), None);
+    assert_eq!(uart.interrupts().collect::<Vec<_>>(), [10]);
+    assert!(uart.property("status").is_none());
+
+    let plic = fdt.find_node("/soc/plic").unwrap();
+    let compat = plic.property("compatible").unwrap();
+    assert_eq!(compat.as_str(), Some("sifive,plic-1.0.0"));
+    assert_eq!(
+        compat.as_str_list().collect::<Vec<_>>(),
+        ["sifive,plic-1.0.0", "riscv,plic0"]
+    );
+
+    let cpus = fdt.find_node("/cpus").unwrap();
+    assert_eq!((cpus.address_cells(), cpus.size_cells()), (1, 0));
+    assert_eq!(
+        cpus.property("timebase-frequency").unwrap().as_u64(),
+        Some(10_000_000)
+    );
+}
+
+#[test]
+fn test_reg() {
+    let dtb = qemu_virt();
+    let fdt = Fdt::new(&dtb).unwrap();
+    assert_eq!(
+        fdt.memory_regions().collect::<Vec<_>>(),
+        [Reg {
+            address: 0x8000_0000,
+            size: Some(0x2000_0000),
+        }]
+    );
+    let virtio = fdt
+        .all_compatible(&["virtio,mmio"])
+        .map(|n| (n.reg().next().unwrap(), n.interrupts().next().unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(virtio.len(), 2);
+    assert_eq!(virtio[1].0.address, 0x1000_2000);
+    assert_eq!(virtio[1].0.size, Some(0x1000));
+    assert_eq!(virtio[1].1, 2);
+
+    // `#size-cells` is 0 under `/cpus`
+    let cpus = fdt.cpus().collect::<Vec<_>>();
+    assert_eq!(cpus.len(), 4);
+    assert_eq!(
+        cpus[3].reg().collect::<Vec<_>>(),
+        [Reg {
+            address: 3,
+            size: None,
+        }]
+    );
+
+    // default cells (2, 1) and multiple entries
+    let dtb = DtbBuilder::default()
+        .begin("")
+        .begin("memory")
+        .prop_str("device_type", "memory")
+        .prop_cells("reg", &[0, 0x4000_0000, 0x1000_0000, 1, 0, 0x2000_0000])
+        .end()
+        .end()
+        .build();
+    let fdt = Fdt::new(&dtb).unwrap();
+    let regions = fdt.memory_regions().collect::<Vec<_>>();
+    assert_eq!(regions.len(), 2);
+    assert_eq!(regions[0].address, 0x4000_0000);
+    assert_eq!(regions[0].size, Some(0x1000_0000));
+    assert_eq!(regions[1].address, 0x1_0000_0000);
+    assert_eq!(regions[1].size, Some(0x2000_0000));
+}
+
+#[test]
+fn test_chosen() {
+    let dtb = qemu_virt();
+    let fdt = Fdt::new(&dtb).unwrap();
+    assert_eq!(fdt.bootargs(), Some("console=ttyS0"));
+    assert_eq!(fdt.stdout().unwrap().name(), "serial@10000000");
+    assert_eq!(fdt.find_alias("serial0").unwrap().name(), "serial@10000000");
+    assert!(fdt.find_alias("serial1").is_none());
+    assert_eq!(
+        fdt.memory_reservations().collect::<Vec<_>>(),
+        [MemReservation {
+            address: 0x8000_0000,
+            size: 0x20_0000,
+        }]
+    );
+
+    let dtb = DtbBuilder::default()
+        .begin("")
+        .begin("chosen")
+        .prop_str("stdout-path", "/pl011@9000000")
+        .end()
+        .begin("pl011@9000000")
+        .end()
+        .end()
+        .build();
+    let fdt = Fdt::new(&dtb).unwrap();
+    assert_eq!(fdt.stdout().unwrap().name(), "pl011@9000000");
+    assert!(fdt.bootargs().is_none());
+    assert_eq!(fdt.memory_reservations().count(), 0);
+}
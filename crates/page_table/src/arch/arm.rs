@@ -0,0 +1,31 @@
+//! ARMv7-A specific page table structures.
+
+use crate::{PageSize, PageTable32, PagingMetaData, PagingMetaData32};
+use page_table_entry::arm::A32PTE;
+
+/// Metadata of ARMv7-A short-descriptor translation tables.
+#[derive(Copy, Clone)]
+pub struct A32PagingMetaData;
+
+impl const PagingMetaData for A32PagingMetaData {
+    const LEVELS: usize = 2;
+    const PA_MAX_BITS: usize = 32;
+    const VA_MAX_BITS: usize = 32;
+
+    const PA_MAX_ADDR: usize = u32::MAX as usize;
+
+    fn vaddr_is_valid(vaddr: usize) -> bool {
+        vaddr as u64 >> Self::VA_MAX_BITS == 0
+    }
+}
+
+impl PagingMetaData32 for A32PagingMetaData {
+    const L1_ENTRY_COUNT: usize = 4096;
+    const L2_ENTRY_COUNT: usize = 256;
+    // the 1MB sections are not used, see `A32PTE`
+    const HUGE_PAGE_SIZE: Option<PageSize> = None;
+}
+
+/// ARMv7-A short-descriptor translation table, with a 16K root table that
+/// translates the whole 32-bit address space by TTBR0 (TTBCR.N = 0).
+pub type A32PageTable<I> = PageTable32<A32PagingMetaData, A32PTE, I>;
@@ -1,7 +1,11 @@
 //! RISC-V specific page table structures.
 
-use crate::{PageTable64, PagingMetaData};
-use page_table_entry::riscv::Rv64PTE;
+use crate::{PageSize, PageTable32, PageTable64, PagingMetaData, PagingMetaData32};
+use page_table_entry::riscv::{Rv32PTE, Rv64PTE};
+
+/// Metadata of RISC-V Sv32 page tables.
+#[derive(Clone, Copy)]
+pub struct Sv32MetaData;
 
 /// Metadata of RISC-V Sv39 page tables.
 #[derive(Clone, Copy)]
@@ -11,6 +15,25 @@ pub struct Sv39MetaData;
 #[derive(Clone, Copy)]
 pub struct Sv48MetaData;
 
+impl const PagingMetaData for Sv32MetaData {
+    const LEVELS: usize = 2;
+    const PA_MAX_BITS: usize = 34;
+    const VA_MAX_BITS: usize = 32;
+
+    // the 34-bit physical addresses do not fit in `usize` on RV32
+    const PA_MAX_ADDR: usize = usize::MAX;
+
+    fn vaddr_is_valid(vaddr: usize) -> bool {
+        vaddr as u64 >> Self::VA_MAX_BITS == 0
+    }
+}
+
+impl PagingMetaData32 for Sv32MetaData {
+    const L1_ENTRY_COUNT: usize = 1024;
+    const L2_ENTRY_COUNT: usize = 1024;
+    const HUGE_PAGE_SIZE: Option<PageSize> = Some(PageSize::Size4M);
+}
+
 impl const PagingMetaData for Sv39MetaData {
     const LEVELS: usize = 3;
     const PA_MAX_BITS: usize = 56;
@@ -23,6 +46,9 @@ impl const PagingMetaData for Sv48MetaData {
     const VA_MAX_BITS: usize = 48;
 }
 
+/// Sv32: Page-Based 32-bit (2 levels) Virtual-Memory System.
+pub type Sv32PageTable<I> = PageTable32<Sv32MetaData, Rv32PTE, I>;
+
 /// Sv39: Page-Based 39-bit (3 levels) Virtual-Memory System.
 pub type Sv39PageTable<I> = PageTable64<Sv39MetaData, Rv64PTE, I>;
 
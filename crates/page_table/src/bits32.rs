@@ -0,0 +1,345 @@
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use memory_addr::{PhysAddr, VirtAddr, PAGE_SIZE_4K};
+
+use crate::{GenericPTE, PagingIf, PagingMetaData32};
+use crate::{MappingFlags, PageSize, PagingError, PagingResult};
+
+/// A generic two-level page table struct for 32-bit platform.
+///
+/// The number of entries in each level is given by [`PagingMetaData32`]. The
+/// root table may be larger than 4K (e.g. 16K for ARMv7), it is allocated by
+/// [`PagingIf::alloc_frames`] and aligned to its size. Each second-level
+/// table takes a 4K frame.
+///
+/// It also tracks all second-level tables. They will be deallocated
+/// When the [`PageTable32`] itself is dropped.
+pub struct PageTable32<M: PagingMetaData32, PTE: GenericPTE, IF: PagingIf> {
+    root_paddr: PhysAddr,
+    intrm_tables: Vec<PhysAddr>,
+    _phantom: PhantomData<(M, PTE, IF)>,
+}
+
+impl<M: PagingMetaData32, PTE: GenericPTE, IF: PagingIf> PageTable32<M, PTE, IF> {
+    const ROOT_FRAMES: usize =
+        (M::L1_ENTRY_COUNT * core::mem::size_of::<PTE>() + PAGE_SIZE_4K - 1) / PAGE_SIZE_4K;
+    const L2_SHIFT: u32 = 12 + M::L2_ENTRY_COUNT.trailing_zeros();
+
+    /// Creates a new page table instance or returns the error.
+    ///
+    /// It will allocate new pages for the root page table.
+    pub fn try_new() -> PagingResult<Self> {
+        assert!(M::L2_ENTRY_COUNT * core::mem::size_of::<PTE>() <= PAGE_SIZE_4K);
+        let root_paddr = IF::alloc_frames(Self::ROOT_FRAMES, Self::ROOT_FRAMES * PAGE_SIZE_4K)
+            .ok_or(PagingError::NoMemory)?;
+        let ptr = IF::phys_to_virt(root_paddr).as_mut_ptr();
+        unsafe { core::ptr::write_bytes(ptr, 0, Self::ROOT_FRAMES * PAGE_SIZE_4K) };
+        Ok(Self {
+            root_paddr,
+            intrm_tables: Vec::new(),
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Returns the physical address of the root page table.
+    pub const fn root_paddr(&self) -> PhysAddr {
+        self.root_paddr
+    }
+
+    /// Maps a virtual page to a physical frame with the given `page_size`
+    /// and mapping `flags`.
+    ///
+    /// The virtual page starts with `vaddr`, amd the physical frame starts with
+    /// `target`. If the addresses is not aligned to the page size, they will be
+    /// aligned down automatically.
+    ///
+    /// The `page_size` must be 4K or [`PagingMetaData32::HUGE_PAGE_SIZE`].
+    ///
+    /// Returns [`Err(PagingError::AlreadyMapped)`](PagingError::AlreadyMapped)
+    /// if the mapping is already present.
+    pub fn map(
+        &mut self,
+        vaddr: VirtAddr,
+        target: PhysAddr,
+        page_size: PageSize,
+        flags: MappingFlags,
+    ) -> PagingResult {
+        let entry = self.get_entry_mut_or_create(vaddr, page_size)?;
+        if !entry.is_unused() {
+            return Err(PagingError::AlreadyMapped);
+        }
+        *entry = GenericPTE::new_page(target.align_down(page_size), flags, page_size.is_huge());
+        Ok(())
+    }
+
+    /// Unmaps the mapping starts with `vaddr`.
+    ///
+    /// Returns [`Err(PagingError::NotMapped)`](PagingError::NotMapped) if the
+    /// mapping is not present.
+    pub fn unmap(&mut self, vaddr: VirtAddr) -> PagingResult<(PhysAddr, PageSize)> {
+        let (entry, size) = self.get_entry_mut(vaddr)?;
+        if entry.is_unused() {
+            return Err(PagingError::NotMapped);
+        }
+        let paddr = entry.paddr();
+        entry.clear();
+        Ok((paddr, size))
+    }
+
+    /// Updates the mapping flags of the mapping starts with `vaddr`, and keeps
+    /// its target frame.
+    ///
+    /// Returns the page size of the mapping, or
+    /// [`Err(PagingError::NotMapped)`](PagingError::NotMapped) if the mapping
+    /// is not present.
+    pub fn protect(&mut self, vaddr: VirtAddr, flags: MappingFlags) -> PagingResult<PageSize> {
+        let (entry, size) = self.get_entry_mut(vaddr)?;
+        if entry.is_unused() {
+            return Err(PagingError::NotMapped);
+        }
+        *entry = GenericPTE::new_page(entry.paddr(), flags, size.is_huge());
+        Ok(size)
+    }
+
+    /// Query the result of the mapping starts with `vaddr`.
+    ///
+    /// Returns the physical address of the target frame, mapping flags, and
+    /// the page size.
+    ///
+    /// Returns [`Err(PagingError::NotMapped)`](PagingError::NotMapped) if the
+    /// mapping is not present.
+    pub fn query(&self, vaddr: VirtAddr) -> PagingResult<(PhysAddr, MappingFlags, PageSize)> {
+        let (entry, size) = self.get_entry_mut(vaddr)?;
+        if entry.is_unused() {
+            return Err(PagingError::NotMapped);
+        }
+        let off = vaddr.align_offset(size);
+        Ok((entry.paddr() + off, entry.flags(), size))
+    }
+
+    /// Map a contiguous virtual memory region to a contiguous physical memory
+    /// region with the given mapping `flags`.
+    ///
+    /// The virtual and physical memory regions start with `vaddr` and `paddr`
+    /// respectively. The region size is `size`. The addresses and `size` must
+    /// be aligned to 4K, otherwise it will return [`Err(PagingError::NotAligned)`].
+    ///
+    /// When `allow_huge` is true, it will try to map the region with huge pages
+    /// if possible and supported. Otherwise, it will map the region with 4K
+    /// pages.
+    ///
+    /// [`Err(PagingError::NotAligned)`]: PagingError::NotAligned
+    pub fn map_region(
+        &mut self,
+        vaddr: VirtAddr,
+        paddr: PhysAddr,
+        size: usize,
+        flags: MappingFlags,
+        allow_huge: bool,
+    ) -> PagingResult {
+        if !vaddr.is_aligned(PageSize::Size4K)
+            || !paddr.is_aligned(PageSize::Size4K)
+            || !memory_addr::is_aligned(size, PageSize::Size4K)
+        {
+            return Err(PagingError::NotAligned);
+        }
+        trace!(
+            "map_region({:#x}): [{:#x}, {:#x}) -> [{:#x}, {:#x}) {:?}",
+            self.root_paddr(),
+            vaddr,
+            vaddr + size,
+            paddr,
+            paddr + size,
+            flags,
+        );
+        let mut vaddr = vaddr;
+        let mut paddr = paddr;
+        let mut size = size;
+        while size > 0 {
+            let page_size = match M::HUGE_PAGE_SIZE {
+                Some(huge_size)
+                    if allow_huge
+                        && vaddr.is_aligned(huge_size)
+                        && paddr.is_aligned(huge_size)
+                        && size >= huge_size as usize =>
+                {
+                    huge_size
+                }
+                _ => PageSize::Size4K,
+            };
+            self.map(vaddr, paddr, page_size, flags).inspect_err(|e| {
+                error!(
+                    "failed to map page: {:#x?}({:?}) -> {:#x?}, {:?}",
+                    vaddr, page_size, paddr, e
+                )
+            })?;
+            vaddr += page_size as usize;
+            paddr += page_size as usize;
+            size -= page_size as usize;
+        }
+        Ok(())
+    }
+
+    /// Unmap a contiguous virtual memory region.
+    ///
+    /// The region must be mapped before using [`PageTable32::map_region`], or
+    /// unexpected behaviors may occur.
+    pub fn unmap_region(&mut self, vaddr: VirtAddr, size: usize) -> PagingResult {
+        trace!(
+            "unmap_region({:#x}) [{:#x}, {:#x})",
+            self.root_paddr(),
+            vaddr,
+            vaddr + size,
+        );
+        let mut vaddr = vaddr;
+        let mut size = size;
+        while size > 0 {
+            let (_, page_size) = self
+                .unmap(vaddr)
+                .inspect_err(|e| error!("failed to unmap page: {:#x?}, {:?}", vaddr, e))?;
+            assert!(vaddr.is_aligned(page_size));
+            assert!(page_size as usize <= size);
+            vaddr += page_size as usize;
+            size -= page_size as usize;
+        }
+        Ok(())
+    }
+
+    /// Walk the page table.
+    ///
+    /// When reaching the leaf page table, call `func` on the current page table
+    /// entry. The max number of enumerations in one table is limited by `limit`.
+    ///
+    /// The arguments of `func` are:
+    /// - Current level (starts with `0`): `usize`
+    /// - The index of the entry in the current-level table: `usize`
+    /// - The virtual address that is mapped to the entry: [`VirtAddr`]
+    /// - The reference of the entry: [`&PTE`](GenericPTE)
+    pub fn walk<F>(&self, limit: usize, func: &F) -> PagingResult
+    where
+        F: Fn(usize, usize, VirtAddr, &PTE),
+    {
+        let l1 = self.table_of(self.root_paddr(), M::L1_ENTRY_COUNT);
+        let mut n = 0;
+        for (i, l1e) in l1.iter().enumerate() {
+            if !l1e.is_present() {
+                continue;
+            }
+            let vaddr = VirtAddr::from(i << Self::L2_SHIFT);
+            func(0, i, vaddr, l1e);
+            if !l1e.is_huge() {
+                let l2 = self.next_table_mut(l1e)?;
+                let mut m = 0;
+                for (j, l2e) in l2.iter().enumerate() {
+                    if l2e.is_present() {
+                        func(1, j, vaddr + (j << 12), l2e);
+                        m += 1;
+                        if m >= limit {
+                            break;
+                        }
+                    }
+                }
+            }
+            n += 1;
+            if n >= limit {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Private implements.
+impl<M: PagingMetaData32, PTE: GenericPTE, IF: PagingIf> PageTable32<M, PTE, IF> {
+    const fn l1_index(vaddr: VirtAddr) -> usize {
+        (vaddr.as_usize() >> Self::L2_SHIFT) & (M::L1_ENTRY_COUNT - 1)
+    }
+
+    const fn l2_index(vaddr: VirtAddr) -> usize {
+        (vaddr.as_usize() >> 12) & (M::L2_ENTRY_COUNT - 1)
+    }
+
+    fn alloc_table() -> PagingResult<PhysAddr> {
+        if let Some(paddr) = IF::alloc_frame() {
+            let ptr = IF::phys_to_virt(paddr).as_mut_ptr();
+            unsafe { core::ptr::write_bytes(ptr, 0, PAGE_SIZE_4K) };
+            Ok(paddr)
+        } else {
+            Err(PagingError::NoMemory)
+        }
+    }
+
+    fn table_of<'a>(&self, paddr: PhysAddr, count: usize) -> &'a [PTE] {
+        let ptr = IF::phys_to_virt(paddr).as_ptr() as _;
+        unsafe { core::slice::from_raw_parts(ptr, count) }
+    }
+
+    fn table_of_mut<'a>(&self, paddr: PhysAddr, count: usize) -> &'a mut [PTE] {
+        let ptr = IF::phys_to_virt(paddr).as_mut_ptr() as _;
+        unsafe { core::slice::from_raw_parts_mut(ptr, count) }
+    }
+
+    fn next_table_mut<'a>(&self, entry: &PTE) -> PagingResult<&'a mut [PTE]> {
+        if !entry.is_present() {
+            Err(PagingError::NotMapped)
+        } else if entry.is_huge() {
+            Err(PagingError::MappedToHugePage)
+        } else {
+            Ok(self.table_of_mut(entry.paddr(), M::L2_ENTRY_COUNT))
+        }
+    }
+
+    fn next_table_mut_or_create<'a>(&mut self, entry: &mut PTE) -> PagingResult<&'a mut [PTE]> {
+        if entry.is_unused() {
+            let paddr = Self::alloc_table()?;
+            self.intrm_tables.push(paddr);
+            *entry = GenericPTE::new_table(paddr);
+            Ok(self.table_of_mut(paddr, M::L2_ENTRY_COUNT))
+        } else {
+            self.next_table_mut(entry)
+        }
+    }
+
+    fn get_entry_mut(&self, vaddr: VirtAddr) -> PagingResult<(&mut PTE, PageSize)> {
+        let l1 = self.table_of_mut(self.root_paddr(), M::L1_ENTRY_COUNT);
+        let l1e = &mut l1[Self::l1_index(vaddr)];
+        if let Some(huge_size) = M::HUGE_PAGE_SIZE {
+            if l1e.is_huge() {
+                return Ok((l1e, huge_size));
+            }
+        }
+
+        let l2 = self.next_table_mut(l1e)?;
+        let l2e = &mut l2[Self::l2_index(vaddr)];
+        Ok((l2e, PageSize::Size4K))
+    }
+
+    fn get_entry_mut_or_create(
+        &mut self,
+        vaddr: VirtAddr,
+        page_size: PageSize,
+    ) -> PagingResult<&mut PTE> {
+        let l1 = self.table_of_mut(self.root_paddr(), M::L1_ENTRY_COUNT);
+        let l1e = &mut l1[Self::l1_index(vaddr)];
+        if page_size.is_huge() {
+            assert_eq!(Some(page_size), M::HUGE_PAGE_SIZE, "unsupported page size");
+            return Ok(l1e);
+        }
+
+        let l2 = self.next_table_mut_or_create(l1e)?;
+        let l2e = &mut l2[Self::l2_index(vaddr)];
+        Ok(l2e)
+    }
+}
+
+impl<M: PagingMetaData32, PTE: GenericPTE, IF: PagingIf> Drop for PageTable32<M, PTE, IF> {
+    fn drop(&mut self) {
+        for frame in &self.intrm_tables {
+            IF::dealloc_frame(*frame);
+        }
+        IF::dealloc_frames(self.root_paddr, Self::ROOT_FRAMES);
+    }
+}
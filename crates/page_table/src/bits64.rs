@@ -92,6 +92,21 @@ impl<M: PagingMetaData, PTE: GenericPTE, IF: PagingIf> PageTable64<M, PTE, IF> {
         Ok((paddr, size))
     }
 
+    /// Updates the mapping flags of the mapping starts with `vaddr`, and keeps
+    /// its target frame.
+    ///
+    /// Returns the page size of the mapping, or
+    /// [`Err(PagingError::NotMapped)`](PagingError::NotMapped) if the mapping
+    /// is not present.
+    pub fn protect(&mut self, vaddr: VirtAddr, flags: MappingFlags) -> PagingResult<PageSize> {
+        let (entry, size) = self.get_entry_mut(vaddr)?;
+        if entry.is_unused() {
+            return Err(PagingError::NotMapped);
+        }
+        *entry = GenericPTE::new_page(entry.paddr(), flags, size.is_huge());
+        Ok(size)
+    }
+
     /// Query the result of the mapping starts with `vaddr`.
     ///
     /// Returns the physical address of the target frame, mapping flags, and
@@ -201,6 +216,90 @@ impl<M: PagingMetaData, PTE: GenericPTE, IF: PagingIf> PageTable64<M, PTE, IF> {
         Ok(())
     }
 
+    /// Maps the pages of `[src_vaddr, src_vaddr + size)` again at
+    /// `dst_vaddr` in the same page table, so both ranges share the target
+    /// frames. The pages not mapped in the source range are skipped.
+    ///
+    /// If `cow` is true, the shared pages are marked [`MappingFlags::COW`]
+    /// and read-only in both ranges, so the first write to either of them can
+    /// be resolved by making a private copy. `on_share` is called with the
+    /// target frame of each shared page, e.g. to increase its reference count.
+    ///
+    /// The addresses and `size` must be aligned to 4K, and a huge page in the
+    /// source range must be aligned at both addresses, otherwise it will
+    /// return [`Err(PagingError::NotAligned)`](PagingError::NotAligned). The
+    /// TLB entries of the source range must be flushed if `cow` is true.
+    pub fn share_region<F>(
+        &mut self,
+        src_vaddr: VirtAddr,
+        dst_vaddr: VirtAddr,
+        size: usize,
+        cow: bool,
+        mut on_share: F,
+    ) -> PagingResult
+    where
+        F: FnMut(PhysAddr),
+    {
+        trace!(
+            "share_region({:#x}): [{:#x}, {:#x}) -> {:#x}, cow={}",
+            self.root_paddr(),
+            src_vaddr,
+            src_vaddr + size,
+            dst_vaddr,
+            cow,
+        );
+        let mut off = 0;
+        while off < size {
+            off += match self.share_page(src_vaddr + off, dst_vaddr + off, size - off, cow)? {
+                Some((paddr, flags, page_size)) => {
+                    self.map(dst_vaddr + off, paddr, page_size, flags)?;
+                    on_share(paddr);
+                    page_size as usize
+                }
+                None => PAGE_SIZE_4K,
+            };
+        }
+        Ok(())
+    }
+
+    /// Maps the pages of `[vaddr, vaddr + size)` at the same addresses in
+    /// another page table `dst`, so both page tables share the target frames,
+    /// e.g. for `fork`. The pages not mapped in `self` are skipped.
+    ///
+    /// See [`PageTable64::share_region`] for `cow` and `on_share`.
+    pub fn share_region_to<F>(
+        &mut self,
+        dst: &mut Self,
+        vaddr: VirtAddr,
+        size: usize,
+        cow: bool,
+        mut on_share: F,
+    ) -> PagingResult
+    where
+        F: FnMut(PhysAddr),
+    {
+        trace!(
+            "share_region_to({:#x} -> {:#x}): [{:#x}, {:#x}), cow={}",
+            self.root_paddr(),
+            dst.root_paddr(),
+            vaddr,
+            vaddr + size,
+            cow,
+        );
+        let mut off = 0;
+        while off < size {
+            off += match self.share_page(vaddr + off, vaddr + off, size - off, cow)? {
+                Some((paddr, flags, page_size)) => {
+                    dst.map(vaddr + off, paddr, page_size, flags)?;
+                    on_share(paddr);
+                    page_size as usize
+                }
+                None => PAGE_SIZE_4K,
+            };
+        }
+        Ok(())
+    }
+
     /// Walk the page table recursively.
     ///
     /// When reaching the leaf page table, call `func` on the current page table
@@ -237,6 +336,45 @@ impl<M: PagingMetaData, PTE: GenericPTE, IF: PagingIf> PageTable64<M, PTE, IF> {
         }
     }
 
+    /// Prepares the page mapped at `src_vaddr` to be shared at `dst_vaddr`,
+    /// with at most `max_size` bytes. Marks it copy-on-write if `cow` is true.
+    ///
+    /// Returns the target frame, the mapping flags and the page size for the
+    /// shared mapping, or `None` if the page is not mapped.
+    fn share_page(
+        &mut self,
+        src_vaddr: VirtAddr,
+        dst_vaddr: VirtAddr,
+        max_size: usize,
+        cow: bool,
+    ) -> PagingResult<Option<(PhysAddr, MappingFlags, PageSize)>> {
+        if !src_vaddr.is_aligned(PageSize::Size4K)
+            || !dst_vaddr.is_aligned(PageSize::Size4K)
+            || !memory_addr::is_aligned(max_size, PageSize::Size4K)
+        {
+            return Err(PagingError::NotAligned);
+        }
+        let (paddr, flags, page_size) = match self.query(src_vaddr) {
+            Ok(res) => res,
+            Err(PagingError::NotMapped) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        if !src_vaddr.is_aligned(page_size)
+            || !dst_vaddr.is_aligned(page_size)
+            || max_size < page_size as usize
+        {
+            return Err(PagingError::NotAligned);
+        }
+        let flags = if cow {
+            let flags = (flags - MappingFlags::WRITE) | MappingFlags::COW;
+            self.protect(src_vaddr, flags)?;
+            flags
+        } else {
+            flags
+        };
+        Ok(Some((paddr, flags, page_size)))
+    }
+
     fn table_of<'a>(&self, paddr: PhysAddr) -> &'a [PTE] {
         let ptr = IF::phys_to_virt(paddr).as_ptr() as _;
         unsafe { core::slice::from_raw_parts(ptr, ENTRY_COUNT) }
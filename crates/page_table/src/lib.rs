@@ -1,11 +1,13 @@
 //! This crate provides generic, unified, architecture-independent, and OS-free
 //! page table structures for various hardware architectures.
 //!
-//! The core struct is [`PageTable64<M, PTE, IF>`]. OS-functions and
-//! architecture-dependent types are provided by generic parameters:
+//! The core structs are [`PageTable64<M, PTE, IF>`] and its two-level
+//! counterpart for 32-bit platforms [`PageTable32<M, PTE, IF>`]. OS-functions
+//! and architecture-dependent types are provided by generic parameters:
 //!
 //! - `M`: The architecture-dependent metadata, requires to implement
-//!   the [`PagingMetaData`] trait.
+//!   the [`PagingMetaData`] trait, and also the [`PagingMetaData32`] trait
+//!   for [`PageTable32`].
 //! - `PTE`: The architecture-dependent page table entry, requires to implement
 //!   the [`GenericPTE`] trait.
 //! - `IF`: OS-functions such as physical memory allocation, requires to
@@ -14,10 +16,11 @@
 //! Currently supported architectures and page table structures:
 //!
 //! - x86: [`x86_64::X64PageTable`]
-//! - ARM: [`aarch64::A64PageTable`]
-//! - RISC-V: [`riscv::Sv39PageTable`], [`riscv::Sv48PageTable`]
+//! - ARM: [`aarch64::A64PageTable`], [`arm::A32PageTable`]
+//! - RISC-V: [`riscv::Sv39PageTable`], [`riscv::Sv48PageTable`],
+//!   [`riscv::Sv32PageTable`]
 
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 #![feature(const_trait_impl)]
 #![feature(result_option_inspect)]
 #![feature(doc_auto_cfg)]
@@ -26,11 +29,16 @@
 extern crate log;
 
 mod arch;
+mod bits32;
 mod bits64;
 
-use memory_addr::{PhysAddr, VirtAddr};
+#[cfg(test)]
+mod tests;
+
+use memory_addr::{PhysAddr, VirtAddr, PAGE_SIZE_4K};
 
 pub use self::arch::*;
+pub use self::bits32::PageTable32;
 pub use self::bits64::PageTable64;
 
 #[doc(no_inline)]
@@ -56,7 +64,7 @@ pub enum PagingError {
 pub type PagingResult<T = ()> = Result<T, PagingError>;
 
 /// The **architecture-dependent** metadata that must be provided for
-/// [`PageTable64`].
+/// [`PageTable64`] and [`PageTable32`].
 #[const_trait]
 pub trait PagingMetaData: Sync + Send + Sized {
     /// The number of levels of the hardware page table.
@@ -84,13 +92,43 @@ pub trait PagingMetaData: Sync + Send + Sized {
     }
 }
 
+/// The **architecture-dependent** layout of the two-level page tables that
+/// must be provided for [`PageTable32`].
+pub trait PagingMetaData32: PagingMetaData {
+    /// The number of entries in the first-level (root) table.
+    const L1_ENTRY_COUNT: usize;
+    /// The number of entries in a second-level table.
+    const L2_ENTRY_COUNT: usize;
+    /// The size of the huge pages mapped by the first-level entries, or
+    /// `None` if they are not supported.
+    const HUGE_PAGE_SIZE: Option<PageSize>;
+}
+
 /// The low-level **OS-dependent** helpers that must be provided for
-/// [`PageTable64`].
+/// [`PageTable64`] and [`PageTable32`].
 pub trait PagingIf: Sized {
     /// Request to allocate a 4K-sized physical frame.
     fn alloc_frame() -> Option<PhysAddr>;
     /// Request to free a allocated physical frame.
     fn dealloc_frame(paddr: PhysAddr);
+    /// Request to allocate `num` contiguous 4K-sized physical frames aligned
+    /// to `align` bytes, e.g. for a root table larger than 4K.
+    ///
+    /// The default implementation only allocates a single frame.
+    fn alloc_frames(num: usize, align: usize) -> Option<PhysAddr> {
+        if num == 1 && align <= PAGE_SIZE_4K {
+            Self::alloc_frame()
+        } else {
+            None
+        }
+    }
+    /// Request to free the contiguous physical frames allocated by
+    /// [`PagingIf::alloc_frames`].
+    fn dealloc_frames(paddr: PhysAddr, num: usize) {
+        for i in 0..num {
+            Self::dealloc_frame(paddr + i * PAGE_SIZE_4K);
+        }
+    }
     /// Returns a virtual address that maps to the given physical address.
     ///
     /// Used to access the physical memory directly in page table implementation.
@@ -105,6 +143,8 @@ pub enum PageSize {
     Size4K = 0x1000,
     /// Size of 2 megabytes (2<sup>21</sup> bytes).
     Size2M = 0x20_0000,
+    /// Size of 4 megabytes (2<sup>22</sup> bytes).
+    Size4M = 0x40_0000,
     /// Size of 1 gigabytes (2<sup>30</sup> bytes).
     Size1G = 0x4000_0000,
 }
@@ -112,7 +152,7 @@ pub enum PageSize {
 impl PageSize {
     /// Whether this page size is considered huge (larger than 4K).
     pub const fn is_huge(self) -> bool {
-        matches!(self, Self::Size1G | Self::Size2M)
+        matches!(self, Self::Size1G | Self::Size4M | Self::Size2M)
     }
 }
 
@@ -0,0 +1,277 @@
+use std::alloc::{alloc_zeroed, dealloc, Layout};
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use memory_addr::{PhysAddr, VirtAddr, PAGE_SIZE_4K};
+
+use crate::arm::A32PageTable;
+use crate::riscv::Sv32PageTable;
+use crate::*;
+
+/// The physical address of the first frame of the arena, so the page tables
+/// only refer to 32-bit physical addresses.
+const PHYS_BASE: usize = 0x8000_0000;
+const ARENA_SIZE: usize = 0x10_0000;
+
+/// Frames of the current test.
+struct Arena {
+    base: *mut u8,
+    next: usize,
+    /// The frames allocated, and their numbers.
+    frames: HashMap<usize, usize>,
+}
+
+impl Arena {
+    const LAYOUT: Layout = match Layout::from_size_align(ARENA_SIZE, 0x4000) {
+        Ok(layout) => layout,
+        Err(_) => panic!(),
+    };
+
+    fn new() -> Self {
+        let base = unsafe { alloc_zeroed(Self::LAYOUT) };
+        assert!(!base.is_null());
+        Self {
+            base,
+            next: PHYS_BASE,
+            frames: HashMap::new(),
+        }
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        unsafe { dealloc(self.base, Self::LAYOUT) }
+    }
+}
+
+thread_local! {
+    static ARENA: RefCell<Arena> = RefCell::new(Arena::new());
+}
+
+/// Allocates frames from an arena on the host heap, which is never reused.
+struct TestPagingIf;
+
+impl PagingIf for TestPagingIf {
+    fn alloc_frame() -> Option<PhysAddr> {
+        Self::alloc_frames(1, PAGE_SIZE_4K)
+    }
+
+    fn dealloc_frame(paddr: PhysAddr) {
+        Self::dealloc_frames(paddr, 1)
+    }
+
+    fn alloc_frames(num: usize, align: usize) -> Option<PhysAddr> {
+        ARENA.with(|arena| {
+            let mut arena = arena.borrow_mut();
+            let paddr = memory_addr::align_up(arena.next, align);
+            if paddr + num * PAGE_SIZE_4K > PHYS_BASE + ARENA_SIZE {
+                return None;
+            }
+            arena.next = paddr + num * PAGE_SIZE_4K;
+            arena.frames.insert(paddr, num);
+            Some(PhysAddr::from(paddr))
+        })
+    }
+
+    fn dealloc_frames(paddr: PhysAddr, num: usize) {
+        let allocated = ARENA.with(|arena| arena.borrow_mut().frames.remove(&paddr.as_usize()));
+        assert_eq!(allocated, Some(num), "freeing frames not allocated");
+    }
+
+    fn phys_to_virt(paddr: PhysAddr) -> VirtAddr {
+        let base = ARENA.with(|arena| arena.borrow().base as usize);
+        VirtAddr::from(base + paddr.as_usize() - PHYS_BASE)
+    }
+}
+
+fn allocated_frames() -> usize {
+    ARENA.with(|arena| arena.borrow().frames.len())
+}
+
+fn flags_eq(a: MappingFlags, b: MappingFlags) -> bool {
+    a.bits() == b.bits()
+}
+
+const RW: MappingFlags = MappingFlags::READ.union(MappingFlags::WRITE);
+const RX: MappingFlags = MappingFlags::READ.union(MappingFlags::EXECUTE);
+
+#[test]
+fn test_sv32_map_unmap() -> PagingResult {
+    let mut pt = Sv32PageTable::<TestPagingIf>::try_new()?;
+    assert!(pt.root_paddr().is_aligned_4k());
+    assert_eq!(allocated_frames(), 1);
+
+    let vaddr = VirtAddr::from(0xc000_1000);
+    let paddr = PhysAddr::from(0x8020_3000);
+    pt.map(vaddr, paddr, PageSize::Size4K, RW)?;
+    assert!(matches!(
+        pt.map(vaddr, paddr, PageSize::Size4K, RW),
+        Err(PagingError::AlreadyMapped)
+    ));
+    assert_eq!(allocated_frames(), 2);
+
+    let (pa, flags, size) = pt.query(vaddr + 0x123)?;
+    assert_eq!(pa, paddr + 0x123);
+    assert!(flags_eq(flags, RW));
+    assert_eq!(size, PageSize::Size4K);
+
+    assert_eq!(pt.protect(vaddr, RX)?, PageSize::Size4K);
+    assert!(flags_eq(pt.query(vaddr)?.1, RX));
+
+    assert_eq!(pt.unmap(vaddr)?, (paddr, PageSize::Size4K));
+    assert!(matches!(pt.query(vaddr), Err(PagingError::NotMapped)));
+    assert!(matches!(pt.unmap(vaddr), Err(PagingError::NotMapped)));
+    assert!(matches!(
+        pt.query(VirtAddr::from(0x1000)),
+        Err(PagingError::NotMapped)
+    ));
+
+    drop(pt);
+    assert_eq!(allocated_frames(), 0);
+    Ok(())
+}
+
+#[test]
+fn test_sv32_huge_pages() -> PagingResult {
+    let mut pt = Sv32PageTable::<TestPagingIf>::try_new()?;
+    // 4K + 4M + 4K, only the middle can be a megapage
+    let vaddr = VirtAddr::from(0x803f_f000);
+    let paddr = PhysAddr::from(0x803f_f000);
+    let size = 0x40_0000 + 2 * PAGE_SIZE_4K;
+    pt.map_region(vaddr, paddr, size, RX, true)?;
+
+    assert_eq!(pt.query(vaddr)?.2, PageSize::Size4K);
+    let (pa, flags, page_size) = pt.query(VirtAddr::from(0x8050_0000))?;
+    assert_eq!(pa, PhysAddr::from(0x8050_0000));
+    assert!(flags_eq(flags, RX));
+    assert_eq!(page_size, PageSize::Size4M);
+    assert_eq!(pt.query(vaddr + size - 1)?.2, PageSize::Size4K);
+    assert!(matches!(
+        pt.map(VirtAddr::from(0x8040_0000), paddr, PageSize::Size4K, RW),
+        Err(PagingError::MappedToHugePage)
+    ));
+
+    let leaves = std::sync::Mutex::new(std::vec::Vec::new());
+    pt.walk(usize::MAX, &|level, _, vaddr, entry| {
+        if level == 1 || entry.is_huge() {
+            leaves.lock().unwrap().push((level, vaddr));
+        }
+    })?;
+    assert_eq!(
+        leaves.into_inner().unwrap(),
+        [
+            (1, VirtAddr::from(0x803f_f000)),
+            (0, VirtAddr::from(0x8040_0000)),
+            (1, VirtAddr::from(0x8080_0000)),
+        ]
+    );
+
+    pt.unmap_region(vaddr, size)?;
+    assert!(matches!(
+        pt.query(VirtAddr::from(0x8050_0000)),
+        Err(PagingError::NotMapped)
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_sv32_pte() {
+    use page_table_entry::riscv::Rv32PTE;
+
+    // Sv32 supports 34-bit physical addresses
+    let paddr = PhysAddr::from(0x3_8000_2000);
+    let pte = Rv32PTE::new_page(paddr, RW | MappingFlags::USER, false);
+    assert_eq!(pte.paddr(), paddr);
+    assert!(flags_eq(pte.flags(), RW | MappingFlags::USER));
+    assert!(pte.is_present() && pte.is_huge());
+
+    let table = Rv32PTE::new_table(PhysAddr::from(0x8000_1000));
+    assert!(table.is_present() && !table.is_huge());
+    assert_eq!(table.paddr(), PhysAddr::from(0x8000_1000));
+}
+
+#[test]
+fn test_a32_map_unmap() -> PagingResult {
+    let mut pt = A32PageTable::<TestPagingIf>::try_new()?;
+    // the 16K root table
+    assert!(pt.root_paddr().is_aligned(0x4000usize));
+    assert_eq!(allocated_frames(), 1);
+
+    let vaddr = VirtAddr::from(0xfff0_0000);
+    let paddr = PhysAddr::from(0x0900_0000);
+    let flags = RW | MappingFlags::DEVICE;
+    pt.map_region(vaddr, paddr, 0x10_0000, flags, true)?;
+    // the sections are not used, and each second-level table maps 1M
+    assert_eq!(allocated_frames(), 2);
+
+    let (pa, f, size) = pt.query(vaddr + 0x4_5678)?;
+    assert_eq!(pa, paddr + 0x4_5678);
+    assert!(flags_eq(f, flags));
+    assert_eq!(size, PageSize::Size4K);
+
+    let user_flags = RX | MappingFlags::USER;
+    pt.map(
+        VirtAddr::from(0x1000),
+        PhysAddr::from(0x4000_0000),
+        PageSize::Size4K,
+        user_flags,
+    )?;
+    assert!(flags_eq(pt.query(VirtAddr::from(0x1000))?.1, user_flags));
+    assert_eq!(allocated_frames(), 3);
+
+    // keeps the frame even if it is not accessible
+    pt.protect(VirtAddr::from(0x1000), MappingFlags::empty())?;
+    let (pa, f, _) = pt.query(VirtAddr::from(0x1000))?;
+    assert_eq!(pa, PhysAddr::from(0x4000_0000));
+    assert!(!f.contains(MappingFlags::READ));
+
+    pt.unmap_region(vaddr, 0x10_0000)?;
+    assert!(matches!(pt.query(vaddr), Err(PagingError::NotMapped)));
+
+    drop(pt);
+    assert_eq!(allocated_frames(), 0);
+    Ok(())
+}
+
+#[test]
+#[should_panic(expected = "unsupported page size")]
+fn test_a32_no_sections() {
+    let mut pt = A32PageTable::<TestPagingIf>::try_new().unwrap();
+    pt.map(
+        VirtAddr::from(0x40_0000),
+        PhysAddr::from(0x40_0000),
+        PageSize::Size4M,
+        RW,
+    )
+    .ok();
+}
+
+#[test]
+fn test_a32_pte() {
+    use page_table_entry::arm::A32PTE;
+
+    let pte = A32PTE::new_page(PhysAddr::from(0x4000_1000), RX, false);
+    assert!(pte.is_present() && !pte.is_huge());
+    assert!(flags_eq(pte.flags(), RX));
+    assert_eq!(pte.paddr(), PhysAddr::from(0x4000_1000));
+
+    let table = A32PTE::new_table(PhysAddr::from(0x4000_1c00));
+    assert!(table.is_present() && !table.is_huge());
+    assert_eq!(table.paddr(), PhysAddr::from(0x4000_1c00));
+
+    let pte = A32PTE::new_page(PhysAddr::from(0x4000_1000), MappingFlags::WRITE, false);
+    assert!(!pte.is_present() && !pte.is_unused());
+}
+
+#[test]
+fn test_a64_pte_mem_types() {
+    use page_table_entry::aarch64::A64PTE;
+
+    let paddr = PhysAddr::from(0x4000_1000);
+    for flags in [RW, RW | MappingFlags::DEVICE, RW | MappingFlags::UNCACHED] {
+        let pte = A64PTE::new_page(paddr, flags, false);
+        assert!(pte.is_present() && !pte.is_huge());
+        assert!(flags_eq(pte.flags(), flags));
+        assert_eq!(pte.paddr(), paddr);
+    }
+}
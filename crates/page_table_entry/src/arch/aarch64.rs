@@ -39,6 +39,8 @@ bitflags::bitflags! {
         const PXN =         1 <<  53;
         /// The Execute-never or Unprivileged execute-never field.
         const UXN =         1 <<  54;
+        /// Reserved for software: the page is shared copy-on-write.
+        const SW_COW =      1 <<  55;
 
         // Next-level attributes in stage 1 VMSAv8-64 Table descriptors:
 
@@ -61,6 +63,7 @@ bitflags::bitflags! {
 enum MemType {
     Device = 0,
     Normal = 1,
+    NormalNonCacheable = 2,
 }
 
 impl DescriptorAttr {
@@ -69,7 +72,7 @@ impl DescriptorAttr {
 
     const fn from_mem_type(mem_type: MemType) -> Self {
         let mut bits = (mem_type as u64) << 2;
-        if matches!(mem_type, MemType::Normal) {
+        if matches!(mem_type, MemType::Normal | MemType::NormalNonCacheable) {
             bits |= Self::INNER.bits() | Self::SHAREABLE.bits();
         }
         Self::from_bits_truncate(bits)
@@ -80,6 +83,7 @@ impl DescriptorAttr {
         match idx {
             0 => MemType::Device,
             1 => MemType::Normal,
+            2 => MemType::NormalNonCacheable,
             _ => panic!("Invalid memory attribute index"),
         }
     }
@@ -102,8 +106,13 @@ impl From<DescriptorAttr> for MappingFlags {
         } else if !attr.intersects(DescriptorAttr::PXN) {
             flags |= Self::EXECUTE;
         }
-        if attr.mem_type() == MemType::Device {
-            flags |= Self::DEVICE;
+        match attr.mem_type() {
+            MemType::Device => flags |= Self::DEVICE,
+            MemType::NormalNonCacheable => flags |= Self::UNCACHED,
+            MemType::Normal => {}
+        }
+        if attr.contains(DescriptorAttr::SW_COW) {
+            flags |= Self::COW;
         }
         flags
     }
@@ -113,6 +122,8 @@ impl From<MappingFlags> for DescriptorAttr {
     fn from(flags: MappingFlags) -> Self {
         let mut attr = if flags.contains(MappingFlags::DEVICE) {
             Self::from_mem_type(MemType::Device)
+        } else if flags.contains(MappingFlags::UNCACHED) {
+            Self::from_mem_type(MemType::NormalNonCacheable)
         } else {
             Self::from_mem_type(MemType::Normal)
         };
@@ -133,6 +144,9 @@ impl From<MappingFlags> for DescriptorAttr {
                 attr |= Self::PXN;
             }
         }
+        if flags.contains(MappingFlags::COW) {
+            attr |= Self::SW_COW;
+        }
         attr
     }
 }
@@ -140,8 +154,8 @@ impl From<MappingFlags> for DescriptorAttr {
 /// A VMSAv8-64 translation table descriptor.
 ///
 /// Note that the **AttrIndx\[2:0\]** (bit\[4:2\]) field is set to `0` for device
-/// memory, and `1` for normal memory. The system must configure the MAIR_ELx
-/// system register accordingly.
+/// memory, `1` for normal memory, and `2` for normal non-cacheable memory. The
+/// system must configure the MAIR_ELx system register accordingly.
 #[derive(Clone, Copy)]
 #[repr(transparent)]
 pub struct A64PTE(u64);
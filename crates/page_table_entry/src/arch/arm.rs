@@ -0,0 +1,182 @@
+//! ARMv7-A short-descriptor translation table format descriptors.
+
+use core::fmt;
+use memory_addr::PhysAddr;
+
+use crate::{GenericPTE, MappingFlags};
+
+bitflags::bitflags! {
+    /// Attribute fields in the ARMv7-A short-descriptor small page descriptors.
+    #[derive(Debug)]
+    pub struct DescriptorAttr: u32 {
+        /// The Execute-never bit.
+        const XN =          1 << 0;
+        /// The descriptor maps a 4KB small page.
+        const SMALL_PAGE =  1 << 1;
+        /// Memory region attribute: bufferable.
+        const B =           1 << 2;
+        /// Memory region attribute: cacheable.
+        const C =           1 << 3;
+        /// Access permission AP\[0\]: the Access flag if SCTLR.AFE is set,
+        /// otherwise it must be set to allow any access.
+        const AF =          1 << 4;
+        /// Access permission AP\[1\]: accessable at PL0.
+        const AP_PL0 =      1 << 5;
+        /// Type extension field.
+        const TEX =         0b111 << 6;
+        /// Access permission AP\[2\]: read-only.
+        const AP_RO =       1 << 9;
+        /// Shareable.
+        const S =           1 << 10;
+        /// The not global bit.
+        const NG =          1 << 11;
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum MemType {
+    Device,
+    Normal,
+}
+
+impl DescriptorAttr {
+    const TEX_NORMAL: u32 = 0b001 << 6;
+
+    const fn from_mem_type(mem_type: MemType) -> Self {
+        match mem_type {
+            // Shareable Device
+            MemType::Device => Self::B,
+            // Normal, Outer and Inner Write-Back, Write-Allocate
+            MemType::Normal => Self::from_bits_truncate(
+                Self::TEX_NORMAL | Self::C.bits() | Self::B.bits() | Self::S.bits(),
+            ),
+        }
+    }
+
+    fn mem_type(&self) -> MemType {
+        if self.intersects(Self::TEX | Self::C) {
+            MemType::Normal
+        } else {
+            MemType::Device
+        }
+    }
+}
+
+impl From<DescriptorAttr> for MappingFlags {
+    fn from(attr: DescriptorAttr) -> Self {
+        let mut flags = Self::empty();
+        if attr.contains(DescriptorAttr::SMALL_PAGE) {
+            flags |= Self::READ;
+            if !attr.contains(DescriptorAttr::XN) {
+                flags |= Self::EXECUTE;
+            }
+        }
+        if !attr.contains(DescriptorAttr::AP_RO) {
+            flags |= Self::WRITE;
+        }
+        if attr.contains(DescriptorAttr::AP_PL0) {
+            flags |= Self::USER;
+        }
+        if attr.mem_type() == MemType::Device {
+            flags |= Self::DEVICE;
+        }
+        flags
+    }
+}
+
+impl From<MappingFlags> for DescriptorAttr {
+    fn from(flags: MappingFlags) -> Self {
+        let mut attr = if flags.contains(MappingFlags::DEVICE) {
+            Self::from_mem_type(MemType::Device)
+        } else {
+            Self::from_mem_type(MemType::Normal)
+        };
+        // bits[1:0] = 0b01 is a large page, so `XN` is only set for valid
+        // small pages
+        if flags.contains(MappingFlags::READ) {
+            attr |= Self::SMALL_PAGE;
+            if !flags.contains(MappingFlags::EXECUTE) {
+                attr |= Self::XN;
+            }
+        }
+        if !flags.contains(MappingFlags::WRITE) {
+            attr |= Self::AP_RO;
+        }
+        if flags.contains(MappingFlags::USER) {
+            attr |= Self::AP_PL0;
+        }
+        attr
+    }
+}
+
+/// An ARMv7-A short-descriptor translation table descriptor.
+///
+/// It is either a first-level page table descriptor or a second-level small
+/// page descriptor. The 1MB sections are not used, as their descriptors can
+/// not be told apart from the small page ones without knowing the level.
+///
+/// Note that the memory attributes are encoded by **TEX\[2:0\]**, **C** and
+/// **B** directly, so the system must clear SCTLR.TRE. There is no bit for
+/// software in the descriptors, so [`MappingFlags::COW`] is not kept.
+#[derive(Clone, Copy)]
+#[repr(transparent)]
+pub struct A32PTE(u32);
+
+impl A32PTE {
+    const TYPE_MASK: u32 = 0b11;
+    const TYPE_PAGE_TABLE: u32 = 0b01;
+    const PAGE_ADDR_MASK: u32 = 0xffff_f000; // bits 12..32
+    const TABLE_ADDR_MASK: u32 = 0xffff_fc00; // bits 10..32
+
+    /// Creates an empty descriptor with all bits set to zero.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    const fn is_table(&self) -> bool {
+        self.0 & Self::TYPE_MASK == Self::TYPE_PAGE_TABLE
+    }
+}
+
+impl GenericPTE for A32PTE {
+    fn new_page(paddr: PhysAddr, flags: MappingFlags, is_huge: bool) -> Self {
+        debug_assert!(!is_huge, "sections are not supported");
+        let attr = DescriptorAttr::from(flags) | DescriptorAttr::AF;
+        Self(attr.bits() | (paddr.as_usize() as u32 & Self::PAGE_ADDR_MASK))
+    }
+    fn new_table(paddr: PhysAddr) -> Self {
+        Self(Self::TYPE_PAGE_TABLE | (paddr.as_usize() as u32 & Self::TABLE_ADDR_MASK))
+    }
+    fn paddr(&self) -> PhysAddr {
+        if self.is_table() {
+            PhysAddr::from((self.0 & Self::TABLE_ADDR_MASK) as usize)
+        } else {
+            PhysAddr::from((self.0 & Self::PAGE_ADDR_MASK) as usize)
+        }
+    }
+    fn flags(&self) -> MappingFlags {
+        DescriptorAttr::from_bits_truncate(self.0).into()
+    }
+    fn is_unused(&self) -> bool {
+        self.0 == 0
+    }
+    fn is_present(&self) -> bool {
+        self.0 & Self::TYPE_MASK != 0
+    }
+    fn is_huge(&self) -> bool {
+        false
+    }
+    fn clear(&mut self) {
+        self.0 = 0
+    }
+}
+
+impl fmt::Debug for A32PTE {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut f = f.debug_struct("A32PTE");
+        f.field("raw", &self.0)
+            .field("paddr", &self.paddr())
+            .field("flags", &self.flags())
+            .finish()
+    }
+}
@@ -7,3 +7,6 @@ pub mod riscv;
 // TODO: `#[cfg(any(target_arch = "aarch64", doc))]` does not work.
 #[doc(cfg(target_arch = "aarch64"))]
 pub mod aarch64;
+
+#[doc(cfg(target_arch = "arm"))]
+pub mod arm;
@@ -27,6 +27,8 @@ bitflags::bitflags! {
         /// Indicates the virtual page has been written since the last time the
         /// D bit was cleared.
         const D =   1 << 7;
+        /// Reserved for software: the page is shared copy-on-write.
+        const COW = 1 << 8;
     }
 }
 
@@ -45,16 +47,20 @@ impl From<PTEFlags> for MappingFlags {
         if f.contains(PTEFlags::U) {
             ret |= Self::USER;
         }
+        if f.contains(PTEFlags::COW) {
+            ret |= Self::COW;
+        }
         ret
     }
 }
 
 impl From<MappingFlags> for PTEFlags {
     fn from(f: MappingFlags) -> Self {
-        if f.is_empty() {
-            return Self::empty();
+        let mut ret = Self::empty();
+        // the software bits are kept even if the page is not accessible
+        if f.intersects(MappingFlags::READ | MappingFlags::WRITE | MappingFlags::EXECUTE) {
+            ret |= Self::V;
         }
-        let mut ret = Self::V;
         if f.contains(MappingFlags::READ) {
             ret |= Self::R;
         }
@@ -67,6 +73,9 @@ impl From<MappingFlags> for PTEFlags {
         if f.contains(MappingFlags::USER) {
             ret |= Self::U;
         }
+        if f.contains(MappingFlags::COW) {
+            ret |= Self::COW;
+        }
         ret
     }
 }
@@ -83,7 +92,8 @@ impl Rv64PTE {
 impl GenericPTE for Rv64PTE {
     fn new_page(paddr: PhysAddr, flags: MappingFlags, _is_huge: bool) -> Self {
         let flags = PTEFlags::from(flags) | PTEFlags::A | PTEFlags::D;
-        debug_assert!(flags.intersects(PTEFlags::R | PTEFlags::X));
+        // a valid leaf must be readable or executable, or it points to a table
+        debug_assert!(!flags.contains(PTEFlags::V) || flags.intersects(PTEFlags::R | PTEFlags::X));
         Self(flags.bits() as u64 | ((paddr.as_usize() >> 2) as u64 & Self::PHYS_ADDR_MASK))
     }
     fn new_table(paddr: PhysAddr) -> Self {
@@ -118,3 +128,55 @@ impl fmt::Debug for Rv64PTE {
             .finish()
     }
 }
+
+/// Sv32 page table entry for RV32 systems.
+///
+/// The hardware supports 34-bit physical addresses, but only the ones fit in
+/// `usize` can be mapped on RV32.
+#[derive(Clone, Copy)]
+#[repr(transparent)]
+pub struct Rv32PTE(u32);
+
+impl Rv32PTE {
+    const PHYS_ADDR_MASK: u32 = !((1 << 10) - 1); // bits 10..32
+}
+
+impl GenericPTE for Rv32PTE {
+    fn new_page(paddr: PhysAddr, flags: MappingFlags, _is_huge: bool) -> Self {
+        let flags = PTEFlags::from(flags) | PTEFlags::A | PTEFlags::D;
+        // a valid leaf must be readable or executable, or it points to a table
+        debug_assert!(!flags.contains(PTEFlags::V) || flags.intersects(PTEFlags::R | PTEFlags::X));
+        Self(flags.bits() as u32 | ((paddr.as_usize() >> 2) as u32 & Self::PHYS_ADDR_MASK))
+    }
+    fn new_table(paddr: PhysAddr) -> Self {
+        Self(PTEFlags::V.bits() as u32 | ((paddr.as_usize() >> 2) as u32 & Self::PHYS_ADDR_MASK))
+    }
+    fn paddr(&self) -> PhysAddr {
+        PhysAddr::from(((self.0 & Self::PHYS_ADDR_MASK) as usize) << 2)
+    }
+    fn flags(&self) -> MappingFlags {
+        PTEFlags::from_bits_truncate(self.0 as usize).into()
+    }
+    fn is_unused(&self) -> bool {
+        self.0 == 0
+    }
+    fn is_present(&self) -> bool {
+        PTEFlags::from_bits_truncate(self.0 as usize).contains(PTEFlags::V)
+    }
+    fn is_huge(&self) -> bool {
+        PTEFlags::from_bits_truncate(self.0 as usize).intersects(PTEFlags::R | PTEFlags::X)
+    }
+    fn clear(&mut self) {
+        self.0 = 0
+    }
+}
+
+impl fmt::Debug for Rv32PTE {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut f = f.debug_struct("Rv32PTE");
+        f.field("raw", &self.0)
+            .field("paddr", &self.paddr())
+            .field("flags", &self.flags())
+            .finish()
+    }
+}
@@ -9,8 +9,12 @@ use crate::{GenericPTE, MappingFlags};
 
 impl From<PTF> for MappingFlags {
     fn from(f: PTF) -> Self {
-        if f.is_empty() {
-            return Self::empty();
+        if !f.contains(PTF::PRESENT) {
+            return if f.contains(PTF::BIT_9) {
+                Self::COW
+            } else {
+                Self::empty()
+            };
         }
         let mut ret = Self::READ;
         if f.contains(PTF::WRITABLE) {
@@ -25,14 +29,22 @@ impl From<PTF> for MappingFlags {
         if f.contains(PTF::NO_CACHE) {
             ret |= Self::DEVICE;
         }
+        if f.contains(PTF::BIT_9) {
+            ret |= Self::COW;
+        }
         ret
     }
 }
 
 impl From<MappingFlags> for PTF {
     fn from(f: MappingFlags) -> Self {
-        if f.is_empty() {
-            return Self::empty();
+        if !f.intersects(MappingFlags::READ | MappingFlags::WRITE | MappingFlags::EXECUTE) {
+            // not accessible at all, but the software bits are kept
+            return if f.contains(MappingFlags::COW) {
+                Self::BIT_9
+            } else {
+                Self::empty()
+            };
         }
         let mut ret = Self::PRESENT;
         if f.contains(MappingFlags::WRITE) {
@@ -47,6 +59,10 @@ impl From<MappingFlags> for PTF {
         if f.contains(MappingFlags::DEVICE) {
             ret |= Self::NO_CACHE | Self::WRITE_THROUGH;
         }
+        if f.contains(MappingFlags::COW) {
+            // available for software
+            ret |= Self::BIT_9;
+        }
         ret
     }
 }
@@ -4,8 +4,8 @@
 //! Currently supported architectures and page table entry types:
 //!
 //! - x86: [`x86_64::X64PTE`]
-//! - ARM: [`aarch64::A64PTE`]
-//! - RISC-V: [`riscv::Rv64PTE`]
+//! - ARM: [`aarch64::A64PTE`], [`arm::A32PTE`]
+//! - RISC-V: [`riscv::Rv64PTE`], [`riscv::Rv32PTE`]
 //!
 //! All these types implement the [`GenericPTE`] trait, which provides unified
 //! methods for manipulating various page table entries.
@@ -37,6 +37,14 @@ bitflags::bitflags! {
         const USER          = 1 << 3;
         /// The memory is device memory.
         const DEVICE        = 1 << 4;
+        /// The page is shared copy-on-write, so it is mapped without `WRITE`
+        /// and a private copy is made on write. It is kept in a bit of the
+        /// entry reserved for software.
+        const COW           = 1 << 5;
+        /// The memory is normal memory but not cached, e.g. shared with the
+        /// devices which are not coherent with the caches. It is ignored on
+        /// the architectures whose devices are always coherent.
+        const UNCACHED      = 1 << 6;
     }
 }
 
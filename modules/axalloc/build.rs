@@ -0,0 +1,12 @@
+fn main() {
+    // The callers of the allocations are found by walking the frame pointers,
+    // so only do it if the kernel is built with them.
+    let flags = std::env::var("CARGO_ENCODED_RUSTFLAGS").unwrap_or_default();
+    if flags
+        .split('\x1f')
+        .any(|flag| flag.trim_start_matches("-C") == "force-frame-pointers=yes")
+    {
+        println!("cargo:rustc-cfg=frame_pointers");
+    }
+    println!("cargo:rerun-if-env-changed=CARGO_ENCODED_RUSTFLAGS");
+}
@@ -0,0 +1,450 @@
+//! Heap debugging, enabled by the `alloc-debug` feature.
+//!
+//! Each allocation is surrounded by red zones, which are checked when it is
+//! freed to catch buffer overflows. The new allocations are filled with
+//! [`ALLOC_POISON`], and the freed ones with [`FREE_POISON`]. The freed
+//! allocations are kept in a quarantine for a while before being returned to
+//! the heap, and the poison is checked then to catch the writes after free.
+//!
+//! An allocation looks like:
+//!
+//! ```text
+//! | padding | Header | red zone | payload (size) | red zone |
+//! ^ block                       ^ returned pointer
+//! ```
+
+use core::alloc::Layout;
+use core::mem::{align_of, size_of};
+use core::ptr::null_mut;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use allocator::AllocResult;
+
+use crate::GlobalAllocator;
+
+/// The size of each red zone.
+const REDZONE_SIZE: usize = 16;
+const REDZONE_BYTE: u8 = 0xfd;
+/// The byte filled in the new allocations.
+pub const ALLOC_POISON: u8 = 0xcd;
+/// The byte filled in the freed allocations.
+pub const FREE_POISON: u8 = 0xdd;
+
+const MAGIC_LIVE: usize = 0xa110_c8ed;
+const MAGIC_FREED: usize = 0xdead_f4ee;
+
+/// The number of the freed allocations kept in the quarantine.
+const QUARANTINE_LEN: usize = 64;
+
+#[repr(C)]
+struct Header {
+    magic: usize,
+    size: usize,
+    align: usize,
+    /// The return address of the allocation call.
+    caller: usize,
+    prev: *mut Header,
+    next: *mut Header,
+}
+
+impl Header {
+    /// Returns the offset of the payload, and the alignment of the block.
+    const fn layout(align: usize) -> (usize, usize) {
+        let block_align = if align > align_of::<Self>() {
+            align
+        } else {
+            align_of::<Self>()
+        };
+        let offset = (size_of::<Self>() + REDZONE_SIZE + block_align - 1) & !(block_align - 1);
+        (offset, block_align)
+    }
+
+    fn from_payload(pos: usize) -> *mut Self {
+        (pos - REDZONE_SIZE - size_of::<Self>()) as *mut Self
+    }
+
+    fn payload(this: *const Self) -> usize {
+        this as usize + size_of::<Self>() + REDZONE_SIZE
+    }
+
+    unsafe fn block(this: *const Self) -> (usize, usize, usize) {
+        let (offset, block_align) = Self::layout((*this).align);
+        let total = offset + (*this).size + REDZONE_SIZE;
+        (Self::payload(this) - offset, total, block_align)
+    }
+
+    unsafe fn redzones(this: *const Self) -> [&'static mut [u8]; 2] {
+        let payload = Self::payload(this);
+        let front = payload - REDZONE_SIZE;
+        let back = payload + (*this).size;
+        [
+            core::slice::from_raw_parts_mut(front as *mut u8, REDZONE_SIZE),
+            core::slice::from_raw_parts_mut(back as *mut u8, REDZONE_SIZE),
+        ]
+    }
+
+    unsafe fn payload_bytes(this: *const Self) -> &'static mut [u8] {
+        core::slice::from_raw_parts_mut(Self::payload(this) as *mut u8, (*this).size)
+    }
+}
+
+/// The live allocations and the quarantine.
+pub(crate) struct DebugState {
+    live_head: *mut Header,
+    live_count: usize,
+    quarantine: [*mut Header; QUARANTINE_LEN],
+    quarantine_start: usize,
+    quarantine_len: usize,
+}
+
+unsafe impl Send for DebugState {}
+
+impl DebugState {
+    pub const fn new() -> Self {
+        Self {
+            live_head: null_mut(),
+            live_count: 0,
+            quarantine: [null_mut(); QUARANTINE_LEN],
+            quarantine_start: 0,
+            quarantine_len: 0,
+        }
+    }
+
+    unsafe fn link(&mut self, hdr: *mut Header) {
+        (*hdr).prev = null_mut();
+        (*hdr).next = self.live_head;
+        if !self.live_head.is_null() {
+            (*self.live_head).prev = hdr;
+        }
+        self.live_head = hdr;
+        self.live_count += 1;
+    }
+
+    unsafe fn unlink(&mut self, hdr: *mut Header) {
+        let (prev, next) = ((*hdr).prev, (*hdr).next);
+        if !next.is_null() {
+            (*next).prev = prev;
+        }
+        if !prev.is_null() {
+            (*prev).next = next;
+        } else {
+            self.live_head = next;
+        }
+        self.live_count -= 1;
+    }
+
+    /// Puts a freed allocation in the quarantine, and returns the oldest one
+    /// if the quarantine is full.
+    fn push_quarantine(&mut self, hdr: *mut Header) -> Option<*mut Header> {
+        let end = (self.quarantine_start + self.quarantine_len) % QUARANTINE_LEN;
+        if self.quarantine_len < QUARANTINE_LEN {
+            self.quarantine[end] = hdr;
+            self.quarantine_len += 1;
+            None
+        } else {
+            let oldest = self.quarantine[self.quarantine_start];
+            self.quarantine[self.quarantine_start] = hdr;
+            self.quarantine_start = (self.quarantine_start + 1) % QUARANTINE_LEN;
+            Some(oldest)
+        }
+    }
+}
+
+/// The maximum number of the frames walked to find the caller of the global
+/// allocator, through the allocator shims (e.g. `__rust_alloc`).
+const MAX_SHIM_FRAMES: usize = 4;
+
+/// The number of the shim frames between [`GlobalAlloc`] methods and their
+/// callers, found by [`calibrate_shim_frames`].
+///
+/// [`GlobalAlloc`]: core::alloc::GlobalAlloc
+static SHIM_FRAMES: AtomicUsize = AtomicUsize::new(0);
+static CALIBRATING: AtomicBool = AtomicBool::new(false);
+/// The return addresses walked by the probe allocation.
+static PROBE_TRACE: [AtomicUsize; MAX_SHIM_FRAMES + 1] = {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const ZERO: AtomicUsize = AtomicUsize::new(0);
+    [ZERO; MAX_SHIM_FRAMES + 1]
+};
+
+// The frames are walked by the frame pointers, so they are only walked if the
+// kernel is built with `-Cforce-frame-pointers=yes` (see `build.rs`), and the
+// callers are 0 otherwise. `frame_pointer` must be called in a function that
+// is never inlined, and returns the frame of that function.
+cfg_if::cfg_if! {
+    if #[cfg(all(frame_pointers, any(target_arch = "riscv32", target_arch = "riscv64")))] {
+        #[inline(always)]
+        fn frame_pointer() -> usize {
+            let fp;
+            unsafe { core::arch::asm!("mv {}, s0", out(reg) fp) };
+            fp
+        }
+
+        #[inline(always)]
+        fn current_pc() -> usize {
+            let pc;
+            unsafe { core::arch::asm!("auipc {}, 0", out(reg) pc) };
+            pc
+        }
+
+        /// Returns the return address and the caller's frame pointer of a
+        /// frame: `s0` points to the top of the frame, below which `ra` and
+        /// the old `s0` are saved.
+        unsafe fn unwind(fp: usize) -> (usize, usize) {
+            let fp = fp as *const usize;
+            (*fp.sub(1), *fp.sub(2))
+        }
+    } else if #[cfg(all(frame_pointers, target_arch = "aarch64"))] {
+        #[inline(always)]
+        fn frame_pointer() -> usize {
+            let fp;
+            unsafe { core::arch::asm!("mov {}, x29", out(reg) fp) };
+            fp
+        }
+
+        #[inline(always)]
+        fn current_pc() -> usize {
+            let pc;
+            unsafe { core::arch::asm!("adr {}, .", out(reg) pc) };
+            pc
+        }
+
+        /// Returns the return address and the caller's frame pointer of a
+        /// frame: `x29` points to the saved pair of the old `x29` and `x30`.
+        unsafe fn unwind(fp: usize) -> (usize, usize) {
+            let fp = fp as *const usize;
+            (*fp.add(1), *fp)
+        }
+    } else {
+        #[inline(always)]
+        fn frame_pointer() -> usize {
+            0
+        }
+
+        #[inline(always)]
+        fn current_pc() -> usize {
+            0
+        }
+
+        unsafe fn unwind(_fp: usize) -> (usize, usize) {
+            (0, 0)
+        }
+    }
+}
+
+/// Returns the return address of the frame `fp`, after skipping `skip`
+/// frames above it.
+fn return_addr(mut fp: usize, skip: usize) -> usize {
+    let mut ra = 0;
+    for _ in 0..=skip {
+        if fp == 0 || fp & (size_of::<usize>() - 1) != 0 {
+            return 0;
+        }
+        (ra, fp) = unsafe { unwind(fp) };
+    }
+    ra
+}
+
+/// Returns the return address of the current function, it must be called in
+/// a function that is never inlined.
+#[inline(always)]
+pub(crate) fn caller_addr() -> usize {
+    return_addr(frame_pointer(), 0)
+}
+
+/// Returns the address which calls the allocator shims, it must be called in
+/// the [`GlobalAlloc`] methods, which are never inlined.
+///
+/// [`GlobalAlloc`]: core::alloc::GlobalAlloc
+#[inline(always)]
+pub(crate) fn global_caller_addr() -> usize {
+    let fp = frame_pointer();
+    if CALIBRATING.load(Ordering::Relaxed) {
+        for (i, ra) in PROBE_TRACE.iter().enumerate() {
+            ra.store(return_addr(fp, i), Ordering::Relaxed);
+        }
+    }
+    return_addr(fp, SHIM_FRAMES.load(Ordering::Relaxed))
+}
+
+/// Allocates from the global allocator, and returns the range of the code
+/// which makes the call.
+#[inline(never)]
+fn probe_alloc() -> (usize, usize) {
+    let layout = Layout::new::<usize>();
+    unsafe {
+        // `black_box` keeps the allocation from being optimized out
+        let ptr = core::hint::black_box(alloc::alloc::alloc(layout));
+        let end = current_pc();
+        alloc::alloc::dealloc(ptr, layout);
+        (probe_alloc as fn() -> (usize, usize) as usize, end)
+    }
+}
+
+/// Finds the number of the allocator shim frames to skip, so that the
+/// callers of the global allocator are recorded instead of the shims.
+pub(crate) fn calibrate_shim_frames() {
+    if !cfg!(frame_pointers) {
+        return;
+    }
+    CALIBRATING.store(true, Ordering::Relaxed);
+    let (start, end) = probe_alloc();
+    CALIBRATING.store(false, Ordering::Relaxed);
+    let found = PROBE_TRACE
+        .iter()
+        .position(|ra| (start..=end).contains(&ra.load(Ordering::Relaxed)));
+    if let Some(skip) = found {
+        SHIM_FRAMES.store(skip, Ordering::Relaxed);
+    } else {
+        warn!("alloc-debug: the callers of the global allocator cannot be found");
+    }
+}
+
+impl GlobalAllocator {
+    pub(crate) fn debug_alloc(
+        &self,
+        size: usize,
+        align_pow2: usize,
+        caller: usize,
+    ) -> AllocResult<usize> {
+        let (offset, block_align) = Header::layout(align_pow2);
+        let block = self.heap_alloc(offset + size + REDZONE_SIZE, block_align, size)?;
+        let hdr = Header::from_payload(block + offset);
+        unsafe {
+            hdr.write(Header {
+                magic: MAGIC_LIVE,
+                size,
+                align: align_pow2,
+                caller,
+                prev: null_mut(),
+                next: null_mut(),
+            });
+            for zone in Header::redzones(hdr) {
+                zone.fill(REDZONE_BYTE);
+            }
+            Header::payload_bytes(hdr).fill(ALLOC_POISON);
+            self.debug.lock().link(hdr);
+        }
+        Ok(block + offset)
+    }
+
+    pub(crate) fn debug_dealloc(&self, pos: usize, size: usize, align_pow2: usize) {
+        let hdr = Header::from_payload(pos);
+        // the debug state is not locked when panicking, which may allocate
+        unsafe {
+            match (*hdr).magic {
+                MAGIC_LIVE => {}
+                MAGIC_FREED => panic!("double free of {:#x} ({} bytes)", pos, size),
+                _ => panic!(
+                    "invalid free of {:#x}: not allocated, or the heap is corrupted",
+                    pos
+                ),
+            }
+            let caller = (*hdr).caller;
+            if (*hdr).size != size || (*hdr).align != align_pow2 {
+                panic!(
+                    "free of {:#x} with a wrong layout: {} bytes aligned to {}, \
+                     but allocated {} bytes aligned to {} at {:#x}",
+                    pos,
+                    size,
+                    align_pow2,
+                    (*hdr).size,
+                    (*hdr).align,
+                    caller
+                );
+            }
+            for (i, zone) in Header::redzones(hdr).iter().enumerate() {
+                if let Some(off) = zone.iter().position(|&b| b != REDZONE_BYTE) {
+                    panic!(
+                        "heap buffer {} of {:#x} ({} bytes, allocated at {:#x}): \
+                         red zone corrupted at {:#x}",
+                        if i == 0 { "underflow" } else { "overflow" },
+                        pos,
+                        size,
+                        caller,
+                        zone.as_ptr() as usize + off
+                    );
+                }
+            }
+
+            Header::payload_bytes(hdr).fill(FREE_POISON);
+            let mut debug = self.debug.lock();
+            // check again with the lock, in case of a concurrent double free
+            if (*hdr).magic != MAGIC_LIVE {
+                drop(debug);
+                panic!("double free of {:#x} ({} bytes)", pos, size);
+            }
+            debug.unlink(hdr);
+            (*hdr).magic = MAGIC_FREED;
+            let oldest = debug.push_quarantine(hdr);
+            drop(debug);
+            self.stats.lock().record_dealloc(size);
+
+            if let Some(oldest) = oldest {
+                if let Some(off) = Header::payload_bytes(oldest)
+                    .iter()
+                    .position(|&b| b != FREE_POISON)
+                {
+                    panic!(
+                        "use after free of {:#x} ({} bytes, allocated at {:#x}): \
+                         written at {:#x}",
+                        Header::payload(oldest),
+                        (*oldest).size,
+                        (*oldest).caller,
+                        Header::payload(oldest) + off
+                    );
+                }
+                // clear the magic, so that a later double free can be caught
+                // as an invalid free
+                (*oldest).magic = 0;
+                let (block, total, block_align) = Header::block(oldest);
+                self.heap_dealloc(block, total, block_align);
+            }
+        }
+    }
+
+    /// Returns the return address of the allocation call of the live
+    /// allocation at `pos`, which is 0 if the kernel is built without the
+    /// frame pointers.
+    pub fn allocation_caller(&self, pos: usize) -> Option<usize> {
+        let debug = self.debug.lock();
+        let mut hdr = debug.live_head;
+        while !hdr.is_null() {
+            unsafe {
+                if Header::payload(hdr) == pos {
+                    return Some((*hdr).caller);
+                }
+                hdr = (*hdr).next;
+            }
+        }
+        None
+    }
+
+    /// Prints the live allocations with their sizes and the return addresses
+    /// of the allocation calls, which can be resolved by `addr2line`.
+    ///
+    /// It includes the allocations of the kernel. Returns the number of the
+    /// live allocations.
+    pub fn dump_live_allocations(&self) -> usize {
+        let debug = self.debug.lock();
+        let mut bytes = 0;
+        let mut hdr = debug.live_head;
+        while !hdr.is_null() {
+            unsafe {
+                warn!(
+                    "  {:#x}: {} bytes, allocated at {:#x}",
+                    Header::payload(hdr),
+                    (*hdr).size,
+                    (*hdr).caller
+                );
+                bytes += (*hdr).size;
+                hdr = (*hdr).next;
+            }
+        }
+        warn!(
+            "{} live allocations, {} bytes in total",
+            debug.live_count, bytes
+        );
+        debug.live_count
+    }
+}
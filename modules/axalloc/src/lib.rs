@@ -6,27 +6,75 @@ extern crate log;
 extern crate alloc;
 
 mod page;
+mod stats;
 
-use allocator::{AllocResult, BaseAllocator, ByteAllocator, PageAllocator};
-use allocator::{BitmapPageAllocator, SlabByteAllocator};
+#[cfg(feature = "alloc-debug")]
+mod debug;
+
+use alloc::collections::BTreeMap;
+use allocator::{AllocError, AllocResult, BaseAllocator, RegionPageAllocator};
+use allocator::{ByteAllocator, PageAllocator};
 use core::alloc::{GlobalAlloc, Layout};
 use spinlock::SpinNoIrq;
 
+use self::stats::StatsCounter;
+
 const PAGE_SIZE: usize = 0x1000;
 const MIN_HEAP_SIZE: usize = 0x8000; // 32 K
+/// The maximum number of the heap expansions that can be reclaimed.
+const MAX_HEAP_CHUNKS: usize = 32;
 
 pub use page::GlobalPage;
+pub use stats::{AllocStats, SizeClassStats, NUM_SIZE_CLASSES};
+
+#[cfg(feature = "alloc-debug")]
+pub use debug::{ALLOC_POISON, FREE_POISON};
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "tlsf")] {
+        type DefaultByteAllocator = allocator::TlsfByteAllocator;
+        const BYTE_ALLOCATOR_NAME: &str = "TLSF";
+        /// Whether the free heap chunks are returned to the page allocator,
+        /// only TLSF supports removing memory from the heap.
+        const HEAP_RECLAIMABLE: bool = true;
+    } else if #[cfg(feature = "buddy")] {
+        type DefaultByteAllocator = allocator::BuddyByteAllocator;
+        const BYTE_ALLOCATOR_NAME: &str = "buddy";
+        const HEAP_RECLAIMABLE: bool = false;
+    } else if #[cfg(feature = "slab")] {
+        type DefaultByteAllocator = allocator::SlabByteAllocator;
+        const BYTE_ALLOCATOR_NAME: &str = "slab";
+        const HEAP_RECLAIMABLE: bool = false;
+    } else {
+        compile_error!("no byte allocator is selected, enable one of the features: slab, buddy, tlsf");
+    }
+}
 
 pub struct GlobalAllocator {
-    balloc: SpinNoIrq<SlabByteAllocator>,
-    palloc: SpinNoIrq<BitmapPageAllocator<PAGE_SIZE>>,
+    balloc: SpinNoIrq<DefaultByteAllocator>,
+    palloc: SpinNoIrq<RegionPageAllocator<PAGE_SIZE>>,
+    /// The memory allocated from the page allocator to expand the heap,
+    /// which is returned to it when the whole chunk is free. Only used with
+    /// the TLSF backend.
+    heap_chunks: SpinNoIrq<[(usize, usize); MAX_HEAP_CHUNKS]>,
+    stats: SpinNoIrq<StatsCounter>,
+    #[cfg(feature = "alloc-debug")]
+    debug: SpinNoIrq<debug::DebugState>,
+    /// The extra references of the shared pages, i.e. the reference count
+    /// minus one. The pages not in it have only one reference.
+    page_refs: SpinNoIrq<BTreeMap<usize, usize>>,
 }
 
 impl GlobalAllocator {
     pub const fn new() -> Self {
         Self {
-            balloc: SpinNoIrq::new(SlabByteAllocator::new()),
-            palloc: SpinNoIrq::new(BitmapPageAllocator::new()),
+            balloc: SpinNoIrq::new(DefaultByteAllocator::new()),
+            palloc: SpinNoIrq::new(RegionPageAllocator::new()),
+            heap_chunks: SpinNoIrq::new([(0, 0); MAX_HEAP_CHUNKS]),
+            stats: SpinNoIrq::new(StatsCounter::new()),
+            #[cfg(feature = "alloc-debug")]
+            debug: SpinNoIrq::new(debug::DebugState::new()),
+            page_refs: SpinNoIrq::new(BTreeMap::new()),
         }
     }
 
@@ -40,42 +88,210 @@ impl GlobalAllocator {
         self.balloc.lock().init(heap_ptr, init_heap_size);
     }
 
+    /// Adds a free memory region, which is managed by the page allocator and
+    /// also used by the heap on demand.
+    ///
+    /// If the page allocator cannot manage it (e.g. too many regions, or too
+    /// small), it is added to the heap directly.
     pub fn add_memory(&self, start_vaddr: usize, size: usize) -> AllocResult {
-        self.balloc.lock().add_memory(start_vaddr, size)
+        match self.palloc.lock().add_memory(start_vaddr, size) {
+            Err(AllocError::NoMemory | AllocError::InvalidParam) => {
+                self.balloc.lock().add_memory(start_vaddr, size)
+            }
+            res => res,
+        }
     }
 
-    pub fn alloc(&self, size: usize, align_pow2: usize) -> AllocResult<usize> {
+    /// Allocates `size` bytes from the heap, and records an allocation of
+    /// `requested` bytes in the statistics.
+    fn heap_alloc(&self, size: usize, align_pow2: usize, requested: usize) -> AllocResult<usize> {
         // simple two-level allocator: if no heap memory, allocate from the page allocator.
         let mut balloc = self.balloc.lock();
-        loop {
+        let mut last_chunk = None;
+        let res = loop {
             if let Ok(ptr) = balloc.alloc(size, align_pow2) {
-                return Ok(ptr);
+                break Ok(ptr);
             } else {
                 let old_size = balloc.total_bytes();
-                let expand_size = old_size.max(size).next_power_of_two().max(PAGE_SIZE);
-                let heap_ptr = self.alloc_pages(expand_size / PAGE_SIZE, PAGE_SIZE)?;
+                let mut expand_size = old_size.max(size).next_power_of_two().max(PAGE_SIZE);
+                // the last chunk is too small for the request, replace it
+                if let Some((start, chunk_size)) = last_chunk.take() {
+                    if self.reclaim_heap_chunk(&mut balloc, start, chunk_size) {
+                        expand_size = chunk_size * 2;
+                    }
+                }
+                let heap_ptr = match self.alloc_pages(expand_size / PAGE_SIZE, PAGE_SIZE) {
+                    Ok(ptr) => ptr,
+                    Err(e) => break Err(e),
+                };
                 debug!(
                     "expand heap memory: [{:#x}, {:#x})",
                     heap_ptr,
                     heap_ptr + expand_size
                 );
-                balloc.add_memory(heap_ptr, expand_size)?;
+                if let Err(e) = balloc.add_memory(heap_ptr, expand_size) {
+                    break Err(e);
+                }
+                if HEAP_RECLAIMABLE {
+                    self.add_heap_chunk(heap_ptr, expand_size);
+                    last_chunk = Some((heap_ptr, expand_size));
+                }
             }
+        };
+        let mut stats = self.stats.lock();
+        match res {
+            Ok(_) => stats.record_alloc(requested, balloc.used_bytes()),
+            Err(_) => stats.record_failure(),
+        }
+        res
+    }
+
+    fn heap_dealloc(&self, pos: usize, size: usize, align_pow2: usize) {
+        let mut balloc = self.balloc.lock();
+        balloc.dealloc(pos, size, align_pow2);
+        if !HEAP_RECLAIMABLE {
+            return;
+        }
+
+        // return the chunk to the page allocator if it is free, but keep it if
+        // the rest of the heap is almost full, so that the small allocations
+        // do not shrink and grow it back and forth
+        let chunk = self
+            .heap_chunks
+            .lock()
+            .iter()
+            .find(|c| (c.0..c.0 + c.1).contains(&pos))
+            .copied();
+        if let Some((start, chunk_size)) = chunk {
+            if balloc.available_bytes() >= chunk_size + MIN_HEAP_SIZE {
+                self.reclaim_heap_chunk(&mut balloc, start, chunk_size);
+            }
+        }
+    }
+
+    /// Records a heap chunk, so that it can be reclaimed when it is free.
+    fn add_heap_chunk(&self, start: usize, size: usize) {
+        let mut chunks = self.heap_chunks.lock();
+        if let Some(chunk) = chunks.iter_mut().find(|c| c.1 == 0) {
+            *chunk = (start, size);
+        } else {
+            warn!(
+                "too many heap chunks, [{:#x}, {:#x}) will not be reclaimed",
+                start,
+                start + size
+            );
+        }
+    }
+
+    /// Returns a heap chunk to the page allocator if nothing in it is
+    /// allocated. Only the TLSF backend supports it.
+    fn reclaim_heap_chunk(
+        &self,
+        balloc: &mut DefaultByteAllocator,
+        start: usize,
+        chunk_size: usize,
+    ) -> bool {
+        if balloc.remove_memory(start, chunk_size).is_err() {
+            return false;
+        }
+        debug!(
+            "reclaim heap memory: [{:#x}, {:#x})",
+            start,
+            start + chunk_size
+        );
+        let mut chunks = self.heap_chunks.lock();
+        if let Some(chunk) = chunks.iter_mut().find(|c| c.0 == start) {
+            *chunk = (0, 0);
+        }
+        self.dealloc_pages(start, chunk_size / PAGE_SIZE);
+        true
+    }
+
+    /// Allocates `size` bytes with the given alignment from the heap.
+    ///
+    /// With the `alloc-debug` feature, the allocation is surrounded by red
+    /// zones, and the caller is recorded.
+    #[cfg_attr(feature = "alloc-debug", inline(never))]
+    pub fn alloc(&self, size: usize, align_pow2: usize) -> AllocResult<usize> {
+        #[cfg(feature = "alloc-debug")]
+        {
+            let caller = debug::caller_addr();
+            self.debug_alloc(size, align_pow2, caller)
         }
+        #[cfg(not(feature = "alloc-debug"))]
+        self.heap_alloc(size, align_pow2, size)
     }
 
+    /// Deallocates the memory allocated by [`GlobalAllocator::alloc`].
+    ///
+    /// With the `alloc-debug` feature, it panics on a double free or a
+    /// corrupted red zone.
     pub fn dealloc(&self, pos: usize, size: usize, align_pow2: usize) {
-        self.balloc.lock().dealloc(pos, size, align_pow2)
+        #[cfg(feature = "alloc-debug")]
+        self.debug_dealloc(pos, size, align_pow2);
+        #[cfg(not(feature = "alloc-debug"))]
+        {
+            self.heap_dealloc(pos, size, align_pow2);
+            self.stats.lock().record_dealloc(size);
+        }
     }
 
     pub fn alloc_pages(&self, num_pages: usize, align_pow2: usize) -> AllocResult<usize> {
         self.palloc.lock().alloc_pages(num_pages, align_pow2)
     }
 
+    /// Allocates contiguous pages that end before the virtual address
+    /// `limit_vaddr`, e.g. for the devices which can only access the low
+    /// memory.
+    pub fn alloc_pages_below(
+        &self,
+        num_pages: usize,
+        align_pow2: usize,
+        limit_vaddr: usize,
+    ) -> AllocResult<usize> {
+        self.palloc
+            .lock()
+            .alloc_pages_below(num_pages, align_pow2, limit_vaddr)
+    }
+
     pub fn dealloc_pages(&self, pos: usize, num_pages: usize) {
         self.palloc.lock().dealloc_pages(pos, num_pages)
     }
 
+    /// Increases the reference count of the allocated page at `pos`.
+    ///
+    /// A page has one reference when it is allocated. After this, it is
+    /// shared and is not freed until all the references are dropped by
+    /// [`GlobalAllocator::page_ref_dec`].
+    pub fn page_ref_inc(&self, pos: usize) {
+        *self.page_refs.lock().entry(pos).or_insert(0) += 1;
+    }
+
+    /// Decreases the reference count of the allocated page at `pos`, and
+    /// frees it if there is no reference any more.
+    ///
+    /// Returns whether the page is freed.
+    pub fn page_ref_dec(&self, pos: usize) -> bool {
+        let mut page_refs = self.page_refs.lock();
+        match page_refs.get_mut(&pos) {
+            Some(extra) if *extra > 1 => *extra -= 1,
+            Some(_) => {
+                page_refs.remove(&pos);
+            }
+            None => {
+                drop(page_refs);
+                self.dealloc_pages(pos, 1);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Returns the reference count of the allocated page at `pos`.
+    pub fn page_ref_count(&self, pos: usize) -> usize {
+        self.page_refs.lock().get(&pos).map_or(1, |extra| extra + 1)
+    }
+
     pub fn used_bytes(&self) -> usize {
         self.balloc.lock().used_bytes()
     }
@@ -91,17 +307,61 @@ impl GlobalAllocator {
     pub fn available_pages(&self) -> usize {
         self.palloc.lock().available_pages()
     }
+
+    /// Returns a snapshot of the allocation statistics.
+    pub fn stats(&self) -> AllocStats {
+        let balloc = self.balloc.lock();
+        let palloc = self.palloc.lock();
+        self.stats
+            .lock()
+            .snapshot(BYTE_ALLOCATOR_NAME, &*balloc, &*palloc)
+    }
 }
 
 unsafe impl GlobalAlloc for GlobalAllocator {
+    #[cfg_attr(feature = "alloc-debug", inline(never))]
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        if let Ok(ptr) = GlobalAllocator::alloc(self, layout.size(), layout.align()) {
+        #[cfg(feature = "alloc-debug")]
+        let res = {
+            let caller = debug::global_caller_addr();
+            self.debug_alloc(layout.size(), layout.align(), caller)
+        };
+        #[cfg(not(feature = "alloc-debug"))]
+        let res = self.heap_alloc(layout.size(), layout.align(), layout.size());
+        if let Ok(ptr) = res {
+            ptr as _
+        } else {
+            alloc::alloc::handle_alloc_error(layout)
+        }
+    }
+
+    // Overridden to record the callers, instead of the default methods which
+    // call `alloc`.
+    #[cfg(feature = "alloc-debug")]
+    #[inline(never)]
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let caller = debug::global_caller_addr();
+        if let Ok(ptr) = self.debug_alloc(layout.size(), layout.align(), caller) {
+            core::ptr::write_bytes(ptr as *mut u8, 0, layout.size());
             ptr as _
         } else {
             alloc::alloc::handle_alloc_error(layout)
         }
     }
 
+    #[cfg(feature = "alloc-debug")]
+    #[inline(never)]
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let caller = debug::global_caller_addr();
+        if let Ok(new_ptr) = self.debug_alloc(new_size, layout.align(), caller) {
+            core::ptr::copy_nonoverlapping(ptr, new_ptr as *mut u8, layout.size().min(new_size));
+            GlobalAllocator::dealloc(self, ptr as _, layout.size(), layout.align());
+            new_ptr as _
+        } else {
+            core::ptr::null_mut()
+        }
+    }
+
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
         GlobalAllocator::dealloc(self, ptr as _, layout.size(), layout.align())
     }
@@ -124,6 +384,11 @@ pub fn global_allocator() -> &'static GlobalAllocator {
     &GLOBAL_ALLOCATOR
 }
 
+/// Returns a snapshot of the statistics of the global allocator.
+pub fn global_stats() -> AllocStats {
+    GLOBAL_ALLOCATOR.stats()
+}
+
 pub fn global_init(start_vaddr: usize, size: usize) {
     debug!(
         "initialize global allocator at: [{:#x}, {:#x})",
@@ -131,6 +396,8 @@ pub fn global_init(start_vaddr: usize, size: usize) {
         start_vaddr + size
     );
     GLOBAL_ALLOCATOR.init(start_vaddr, size);
+    #[cfg(feature = "alloc-debug")]
+    debug::calibrate_shim_frames();
 }
 
 pub fn global_add_memory(start_vaddr: usize, size: usize) -> AllocResult {
@@ -41,6 +41,38 @@ impl GlobalPage {
             .map_err(alloc_err_to_ax_err)
     }
 
+    /// Allocate contiguous 4K-sized pages that end before the virtual address
+    /// `limit_vaddr`, e.g. for the devices which can only access the low
+    /// memory.
+    pub fn alloc_contiguous_below(
+        num_pages: usize,
+        align_pow2: usize,
+        limit_vaddr: usize,
+    ) -> AxResult<Self> {
+        global_allocator()
+            .alloc_pages_below(num_pages, align_pow2, limit_vaddr)
+            .map(|vaddr| Self {
+                start_vaddr: vaddr.into(),
+                num_pages,
+            })
+            .map_err(alloc_err_to_ax_err)
+    }
+
+    /// Allocate contiguous pages of at least `size` bytes to be mapped with
+    /// huge pages, whose start address is aligned to the huge page size
+    /// `align_pow2`, e.g. 2M or 1G.
+    ///
+    /// The size is rounded up to a multiple of `align_pow2`. The physical
+    /// address is aligned as well if the physical memory is mapped linearly
+    /// with an offset aligned to it.
+    pub fn alloc_huge(size: usize, align_pow2: usize) -> AxResult<Self> {
+        if size == 0 || !align_pow2.is_power_of_two() || align_pow2 < PAGE_SIZE {
+            return Err(AxError::InvalidInput);
+        }
+        let size = memory_addr::align_up(size, align_pow2);
+        Self::alloc_contiguous(size / PAGE_SIZE, align_pow2)
+    }
+
     /// Get the start virtual address of this page.
     pub fn start_vaddr(&self) -> VirtAddr {
         self.start_vaddr
@@ -0,0 +1,136 @@
+//! Statistics of the global allocator.
+
+use allocator::{ByteAllocator, PageAllocator};
+
+/// The number of the size classes: powers of two from 8 bytes to 4K, and one
+/// more for the larger allocations.
+pub const NUM_SIZE_CLASSES: usize = 11;
+
+const MIN_CLASS_SHIFT: u32 = 3;
+
+/// Usage of the heap allocations in a size class.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SizeClassStats {
+    /// The maximum requested size of the class, `usize::MAX` for the last.
+    pub max_size: usize,
+    /// The number of the live allocations.
+    pub live_count: usize,
+    /// The requested bytes of the live allocations.
+    pub live_bytes: usize,
+    /// The number of the allocations since boot.
+    pub total_count: usize,
+}
+
+/// A snapshot of the global allocator statistics, returned by
+/// [`GlobalAllocator::stats`](crate::GlobalAllocator::stats).
+#[derive(Debug, Clone, Copy)]
+pub struct AllocStats {
+    /// The name of the byte allocator backend.
+    pub backend: &'static str,
+    /// The heap allocations grouped by the requested size.
+    pub size_classes: [SizeClassStats; NUM_SIZE_CLASSES],
+    /// The bytes of the heap, which grows on demand from the pages.
+    pub heap_bytes: usize,
+    /// The bytes of the heap used by the byte allocator, including its
+    /// rounding and metadata.
+    pub used_bytes: usize,
+    /// The bytes requested by the live allocations.
+    pub requested_bytes: usize,
+    /// The maximum of `used_bytes` since boot.
+    pub peak_used_bytes: usize,
+    /// The number of the failed heap allocations.
+    pub failed_allocs: usize,
+    /// The number of the allocated pages, including those of the heap.
+    pub used_pages: usize,
+    /// The number of the available pages.
+    pub available_pages: usize,
+}
+
+impl AllocStats {
+    /// The bytes wasted inside the allocated blocks, i.e. `used_bytes` minus
+    /// `requested_bytes`.
+    pub fn internal_fragmentation(&self) -> usize {
+        self.used_bytes.saturating_sub(self.requested_bytes)
+    }
+
+    /// The free bytes of the heap, scattered between the allocated blocks.
+    pub fn external_fragmentation(&self) -> usize {
+        self.heap_bytes.saturating_sub(self.used_bytes)
+    }
+}
+
+/// The counters updated on each allocation.
+pub(crate) struct StatsCounter {
+    size_classes: [SizeClassStats; NUM_SIZE_CLASSES],
+    requested_bytes: usize,
+    peak_used_bytes: usize,
+    failed_allocs: usize,
+}
+
+impl StatsCounter {
+    pub const fn new() -> Self {
+        const EMPTY: SizeClassStats = SizeClassStats {
+            max_size: 0,
+            live_count: 0,
+            live_bytes: 0,
+            total_count: 0,
+        };
+        let mut size_classes = [EMPTY; NUM_SIZE_CLASSES];
+        let mut i = 0;
+        while i < NUM_SIZE_CLASSES - 1 {
+            size_classes[i].max_size = 1 << (i as u32 + MIN_CLASS_SHIFT);
+            i += 1;
+        }
+        size_classes[NUM_SIZE_CLASSES - 1].max_size = usize::MAX;
+        Self {
+            size_classes,
+            requested_bytes: 0,
+            peak_used_bytes: 0,
+            failed_allocs: 0,
+        }
+    }
+
+    fn class_index(size: usize) -> usize {
+        let shift = size.max(1).next_power_of_two().trailing_zeros();
+        (shift.saturating_sub(MIN_CLASS_SHIFT) as usize).min(NUM_SIZE_CLASSES - 1)
+    }
+
+    pub fn record_alloc(&mut self, size: usize, used_bytes: usize) {
+        let class = &mut self.size_classes[Self::class_index(size)];
+        class.live_count += 1;
+        class.live_bytes += size;
+        class.total_count += 1;
+        self.requested_bytes += size;
+        self.peak_used_bytes = self.peak_used_bytes.max(used_bytes);
+    }
+
+    pub fn record_dealloc(&mut self, size: usize) {
+        let class = &mut self.size_classes[Self::class_index(size)];
+        class.live_count -= 1;
+        class.live_bytes -= size;
+        self.requested_bytes -= size;
+    }
+
+    pub fn record_failure(&mut self) {
+        self.failed_allocs += 1;
+    }
+
+    pub fn snapshot(
+        &self,
+        backend: &'static str,
+        balloc: &impl ByteAllocator,
+        palloc: &impl PageAllocator,
+    ) -> AllocStats {
+        AllocStats {
+            backend,
+            size_classes: self.size_classes,
+            heap_bytes: balloc.total_bytes(),
+            used_bytes: balloc.used_bytes(),
+            requested_bytes: self.requested_bytes,
+            peak_used_bytes: self.peak_used_bytes,
+            failed_allocs: self.failed_allocs,
+            used_pages: palloc.used_pages(),
+            available_pages: palloc.available_pages(),
+        }
+    }
+}
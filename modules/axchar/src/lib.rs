@@ -0,0 +1,68 @@
+//! Character devices (e.g., virtio-console ports) of ArceOS.
+//!
+//! Each device found by [`axdriver`] becomes a port, numbered in the order
+//! they were probed. Port 0 serves as an alternate console.
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use axdriver::{AxCharDevice, AxDevice};
+use axerrno::{AxError, AxResult};
+use axsync::Mutex;
+use driver_common::DevError;
+use lazy_init::LazyInit;
+
+static PORTS: LazyInit<Vec<Mutex<AxCharDevice>>> = LazyInit::new();
+
+/// Initializes the character devices found by [`axdriver`].
+pub fn init_char_devices(char_devs: Vec<AxDevice<AxCharDevice>>) {
+    info!("Initialize character devices...");
+
+    info!("number of character devices: {}", char_devs.len());
+    for (i, dev) in char_devs.iter().enumerate() {
+        info!("  port {}: {:?}", i, dev.device_name());
+    }
+    PORTS.init_by(
+        char_devs
+            .into_iter()
+            .map(|dev| Mutex::new(dev.into_inner()))
+            .collect(),
+    );
+}
+
+/// The number of ports, 0 if there is no device or it is not initialized.
+pub fn num_ports() -> usize {
+    PORTS.try_get().map_or(0, Vec::len)
+}
+
+fn port(port: usize) -> AxResult<&'static Mutex<AxCharDevice>> {
+    PORTS
+        .try_get()
+        .and_then(|ports| ports.get(port))
+        .ok_or(AxError::NotFound)
+}
+
+/// Reads the bytes already received on `port` into `buf` without blocking.
+///
+/// Returns the number of bytes read, which is 0 if nothing has been received.
+pub fn read(port_idx: usize, buf: &mut [u8]) -> AxResult<usize> {
+    port(port_idx)?.lock().read_bytes(buf).map_err(as_ax_err)
+}
+
+/// Writes bytes from `buf` to `port`, returns the number of bytes written.
+pub fn write(port_idx: usize, buf: &[u8]) -> AxResult<usize> {
+    port(port_idx)?.lock().write_bytes(buf).map_err(as_ax_err)
+}
+
+const fn as_ax_err(e: DevError) -> AxError {
+    match e {
+        DevError::Again => AxError::Again,
+        DevError::ResourceBusy => AxError::ResourceBusy,
+        DevError::Unsupported => AxError::Unsupported,
+        _ => AxError::Io,
+    }
+}
@@ -68,7 +68,7 @@ fn gen_config_rs(arch: &str, platform: &str) -> Result<()> {
                 writeln!(output, "pub const {var_name}: &str = \"{s}\";")?;
             }
         } else if let Value::Array(regions) = value {
-            if key != "mmio-regions" && key != "virtio-mmio-regions" {
+            if key != "mmio-regions" && key != "virtio-mmio-regions" && key != "pci-ranges" {
                 continue;
             }
             writeln!(output, "pub const {var_name}: &[(usize, usize)] = &[")?;
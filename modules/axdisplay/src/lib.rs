@@ -1,34 +1,99 @@
+//! Graphics display devices of ArceOS.
+//!
+//! Only the first device found by [`axdriver`] is used. Its framebuffer can
+//! be taken once by [`framebuffer`], and the changed part is flushed to the
+//! screen by [`framebuffer_flush_rect`].
+
 #![no_std]
 
 #[macro_use]
 extern crate log;
+extern crate alloc;
 
 #[doc(no_inline)]
-pub use driver_display::DisplayInfo;
+pub use driver_display::{Color, DisplayInfo, Framebuffer, PixelFormat, Rect};
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
 
-use axdriver::DisplayDevices;
+use axdriver::{AxDevice, AxDisplayDevice};
+use axerrno::{AxError, AxResult};
 use axsync::Mutex;
-use driver_display::DisplayDriverOps;
+use driver_common::DevError;
 use lazy_init::LazyInit;
 
-struct DisplayDevicesWrapper(Mutex<DisplayDevices>);
-
-static DISPLAYS: LazyInit<DisplayDevicesWrapper> = LazyInit::new();
+static MAIN_DISPLAY: LazyInit<Mutex<AxDisplayDevice>> = LazyInit::new();
+static FB_TAKEN: AtomicBool = AtomicBool::new(false);
 
-pub fn init_display(display_devs: DisplayDevices) {
+/// Initializes the display subsystem with the devices found by [`axdriver`].
+///
+/// Only the first device is used. If there is none, [`framebuffer_info`]
+/// will panic, and the other functions will fail.
+pub fn init_display(display_devs: Vec<AxDevice<AxDisplayDevice>>) {
     info!("Initialize Display subsystem...");
 
     info!("number of Displays: {}", display_devs.len());
-    DISPLAYS.init_by(DisplayDevicesWrapper(Mutex::new(display_devs)));
+    let Some(dev) = display_devs.into_iter().next() else {
+        warn!("no display device found");
+        return;
+    };
+    info!("  use display device: {:?}", dev.device_name());
+    let info = dev.info();
+    info!(
+        "  resolution: {}x{}, format: {:?}",
+        info.width, info.height, info.format
+    );
+    MAIN_DISPLAY.init_by(Mutex::new(dev.into_inner()));
 }
 
+fn main_display() -> AxResult<&'static Mutex<AxDisplayDevice>> {
+    MAIN_DISPLAY.try_get().ok_or(AxError::NotFound)
+}
+
+/// Returns the information of the display.
 pub fn framebuffer_info() -> DisplayInfo {
-    let device = DISPLAYS.0.lock();
-    device.0.info()
+    MAIN_DISPLAY.lock().info()
+}
+
+/// Takes the framebuffer of the display.
+///
+/// Returns `None` if there is no display, or the framebuffer has already
+/// been taken, as it can not be shared.
+pub fn framebuffer() -> Option<Framebuffer<'static>> {
+    let info = main_display().ok()?.lock().info();
+    if FB_TAKEN.swap(true, Ordering::SeqCst) {
+        return None;
+    }
+    // Safety: the framebuffer of the device lives forever, and is only
+    // handed out once.
+    Some(unsafe { Framebuffer::from_info(&info) })
+}
+
+/// Flushes the whole framebuffer to the screen.
+pub fn framebuffer_flush() -> AxResult {
+    let mut dev = main_display()?.lock();
+    if dev.need_flush() {
+        dev.flush().map_err(as_ax_err)?;
+    }
+    Ok(())
+}
+
+/// Flushes the pixels in `rect` of the framebuffer to the screen, usually
+/// the damaged rectangle taken by [`Framebuffer::take_damage`].
+pub fn framebuffer_flush_rect(rect: Rect) -> AxResult {
+    let mut dev = main_display()?.lock();
+    if dev.need_flush() && !rect.is_empty() {
+        dev.flush_rect(rect).map_err(as_ax_err)?;
+    }
+    Ok(())
 }
 
-pub fn framebuffer_flush() -> isize {
-    let mut device = DISPLAYS.0.lock();
-    device.0.flush().unwrap();
-    0
+const fn as_ax_err(e: DevError) -> AxError {
+    match e {
+        DevError::Again => AxError::Again,
+        DevError::InvalidParam => AxError::InvalidInput,
+        DevError::ResourceBusy => AxError::ResourceBusy,
+        DevError::Unsupported => AxError::Unsupported,
+        _ => AxError::Io,
+    }
 }
@@ -0,0 +1,298 @@
+//! [ArceOS](https://github.com/rcore-os/arceos) DMA memory management for the
+//! device drivers.
+//!
+//! There are two kinds of DMA memory:
+//!
+//! - The coherent memory, allocated by [`alloc_coherent`], is shared by the
+//!   CPU and the device for a long time, e.g. the descriptor rings. It is
+//!   mapped without caching on aarch64, where the devices may not be coherent
+//!   with the caches, so it needs no cache maintenance.
+//! - The streaming mappings, created by [`map_single`], give a buffer to the
+//!   device for one transfer. The data caches are cleaned or invalidated by
+//!   the [`DmaDirection`], so they also work for the devices that are not
+//!   coherent with the caches.
+//!
+//! A device may only access a part of the bus addresses, given by its
+//! [`DmaMask`]. The coherent memory is allocated below the mask, and the
+//! streaming buffers beyond it are copied through the bounce buffers. So are
+//! the buffers not in the linear mapping of the physical memory (e.g. those
+//! mapped by `mmap`), and the buffers sharing the cache lines with others
+//! if the caches are invalidated for the device.
+//!
+//! The bus addresses are the same as the physical addresses on the
+//! supported platforms.
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+use core::ptr::NonNull;
+
+use axalloc::{global_allocator, GlobalPage};
+use axerrno::{AxError, AxResult};
+use axhal::arch::{clean_dcache_range, flush_dcache_range, invalidate_dcache_range};
+use axhal::mem::{phys_to_virt, virt_to_phys, PAGE_SIZE_4K};
+
+/// An address of the memory seen by the devices.
+pub type DmaAddr = usize;
+
+/// The direction of a DMA transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DmaDirection {
+    /// The device reads the buffer.
+    ToDevice,
+    /// The device writes the buffer.
+    FromDevice,
+    /// The device both reads and writes the buffer.
+    Bidirectional,
+}
+
+/// The bus addresses that a device can access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DmaMask(u64);
+
+impl DmaMask {
+    /// The device can access all the addresses.
+    pub const ALL: Self = Self(u64::MAX);
+
+    /// The device can access the addresses of `bits` bits, e.g. `32` for the
+    /// devices with 32-bit DMA.
+    pub const fn bits(bits: u32) -> Self {
+        if bits >= 64 {
+            Self::ALL
+        } else {
+            Self((1 << bits) - 1)
+        }
+    }
+
+    /// Returns the highest address that the device can access.
+    pub const fn max_addr(self) -> u64 {
+        self.0
+    }
+
+    /// Whether the device can access all of `[addr, addr + size)`.
+    pub const fn contains(self, addr: DmaAddr, size: usize) -> bool {
+        size == 0 || (addr as u64).saturating_add(size as u64 - 1) <= self.0
+    }
+
+    /// Returns the end of the virtual addresses in the linear mapping that
+    /// the device can access.
+    fn limit_vaddr(self) -> usize {
+        usize::try_from(self.0)
+            .ok()
+            .and_then(|max| max.checked_add(1))
+            .and_then(|end| end.checked_add(axconfig::PHYS_VIRT_OFFSET))
+            .unwrap_or(usize::MAX)
+    }
+}
+
+fn alloc_pages(num_pages: usize, mask: DmaMask) -> AxResult<usize> {
+    global_allocator()
+        .alloc_pages_below(num_pages, PAGE_SIZE_4K, mask.limit_vaddr())
+        .map_err(|_| AxError::NoMemory)
+}
+
+fn num_pages(size: usize) -> usize {
+    (size + PAGE_SIZE_4K - 1) / PAGE_SIZE_4K
+}
+
+/// Whether the buffer may share the cache lines with the other data, which
+/// are lost if the lines are invalidated for the device.
+#[cfg(target_arch = "aarch64")]
+fn shares_cache_lines(vaddr: usize, size: usize) -> bool {
+    // the maximum cache line size
+    const DMA_MIN_ALIGN: usize = 128;
+    vaddr % DMA_MIN_ALIGN != 0 || size % DMA_MIN_ALIGN != 0
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+fn shares_cache_lines(_vaddr: usize, _size: usize) -> bool {
+    false
+}
+
+/// Returns the bus address of a buffer in the linear mapping, which is
+/// physically contiguous.
+fn linear_dma_addr(vaddr: usize) -> Option<DmaAddr> {
+    let mmap_end = axconfig::MMAP_BASE + axconfig::MMAP_SIZE;
+    if (axconfig::MMAP_BASE..mmap_end).contains(&vaddr) {
+        return None;
+    }
+    vaddr.checked_sub(axconfig::PHYS_VIRT_OFFSET)
+}
+
+/// Allocates `num_pages` zeroed pages of the coherent memory that the device
+/// with the DMA `mask` can access.
+///
+/// Returns the bus address and the virtual address of the memory.
+///
+/// On aarch64, the memory is mapped again without caching in the range of
+/// the memory areas of [`axmm`], so it is coherent even if the device is not
+/// coherent with the caches. The devices are coherent on the other
+/// architectures, where the linear mapping is used.
+pub fn alloc_coherent(num_pages: usize, mask: DmaMask) -> AxResult<(DmaAddr, NonNull<u8>)> {
+    let mut pages = GlobalPage::alloc_contiguous_below(num_pages, PAGE_SIZE_4K, mask.limit_vaddr())
+        .map_err(|_| AxError::NoMemory)?;
+    pages.zero();
+    // no dirty cache lines are written back over the data from the device
+    flush_dcache_range(pages.start_vaddr(), pages.size());
+    let dma_addr = pages.start_paddr(virt_to_phys).as_usize();
+    let vaddr = map_coherent(pages)?;
+    Ok((dma_addr, NonNull::new(vaddr as *mut u8).unwrap()))
+}
+
+#[cfg(target_arch = "aarch64")]
+fn map_coherent(pages: GlobalPage) -> AxResult<usize> {
+    use alloc::sync::Arc;
+    use axmm::{Backend, MappingFlags};
+
+    let size = pages.size();
+    let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::UNCACHED;
+    let backend = Backend::Contiguous {
+        pages: Arc::new(pages),
+        offset: 0,
+    };
+    let vaddr = axmm::mmap(axconfig::MMAP_BASE.into(), size, flags, false, backend)?;
+    Ok(vaddr.as_usize())
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+fn map_coherent(pages: GlobalPage) -> AxResult<usize> {
+    let vaddr = pages.start_vaddr().as_usize();
+    // freed by `dealloc_coherent`
+    core::mem::forget(pages);
+    Ok(vaddr)
+}
+
+/// Deallocates the coherent memory allocated by [`alloc_coherent`], with the
+/// returned bus address and virtual address.
+///
+/// # Safety
+///
+/// The memory must not be accessed by the device or the CPU anymore.
+pub unsafe fn dealloc_coherent(dma_addr: DmaAddr, vaddr: NonNull<u8>, num_pages: usize) {
+    #[cfg(target_arch = "aarch64")]
+    {
+        let size = num_pages * PAGE_SIZE_4K;
+        // the cache lines filled speculatively through the linear mapping
+        // are stale, since the memory is written without caching
+        invalidate_dcache_range(phys_to_virt(dma_addr.into()), size);
+        // the pages are freed with the mapping
+        if let Err(e) = axmm::munmap((vaddr.as_ptr() as usize).into(), size) {
+            warn!(
+                "failed to unmap coherent DMA memory at {:p}: {:?}",
+                vaddr, e
+            );
+        }
+    }
+    #[cfg(not(target_arch = "aarch64"))]
+    {
+        let _ = dma_addr;
+        global_allocator().dealloc_pages(vaddr.as_ptr() as usize, num_pages);
+    }
+}
+
+/// Maps the buffer `buf` for a DMA transfer of the device with the DMA
+/// `mask`, and returns the bus address that the device can access.
+///
+/// The buffer is copied to a bounce buffer if the device cannot access it
+/// directly. The mapping must be unmapped by [`unmap_single`] when the
+/// transfer completes, before the CPU accesses the buffer again.
+///
+/// # Safety
+///
+/// The buffer must be valid until it is unmapped.
+pub unsafe fn map_single(
+    buf: NonNull<[u8]>,
+    dir: DmaDirection,
+    mask: DmaMask,
+) -> AxResult<DmaAddr> {
+    let vaddr = buf.as_ptr() as *mut u8 as usize;
+    let size = buf.len();
+    if size == 0 {
+        return Ok(linear_dma_addr(vaddr).unwrap_or(0));
+    }
+    let unaligned = dir != DmaDirection::ToDevice && shares_cache_lines(vaddr, size);
+    let dma_addr = match linear_dma_addr(vaddr) {
+        Some(dma_addr) if !unaligned && mask.contains(dma_addr, size) => dma_addr,
+        _ => {
+            let bounce = alloc_pages(num_pages(size), mask)?;
+            trace!(
+                "bounce {} bytes at {:#x} for DMA: {:#x}",
+                size,
+                vaddr,
+                bounce
+            );
+            virt_to_phys(bounce.into()).as_usize()
+        }
+    };
+    sync_for_device(dma_addr, buf, dir);
+    Ok(dma_addr)
+}
+
+/// Unmaps the buffer mapped by [`map_single`], with the same arguments and
+/// the returned bus address.
+///
+/// The data written by the device is visible to the CPU after it.
+///
+/// # Safety
+///
+/// The buffer must not be accessed by the device anymore.
+pub unsafe fn unmap_single(dma_addr: DmaAddr, buf: NonNull<[u8]>, dir: DmaDirection) {
+    let size = buf.len();
+    if size == 0 {
+        return;
+    }
+    sync_for_cpu(dma_addr, buf, dir);
+    if is_bounced(dma_addr, buf) {
+        let bounce = phys_to_virt(dma_addr.into()).as_usize();
+        global_allocator().dealloc_pages(bounce, num_pages(size));
+    }
+}
+
+fn is_bounced(dma_addr: DmaAddr, buf: NonNull<[u8]>) -> bool {
+    linear_dma_addr(buf.as_ptr() as *mut u8 as usize) != Some(dma_addr)
+}
+
+/// Gives the buffer back to the device after the CPU accessed it, e.g. to
+/// reuse a mapped buffer for another transfer.
+///
+/// `dma_addr` is the bus address of the buffer, returned by [`map_single`].
+///
+/// # Safety
+///
+/// The buffer must not be accessed by the CPU until [`sync_for_cpu`].
+pub unsafe fn sync_for_device(dma_addr: DmaAddr, buf: NonNull<[u8]>, dir: DmaDirection) {
+    let size = buf.len();
+    let vaddr = phys_to_virt(dma_addr.into());
+    if is_bounced(dma_addr, buf) && dir != DmaDirection::FromDevice {
+        core::ptr::copy_nonoverlapping(buf.as_ptr() as *const u8, vaddr.as_mut_ptr(), size);
+    }
+    match dir {
+        DmaDirection::ToDevice => clean_dcache_range(vaddr, size),
+        DmaDirection::FromDevice => invalidate_dcache_range(vaddr, size),
+        DmaDirection::Bidirectional => flush_dcache_range(vaddr, size),
+    }
+}
+
+/// Gives the buffer to the CPU after the device accessed it, so that the
+/// data written by the device can be read.
+///
+/// `dma_addr` is the bus address of the buffer, returned by [`map_single`].
+///
+/// # Safety
+///
+/// The buffer must not be accessed by the device until [`sync_for_device`].
+pub unsafe fn sync_for_cpu(dma_addr: DmaAddr, buf: NonNull<[u8]>, dir: DmaDirection) {
+    if dir == DmaDirection::ToDevice {
+        return;
+    }
+    let size = buf.len();
+    let vaddr = phys_to_virt(dma_addr.into());
+    // the cache lines may be filled speculatively during the transfer
+    invalidate_dcache_range(vaddr, size);
+    if is_bounced(dma_addr, buf) {
+        core::ptr::copy_nonoverlapping(vaddr.as_ptr(), buf.as_ptr() as *mut u8, size);
+    }
+}
@@ -0,0 +1,213 @@
+//! The registry of device drivers and their probe functions.
+
+use alloc::{vec, vec::Vec};
+
+use axhal::mem::PhysAddr;
+use driver_common::DeviceType;
+#[cfg(feature = "bus-pci")]
+use driver_pci::{BusDeviceFunction, DeviceFunctionInfo, Ecam, PciRoot};
+use spinlock::SpinNoIrq;
+
+use crate::AxDeviceEnum;
+
+/// How a driver finds its devices.
+#[derive(Clone, Copy)]
+pub enum ProbeMethod {
+    /// The device is not on any bus (e.g., a RAM disk). The function is called
+    /// once, and returns the device if it is available.
+    Global(fn() -> Option<AxDeviceEnum>),
+    /// The device is memory-mapped. The function is called for each
+    /// unclaimed virtio-mmio region `(paddr, size)` found on the platform,
+    /// and returns the device if the driver recognizes it. Each region is
+    /// claimed by at most one device.
+    Mmio(fn(PhysAddr, usize) -> Option<AxDeviceEnum>),
+    /// The device is on the PCI bus. The function is called for each
+    /// unclaimed PCI function, whose memory BARs have been assigned, and
+    /// returns the device if the driver recognizes it. Each function is
+    /// claimed by at most one device.
+    #[cfg(feature = "bus-pci")]
+    Pci(fn(&mut PciRoot<Ecam>, BusDeviceFunction, &DeviceFunctionInfo) -> Option<AxDeviceEnum>),
+}
+
+/// A device driver, described by the type of devices it creates and how it
+/// finds them.
+#[derive(Clone, Copy)]
+pub struct DriverProbe {
+    /// The name of the driver.
+    pub name: &'static str,
+    /// The type of the devices created by the driver.
+    pub device_type: DeviceType,
+    /// How the driver finds its devices.
+    pub method: ProbeMethod,
+}
+
+/// The drivers selected by cargo features.
+const BUILTIN_DRIVERS: &[DriverProbe] = &[
+    #[cfg(feature = "ramdisk")]
+    DriverProbe {
+        name: "ramdisk",
+        device_type: DeviceType::Block,
+        method: ProbeMethod::Global(probe_ramdisk),
+    },
+    #[cfg(all(feature = "virtio-blk", feature = "bus-mmio"))]
+    DriverProbe {
+        name: "virtio-blk",
+        device_type: DeviceType::Block,
+        method: ProbeMethod::Mmio(crate::virtio::probe_mmio_blk),
+    },
+    #[cfg(all(feature = "virtio-net", feature = "bus-mmio"))]
+    DriverProbe {
+        name: "virtio-net",
+        device_type: DeviceType::Net,
+        method: ProbeMethod::Mmio(crate::virtio::probe_mmio_net),
+    },
+    #[cfg(all(feature = "virtio-gpu", feature = "bus-mmio"))]
+    DriverProbe {
+        name: "virtio-gpu",
+        device_type: DeviceType::Display,
+        method: ProbeMethod::Mmio(crate::virtio::probe_mmio_gpu),
+    },
+    #[cfg(all(feature = "virtio-console", feature = "bus-mmio"))]
+    DriverProbe {
+        name: "virtio-console",
+        device_type: DeviceType::Char,
+        method: ProbeMethod::Mmio(crate::virtio::probe_mmio_console),
+    },
+    #[cfg(all(feature = "virtio-rng", feature = "bus-mmio"))]
+    DriverProbe {
+        name: "virtio-rng",
+        device_type: DeviceType::Rng,
+        method: ProbeMethod::Mmio(crate::virtio::probe_mmio_rng),
+    },
+    #[cfg(all(feature = "virtio-input", feature = "bus-mmio"))]
+    DriverProbe {
+        name: "virtio-input",
+        device_type: DeviceType::Input,
+        method: ProbeMethod::Mmio(crate::virtio::probe_mmio_input),
+    },
+    #[cfg(all(feature = "virtio-blk", feature = "bus-pci"))]
+    DriverProbe {
+        name: "virtio-blk-pci",
+        device_type: DeviceType::Block,
+        method: ProbeMethod::Pci(crate::virtio::probe_pci_blk),
+    },
+    #[cfg(all(feature = "nvme", feature = "bus-pci"))]
+    DriverProbe {
+        name: "nvme",
+        device_type: DeviceType::Block,
+        method: ProbeMethod::Pci(crate::nvme::probe_pci_nvme),
+    },
+    #[cfg(all(feature = "virtio-net", feature = "bus-pci"))]
+    DriverProbe {
+        name: "virtio-net-pci",
+        device_type: DeviceType::Net,
+        method: ProbeMethod::Pci(crate::virtio::probe_pci_net),
+    },
+    #[cfg(all(feature = "e1000", feature = "bus-pci"))]
+    DriverProbe {
+        name: "e1000",
+        device_type: DeviceType::Net,
+        method: ProbeMethod::Pci(crate::e1000::probe_pci_e1000),
+    },
+    #[cfg(all(feature = "virtio-gpu", feature = "bus-pci"))]
+    DriverProbe {
+        name: "virtio-gpu-pci",
+        device_type: DeviceType::Display,
+        method: ProbeMethod::Pci(crate::virtio::probe_pci_gpu),
+    },
+    #[cfg(all(feature = "virtio-console", feature = "bus-pci"))]
+    DriverProbe {
+        name: "virtio-console-pci",
+        device_type: DeviceType::Char,
+        method: ProbeMethod::Pci(crate::virtio::probe_pci_console),
+    },
+    #[cfg(all(feature = "virtio-rng", feature = "bus-pci"))]
+    DriverProbe {
+        name: "virtio-rng-pci",
+        device_type: DeviceType::Rng,
+        method: ProbeMethod::Pci(crate::virtio::probe_pci_rng),
+    },
+    #[cfg(all(feature = "virtio-input", feature = "bus-pci"))]
+    DriverProbe {
+        name: "virtio-input-pci",
+        device_type: DeviceType::Input,
+        method: ProbeMethod::Pci(crate::virtio::probe_pci_input),
+    },
+];
+
+static EXTRA_DRIVERS: SpinNoIrq<Vec<DriverProbe>> = SpinNoIrq::new(Vec::new());
+
+/// Registers a driver other than the built-in ones.
+///
+/// It must be called before [`init_drivers`](crate::init_drivers) to take
+/// effect.
+pub fn register_driver(driver: DriverProbe) {
+    EXTRA_DRIVERS.lock().push(driver);
+}
+
+#[cfg(feature = "ramdisk")]
+fn probe_ramdisk() -> Option<AxDeviceEnum> {
+    use alloc::boxed::Box;
+    const RAMDISK_SIZE: usize = 0x100_0000; // 16M
+    Some(AxDeviceEnum::Block(Box::new(crate::RamDisk::new(
+        RAMDISK_SIZE,
+    ))))
+}
+
+/// Runs all registered drivers, passes each device found to `add` along with
+/// its IRQ number.
+pub(crate) fn probe_all<F>(mut add: F)
+where
+    F: FnMut(AxDeviceEnum, Option<usize>),
+{
+    let drivers: Vec<DriverProbe> = BUILTIN_DRIVERS
+        .iter()
+        .copied()
+        .chain(EXTRA_DRIVERS.lock().iter().copied())
+        .collect();
+
+    let mmio_devs = axhal::dtb::virtio_mmio_devices();
+    let mut mmio_claimed = vec![false; mmio_devs.len()];
+    #[cfg(feature = "bus-pci")]
+    let mut pci_bus = crate::pci::PciBus::scan();
+    #[cfg(feature = "bus-pci")]
+    let mut pci_claimed = vec![false; pci_bus.as_ref().map_or(0, |bus| bus.functions.len())];
+
+    for driver in drivers {
+        debug!("probing {:?} driver {:?}", driver.device_type, driver.name);
+        match driver.method {
+            ProbeMethod::Global(probe) => {
+                if let Some(dev) = probe() {
+                    add(dev, None);
+                }
+            }
+            ProbeMethod::Mmio(probe) => {
+                for (mmio, claimed) in mmio_devs.iter().zip(mmio_claimed.iter_mut()) {
+                    if *claimed {
+                        continue;
+                    }
+                    if let Some(dev) = probe(mmio.paddr, mmio.size) {
+                        *claimed = true;
+                        add(dev, mmio.irq);
+                    }
+                }
+            }
+            #[cfg(feature = "bus-pci")]
+            ProbeMethod::Pci(probe) => {
+                let bus = match pci_bus.as_mut() {
+                    Some(bus) => bus,
+                    None => continue,
+                };
+                for (&(bdf, info), claimed) in bus.functions.iter().zip(pci_claimed.iter_mut()) {
+                    if *claimed {
+                        continue;
+                    }
+                    if let Some(dev) = probe(&mut bus.root, bdf, &info) {
+                        *claimed = true;
+                        add(dev, crate::pci::legacy_irq(&bus.root, bdf));
+                    }
+                }
+            }
+        }
+    }
+}
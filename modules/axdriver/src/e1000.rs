@@ -0,0 +1,59 @@
+use core::ptr::NonNull;
+
+use axdma::DmaMask;
+use driver_net::e1000::E1000Hal;
+
+#[cfg(feature = "bus-pci")]
+use crate::AxDeviceEnum;
+#[cfg(feature = "bus-pci")]
+use driver_pci::{BarInfo, BusDeviceFunction, DeviceFunctionInfo, Ecam, PciRoot};
+
+pub type E1000Dev = driver_net::e1000::E1000Dev<E1000HalImpl>;
+
+pub struct E1000HalImpl;
+
+unsafe impl E1000Hal for E1000HalImpl {
+    fn dma_alloc(pages: usize) -> Option<(usize, NonNull<u8>)> {
+        axdma::alloc_coherent(pages, DmaMask::ALL).ok()
+    }
+
+    unsafe fn dma_dealloc(paddr: usize, vaddr: NonNull<u8>, pages: usize) {
+        axdma::dealloc_coherent(paddr, vaddr, pages);
+    }
+}
+
+#[cfg(feature = "bus-pci")]
+pub(crate) fn probe_pci_e1000(
+    root: &mut PciRoot<Ecam>,
+    bdf: BusDeviceFunction,
+    info: &DeviceFunctionInfo,
+) -> Option<AxDeviceEnum> {
+    use driver_net::e1000::{E1000_DEVICE_IDS, INTEL_VENDOR_ID};
+
+    if info.vendor_id != INTEL_VENDOR_ID || !E1000_DEVICE_IDS.contains(&info.device_id) {
+        return None;
+    }
+    // the registers are in BAR 0, whose bus address is the physical address
+    let paddr = match root.bar_info(bdf, 0) {
+        Ok(BarInfo::Memory { address, size, .. }) if address != 0 && size != 0 => address,
+        _ => {
+            warn!("e1000 NIC at PCI {} has no valid BAR 0", bdf);
+            return None;
+        }
+    };
+    let mmio_base = axhal::mem::phys_to_virt((paddr as usize).into());
+    match unsafe { E1000Dev::try_new(mmio_base.as_usize()) } {
+        Ok(dev) => {
+            debug!(
+                "e1000 NIC {:04x}, link up: {}",
+                info.device_id,
+                dev.link_up()
+            );
+            Some(AxDeviceEnum::Net(crate::AxNetDevice::new(dev)))
+        }
+        Err(e) => {
+            warn!("failed to initialize e1000 NIC at PCI {}: {:?}", bdf, e);
+            None
+        }
+    }
+}
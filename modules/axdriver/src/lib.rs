@@ -1,69 +1,246 @@
+//! Device drivers of [ArceOS](https://github.com/rcore-os/arceos).
+//!
+//! Drivers are registered as [`DriverProbe`]s, each of which knows how to
+//! find its devices (e.g., on the virtio-mmio regions or the PCI bus).
+//! [`init_drivers`] runs all probes and returns every device found, grouped by
+//! the device type as trait objects. The built-in drivers are selected by
+//! cargo features, others can be added at runtime by [`register_driver`].
+
 #![no_std]
 #![feature(doc_auto_cfg)]
 
 #[macro_use]
 extern crate log;
+extern crate alloc;
 
+mod drivers;
+#[cfg(feature = "e1000")]
+mod e1000;
+#[cfg(feature = "net")]
+mod net;
+#[cfg(feature = "nvme")]
+mod nvme;
+#[cfg(feature = "bus-pci")]
+mod pci;
 #[cfg(feature = "virtio")]
 mod virtio;
 
-use tuple_for_each::TupleForEach;
+#[cfg(any(
+    feature = "block",
+    feature = "display",
+    feature = "char",
+    feature = "rng",
+    feature = "input"
+))]
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::ops::{Deref, DerefMut};
+
+#[cfg(feature = "net")]
+use driver_common::BaseDriverOps;
+use driver_common::DeviceType;
+
+pub use self::drivers::{register_driver, DriverProbe, ProbeMethod};
+#[cfg(feature = "net")]
+pub use self::net::{AxNetBuffer, AxNetDevice};
 
 #[cfg(feature = "virtio-blk")]
 pub use self::virtio::VirtIoBlockDev;
+#[cfg(feature = "virtio-console")]
+pub use self::virtio::VirtIoConsoleDev;
 #[cfg(feature = "virtio-gpu")]
 pub use self::virtio::VirtIoGpuDev;
+#[cfg(feature = "virtio-input")]
+pub use self::virtio::VirtIoInputDev;
 #[cfg(feature = "virtio-net")]
 pub use self::virtio::VirtIoNetDev;
+#[cfg(feature = "virtio-rng")]
+pub use self::virtio::VirtIoRngDev;
 
 #[cfg(feature = "ramdisk")]
 pub type RamDisk = driver_block::ramdisk::RamDisk;
+#[cfg(feature = "e1000")]
+pub use self::e1000::E1000Dev;
+#[cfg(feature = "nvme")]
+pub use self::nvme::NvmeDev;
+
+/// A block storage device of any driver.
+#[cfg(feature = "block")]
+pub type AxBlockDevice = Box<dyn driver_block::BlockDriverOps>;
+
+/// A graphics display device of any driver.
+#[cfg(feature = "display")]
+pub type AxDisplayDevice = Box<dyn driver_display::DisplayDriverOps>;
+
+/// A character device (e.g., console) of any driver.
+#[cfg(feature = "char")]
+pub type AxCharDevice = Box<dyn driver_char::CharDriverOps>;
+
+/// A random number generator of any driver.
+#[cfg(feature = "rng")]
+pub type AxRngDevice = Box<dyn driver_rng::RngDriverOps>;
+
+/// An input device of any driver.
+#[cfg(feature = "input")]
+pub type AxInputDevice = Box<dyn driver_input::InputDriverOps>;
+
+/// A device created by a driver, of any type.
+pub enum AxDeviceEnum {
+    /// Block storage device.
+    #[cfg(feature = "block")]
+    Block(AxBlockDevice),
+    /// Network device.
+    #[cfg(feature = "net")]
+    Net(AxNetDevice),
+    /// Graphics display device.
+    #[cfg(feature = "display")]
+    Display(AxDisplayDevice),
+    /// Character device.
+    #[cfg(feature = "char")]
+    Char(AxCharDevice),
+    /// Random number generator.
+    #[cfg(feature = "rng")]
+    Rng(AxRngDevice),
+    /// Input device.
+    #[cfg(feature = "input")]
+    Input(AxInputDevice),
+}
+
+macro_rules! for_each_device {
+    ($self:expr, |$dev:ident| $body:expr) => {
+        match $self {
+            #[cfg(feature = "block")]
+            AxDeviceEnum::Block($dev) => $body,
+            #[cfg(feature = "net")]
+            AxDeviceEnum::Net($dev) => $body,
+            #[cfg(feature = "display")]
+            AxDeviceEnum::Display($dev) => $body,
+            #[cfg(feature = "char")]
+            AxDeviceEnum::Char($dev) => $body,
+            #[cfg(feature = "rng")]
+            AxDeviceEnum::Rng($dev) => $body,
+            #[cfg(feature = "input")]
+            AxDeviceEnum::Input($dev) => $body,
+            #[allow(unreachable_patterns)]
+            _ => unreachable!(),
+        }
+    };
+}
+
+impl AxDeviceEnum {
+    /// The name of the device.
+    pub fn device_name(&self) -> &str {
+        for_each_device!(self, |dev| dev.device_name())
+    }
+
+    /// The type of the device.
+    pub fn device_type(&self) -> DeviceType {
+        for_each_device!(self, |dev| dev.device_type())
+    }
+}
+
+/// A device found by [`init_drivers`], and the IRQ number it uses.
+pub struct AxDevice<D> {
+    inner: D,
+    irq: Option<usize>,
+}
+
+impl<D> AxDevice<D> {
+    /// The IRQ number of the device, `None` if it does not support interrupts.
+    pub fn irq(&self) -> Option<usize> {
+        self.irq
+    }
+
+    /// Takes the inner device out.
+    pub fn into_inner(self) -> D {
+        self.inner
+    }
+}
 
-#[derive(TupleForEach)]
-pub struct BlockDevices(
-    #[cfg(feature = "virtio-blk")] pub VirtIoBlockDev,
-    #[cfg(feature = "ramdisk")] pub RamDisk,
-    // e.g. #[cfg(feature = "nvme")] pub nvme::NVMeDev,
-);
+impl<D> Deref for AxDevice<D> {
+    type Target = D;
 
-#[derive(TupleForEach)]
-pub struct NetDevices(
-    #[cfg(feature = "virtio-net")] pub VirtIoNetDev,
-    // e.g. #[cfg(feature = "e1000")] pub e1000::E1000Dev,
-);
+    fn deref(&self) -> &D {
+        &self.inner
+    }
+}
 
-#[derive(TupleForEach)]
-pub struct DisplayDevices(#[cfg(feature = "virtio-gpu")] pub VirtIoGpuDev);
+impl<D> DerefMut for AxDevice<D> {
+    fn deref_mut(&mut self) -> &mut D {
+        &mut self.inner
+    }
+}
 
+/// All devices found by [`init_drivers`], grouped by the device type, in the
+/// order they were probed.
+#[derive(Default)]
 pub struct AllDevices {
-    pub block: BlockDevices,
-    pub net: NetDevices,
-    pub display: DisplayDevices,
+    #[cfg(feature = "block")]
+    pub block: Vec<AxDevice<AxBlockDevice>>,
+    #[cfg(feature = "net")]
+    pub net: Vec<AxDevice<AxNetDevice>>,
+    #[cfg(feature = "display")]
+    pub display: Vec<AxDevice<AxDisplayDevice>>,
+    #[cfg(feature = "char")]
+    pub char: Vec<AxDevice<AxCharDevice>>,
+    #[cfg(feature = "rng")]
+    pub rng: Vec<AxDevice<AxRngDevice>>,
+    #[cfg(feature = "input")]
+    pub input: Vec<AxDevice<AxInputDevice>>,
 }
 
 impl AllDevices {
-    fn probe() -> Self {
-        Self {
-            block: BlockDevices(
-                #[cfg(feature = "virtio-blk")]
-                Self::probe_virtio_blk().expect("no virtio-blk device found"),
-                #[cfg(feature = "ramdisk")] // TODO: format RAM disk
-                RamDisk::new(0x100_0000), // 16 MiB
-            ),
-            net: NetDevices(
-                #[cfg(feature = "virtio-net")]
-                Self::probe_virtio_net().expect("no virtio-net device found"),
-            ),
-            display: DisplayDevices(
-                #[cfg(feature = "virtio-gpu")]
-                Self::probe_virtio_display().expect("no virtio-gpu device found"),
-            ),
+    fn add(&mut self, dev: AxDeviceEnum, irq: Option<usize>) {
+        info!(
+            "created a new {:?} device: {:?}, IRQ {:?}",
+            dev.device_type(),
+            dev.device_name(),
+            irq,
+        );
+        match dev {
+            #[cfg(feature = "block")]
+            AxDeviceEnum::Block(inner) => self.block.push(AxDevice { inner, irq }),
+            #[cfg(feature = "net")]
+            AxDeviceEnum::Net(inner) => self.net.push(AxDevice { inner, irq }),
+            #[cfg(feature = "display")]
+            AxDeviceEnum::Display(inner) => self.display.push(AxDevice { inner, irq }),
+            #[cfg(feature = "char")]
+            AxDeviceEnum::Char(inner) => self.char.push(AxDevice { inner, irq }),
+            #[cfg(feature = "rng")]
+            AxDeviceEnum::Rng(inner) => self.rng.push(AxDevice { inner, irq }),
+            #[cfg(feature = "input")]
+            AxDeviceEnum::Input(inner) => self.input.push(AxDevice { inner, irq }),
+            #[allow(unreachable_patterns)]
+            _ => unreachable!(),
+        }
+    }
+
+    /// Enumerates the names of all devices of the given type.
+    pub fn device_names(&self, dev_type: DeviceType) -> Vec<&str> {
+        match dev_type {
+            #[cfg(feature = "block")]
+            DeviceType::Block => self.block.iter().map(|d| d.device_name()).collect(),
+            #[cfg(feature = "net")]
+            DeviceType::Net => self.net.iter().map(|d| d.device_name()).collect(),
+            #[cfg(feature = "display")]
+            DeviceType::Display => self.display.iter().map(|d| d.device_name()).collect(),
+            #[cfg(feature = "char")]
+            DeviceType::Char => self.char.iter().map(|d| d.device_name()).collect(),
+            #[cfg(feature = "rng")]
+            DeviceType::Rng => self.rng.iter().map(|d| d.device_name()).collect(),
+            #[cfg(feature = "input")]
+            DeviceType::Input => self.input.iter().map(|d| d.device_name()).collect(),
+            #[allow(unreachable_patterns)]
+            _ => Vec::new(),
         }
     }
 }
 
+/// Probes all registered drivers, and returns the devices found.
 pub fn init_drivers() -> AllDevices {
     info!("Initialize device drivers...");
 
-    AllDevices::probe()
+    let mut all_devs = AllDevices::default();
+    drivers::probe_all(|dev, irq| all_devs.add(dev, irq));
+    all_devs
 }
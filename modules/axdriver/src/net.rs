@@ -0,0 +1,208 @@
+//! Type-erased network devices.
+//!
+//! [`NetDriverOps`] has associated buffer types, so it cannot be used as a
+//! trait object directly. [`AxNetDevice`] wraps a NIC of any driver, and boxes
+//! its buffers as [`AxNetBuffer`].
+
+use alloc::boxed::Box;
+use core::any::Any;
+
+use driver_common::{BaseDriverOps, DevError, DevResult, DeviceType};
+use driver_net::{EthernetAddress, NetBuffer, NetCapabilities, NetDriverOps};
+
+trait AnyNetBuffer: NetBuffer + Send {
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<B: NetBuffer + Send + 'static> AnyNetBuffer for B {
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+/// A transmit or receive buffer of an [`AxNetDevice`].
+pub struct AxNetBuffer(Box<dyn AnyNetBuffer>);
+
+impl AxNetBuffer {
+    fn new<B: NetBuffer + Send + 'static>(buf: B) -> Self {
+        Self(Box::new(buf))
+    }
+
+    /// Takes the driver's buffer out, fails if the buffer was not allocated
+    /// by the same driver.
+    fn downcast<B: 'static>(self) -> DevResult<B> {
+        self.0
+            .into_any()
+            .downcast::<B>()
+            .map(|b| *b)
+            .map_err(|_| DevError::InvalidParam)
+    }
+}
+
+impl NetBuffer for AxNetBuffer {
+    #[inline]
+    fn packet_len(&self) -> usize {
+        self.0.packet_len()
+    }
+
+    #[inline]
+    fn packet(&self) -> &[u8] {
+        self.0.packet()
+    }
+
+    #[inline]
+    fn packet_mut(&mut self) -> &mut [u8] {
+        self.0.packet_mut()
+    }
+}
+
+/// The object-safe version of [`NetDriverOps`].
+trait DynNetDriverOps: BaseDriverOps {
+    fn mac_address(&self) -> EthernetAddress;
+    fn capabilities(&self) -> NetCapabilities;
+    fn can_send(&self) -> bool;
+    fn can_recv(&self) -> bool;
+    fn ack_interrupt(&mut self) -> bool;
+    fn new_tx_buffer(&mut self, buf_len: usize) -> DevResult<AxNetBuffer>;
+    fn recycle_rx_buffer(&mut self, rx_buf: AxNetBuffer) -> DevResult;
+    fn transmit(&mut self, tx_buf: AxNetBuffer) -> DevResult;
+    fn reclaim_tx_buffers(&mut self) -> usize;
+    fn flush(&mut self) -> DevResult;
+    fn receive(&mut self) -> DevResult<AxNetBuffer>;
+}
+
+impl<D> DynNetDriverOps for D
+where
+    D: NetDriverOps,
+    D::RxBuffer: Send + 'static,
+    D::TxBuffer: Send + 'static,
+{
+    fn mac_address(&self) -> EthernetAddress {
+        NetDriverOps::mac_address(self)
+    }
+
+    fn capabilities(&self) -> NetCapabilities {
+        NetDriverOps::capabilities(self)
+    }
+
+    fn can_send(&self) -> bool {
+        NetDriverOps::can_send(self)
+    }
+
+    fn can_recv(&self) -> bool {
+        NetDriverOps::can_recv(self)
+    }
+
+    fn ack_interrupt(&mut self) -> bool {
+        NetDriverOps::ack_interrupt(self)
+    }
+
+    fn new_tx_buffer(&mut self, buf_len: usize) -> DevResult<AxNetBuffer> {
+        NetDriverOps::new_tx_buffer(self, buf_len).map(AxNetBuffer::new)
+    }
+
+    fn recycle_rx_buffer(&mut self, rx_buf: AxNetBuffer) -> DevResult {
+        NetDriverOps::recycle_rx_buffer(self, rx_buf.downcast()?)
+    }
+
+    fn transmit(&mut self, tx_buf: AxNetBuffer) -> DevResult {
+        NetDriverOps::transmit(self, tx_buf.downcast()?)
+    }
+
+    fn reclaim_tx_buffers(&mut self) -> usize {
+        NetDriverOps::reclaim_tx_buffers(self)
+    }
+
+    fn flush(&mut self) -> DevResult {
+        NetDriverOps::flush(self)
+    }
+
+    fn receive(&mut self) -> DevResult<AxNetBuffer> {
+        NetDriverOps::receive(self).map(AxNetBuffer::new)
+    }
+}
+
+/// A network device of any driver.
+pub struct AxNetDevice(Box<dyn DynNetDriverOps>);
+
+impl AxNetDevice {
+    /// Wraps a NIC driver instance.
+    pub fn new<D>(dev: D) -> Self
+    where
+        D: NetDriverOps + 'static,
+        D::RxBuffer: Send + 'static,
+        D::TxBuffer: Send + 'static,
+    {
+        Self(Box::new(dev))
+    }
+}
+
+impl BaseDriverOps for AxNetDevice {
+    fn device_name(&self) -> &str {
+        self.0.device_name()
+    }
+
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Net
+    }
+}
+
+impl NetDriverOps for AxNetDevice {
+    type RxBuffer = AxNetBuffer;
+    type TxBuffer = AxNetBuffer;
+
+    #[inline]
+    fn mac_address(&self) -> EthernetAddress {
+        self.0.mac_address()
+    }
+
+    #[inline]
+    fn capabilities(&self) -> NetCapabilities {
+        self.0.capabilities()
+    }
+
+    #[inline]
+    fn can_send(&self) -> bool {
+        self.0.can_send()
+    }
+
+    #[inline]
+    fn can_recv(&self) -> bool {
+        self.0.can_recv()
+    }
+
+    #[inline]
+    fn ack_interrupt(&mut self) -> bool {
+        self.0.ack_interrupt()
+    }
+
+    #[inline]
+    fn new_tx_buffer(&mut self, buf_len: usize) -> DevResult<AxNetBuffer> {
+        self.0.new_tx_buffer(buf_len)
+    }
+
+    #[inline]
+    fn recycle_rx_buffer(&mut self, rx_buf: AxNetBuffer) -> DevResult {
+        self.0.recycle_rx_buffer(rx_buf)
+    }
+
+    #[inline]
+    fn transmit(&mut self, tx_buf: AxNetBuffer) -> DevResult {
+        self.0.transmit(tx_buf)
+    }
+
+    #[inline]
+    fn reclaim_tx_buffers(&mut self) -> usize {
+        self.0.reclaim_tx_buffers()
+    }
+
+    #[inline]
+    fn flush(&mut self) -> DevResult {
+        self.0.flush()
+    }
+
+    #[inline]
+    fn receive(&mut self) -> DevResult<AxNetBuffer> {
+        self.0.receive()
+    }
+}
@@ -0,0 +1,60 @@
+use core::ptr::NonNull;
+
+use axdma::DmaMask;
+use driver_block::nvme::NvmeHal;
+
+#[cfg(feature = "bus-pci")]
+use crate::AxDeviceEnum;
+#[cfg(feature = "bus-pci")]
+use driver_pci::{BarInfo, BusDeviceFunction, DeviceFunctionInfo, Ecam, PciRoot};
+
+pub type NvmeDev = driver_block::nvme::NvmeDev<NvmeHalImpl>;
+
+pub struct NvmeHalImpl;
+
+unsafe impl NvmeHal for NvmeHalImpl {
+    fn dma_alloc(pages: usize) -> Option<(usize, NonNull<u8>)> {
+        axdma::alloc_coherent(pages, DmaMask::ALL).ok()
+    }
+
+    unsafe fn dma_dealloc(paddr: usize, vaddr: NonNull<u8>, pages: usize) {
+        axdma::dealloc_coherent(paddr, vaddr, pages);
+    }
+}
+
+#[cfg(feature = "bus-pci")]
+pub(crate) fn probe_pci_nvme(
+    root: &mut PciRoot<Ecam>,
+    bdf: BusDeviceFunction,
+    info: &DeviceFunctionInfo,
+) -> Option<AxDeviceEnum> {
+    use driver_block::nvme::NVME_PCI_CLASS;
+
+    if (info.class, info.subclass, info.prog_if) != NVME_PCI_CLASS {
+        return None;
+    }
+    // the registers are in BAR 0, whose bus address is the physical address
+    let paddr = match root.bar_info(bdf, 0) {
+        Ok(BarInfo::Memory { address, size, .. }) if address != 0 && size != 0 => address,
+        _ => {
+            warn!("NVMe controller at PCI {} has no valid BAR 0", bdf);
+            return None;
+        }
+    };
+    let mmio_base = axhal::mem::phys_to_virt((paddr as usize).into());
+    match unsafe { NvmeDev::try_new(mmio_base.as_usize()) } {
+        Ok(dev) => {
+            for ns in dev.namespaces() {
+                debug!(
+                    "NVMe namespace {}: {} blocks of {} bytes",
+                    ns.nsid, ns.num_blocks, ns.block_size
+                );
+            }
+            Some(AxDeviceEnum::Block(alloc::boxed::Box::new(dev)))
+        }
+        Err(e) => {
+            warn!("failed to initialize NVMe controller at PCI {}: {:?}", bdf, e);
+            None
+        }
+    }
+}
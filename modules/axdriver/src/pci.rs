@@ -0,0 +1,64 @@
+//! Enumeration and resource assignment of the PCI bus.
+
+use alloc::vec::Vec;
+
+use axhal::mem::phys_to_virt;
+use driver_pci::{BusDeviceFunction, DeviceFunctionInfo, Ecam, HeaderType};
+use driver_pci::{PciRangeAllocator, PciRoot};
+
+/// The PCI functions found on the platform.
+pub(crate) struct PciBus {
+    pub root: PciRoot<Ecam>,
+    pub functions: Vec<(BusDeviceFunction, DeviceFunctionInfo)>,
+}
+
+impl PciBus {
+    /// Enumerates all functions of the PCI host bridge, and assigns the
+    /// memory BARs of them.
+    ///
+    /// Returns `None` if the platform does not have a PCI host bridge.
+    pub fn scan() -> Option<Self> {
+        let host = axhal::dtb::pci_host()?;
+        let (bus_start, bus_end) = host.bus_range;
+        let ecam = unsafe { Ecam::new(phys_to_virt(host.ecam.0).as_mut_ptr(), bus_start) };
+        let mut root = PciRoot::new(ecam);
+
+        // the drivers use the bus addresses of BARs as physical addresses
+        let mut allocator = match host.mem32 {
+            Some(mem) if mem.pci_addr == mem.paddr.as_usize() as u64 => {
+                Some(PciRangeAllocator::new(mem.pci_addr, mem.size as u64))
+            }
+            Some(_) => {
+                warn!("PCI memory window is not identity-mapped, BARs are not assigned");
+                None
+            }
+            None => None,
+        };
+
+        let mut functions = Vec::new();
+        for bus in bus_start..=bus_end {
+            functions.extend(root.enumerate_bus(bus));
+        }
+        for (bdf, info) in functions.iter() {
+            debug!("PCI {}: {}", bdf, info);
+            if info.header_type != HeaderType::Standard {
+                continue; // bridges are not configured
+            }
+            if let Some(allocator) = allocator.as_mut() {
+                if let Err(e) = root.assign_bars(*bdf, allocator) {
+                    warn!("failed to assign BARs of PCI {}: {}", bdf, e);
+                }
+            }
+            if let Some(irq) = legacy_irq(&root, *bdf) {
+                root.set_interrupt_line(*bdf, irq as u8);
+            }
+        }
+        Some(Self { root, functions })
+    }
+}
+
+/// Returns the IRQ number of the legacy interrupt of `bdf`, if any.
+pub(crate) fn legacy_irq(root: &PciRoot<Ecam>, bdf: BusDeviceFunction) -> Option<usize> {
+    let pin = root.interrupt_pin(bdf)?;
+    axhal::dtb::pci_legacy_irq(bdf.bus, bdf.device, bdf.function, pin)
+}
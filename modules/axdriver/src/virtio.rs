@@ -1,57 +1,76 @@
 use core::ptr::NonNull;
 
-use axalloc::global_allocator;
-use axhal::mem::{phys_to_virt, virt_to_phys};
+use axdma::{DmaDirection, DmaMask};
+use axhal::mem::phys_to_virt;
 use cfg_if::cfg_if;
-use driver_common::{BaseDriverOps, DeviceType};
 use driver_virtio::{BufferDirection, PhysAddr, VirtIoHal};
 
-use crate::AllDevices;
-
-cfg_if! {
-    if #[cfg(feature =  "bus-mmio")] {
-        type VirtIoTransport = driver_virtio::MmioTransport;
-    } else if #[cfg(feature = "bus-pci")] {
-        type VirtIoTransport = driver_virtio::PciTransport;
-    }
-}
+#[cfg(any(feature = "bus-mmio", feature = "bus-pci"))]
+use crate::AxDeviceEnum;
+#[cfg(any(feature = "bus-mmio", feature = "bus-pci"))]
+use driver_common::{DevResult, DeviceType};
+#[cfg(feature = "bus-pci")]
+use driver_pci::{BusDeviceFunction, DeviceFunctionInfo, Ecam, PciRoot};
 
 cfg_if! {
     if #[cfg(feature = "virtio-blk")] {
-        pub type VirtIoBlockDev = driver_virtio::VirtIoBlkDev<VirtIoHalImpl, VirtIoTransport>;
+        pub type VirtIoBlockDev<T> = driver_virtio::VirtIoBlkDev<VirtIoHalImpl, T>;
     }
 }
 
 cfg_if! {
     if #[cfg(feature = "virtio-net")] {
-        const NET_QUEUE_SIZE: usize = 64;
+        const NET_QUEUE_SIZE: usize = 128;
         const NET_BUFFER_SIZE: usize = 2048;
-        pub type VirtIoNetDev = driver_virtio::VirtIoNetDev<VirtIoHalImpl, VirtIoTransport, NET_QUEUE_SIZE>;
+        pub type VirtIoNetDev<T> = driver_virtio::VirtIoNetDev<VirtIoHalImpl, T, NET_QUEUE_SIZE>;
     }
 }
 
 cfg_if! {
     if #[cfg(feature = "virtio-gpu")] {
-        pub type VirtIoGpuDev = driver_virtio::VirtIoGpuDev<VirtIoHalImpl, VirtIoTransport>;
+        pub type VirtIoGpuDev<T> = driver_virtio::VirtIoGpuDev<VirtIoHalImpl, T>;
+    }
+}
+
+cfg_if! {
+    if #[cfg(feature = "virtio-console")] {
+        pub type VirtIoConsoleDev<T> = driver_virtio::VirtIoConsoleDev<VirtIoHalImpl, T>;
+    }
+}
+
+cfg_if! {
+    if #[cfg(feature = "virtio-rng")] {
+        pub type VirtIoRngDev<T> = driver_virtio::VirtIoRngDev<VirtIoHalImpl, T>;
+    }
+}
+
+cfg_if! {
+    if #[cfg(feature = "virtio-input")] {
+        pub type VirtIoInputDev<T> = driver_virtio::VirtIoInputDev<VirtIoHalImpl, T>;
     }
 }
 
 pub struct VirtIoHalImpl;
 
+impl VirtIoHalImpl {
+    const DMA_MASK: DmaMask = DmaMask::ALL;
+
+    fn dma_direction(direction: BufferDirection) -> DmaDirection {
+        match direction {
+            BufferDirection::DriverToDevice => DmaDirection::ToDevice,
+            BufferDirection::DeviceToDriver => DmaDirection::FromDevice,
+            BufferDirection::Both => DmaDirection::Bidirectional,
+        }
+    }
+}
+
 unsafe impl VirtIoHal for VirtIoHalImpl {
     fn dma_alloc(pages: usize, _direction: BufferDirection) -> (PhysAddr, NonNull<u8>) {
-        let vaddr = if let Ok(vaddr) = global_allocator().alloc_pages(pages, 0x1000) {
-            vaddr
-        } else {
-            return (0, NonNull::dangling());
-        };
-        let paddr = virt_to_phys(vaddr.into());
-        let ptr = NonNull::new(vaddr as _).unwrap();
-        (paddr.as_usize(), ptr)
+        axdma::alloc_coherent(pages, Self::DMA_MASK).unwrap_or((0, NonNull::dangling()))
     }
 
-    unsafe fn dma_dealloc(_paddr: PhysAddr, vaddr: NonNull<u8>, pages: usize) -> i32 {
-        global_allocator().dealloc_pages(vaddr.as_ptr() as usize, pages);
+    unsafe fn dma_dealloc(paddr: PhysAddr, vaddr: NonNull<u8>, pages: usize) -> i32 {
+        axdma::dealloc_coherent(paddr, vaddr, pages);
         0
     }
 
@@ -60,56 +79,204 @@ unsafe impl VirtIoHal for VirtIoHalImpl {
         NonNull::new(phys_to_virt(paddr.into()).as_mut_ptr()).unwrap()
     }
 
-    #[inline]
-    unsafe fn share(buffer: NonNull<[u8]>, _direction: BufferDirection) -> PhysAddr {
-        let vaddr = buffer.as_ptr() as *mut u8 as usize;
-        virt_to_phys(vaddr.into()).into()
-    }
-
-    #[inline]
-    unsafe fn unshare(_paddr: PhysAddr, _buffer: NonNull<[u8]>, _direction: BufferDirection) {}
-}
-
-impl AllDevices {
-    #[cfg(feature = "bus-mmio")]
-    fn probe_devices_common<D, F>(dev_type: DeviceType, ret: F) -> Option<D>
-    where
-        D: BaseDriverOps,
-        F: FnOnce(VirtIoTransport) -> Option<D>,
-    {
-        // TODO: parse device tree
-        for reg in axconfig::VIRTIO_MMIO_REGIONS {
-            if let Some(transport) = driver_virtio::probe_mmio_device(
-                phys_to_virt(reg.0.into()).as_mut_ptr(),
-                reg.1,
-                Some(dev_type),
-            ) {
-                let dev = ret(transport)?;
-                info!(
-                    "created a new {:?} device: {:?}",
-                    dev.device_type(),
-                    dev.device_name()
+    // `share` cannot fail, so the address `0` is given to the device if the
+    // buffer cannot be mapped, which is not RAM on the supported platforms,
+    // and the device fails the request.
+    unsafe fn share(buffer: NonNull<[u8]>, direction: BufferDirection) -> PhysAddr {
+        let dir = Self::dma_direction(direction);
+        match axdma::map_single(buffer, dir, Self::DMA_MASK) {
+            Ok(paddr) => paddr,
+            Err(e) => {
+                error!(
+                    "failed to map a DMA buffer of {} bytes: {:?}",
+                    buffer.len(),
+                    e
                 );
-                return Some(dev);
+                0
             }
         }
-        None
     }
 
-    #[cfg(feature = "virtio-blk")]
-    pub(crate) fn probe_virtio_blk() -> Option<VirtIoBlockDev> {
-        Self::probe_devices_common(DeviceType::Block, |t| VirtIoBlockDev::try_new(t).ok())
+    unsafe fn unshare(paddr: PhysAddr, buffer: NonNull<[u8]>, direction: BufferDirection) {
+        if paddr != 0 {
+            axdma::unmap_single(paddr, buffer, Self::dma_direction(direction))
+        }
     }
+}
 
-    #[cfg(feature = "virtio-net")]
-    pub(crate) fn probe_virtio_net() -> Option<VirtIoNetDev> {
-        Self::probe_devices_common(DeviceType::Net, |t| {
-            VirtIoNetDev::try_new(t, NET_BUFFER_SIZE).ok()
+#[cfg(feature = "bus-mmio")]
+fn probe_mmio_common<D, F>(paddr: PhysAddr, size: usize, dev_type: DeviceType, ret: F) -> Option<D>
+where
+    F: FnOnce(driver_virtio::MmioTransport) -> DevResult<D>,
+{
+    let transport = driver_virtio::probe_mmio_device(
+        phys_to_virt(paddr.into()).as_mut_ptr(),
+        size,
+        Some(dev_type),
+    )?;
+    ret(transport)
+        .map_err(|e| {
+            warn!(
+                "failed to initialize virtio {:?} device at {:#x}: {:?}",
+                dev_type, paddr, e
+            )
         })
-    }
+        .ok()
+}
 
-    #[cfg(feature = "virtio-gpu")]
-    pub(crate) fn probe_virtio_display() -> Option<VirtIoGpuDev> {
-        Self::probe_devices_common(DeviceType::Display, |t| VirtIoGpuDev::try_new(t).ok())
-    }
+#[cfg(all(feature = "virtio-blk", feature = "bus-mmio"))]
+pub(crate) fn probe_mmio_blk(paddr: axhal::mem::PhysAddr, size: usize) -> Option<AxDeviceEnum> {
+    let dev = probe_mmio_common(
+        paddr.as_usize(),
+        size,
+        DeviceType::Block,
+        VirtIoBlockDev::try_new,
+    )?;
+    Some(AxDeviceEnum::Block(alloc::boxed::Box::new(dev)))
+}
+
+#[cfg(all(feature = "virtio-net", feature = "bus-mmio"))]
+pub(crate) fn probe_mmio_net(paddr: axhal::mem::PhysAddr, size: usize) -> Option<AxDeviceEnum> {
+    let dev = probe_mmio_common(paddr.as_usize(), size, DeviceType::Net, |t| {
+        VirtIoNetDev::try_new(t, NET_BUFFER_SIZE)
+    })?;
+    Some(AxDeviceEnum::Net(crate::AxNetDevice::new(dev)))
+}
+
+#[cfg(all(feature = "virtio-gpu", feature = "bus-mmio"))]
+pub(crate) fn probe_mmio_gpu(paddr: axhal::mem::PhysAddr, size: usize) -> Option<AxDeviceEnum> {
+    let dev = probe_mmio_common(
+        paddr.as_usize(),
+        size,
+        DeviceType::Display,
+        VirtIoGpuDev::try_new,
+    )?;
+    Some(AxDeviceEnum::Display(alloc::boxed::Box::new(dev)))
+}
+
+#[cfg(all(feature = "virtio-console", feature = "bus-mmio"))]
+pub(crate) fn probe_mmio_console(paddr: axhal::mem::PhysAddr, size: usize) -> Option<AxDeviceEnum> {
+    let dev = probe_mmio_common(
+        paddr.as_usize(),
+        size,
+        DeviceType::Char,
+        VirtIoConsoleDev::try_new,
+    )?;
+    Some(AxDeviceEnum::Char(alloc::boxed::Box::new(dev)))
+}
+
+#[cfg(all(feature = "virtio-rng", feature = "bus-mmio"))]
+pub(crate) fn probe_mmio_rng(paddr: axhal::mem::PhysAddr, size: usize) -> Option<AxDeviceEnum> {
+    let dev = probe_mmio_common(
+        paddr.as_usize(),
+        size,
+        DeviceType::Rng,
+        VirtIoRngDev::try_new,
+    )?;
+    Some(AxDeviceEnum::Rng(alloc::boxed::Box::new(dev)))
+}
+
+#[cfg(all(feature = "virtio-input", feature = "bus-mmio"))]
+pub(crate) fn probe_mmio_input(paddr: axhal::mem::PhysAddr, size: usize) -> Option<AxDeviceEnum> {
+    let dev = probe_mmio_common(
+        paddr.as_usize(),
+        size,
+        DeviceType::Input,
+        VirtIoInputDev::try_new,
+    )?;
+    Some(AxDeviceEnum::Input(alloc::boxed::Box::new(dev)))
+}
+
+#[cfg(feature = "bus-pci")]
+fn probe_pci_common<D, F>(
+    root: &PciRoot<Ecam>,
+    bdf: BusDeviceFunction,
+    info: &DeviceFunctionInfo,
+    dev_type: DeviceType,
+    ret: F,
+) -> Option<D>
+where
+    F: FnOnce(driver_virtio::PciTransport) -> DevResult<D>,
+{
+    // `probe_pci_device` takes the ECAM region starting from bus 0
+    let ecam = root.access();
+    let ecam_base = ecam
+        .base()
+        .wrapping_sub(ecam.bus_start() as usize * Ecam::BUS_SIZE);
+    let transport = driver_virtio::probe_pci_device::<VirtIoHalImpl>(
+        ecam_base,
+        (bdf.bus, bdf.device, bdf.function),
+        (info.vendor_id, info.device_id),
+        Some(dev_type),
+    )?;
+    ret(transport)
+        .map_err(|e| {
+            warn!(
+                "failed to initialize virtio {:?} device at PCI {}: {:?}",
+                dev_type, bdf, e
+            )
+        })
+        .ok()
+}
+
+#[cfg(all(feature = "virtio-blk", feature = "bus-pci"))]
+pub(crate) fn probe_pci_blk(
+    root: &mut PciRoot<Ecam>,
+    bdf: BusDeviceFunction,
+    info: &DeviceFunctionInfo,
+) -> Option<AxDeviceEnum> {
+    let dev = probe_pci_common(root, bdf, info, DeviceType::Block, VirtIoBlockDev::try_new)?;
+    Some(AxDeviceEnum::Block(alloc::boxed::Box::new(dev)))
+}
+
+#[cfg(all(feature = "virtio-net", feature = "bus-pci"))]
+pub(crate) fn probe_pci_net(
+    root: &mut PciRoot<Ecam>,
+    bdf: BusDeviceFunction,
+    info: &DeviceFunctionInfo,
+) -> Option<AxDeviceEnum> {
+    let dev = probe_pci_common(root, bdf, info, DeviceType::Net, |t| {
+        VirtIoNetDev::try_new(t, NET_BUFFER_SIZE)
+    })?;
+    Some(AxDeviceEnum::Net(crate::AxNetDevice::new(dev)))
+}
+
+#[cfg(all(feature = "virtio-gpu", feature = "bus-pci"))]
+pub(crate) fn probe_pci_gpu(
+    root: &mut PciRoot<Ecam>,
+    bdf: BusDeviceFunction,
+    info: &DeviceFunctionInfo,
+) -> Option<AxDeviceEnum> {
+    let dev = probe_pci_common(root, bdf, info, DeviceType::Display, VirtIoGpuDev::try_new)?;
+    Some(AxDeviceEnum::Display(alloc::boxed::Box::new(dev)))
+}
+
+#[cfg(all(feature = "virtio-console", feature = "bus-pci"))]
+pub(crate) fn probe_pci_console(
+    root: &mut PciRoot<Ecam>,
+    bdf: BusDeviceFunction,
+    info: &DeviceFunctionInfo,
+) -> Option<AxDeviceEnum> {
+    let dev = probe_pci_common(root, bdf, info, DeviceType::Char, VirtIoConsoleDev::try_new)?;
+    Some(AxDeviceEnum::Char(alloc::boxed::Box::new(dev)))
+}
+
+#[cfg(all(feature = "virtio-rng", feature = "bus-pci"))]
+pub(crate) fn probe_pci_rng(
+    root: &mut PciRoot<Ecam>,
+    bdf: BusDeviceFunction,
+    info: &DeviceFunctionInfo,
+) -> Option<AxDeviceEnum> {
+    let dev = probe_pci_common(root, bdf, info, DeviceType::Rng, VirtIoRngDev::try_new)?;
+    Some(AxDeviceEnum::Rng(alloc::boxed::Box::new(dev)))
+}
+
+#[cfg(all(feature = "virtio-input", feature = "bus-pci"))]
+pub(crate) fn probe_pci_input(
+    root: &mut PciRoot<Ecam>,
+    bdf: BusDeviceFunction,
+    info: &DeviceFunctionInfo,
+) -> Option<AxDeviceEnum> {
+    let dev = probe_pci_common(root, bdf, info, DeviceType::Input, VirtIoInputDev::try_new)?;
+    Some(AxDeviceEnum::Input(alloc::boxed::Box::new(dev)))
 }
@@ -1,18 +1,217 @@
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+
+use axsync::spin::SpinNoIrq;
+use driver_block::RequestToken;
+use driver_common::{DevError, DevResult};
+use lazy_init::LazyInit;
+
 use crate::BlockDevice;
-use driver_block::BlockDriverOps;
-use driver_common::DevResult;
 
 const BLOCK_SIZE: usize = 512;
 
+/// The maximum number of blocks in one request of a batch.
+const BLOCKS_PER_REQUEST: usize = 8;
+
+/// The block device whose requests are completed by [`block_irq_handler`].
+static IRQ_QUEUE: LazyInit<Arc<BlockQueue>> = LazyInit::new();
+
+struct QueueInner {
+    dev: BlockDevice,
+    /// The unique IDs of the in-flight requests, by their tokens. Tokens are
+    /// reused by the driver, but IDs are not.
+    inflight: BTreeMap<RequestToken, u64>,
+    /// The results of the completed requests that are not yet taken.
+    completed: BTreeMap<u64, DevResult>,
+    next_id: u64,
+}
+
+impl QueueInner {
+    /// Moves the requests completed by the device to `completed`.
+    fn reap(&mut self) {
+        while let Some((token, res)) = self.dev.poll_completion() {
+            if let Some(id) = self.inflight.remove(&token) {
+                self.completed.insert(id, res);
+            }
+        }
+    }
+
+    /// Submits a request, returns its unique ID.
+    ///
+    /// # Safety
+    ///
+    /// See [`BlockDriverOps::submit_read`](driver_block::BlockDriverOps::submit_read).
+    unsafe fn submit(&mut self, block_id: u64, buf: *mut [u8], is_write: bool) -> DevResult<u64> {
+        let token = if is_write {
+            self.dev.submit_write(block_id, buf)?
+        } else {
+            self.dev.submit_read(block_id, buf)?
+        };
+        let id = self.next_id;
+        self.next_id += 1;
+        self.inflight.insert(token, id);
+        Ok(id)
+    }
+}
+
+/// A block device shared between tasks and its interrupt handler.
+///
+/// If the device has an IRQ and the driver has a request queue, a batch of
+/// requests is submitted at once, the requests are completed in the
+/// interrupt handler, and the waiting tasks are woken up then. Otherwise,
+/// the blocking operations of the driver are used.
+pub struct BlockQueue {
+    inner: SpinNoIrq<QueueInner>,
+    irq_num: Option<usize>,
+    #[cfg(feature = "multitask")]
+    wq: axtask::WaitQueue,
+}
+
+impl BlockQueue {
+    /// Creates a new queue of `dev`, and registers the interrupt handler if
+    /// `irq_num` is given.
+    ///
+    /// Only one device can be interrupt-driven, the later ones use the
+    /// blocking operations.
+    pub fn new(dev: BlockDevice, irq_num: Option<usize>) -> Arc<Self> {
+        let irq_num = irq_num.filter(|&irq_num| {
+            // the handler does nothing until `IRQ_QUEUE` is initialized
+            !IRQ_QUEUE.is_init() && axhal::irq::register_handler(irq_num, block_irq_handler)
+        });
+        let queue = Arc::new(Self {
+            inner: SpinNoIrq::new(QueueInner {
+                dev,
+                inflight: BTreeMap::new(),
+                completed: BTreeMap::new(),
+                next_id: 0,
+            }),
+            irq_num,
+            #[cfg(feature = "multitask")]
+            wq: axtask::WaitQueue::new(),
+        });
+        if irq_num.is_some() {
+            IRQ_QUEUE.init_by(queue.clone());
+        }
+        queue
+    }
+
+    /// The number of blocks of the device.
+    pub fn num_blocks(&self) -> u64 {
+        self.inner.lock().dev.num_blocks()
+    }
+
+    /// The size of each block in bytes.
+    pub fn block_size(&self) -> usize {
+        self.inner.lock().dev.block_size()
+    }
+
+    /// Reads a batch of requests, each of which is `(block_id, buf)`.
+    pub fn read_blocks(&self, requests: &mut [(u64, &mut [u8])]) -> DevResult {
+        let bufs = requests
+            .iter_mut()
+            .map(|(block_id, buf)| (*block_id, &mut **buf as *mut [u8]));
+        self.transfer(bufs, false)
+            .unwrap_or_else(|| self.inner.lock().dev.read_blocks(requests))
+    }
+
+    /// Writes a batch of requests, each of which is `(block_id, buf)`.
+    pub fn write_blocks(&self, requests: &[(u64, &[u8])]) -> DevResult {
+        // the buffers are only read by the device
+        let bufs = requests
+            .iter()
+            .map(|(block_id, buf)| (*block_id, *buf as *const [u8] as *mut [u8]));
+        self.transfer(bufs, true)
+            .unwrap_or_else(|| self.inner.lock().dev.write_blocks(requests))
+    }
+
+    /// Submits the requests to the device queue and waits for all of them to
+    /// complete, returns the first error.
+    ///
+    /// Returns `None` if the requests should be done by the blocking
+    /// operations instead.
+    fn transfer<I>(&self, requests: I, is_write: bool) -> Option<DevResult>
+    where
+        I: Iterator<Item = (u64, *mut [u8])>,
+    {
+        self.irq_num?;
+        let mut ids = Vec::new();
+        let mut result = Ok(());
+        for (block_id, buf) in requests {
+            loop {
+                let res = unsafe { self.inner.lock().submit(block_id, buf, is_write) };
+                match res {
+                    Ok(id) => ids.push(id),
+                    Err(DevError::Unsupported) if ids.is_empty() => return None,
+                    // the queue is full, wait for our oldest request
+                    Err(DevError::Again) if !ids.is_empty() => {
+                        let res = self.wait(ids.remove(0));
+                        result = result.and(res);
+                        continue;
+                    }
+                    // the queue is occupied by other tasks
+                    Err(DevError::Again) => {
+                        self.yield_or_reap();
+                        continue;
+                    }
+                    Err(e) => result = result.and(Err(e)),
+                }
+                break;
+            }
+        }
+        for id in ids {
+            result = result.and(self.wait(id));
+        }
+        Some(result)
+    }
+
+    /// Waits for the request `id` to complete, returns its result.
+    fn wait(&self, id: u64) -> DevResult {
+        #[cfg(feature = "multitask")]
+        if axhal::arch::irqs_enabled() {
+            self.wq
+                .wait_until(|| self.inner.lock().completed.contains_key(&id));
+        }
+        loop {
+            let mut inner = self.inner.lock();
+            if let Some(res) = inner.completed.remove(&id) {
+                return res;
+            }
+            // interrupts are not available yet, poll the device instead
+            inner.reap();
+            drop(inner);
+            core::hint::spin_loop();
+        }
+    }
+
+    fn yield_or_reap(&self) {
+        if axhal::arch::irqs_enabled() {
+            axtask::yield_now();
+        } else {
+            self.inner.lock().reap();
+        }
+    }
+}
+
+/// Completes the requests of [`IRQ_QUEUE`], and wakes up the waiting tasks.
+fn block_irq_handler() {
+    if let Some(queue) = IRQ_QUEUE.try_get() {
+        let mut inner = queue.inner.lock();
+        inner.dev.ack_interrupt();
+        inner.reap();
+        drop(inner);
+        #[cfg(feature = "multitask")]
+        queue.wq.notify_all(false);
+    }
+}
+
 pub struct Disk {
     block_id: u64,
     offset: usize,
-    dev: BlockDevice,
+    dev: Arc<BlockQueue>,
 }
 
 impl Disk {
     /// Create a new disk.
-    pub fn new(dev: BlockDevice) -> Self {
+    pub fn new(dev: Arc<BlockQueue>) -> Self {
         assert_eq!(BLOCK_SIZE, dev.block_size());
         Self {
             block_id: 0,
@@ -37,21 +236,30 @@ impl Disk {
         self.offset = pos as usize % BLOCK_SIZE;
     }
 
-    /// Read within one block, returns the number of bytes read.
+    /// Read within one block, or as many whole blocks as possible if the
+    /// cursor is at the start of a block. Returns the number of bytes read.
     pub fn read_one(&mut self, buf: &mut [u8]) -> DevResult<usize> {
         let read_size = if self.offset == 0 && buf.len() >= BLOCK_SIZE {
-            // whole block
-            self.dev
-                .read_block(self.block_id, &mut buf[0..BLOCK_SIZE])?;
-            self.block_id += 1;
-            BLOCK_SIZE
+            // whole blocks
+            let blocks = self.whole_blocks(buf.len());
+            let size = blocks * BLOCK_SIZE;
+            let block_id = self.block_id;
+            let mut requests: Vec<_> = buf[..size]
+                .chunks_mut(BLOCKS_PER_REQUEST * BLOCK_SIZE)
+                .enumerate()
+                .map(|(i, chunk)| (block_id + (i * BLOCKS_PER_REQUEST) as u64, chunk))
+                .collect();
+            self.dev.read_blocks(&mut requests)?;
+            self.block_id += blocks as u64;
+            size
         } else {
             // partial block
             let mut data = [0u8; BLOCK_SIZE];
             let start = self.offset;
             let count = buf.len().min(BLOCK_SIZE - self.offset);
 
-            self.dev.read_block(self.block_id, &mut data)?;
+            self.dev
+                .read_blocks(&mut [(self.block_id, &mut data[..])])?;
             buf[..count].copy_from_slice(&data[start..start + count]);
 
             self.offset += count;
@@ -64,22 +272,33 @@ impl Disk {
         Ok(read_size)
     }
 
-    /// Write within one block, returns the number of bytes written.
+    /// Write within one block, or as many whole blocks as possible if the
+    /// cursor is at the start of a block. Returns the number of bytes
+    /// written.
     pub fn write_one(&mut self, buf: &[u8]) -> DevResult<usize> {
         let write_size = if self.offset == 0 && buf.len() >= BLOCK_SIZE {
-            // whole block
-            self.dev.write_block(self.block_id, &buf[0..BLOCK_SIZE])?;
-            self.block_id += 1;
-            BLOCK_SIZE
+            // whole blocks
+            let blocks = self.whole_blocks(buf.len());
+            let size = blocks * BLOCK_SIZE;
+            let block_id = self.block_id;
+            let requests: Vec<_> = buf[..size]
+                .chunks(BLOCKS_PER_REQUEST * BLOCK_SIZE)
+                .enumerate()
+                .map(|(i, chunk)| (block_id + (i * BLOCKS_PER_REQUEST) as u64, chunk))
+                .collect();
+            self.dev.write_blocks(&requests)?;
+            self.block_id += blocks as u64;
+            size
         } else {
             // partial block
             let mut data = [0u8; BLOCK_SIZE];
             let start = self.offset;
             let count = buf.len().min(BLOCK_SIZE - self.offset);
 
-            self.dev.read_block(self.block_id, &mut data)?;
+            self.dev
+                .read_blocks(&mut [(self.block_id, &mut data[..])])?;
             data[start..start + count].copy_from_slice(&buf[..count]);
-            self.dev.write_block(self.block_id, &data)?;
+            self.dev.write_blocks(&[(self.block_id, &data[..])])?;
 
             self.offset += count;
             if self.offset >= BLOCK_SIZE {
@@ -90,4 +309,11 @@ impl Disk {
         };
         Ok(write_size)
     }
+
+    /// The number of whole blocks in `len` bytes, not beyond the end of the
+    /// disk.
+    fn whole_blocks(&self, len: usize) -> usize {
+        let remaining = self.dev.num_blocks().saturating_sub(self.block_id);
+        (len / BLOCK_SIZE).min(remaining as usize).max(1)
+    }
 }
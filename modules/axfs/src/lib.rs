@@ -7,24 +7,29 @@ extern crate alloc;
 mod dev;
 mod fs;
 mod root;
+#[cfg(feature = "devfs")]
+mod tty;
 
 pub mod api;
 pub mod fops;
 
-use driver_common::BaseDriverOps;
+use alloc::boxed::Box;
+use driver_block::BlockDriverOps;
 
-cfg_if::cfg_if! {
-    if #[cfg(feature = "use-virtio-blk")] {
-        type BlockDevice = axdriver::VirtIoBlockDev;
-    } else if #[cfg(feature = "use-ramdisk")] {
-        type BlockDevice = driver_block::ramdisk::RamDisk;
-    }
-}
+/// The block device that the root filesystem resides on, of any driver.
+type BlockDevice = Box<dyn BlockDriverOps>;
 
-pub fn init_filesystems(blk_dev: BlockDevice) {
+/// Initializes the filesystems on `blk_dev`, whose interrupt is `irq_num`.
+///
+/// Without an IRQ, the block device is accessed by its blocking operations.
+pub fn init_filesystems(blk_dev: BlockDevice, irq_num: Option<usize>) {
     info!("Initialize filesystems...");
     info!("  use block device: {:?}", blk_dev.device_name());
+    if let Some(irq_num) = irq_num {
+        info!("  irq: {}", irq_num);
+    }
 
-    let disk = self::dev::Disk::new(blk_dev);
+    let queue = self::dev::BlockQueue::new(blk_dev, irq_num);
+    let disk = self::dev::Disk::new(queue);
     self::root::init_rootfs(disk);
 }
@@ -156,6 +156,13 @@ pub(crate) fn init_rootfs(disk: crate::dev::Disk) {
         devfs.add("null", Arc::new(null));
         devfs.add("zero", Arc::new(zero));
         foo_dir.add("bar", Arc::new(bar));
+        for (port, name) in crate::tty::TTY_NAMES
+            .into_iter()
+            .enumerate()
+            .take(axhal::serial::num_ports())
+        {
+            devfs.add(name, Arc::new(crate::tty::SerialDev::new(port)));
+        }
 
         root_dir
             .mount("/dev", Arc::new(devfs))
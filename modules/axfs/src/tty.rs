@@ -0,0 +1,56 @@
+//! Serial port nodes (`/dev/ttyS*`) in devfs.
+
+use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
+use axhal::serial::MAX_SERIAL_PORTS;
+
+/// The node names of the serial ports, by port number.
+pub(crate) const TTY_NAMES: [&str; MAX_SERIAL_PORTS] = ["ttyS0", "ttyS1", "ttyS2", "ttyS3"];
+
+/// A serial port, see [`axhal::serial`].
+///
+/// Reads block until at least one byte is received, writes never block for
+/// long.
+pub(crate) struct SerialDev {
+    port: usize,
+}
+
+impl SerialDev {
+    pub const fn new(port: usize) -> Self {
+        Self { port }
+    }
+}
+
+impl VfsNodeOps for SerialDev {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::default_file(),
+            VfsNodeType::CharDevice,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let n = axhal::serial::read_bytes(self.port, buf);
+            if n > 0 {
+                return Ok(n);
+            }
+            axtask::yield_now();
+        }
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        axhal::serial::write_bytes(self.port, buf);
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
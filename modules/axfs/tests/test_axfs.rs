@@ -1,5 +1,3 @@
-#![cfg(not(feature = "use-virtio-blk"))]
-
 use axfs::api as fs;
 use axio as io;
 
@@ -257,7 +255,7 @@ fn test_axfs() {
     axtask::init_scheduler(); // call this to use `axsync::Mutex`.
 
     let disk = make_disk().expect("failed to load disk image");
-    axfs::init_filesystems(disk);
+    axfs::init_filesystems(Box::new(disk), None);
 
     test_read_write_file().expect("test_read_write_file() failed");
     test_read_dir().expect("test_read_dir() failed");
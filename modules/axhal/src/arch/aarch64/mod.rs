@@ -65,6 +65,54 @@ pub fn flush_icache_all() {
     unsafe { asm!("ic iallu; dsb sy; isb") };
 }
 
+/// Returns the minimum size of the data cache lines.
+#[inline]
+fn dcache_line_size() -> usize {
+    let ctr: usize;
+    unsafe { asm!("mrs {}, ctr_el0", out(reg) ctr) };
+    // `DminLine` is the log2 of the number of words
+    4 << ((ctr >> 16) & 0xf)
+}
+
+/// Writes back the dirty data cache lines of `[vaddr, vaddr + size)` to the
+/// memory, so that the devices can read the latest data.
+pub fn clean_dcache_range(vaddr: VirtAddr, size: usize) {
+    let line = dcache_line_size();
+    let end = vaddr.as_usize() + size;
+    for addr in (vaddr.as_usize() & !(line - 1)..end).step_by(line) {
+        unsafe { asm!("dc cvac, {}", in(reg) addr) };
+    }
+    unsafe { asm!("dsb sy") };
+}
+
+/// Discards the data cache lines of `[vaddr, vaddr + size)`, so that the
+/// data written to the memory by the devices can be read.
+///
+/// The partial lines at both ends are written back first, to keep the other
+/// data in them.
+pub fn invalidate_dcache_range(vaddr: VirtAddr, size: usize) {
+    let line = dcache_line_size();
+    let (start, end) = (vaddr.as_usize(), vaddr.as_usize() + size);
+    for addr in (start & !(line - 1)..end).step_by(line) {
+        if addr < start || addr + line > end {
+            unsafe { asm!("dc civac, {}", in(reg) addr) };
+        } else {
+            unsafe { asm!("dc ivac, {}", in(reg) addr) };
+        }
+    }
+    unsafe { asm!("dsb sy") };
+}
+
+/// Writes back and discards the data cache lines of `[vaddr, vaddr + size)`.
+pub fn flush_dcache_range(vaddr: VirtAddr, size: usize) {
+    let line = dcache_line_size();
+    let end = vaddr.as_usize() + size;
+    for addr in (vaddr.as_usize() & !(line - 1)..end).step_by(line) {
+        unsafe { asm!("dc civac, {}", in(reg) addr) };
+    }
+    unsafe { asm!("dsb sy") };
+}
+
 #[inline]
 pub fn set_exception_vector_base(vbar_el1: usize) {
     VBAR_EL1.set(vbar_el1 as _);
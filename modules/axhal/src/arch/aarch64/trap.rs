@@ -1,6 +1,8 @@
 use core::arch::global_asm;
 
 use aarch64_cpu::registers::{ESR_EL1, FAR_EL1};
+use memory_addr::VirtAddr;
+use page_table_entry::MappingFlags;
 use tock_registers::interfaces::Readable;
 
 use super::TrapFrame;
@@ -35,45 +37,69 @@ fn invalid_exception(tf: &mut TrapFrame, kind: TrapKind, source: TrapSource) {
     );
 }
 
+/// Whether the fault status code (IFSC/DFSC) in `iss` indicates a
+/// translation fault or a permission fault, which may be resolved by
+/// updating the page table.
+fn is_valid_page_fault(iss: u64) -> bool {
+    let fsc = iss & 0b111111;
+    // 0b0001xx: translation fault, 0b0011xx: permission fault
+    matches!(fsc & 0b111100, 0b000100 | 0b001100)
+}
+
+fn handle_page_fault(tf: &TrapFrame, iss: u64, access_flags: MappingFlags, is_user: bool) {
+    let vaddr = VirtAddr::from(FAR_EL1.get() as usize);
+    if !is_valid_page_fault(iss)
+        || !crate::trap::handle_page_fault_extern(vaddr, access_flags, is_user)
+    {
+        panic!(
+            "Unhandled {} Page Fault @ {:#x}, fault_vaddr={:#x}, ISS={:#x} ({:?}):\n{:#x?}",
+            if is_user { "EL0" } else { "EL1" },
+            tf.elr,
+            vaddr,
+            iss,
+            access_flags,
+            tf,
+        );
+    }
+}
+
+fn handle_instruction_abort(tf: &TrapFrame, iss: u64, is_user: bool) {
+    handle_page_fault(tf, iss, MappingFlags::EXECUTE, is_user);
+}
+
+fn handle_data_abort(tf: &TrapFrame, iss: u64, is_user: bool) {
+    // ISS[6]: WnR, whether the abort is caused by a write
+    let access_flags = if iss & (1 << 6) != 0 {
+        MappingFlags::WRITE
+    } else {
+        MappingFlags::READ
+    };
+    handle_page_fault(tf, iss, access_flags, is_user);
+}
+
 #[no_mangle]
 fn handle_sync_exception(tf: &mut TrapFrame) {
     let esr = ESR_EL1.extract();
+    let iss = esr.read(ESR_EL1::ISS);
     match esr.read_as_enum(ESR_EL1::EC) {
         Some(ESR_EL1::EC::Value::Brk64) => {
-            let iss = esr.read(ESR_EL1::ISS);
             debug!("BRK #{:#x} @ {:#x} ", iss, tf.elr);
             tf.elr += 4;
         }
         Some(ESR_EL1::EC::Value::SVC64) => {
             warn!("No supervisor call is supported currently!");
         }
-        Some(ESR_EL1::EC::Value::DataAbortLowerEL)
-        | Some(ESR_EL1::EC::Value::InstrAbortLowerEL) => {
-            let iss = esr.read(ESR_EL1::ISS);
-            warn!(
-                "EL0 Page Fault @ {:#x}, FAR={:#x}, ISS={:#x}",
-                tf.elr,
-                FAR_EL1.get(),
-                iss
-            );
-        }
-        Some(ESR_EL1::EC::Value::DataAbortCurrentEL)
-        | Some(ESR_EL1::EC::Value::InstrAbortCurrentEL) => {
-            let iss = esr.read(ESR_EL1::ISS);
-            panic!(
-                "EL1 Page Fault @ {:#x}, FAR={:#x}, ISS={:#x}",
-                tf.elr,
-                FAR_EL1.get(),
-                iss
-            );
-        }
+        Some(ESR_EL1::EC::Value::InstrAbortLowerEL) => handle_instruction_abort(tf, iss, true),
+        Some(ESR_EL1::EC::Value::InstrAbortCurrentEL) => handle_instruction_abort(tf, iss, false),
+        Some(ESR_EL1::EC::Value::DataAbortLowerEL) => handle_data_abort(tf, iss, true),
+        Some(ESR_EL1::EC::Value::DataAbortCurrentEL) => handle_data_abort(tf, iss, false),
         _ => {
             panic!(
                 "Unhandled synchronous exception @ {:#x}: ESR={:#x} (EC {:#08b}, ISS {:#x})",
                 tf.elr,
                 esr.get(),
                 esr.read(ESR_EL1::EC),
-                esr.read(ESR_EL1::ISS),
+                iss,
             );
         }
     }
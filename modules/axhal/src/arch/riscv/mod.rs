@@ -42,6 +42,9 @@ pub unsafe fn write_page_table_root(root_paddr: PhysAddr) {
     let old_root = read_page_table_root();
     trace!("set page table root: {:#x} => {:#x}", old_root, root_paddr);
     if old_root != root_paddr {
+        #[cfg(target_arch = "riscv32")]
+        satp::set(satp::Mode::Sv32, 0, root_paddr.as_usize() >> 12);
+        #[cfg(target_arch = "riscv64")]
         satp::set(satp::Mode::Sv39, 0, root_paddr.as_usize() >> 12);
         asm::sfence_vma_all();
     }
@@ -58,6 +61,18 @@ pub fn flush_tlb(vaddr: Option<VirtAddr>) {
     }
 }
 
+// No data cache maintenance is needed for DMA, the DMA of the supported
+// platforms is coherent with the caches.
+
+#[inline]
+pub fn clean_dcache_range(_vaddr: VirtAddr, _size: usize) {}
+
+#[inline]
+pub fn invalidate_dcache_range(_vaddr: VirtAddr, _size: usize) {}
+
+#[inline]
+pub fn flush_dcache_range(_vaddr: VirtAddr, _size: usize) {}
+
 #[inline]
 pub fn set_tap_vector_base(stvec: usize) {
     unsafe { stvec::write(stvec, stvec::TrapMode::Direct) }
@@ -7,6 +7,11 @@ use x86::{bits64::rflags, bits64::rflags::RFlags, controlregs, tlb};
 
 pub use context::{TaskContext, TrapFrame};
 
+// There is no trap handling on x86_64, so the page faults, e.g. of the mmap
+// pages allocated on the first access, can not be handled.
+#[cfg(all(target_os = "none", feature = "paging"))]
+compile_error!("paging is not supported on x86_64");
+
 #[inline]
 pub fn enable_irqs() {
     #[cfg(target_os = "none")]
@@ -66,3 +71,15 @@ pub fn flush_tlb(vaddr: Option<VirtAddr>) {
         unsafe { tlb::flush_all() }
     }
 }
+
+// No data cache maintenance is needed for DMA, which is coherent with the
+// caches on x86.
+
+#[inline]
+pub fn clean_dcache_range(_vaddr: VirtAddr, _size: usize) {}
+
+#[inline]
+pub fn invalidate_dcache_range(_vaddr: VirtAddr, _size: usize) {}
+
+#[inline]
+pub fn flush_dcache_range(_vaddr: VirtAddr, _size: usize) {}
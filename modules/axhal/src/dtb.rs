@@ -0,0 +1,570 @@
+//! Platform information discovered from the device tree blob (DTB).
+//!
+//! The DTB passed by the bootloader is parsed once in the early boot stage.
+//! If no valid DTB is passed, or some information is absent from it, the
+//! build-time configuration in [`axconfig`] is used instead.
+
+use fdt_parser::{Fdt, Node};
+use lazy_init::LazyInit;
+use memory_addr::PhysAddr;
+
+use crate::mem::phys_to_virt;
+
+/// The maximum number of RAM regions that can be recorded.
+pub const MAX_RAM_REGIONS: usize = 8;
+
+/// The maximum number of virtio-mmio devices that can be recorded.
+pub const MAX_VIRTIO_MMIO_DEVICES: usize = 32;
+
+/// The maximum number of UARTs that can be recorded.
+pub const MAX_UARTS: usize = 4;
+
+/// The maximum number of register regions of the interrupt controller.
+const MAX_INTC_REGS: usize = 2;
+
+/// The maximum number of entries in the `interrupt-map` of the PCI host
+/// bridge that can be recorded.
+const MAX_PCI_IRQ_MAP: usize = 32;
+
+const NS16550_COMPATIBLE: &[&str] = &["ns16550a", "ns16550"];
+const PL011_COMPATIBLE: &[&str] = &["arm,pl011"];
+const UART_COMPATIBLE: &[&str] = &["ns16550a", "ns16550", "arm,pl011"];
+const INTC_COMPATIBLE: &[&str] = &[
+    "riscv,plic0",
+    "sifive,plic-1.0.0",
+    "arm,cortex-a15-gic",
+    "arm,gic-400",
+];
+const PCI_HOST_COMPATIBLE: &[&str] = &["pci-host-ecam-generic"];
+
+/// A physical memory region, `(start, size)`.
+pub type PhysRegion = (PhysAddr, usize);
+
+/// A memory-mapped device.
+#[derive(Debug, Clone, Copy)]
+pub struct MmioDevice {
+    /// The physical address of its registers.
+    pub paddr: PhysAddr,
+    /// The size of its registers.
+    pub size: usize,
+    /// The IRQ number in the numbering of [`crate::irq`], if any.
+    pub irq: Option<usize>,
+}
+
+impl MmioDevice {
+    const EMPTY: Self = Self {
+        paddr: PhysAddr::from(0),
+        size: 0,
+        irq: None,
+    };
+}
+
+/// The register interface of a UART.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum UartModel {
+    /// NS16550(A) compatible.
+    Ns16550,
+    /// ARM PrimeCell UART (PL011).
+    Pl011,
+}
+
+impl UartModel {
+    fn from_compatible(compatible: &str) -> Option<Self> {
+        if NS16550_COMPATIBLE.contains(&compatible) {
+            Some(Self::Ns16550)
+        } else if PL011_COMPATIBLE.contains(&compatible) {
+            Some(Self::Pl011)
+        } else {
+            None
+        }
+    }
+}
+
+/// A UART.
+#[derive(Debug, Clone, Copy)]
+pub struct UartDevice {
+    /// The register interface.
+    pub model: UartModel,
+    /// The registers and the IRQ.
+    pub mmio: MmioDevice,
+    /// The log2 of the spacing of the registers (`reg-shift`).
+    pub reg_shift: u8,
+    /// The width in bytes of the register accesses (`reg-io-width`).
+    pub reg_io_width: u8,
+}
+
+impl UartDevice {
+    const EMPTY: Self = Self {
+        model: UartModel::Ns16550,
+        mmio: MmioDevice::EMPTY,
+        reg_shift: 0,
+        reg_io_width: 1,
+    };
+}
+
+/// A PCI host bridge whose configuration space is accessed by ECAM.
+#[derive(Debug, Clone, Copy)]
+pub struct PciHost {
+    /// The physical memory region of the ECAM configuration space, which
+    /// starts at the first bus of `bus_range`.
+    pub ecam: PhysRegion,
+    /// The first and the last bus numbers.
+    pub bus_range: (u8, u8),
+    /// The 32-bit memory window to allocate BARs from, if any.
+    pub mem32: Option<PciRange>,
+}
+
+/// An address window of a PCI host bridge.
+#[derive(Debug, Clone, Copy)]
+pub struct PciRange {
+    /// The start address on the PCI bus.
+    pub pci_addr: u64,
+    /// The start address in the CPU physical address space.
+    pub paddr: PhysAddr,
+    /// The size of the window.
+    pub size: usize,
+}
+
+/// An entry of the `interrupt-map` of the PCI host bridge.
+#[derive(Clone, Copy)]
+struct PciIrqMapEntry {
+    /// The `phys.hi` cell of the child unit address (bus, device and
+    /// function), and the interrupt pin.
+    child: (u32, u32),
+    irq: usize,
+}
+
+impl PciIrqMapEntry {
+    const EMPTY: Self = Self {
+        child: (0, 0),
+        irq: 0,
+    };
+}
+
+struct PlatformInfo {
+    dtb: Option<PhysRegion>,
+    ram: [PhysRegion; MAX_RAM_REGIONS],
+    ram_num: usize,
+    cpu_num: usize,
+    timer_freq: Option<u64>,
+    /// The console is the first one.
+    uarts: [UartDevice; MAX_UARTS],
+    uart_num: usize,
+    intc: [PhysRegion; MAX_INTC_REGS],
+    intc_num: usize,
+    virtio: [MmioDevice; MAX_VIRTIO_MMIO_DEVICES],
+    virtio_num: usize,
+    pci: Option<PciHost>,
+    /// The IRQ of INTA of device 0 on the root bus, if the legacy interrupts
+    /// are routed by the standard swizzling rather than `pci_irq_map`.
+    pci_irq_base: Option<usize>,
+    pci_irq_map: [PciIrqMapEntry; MAX_PCI_IRQ_MAP],
+    pci_irq_map_num: usize,
+    pci_irq_map_mask: (u32, u32),
+}
+
+static PLATFORM_INFO: LazyInit<PlatformInfo> = LazyInit::new();
+
+fn first_reg(node: &Node) -> Option<PhysRegion> {
+    let reg = node.reg().next()?;
+    Some((
+        PhysAddr::from(reg.address as usize),
+        reg.size.unwrap_or(0) as usize,
+    ))
+}
+
+/// Reads a number of `cells` 32-bit cells from `list`.
+fn read_cells(list: &mut impl Iterator<Item = u32>, cells: u32) -> Option<u64> {
+    (0..cells).try_fold(0, |acc, _| Some(acc << 32 | list.next()? as u64))
+}
+
+/// Translates an interrupt specifier of `cells` cells, taken from `spec`, to
+/// an IRQ number.
+///
+/// Only the specifiers of the ARM GIC (3 cells: type, number, flags) and the
+/// RISC-V PLIC (1 cell) are supported. All the cells of the specifier are
+/// consumed even if it is not supported.
+fn translate_irq_spec(spec: &mut impl Iterator<Item = u32>, cells: u32) -> Option<usize> {
+    let mut raw = [0; 3];
+    for i in 0..cells as usize {
+        let cell = spec.next()?;
+        if i < raw.len() {
+            raw[i] = cell;
+        }
+    }
+    match (cells, raw[0]) {
+        (1, irq) => Some(irq as usize),
+        (3, 0) => Some(raw[1] as usize + 32), // SPI
+        (3, 1) => Some(raw[1] as usize + 16), // PPI
+        _ => None,
+    }
+}
+
+/// Translates the first interrupt specifier of `node` to an IRQ number.
+fn translate_irq(fdt: &Fdt, node: &Node) -> Option<usize> {
+    let parent = node
+        .interrupt_parent()
+        .or_else(|| fdt.root().interrupt_parent())?;
+    let intc = fdt.find_phandle(parent)?;
+    let cells = intc.property("#interrupt-cells")?.as_u32()?;
+    translate_irq_spec(&mut node.interrupts(), cells)
+}
+
+/// Returns the UART described by `node`, or `None` if it is disabled or not
+/// supported.
+fn uart_device(fdt: &Fdt, node: &Node) -> Option<UartDevice> {
+    if node.property("status").and_then(|p| p.as_str()) == Some("disabled") {
+        return None;
+    }
+    let model = node.compatible().find_map(UartModel::from_compatible)?;
+    let (paddr, size) = first_reg(node)?;
+    let irq = translate_irq(fdt, node);
+    let reg_shift = node.property("reg-shift").and_then(|p| p.as_u32());
+    let reg_io_width = node.property("reg-io-width").and_then(|p| p.as_u32());
+    Some(UartDevice {
+        model,
+        mmio: MmioDevice { paddr, size, irq },
+        reg_shift: reg_shift.unwrap_or(0) as u8,
+        reg_io_width: reg_io_width.unwrap_or(1) as u8,
+    })
+}
+
+/// Returns the 32-bit memory window in the `ranges` of the PCI host bridge.
+///
+/// Each entry is a 3-cell PCI address, whose space code (bits 24-25 of the
+/// first cell) is `0b10` for the 32-bit memory space, followed by the CPU
+/// address and the size.
+fn pci_mem32_range(node: &Node) -> Option<PciRange> {
+    let parent_address_cells = node.parent_cells().0;
+    let size_cells = node.size_cells();
+    let mut ranges = node.property("ranges")?.as_u32_list();
+    loop {
+        let space = (ranges.next()? >> 24) & 0b11;
+        let pci_addr = read_cells(&mut ranges, 2)?;
+        let paddr = read_cells(&mut ranges, parent_address_cells)?;
+        let size = read_cells(&mut ranges, size_cells)?;
+        if space == 0b10 && size > 0 {
+            return Some(PciRange {
+                pci_addr,
+                paddr: PhysAddr::from(paddr as usize),
+                size: size as usize,
+            });
+        }
+    }
+}
+
+impl PlatformInfo {
+    /// The information from the build-time configuration.
+    fn from_config() -> Self {
+        let mut info = Self {
+            dtb: None,
+            ram: [(PhysAddr::from(0), 0); MAX_RAM_REGIONS],
+            ram_num: 1,
+            cpu_num: axconfig::SMP,
+            timer_freq: None,
+            uarts: [UartDevice::EMPTY; MAX_UARTS],
+            uart_num: 0,
+            intc: [(PhysAddr::from(0), 0); MAX_INTC_REGS],
+            intc_num: 0,
+            virtio: [MmioDevice::EMPTY; MAX_VIRTIO_MMIO_DEVICES],
+            virtio_num: 0,
+            pci: None,
+            pci_irq_base: None,
+            pci_irq_map: [PciIrqMapEntry::EMPTY; MAX_PCI_IRQ_MAP],
+            pci_irq_map_num: 0,
+            pci_irq_map_mask: (0, 0),
+        };
+        info.ram[0] = (
+            PhysAddr::from(axconfig::PHYS_MEMORY_BASE),
+            axconfig::PHYS_MEMORY_SIZE,
+        );
+        if let Some(model) = UartModel::from_compatible(axconfig::UART_COMPATIBLE) {
+            info.uarts[0] = UartDevice {
+                model,
+                mmio: MmioDevice {
+                    paddr: PhysAddr::from(axconfig::UART_PADDR),
+                    size: 0x1000,
+                    irq: Some(axconfig::UART_IRQ),
+                },
+                ..UartDevice::EMPTY
+            };
+            info.uart_num = 1;
+        }
+        for (i, reg) in axconfig::VIRTIO_MMIO_REGIONS
+            .iter()
+            .take(MAX_VIRTIO_MMIO_DEVICES)
+            .enumerate()
+        {
+            info.virtio[i] = MmioDevice {
+                paddr: PhysAddr::from(reg.0),
+                size: reg.1,
+                irq: Some(axconfig::VIRTIO_MMIO_IRQ_BASE + i),
+            };
+            info.virtio_num += 1;
+        }
+        if axconfig::PCI_ECAM_BASE != 0 {
+            info.pci = Some(PciHost {
+                ecam: (
+                    PhysAddr::from(axconfig::PCI_ECAM_BASE),
+                    (axconfig::PCI_BUS_END + 1) << 20,
+                ),
+                bus_range: (0, axconfig::PCI_BUS_END as u8),
+                mem32: axconfig::PCI_RANGES.get(1).map(|&(base, size)| PciRange {
+                    pci_addr: base as u64,
+                    paddr: PhysAddr::from(base),
+                    size,
+                }),
+            });
+            info.pci_irq_base = Some(axconfig::PCI_IRQ_BASE);
+        }
+        info
+    }
+
+    /// Records the PCI host bridge `node`, and its legacy interrupt routing.
+    fn update_pci_from_fdt(&mut self, fdt: &Fdt, node: &Node) {
+        let reg = match node.reg().next() {
+            Some(reg) => reg,
+            None => return,
+        };
+        let mut bus_range = node
+            .property("bus-range")
+            .map(|p| p.as_u32_list())
+            .into_iter()
+            .flatten();
+        let bus_start = bus_range.next().unwrap_or(0).min(0xff) as u8;
+        let bus_end = bus_range
+            .next()
+            .unwrap_or(0xff)
+            .clamp(bus_start as u32, 0xff) as u8;
+        let ecam_size =
+            (((bus_end - bus_start) as usize + 1) << 20).min(reg.size.unwrap_or(u64::MAX) as usize);
+        self.pci = Some(PciHost {
+            ecam: (PhysAddr::from(reg.address as usize), ecam_size),
+            bus_range: (bus_start, bus_end),
+            mem32: pci_mem32_range(node),
+        });
+
+        // Each entry of `interrupt-map` is a child unit address (3 cells), a
+        // child interrupt specifier (the pin, 1 cell), the phandle of the
+        // interrupt controller, the parent unit address (`#address-cells` of
+        // the controller) and the parent interrupt specifier.
+        let mut mask = node
+            .property("interrupt-map-mask")
+            .map(|p| p.as_u32_list())
+            .into_iter()
+            .flatten();
+        let mask_hi = mask.next().unwrap_or(u32::MAX);
+        let mask_pin = mask.nth(2).unwrap_or(u32::MAX);
+        let mut map = match node.property("interrupt-map") {
+            Some(p) => p.as_u32_list(),
+            None => return,
+        };
+        let mut map_num = 0;
+        while let (Some(hi), _, _, Some(pin)) = (map.next(), map.next(), map.next(), map.next()) {
+            let intc = match map.next().and_then(|phandle| fdt.find_phandle(phandle)) {
+                Some(intc) => intc,
+                None => break,
+            };
+            let address_cells = intc
+                .property("#address-cells")
+                .and_then(|p| p.as_u32())
+                .unwrap_or(0);
+            let interrupt_cells = intc
+                .property("#interrupt-cells")
+                .and_then(|p| p.as_u32())
+                .unwrap_or(1);
+            if read_cells(&mut map, address_cells).is_none() {
+                break;
+            }
+            if let Some(irq) = translate_irq_spec(&mut map, interrupt_cells) {
+                if map_num >= MAX_PCI_IRQ_MAP {
+                    warn!("too many entries in the PCI interrupt map");
+                    break;
+                }
+                self.pci_irq_map[map_num] = PciIrqMapEntry {
+                    child: (hi & mask_hi, pin & mask_pin),
+                    irq,
+                };
+                map_num += 1;
+            }
+        }
+        if map_num > 0 {
+            self.pci_irq_base = None;
+            self.pci_irq_map_num = map_num;
+            self.pci_irq_map_mask = (mask_hi, mask_pin);
+        }
+    }
+
+    /// Overrides the information found in the DTB.
+    fn update_from_fdt(&mut self, fdt: &Fdt, dtb_paddr: usize) {
+        self.dtb = Some((PhysAddr::from(dtb_paddr), fdt.total_size()));
+
+        let mut ram_num = 0;
+        for reg in fdt.memory_regions() {
+            match reg.size {
+                Some(size) if size > 0 && ram_num < MAX_RAM_REGIONS => {
+                    self.ram[ram_num] = (PhysAddr::from(reg.address as usize), size as usize);
+                    ram_num += 1;
+                }
+                _ => {}
+            }
+        }
+        if ram_num > 0 {
+            self.ram_num = ram_num;
+        }
+
+        let cpu_num = fdt.cpus().count();
+        if cpu_num > 0 {
+            self.cpu_num = cpu_num;
+        }
+        self.timer_freq = fdt
+            .find_node("/cpus")
+            .and_then(|cpus| cpus.property("timebase-frequency"))
+            .and_then(|p| p.as_u64());
+
+        let console = fdt.stdout().and_then(|n| uart_device(fdt, &n));
+        let uarts = fdt
+            .all_compatible(UART_COMPATIBLE)
+            .filter_map(|n| uart_device(fdt, &n));
+        let mut uart_num = 0;
+        for uart in console.into_iter().chain(uarts) {
+            if uart_num > 0 && uart.mmio.paddr == self.uarts[0].mmio.paddr {
+                continue; // the console found again
+            }
+            if uart_num >= MAX_UARTS {
+                warn!("too many UARTs in the device tree");
+                break;
+            }
+            self.uarts[uart_num] = uart;
+            uart_num += 1;
+        }
+        if uart_num > 0 {
+            self.uart_num = uart_num;
+        }
+
+        if let Some(intc) = fdt.find_compatible(INTC_COMPATIBLE) {
+            self.intc_num = 0;
+            for reg in intc.reg().take(MAX_INTC_REGS) {
+                self.intc[self.intc_num] = (
+                    PhysAddr::from(reg.address as usize),
+                    reg.size.unwrap_or(0) as usize,
+                );
+                self.intc_num += 1;
+            }
+        }
+
+        let mut virtio_num = 0;
+        for node in fdt.all_compatible(&["virtio,mmio"]) {
+            if virtio_num >= MAX_VIRTIO_MMIO_DEVICES {
+                warn!("too many virtio-mmio devices in the device tree");
+                break;
+            }
+            if let Some((paddr, size)) = first_reg(&node) {
+                let irq = translate_irq(fdt, &node);
+                self.virtio[virtio_num] = MmioDevice { paddr, size, irq };
+                virtio_num += 1;
+            }
+        }
+        if virtio_num > 0 {
+            self.virtio_num = virtio_num;
+        }
+
+        if let Some(pci) = fdt.find_compatible(PCI_HOST_COMPATIBLE) {
+            self.update_pci_from_fdt(fdt, &pci);
+        }
+    }
+}
+
+/// Parses the DTB at physical address `dtb_paddr`.
+///
+/// It must be called only once, after the `.bss` section is cleared, and
+/// the DTB must be in the memory mapped by the boot page table. Platforms
+/// without a DTB must also call it with `dtb_paddr` 0, before any other
+/// functions in this module are used.
+#[allow(dead_code)]
+pub(crate) fn init(dtb_paddr: usize) {
+    let mut info = PlatformInfo::from_config();
+    if dtb_paddr != 0 {
+        let ptr = phys_to_virt(dtb_paddr.into()).as_ptr();
+        if let Ok(fdt) = unsafe { Fdt::from_ptr(ptr) } {
+            info.update_from_fdt(&fdt, dtb_paddr);
+        }
+    }
+    PLATFORM_INFO.init_by(info);
+}
+
+fn info() -> &'static PlatformInfo {
+    PLATFORM_INFO
+        .try_get()
+        .expect("the platform info is used before `dtb::init()`")
+}
+
+/// The physical memory region of the DTB, or `None` if no valid DTB was
+/// passed.
+pub fn dtb_region() -> Option<PhysRegion> {
+    info().dtb
+}
+
+/// The RAM regions.
+pub fn ram_regions() -> &'static [PhysRegion] {
+    let info = info();
+    &info.ram[..info.ram_num]
+}
+
+/// The number of CPUs on the machine.
+///
+/// It may be larger than [`axconfig::SMP`], only the first `SMP` CPUs are
+/// used.
+pub fn cpu_num() -> usize {
+    info().cpu_num
+}
+
+/// The frequency of the timer (`timebase-frequency` of `/cpus`) in Hz.
+pub fn timer_frequency() -> Option<u64> {
+    info().timer_freq
+}
+
+/// The UARTs, the first one is used as the console.
+pub fn uarts() -> &'static [UartDevice] {
+    let info = info();
+    &info.uarts[..info.uart_num]
+}
+
+/// The register regions of the interrupt controller.
+///
+/// They are the distributor and the CPU interface of the GIC, or the only
+/// region of the PLIC.
+pub fn intc_regions() -> &'static [PhysRegion] {
+    let info = info();
+    &info.intc[..info.intc_num]
+}
+
+/// The virtio-mmio devices.
+pub fn virtio_mmio_devices() -> &'static [MmioDevice] {
+    let info = info();
+    &info.virtio[..info.virtio_num]
+}
+
+/// The PCI host bridge, or `None` if the platform does not have one.
+pub fn pci_host() -> Option<PciHost> {
+    info().pci
+}
+
+/// Returns the IRQ number of the legacy interrupt `pin` (`1..=4` for INTA# to
+/// INTD#) of a PCI function, or `None` if it is not routed.
+pub fn pci_legacy_irq(bus: u8, device: u8, function: u8, pin: u8) -> Option<usize> {
+    if !(1..=4).contains(&pin) {
+        return None;
+    }
+    let info = info();
+    if let Some(base) = info.pci_irq_base {
+        return Some(base + (device as usize + pin as usize - 1) % 4);
+    }
+    let hi = (bus as u32) << 16 | (device as u32) << 11 | (function as u32) << 8;
+    let (mask_hi, mask_pin) = info.pci_irq_map_mask;
+    let child = (hi & mask_hi, pin as u32 & mask_pin);
+    info.pci_irq_map[..info.pci_irq_map_num]
+        .iter()
+        .find(|entry| entry.child == child)
+        .map(|entry| entry.irq)
+}
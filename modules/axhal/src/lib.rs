@@ -13,17 +13,34 @@ mod platform;
 
 pub mod arch;
 pub mod cpu;
+pub mod dtb;
 pub mod irq;
 pub mod mem;
+pub mod serial;
 pub mod time;
 pub mod trap;
 
 #[cfg(feature = "paging")]
 pub mod paging;
 
+/// The console, which is the serial port 0.
 pub mod console {
-    pub use super::platform::console::*;
+    use crate::serial;
 
+    /// Writes a byte to the console, `\n` is written as `\r\n`.
+    pub fn putchar(c: u8) {
+        match c {
+            b'\n' => serial::write_bytes(0, b"\r\n"),
+            c => serial::putchar(0, c),
+        }
+    }
+
+    /// Reads a byte from the console without blocking.
+    pub fn getchar() -> Option<u8> {
+        serial::getchar(0)
+    }
+
+    /// Writes bytes to the console.
     pub fn write_bytes(bytes: &[u8]) {
         for c in bytes {
             putchar(*c);
@@ -37,13 +37,15 @@ impl Iterator for MemRegionIter {
 
     fn next(&mut self) -> Option<Self::Item> {
         use crate::platform::mem::{memory_region_at, memory_regions_num};
-        let ret = if self.idx < memory_regions_num() {
-            memory_region_at(self.idx)
-        } else {
-            None
-        };
-        self.idx += 1;
-        ret
+        // some regions are optional, skip the absent ones
+        while self.idx < memory_regions_num() {
+            let ret = memory_region_at(self.idx);
+            self.idx += 1;
+            if ret.is_some() {
+                return ret;
+            }
+        }
+        None
     }
 }
 
@@ -120,6 +122,73 @@ pub(crate) fn common_memory_region_at(idx: usize) -> Option<MemRegion> {
     Some(r)
 }
 
+/// Returns the free memory after the kernel image.
+///
+/// It ends at the end of the RAM region containing the kernel, but not beyond
+/// `mapped_end`, the end of the physical memory mapped by the boot page table.
+/// The DTB is excluded if it lies in between.
+#[allow(dead_code)]
+pub(crate) fn free_memory_region(mapped_end: usize) -> MemRegion {
+    let start = virt_to_phys((ekernel as usize).into()).align_up_4k();
+    let ram_end = crate::dtb::ram_regions()
+        .iter()
+        .map(|&(base, size)| (base.as_usize(), base.as_usize() + size))
+        .find(|&(base, end)| (base..end).contains(&start.as_usize()))
+        .map_or(axconfig::PHYS_MEMORY_END, |(_, end)| end);
+    let mut end = ram_end.min(mapped_end);
+    if let Some((dtb_paddr, _)) = crate::dtb::dtb_region() {
+        if (start.as_usize()..end).contains(&dtb_paddr.as_usize()) {
+            end = dtb_paddr.as_usize();
+        }
+    }
+    let end = PhysAddr::from(end).align_down_4k();
+    MemRegion {
+        paddr: start,
+        size: end.as_usize() - start.as_usize(),
+        flags: MemRegionFlags::FREE | MemRegionFlags::READ | MemRegionFlags::WRITE,
+        name: "free memory",
+    }
+}
+
+/// Returns the memory occupied by the DTB, or `None` if no DTB was passed.
+#[allow(dead_code)]
+pub(crate) fn dtb_memory_region() -> Option<MemRegion> {
+    let (paddr, size) = crate::dtb::dtb_region()?;
+    let start = paddr.align_down_4k();
+    let end = (paddr + size).align_up_4k();
+    Some(MemRegion {
+        paddr: start,
+        size: end.as_usize() - start.as_usize(),
+        flags: MemRegionFlags::RESERVED | MemRegionFlags::READ,
+        name: "dtb",
+    })
+}
+
+/// Returns the regions of the PCI host bridge to be mapped: the ECAM
+/// configuration space (`idx` 0), and the 32-bit memory window for BARs
+/// (`idx` 1). Returns `None` if the region does not exist.
+#[allow(dead_code)]
+pub(crate) fn pci_memory_region_at(idx: usize) -> Option<MemRegion> {
+    let pci = crate::dtb::pci_host()?;
+    let (paddr, size, name) = match idx {
+        0 => (pci.ecam.0, pci.ecam.1, "pci ecam"),
+        1 => {
+            let mem32 = pci.mem32?;
+            (mem32.paddr, mem32.size, "pci mem32")
+        }
+        _ => return None,
+    };
+    Some(MemRegion {
+        paddr,
+        size,
+        flags: MemRegionFlags::RESERVED
+            | MemRegionFlags::DEVICE
+            | MemRegionFlags::READ
+            | MemRegionFlags::WRITE,
+        name,
+    })
+}
+
 #[allow(dead_code)]
 pub(crate) fn clear_bss() {
     unsafe {
@@ -141,4 +210,5 @@ extern "C" {
     fn boot_stack_top();
     fn percpu_start();
     fn percpu_end();
+    fn ekernel();
 }
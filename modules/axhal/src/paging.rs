@@ -39,6 +39,17 @@ impl PagingIf for PagingIfImpl {
         global_allocator().dealloc_pages(phys_to_virt(paddr).as_usize(), 1)
     }
 
+    fn alloc_frames(num: usize, align: usize) -> Option<PhysAddr> {
+        global_allocator()
+            .alloc_pages(num, align)
+            .map(|vaddr| virt_to_phys(vaddr.into()))
+            .ok()
+    }
+
+    fn dealloc_frames(paddr: PhysAddr, num: usize) {
+        global_allocator().dealloc_pages(phys_to_virt(paddr).as_usize(), num)
+    }
+
     #[inline]
     fn phys_to_virt(paddr: PhysAddr) -> VirtAddr {
         phys_to_virt(paddr)
@@ -48,7 +59,9 @@ impl PagingIf for PagingIfImpl {
 cfg_if::cfg_if! {
     if #[cfg(target_arch = "x86_64")] {
         pub type PageTable = page_table::x86_64::X64PageTable<PagingIfImpl>;
-    } else if #[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))] {
+    } else if #[cfg(target_arch = "riscv32")] {
+        pub type PageTable = page_table::riscv::Sv32PageTable<PagingIfImpl>;
+    } else if #[cfg(target_arch = "riscv64")] {
         pub type PageTable = page_table::riscv::Sv39PageTable<PagingIfImpl>;
     } else if #[cfg(target_arch = "aarch64")]{
         pub type PageTable = page_table::aarch64::A64PageTable<PagingIfImpl>;
@@ -3,16 +3,6 @@
 
 pub(crate) fn handle_irq(_irq_num: usize) {}
 
-pub mod console {
-    pub fn putchar(c: u8) {
-        unimplemented!()
-    }
-
-    pub fn getchar() -> Option<u8> {
-        unimplemented!()
-    }
-}
-
 pub mod misc {
     pub fn terminate() -> ! {
         unimplemented!()
@@ -75,7 +75,10 @@ unsafe fn init_mmu() {
     // Normal memory
     let attr1 = MAIR_EL1::Attr1_Normal_Inner::WriteBack_NonTransient_ReadWriteAlloc
         + MAIR_EL1::Attr1_Normal_Outer::WriteBack_NonTransient_ReadWriteAlloc;
-    MAIR_EL1.write(attr0 + attr1); // 0xff_04
+    // Normal non-cacheable memory
+    let attr2 =
+        MAIR_EL1::Attr2_Normal_Inner::NonCacheable + MAIR_EL1::Attr2_Normal_Outer::NonCacheable;
+    MAIR_EL1.write(attr0 + attr1 + attr2); // 0x44_ff_04
 
     // Enable TTBR0 and TTBR1 walks, page size = 4K, vaddr size = 48 bits, paddr size = 40 bits.
     let tcr_flags0 = TCR_EL1::EPD0::EnableTTBR0Walks
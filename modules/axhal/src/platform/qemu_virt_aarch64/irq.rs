@@ -1,20 +1,21 @@
 use crate::irq::IrqHandler;
 use crate::mem::phys_to_virt;
 use arm_gic::gic_v2::{GicCpuInterface, GicDistributor};
+use lazy_init::LazyInit;
 use memory_addr::PhysAddr;
 use spinlock::SpinNoIrq;
 
 pub const MAX_IRQ_COUNT: usize = 1024;
 
+// the default base addresses if not found in the device tree
 const GIC_BASE: usize = 0x0800_0000;
 const GICD_BASE: PhysAddr = PhysAddr::from(GIC_BASE);
 const GICC_BASE: PhysAddr = PhysAddr::from(GIC_BASE + 0x10000);
 
-static GICD: SpinNoIrq<GicDistributor> =
-    SpinNoIrq::new(GicDistributor::new(phys_to_virt(GICD_BASE).as_mut_ptr()));
+static GICD: LazyInit<SpinNoIrq<GicDistributor>> = LazyInit::new();
 
 // per-CPU, no lock
-static GICC: GicCpuInterface = GicCpuInterface::new(phys_to_virt(GICC_BASE).as_mut_ptr());
+static GICC: LazyInit<GicCpuInterface> = LazyInit::new();
 
 pub fn set_enable(irq_num: usize, enabled: bool) {
     GICD.lock().set_enable(irq_num as _, enabled);
@@ -29,6 +30,14 @@ pub fn dispatch_irq(_unused: usize) {
 }
 
 pub(super) fn init() {
+    let (gicd_base, gicc_base) = match crate::dtb::intc_regions() {
+        [gicd, gicc, ..] => (gicd.0, gicc.0),
+        _ => (GICD_BASE, GICC_BASE),
+    };
+    GICD.init_by(SpinNoIrq::new(GicDistributor::new(
+        phys_to_virt(gicd_base).as_mut_ptr(),
+    )));
+    GICC.init_by(GicCpuInterface::new(phys_to_virt(gicc_base).as_mut_ptr()));
     GICD.lock().init();
 }
 
@@ -1,9 +1,7 @@
 mod boot;
 mod generic_timer;
-mod pl011;
 mod psci;
 
-pub mod console;
 pub mod irq;
 pub mod mem;
 
@@ -22,13 +20,15 @@ extern "C" {
     fn exception_vector_base();
 }
 
-pub(crate) fn platform_init(cpu_id: usize, _dtb: *const u8) {
+pub(crate) fn platform_init(cpu_id: usize, dtb: *const u8) {
     crate::mem::clear_bss();
+    crate::dtb::init(dtb as usize);
+    crate::serial::init_early();
     crate::arch::set_exception_vector_base(exception_vector_base as usize);
     crate::cpu::init_percpu(cpu_id, true);
     self::irq::init();
     self::irq::init_percpu(cpu_id);
-    self::pl011::init();
+    crate::serial::init_irq();
     self::generic_timer::init();
 }
 
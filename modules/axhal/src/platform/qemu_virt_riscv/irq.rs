@@ -1,7 +1,8 @@
-//! TODO: PLIC
-
 use crate::irq::IrqHandler;
+use crate::mem::phys_to_virt;
 use lazy_init::LazyInit;
+use memory_addr::PhysAddr;
+use spinlock::SpinNoIrq;
 
 /// `Interrupt` bit in `scause`
 pub(super) const INTC_IRQ_BASE: usize = 1 << (usize::BITS - 1);
@@ -18,8 +19,22 @@ pub(super) const S_EXT: usize = INTC_IRQ_BASE + 9;
 
 static TIMER_HANDLER: LazyInit<IrqHandler> = LazyInit::new();
 
+/// The maximum number of external interrupt sources of the PLIC.
 pub const MAX_IRQ_COUNT: usize = 1024;
 
+/// The default base address if not found in the device tree.
+const DEFAULT_PLIC_BASE: PhysAddr = PhysAddr::from(0x0c00_0000);
+const PLIC_PRIORITY_OFFSET: usize = 0;
+const PLIC_ENABLE_OFFSET: usize = 0x2000;
+const PLIC_ENABLE_STRIDE: usize = 0x80;
+const PLIC_CONTEXT_OFFSET: usize = 0x20_0000;
+const PLIC_CONTEXT_STRIDE: usize = 0x1000;
+
+static PLIC_BASE: LazyInit<PhysAddr> = LazyInit::new();
+
+// protect the read-modify-write of the enable bits
+static PLIC_LOCK: SpinNoIrq<()> = SpinNoIrq::new(());
+
 macro_rules! with_cause {
     ($cause: expr, @TIMER => $timer_op: expr, @EXT => $ext_op: expr $(,)?) => {
         match $cause {
@@ -30,23 +45,54 @@ macro_rules! with_cause {
     };
 }
 
-pub fn set_enable(scause: usize, _enabled: bool) {
-    if scause == S_EXT {
-        // TODO: set enable in PLIC
+/// The PLIC context of the supervisor mode of the current hart.
+fn plic_context() -> usize {
+    crate::cpu::this_cpu_id() * 2 + 1
+}
+
+fn plic_reg(offset: usize) -> *mut u32 {
+    (phys_to_virt(*PLIC_BASE).as_usize() + offset) as *mut u32
+}
+
+fn plic_claim_reg() -> *mut u32 {
+    plic_reg(PLIC_CONTEXT_OFFSET + plic_context() * PLIC_CONTEXT_STRIDE + 4)
+}
+
+/// Enables or disables the given external IRQ in the PLIC for the current hart.
+///
+/// The timer interrupt is always enabled.
+pub fn set_enable(irq_num: usize, enabled: bool) {
+    if irq_num == 0 || irq_num >= MAX_IRQ_COUNT {
+        return;
+    }
+    let enable_reg = plic_reg(
+        PLIC_ENABLE_OFFSET + plic_context() * PLIC_ENABLE_STRIDE + irq_num / 32 * 4,
+    );
+    let bit = 1 << (irq_num % 32);
+    let _guard = PLIC_LOCK.lock();
+    unsafe {
+        plic_reg(PLIC_PRIORITY_OFFSET + irq_num * 4).write_volatile(1);
+        let val = enable_reg.read_volatile();
+        if enabled {
+            enable_reg.write_volatile(val | bit);
+        } else {
+            enable_reg.write_volatile(val & !bit);
+        }
     }
 }
 
-pub fn register_handler(scause: usize, handler: IrqHandler) -> bool {
-    with_cause!(
-        scause,
-        @TIMER => if !TIMER_HANDLER.is_init() {
+/// Registers an IRQ handler for the timer interrupt (`irq_num` is
+/// [`TIMER_IRQ_NUM`](crate::time::TIMER_IRQ_NUM)), or for an external
+/// interrupt source of the PLIC.
+pub fn register_handler(irq_num: usize, handler: IrqHandler) -> bool {
+    if irq_num == S_TIMER {
+        if !TIMER_HANDLER.is_init() {
             TIMER_HANDLER.init_by(handler);
-            true
-        } else {
-            false
-        },
-        @EXT => crate::irq::register_handler_common(scause & !INTC_IRQ_BASE, handler),
-    )
+            return true;
+        }
+        return false;
+    }
+    crate::irq::register_handler_common(irq_num, handler)
 }
 
 pub fn dispatch_irq(scause: usize) {
@@ -56,8 +102,25 @@ pub fn dispatch_irq(scause: usize) {
             trace!("IRQ: timer");
             TIMER_HANDLER();
         },
-        @EXT => crate::irq::dispatch_irq_common(0), // TODO: get IRQ number from PLIC
+        @EXT => loop {
+            let claim_reg = plic_claim_reg();
+            let irq_num = unsafe { claim_reg.read_volatile() } as usize;
+            if irq_num == 0 {
+                break;
+            }
+            crate::irq::dispatch_irq_common(irq_num);
+            unsafe { claim_reg.write_volatile(irq_num as u32) }; // complete
+        },
     );
 }
 
-pub(super) fn init() {}
+pub(super) fn init() {
+    let base = crate::dtb::intc_regions().first().map(|r| r.0);
+    PLIC_BASE.init_by(base.unwrap_or(DEFAULT_PLIC_BASE));
+}
+
+pub(super) fn init_percpu(_cpu_id: usize) {
+    // accept all interrupts with non-zero priority
+    let threshold_reg = plic_reg(PLIC_CONTEXT_OFFSET + plic_context() * PLIC_CONTEXT_STRIDE);
+    unsafe { threshold_reg.write_volatile(0) };
+}
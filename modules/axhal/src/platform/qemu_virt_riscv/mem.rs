@@ -1,27 +1,19 @@
 use crate::mem::*;
 
+/// The end of the physical memory mapped by the boot page table.
+const BOOT_MAPPED_END: usize = 0xc000_0000;
+
 pub(crate) fn memory_regions_num() -> usize {
-    common_memory_regions_num() + 1
+    common_memory_regions_num() + 4
 }
 
 pub(crate) fn memory_region_at(idx: usize) -> Option<MemRegion> {
-    use core::cmp::Ordering;
-    match idx.cmp(&common_memory_regions_num()) {
-        Ordering::Less => common_memory_region_at(idx),
-        Ordering::Equal => {
-            // free memory
-            extern "C" {
-                fn ekernel();
-            }
-            let start = virt_to_phys((ekernel as usize).into()).align_up_4k();
-            let end = PhysAddr::from(axconfig::PHYS_MEMORY_END).align_down_4k();
-            Some(MemRegion {
-                paddr: start,
-                size: end.as_usize() - start.as_usize(),
-                flags: MemRegionFlags::FREE | MemRegionFlags::READ | MemRegionFlags::WRITE,
-                name: "free memory",
-            })
-        }
-        Ordering::Greater => None,
+    let common_num = common_memory_regions_num();
+    match idx {
+        i if i < common_num => common_memory_region_at(i),
+        i if i == common_num => Some(free_memory_region(BOOT_MAPPED_END)),
+        i if i == common_num + 1 => dtb_memory_region(),
+        i if i < common_num + 4 => pci_memory_region_at(i - common_num - 2),
+        _ => None,
     }
 }
@@ -1,6 +1,5 @@
 mod boot;
 
-pub mod console;
 pub mod irq;
 pub mod mem;
 pub mod misc;
@@ -13,11 +12,16 @@ extern "C" {
     fn trap_vector_base();
 }
 
-pub(crate) fn platform_init(cpu_id: usize, _dtb: usize) {
+pub(crate) fn platform_init(cpu_id: usize, dtb: usize) {
     crate::mem::clear_bss();
+    crate::dtb::init(dtb);
+    crate::serial::init_early();
+    self::time::init_early();
     crate::arch::set_tap_vector_base(trap_vector_base as usize);
     crate::cpu::init_percpu(cpu_id, true);
     self::irq::init();
+    self::irq::init_percpu(cpu_id);
+    crate::serial::init_irq();
     self::time::init();
 }
 
@@ -25,5 +29,6 @@ pub(crate) fn platform_init(cpu_id: usize, _dtb: usize) {
 pub(crate) fn platform_init_secondary(cpu_id: usize) {
     crate::arch::set_tap_vector_base(trap_vector_base as usize);
     crate::cpu::init_percpu(cpu_id, false);
+    self::irq::init_percpu(cpu_id);
     self::time::init();
 }
@@ -1,6 +1,9 @@
+use lazy_init::LazyInit;
+use ratio::Ratio;
 use riscv::register::{sie, time};
 
-const NANOS_PER_TICK: u64 = crate::time::NANOS_PER_SEC / axconfig::TIMER_FREQUENCY as u64;
+static TICKS_TO_NANOS_RATIO: LazyInit<Ratio> = LazyInit::new();
+static NANOS_TO_TICKS_RATIO: LazyInit<Ratio> = LazyInit::new();
 
 pub const TIMER_IRQ_NUM: usize = super::irq::S_TIMER;
 
@@ -10,20 +13,26 @@ pub fn current_ticks() -> u64 {
 }
 
 #[inline]
-pub const fn ticks_to_nanos(ticks: u64) -> u64 {
-    ticks * NANOS_PER_TICK
+pub fn ticks_to_nanos(ticks: u64) -> u64 {
+    TICKS_TO_NANOS_RATIO.mul_trunc(ticks)
 }
 
 #[inline]
-pub const fn nanos_to_ticks(nanos: u64) -> u64 {
-    nanos / NANOS_PER_TICK
+pub fn nanos_to_ticks(nanos: u64) -> u64 {
+    NANOS_TO_TICKS_RATIO.mul_trunc(nanos)
 }
 
 pub fn set_oneshot_timer(deadline_ns: u64) {
     sbi_rt::set_timer(nanos_to_ticks(deadline_ns));
 }
 
-pub fn init() {
+pub(super) fn init_early() {
+    let freq = crate::dtb::timer_frequency().unwrap_or(axconfig::TIMER_FREQUENCY as u64);
+    TICKS_TO_NANOS_RATIO.init_by(Ratio::new(crate::time::NANOS_PER_SEC as u32, freq as u32));
+    NANOS_TO_TICKS_RATIO.init_by(TICKS_TO_NANOS_RATIO.inverse());
+}
+
+pub(super) fn init() {
     unsafe {
         sie::set_ssoft();
         sie::set_stimer();
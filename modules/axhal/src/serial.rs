@@ -0,0 +1,161 @@
+//! Serial ports (UARTs).
+//!
+//! All the UARTs found in the device tree (see [`crate::dtb::uarts`]) are
+//! driven natively, and port 0 is the console. When the interrupts are
+//! available, the received bytes are moved from the FIFO of the UART into a
+//! ring buffer of the port by the interrupt handler, so that they are not
+//! lost while nobody is reading.
+
+use driver_serial::{ns16550::Ns16550, pl011::Pl011, RxRingBuffer, SerialDriverOps};
+use spinlock::SpinNoIrq;
+
+use crate::dtb::{UartDevice, UartModel};
+use crate::irq::IrqHandler;
+use crate::mem::phys_to_virt;
+
+pub use crate::dtb::MAX_UARTS as MAX_SERIAL_PORTS;
+
+/// The size of the receive buffer of each port.
+const RX_BUF_SIZE: usize = 256;
+
+enum Uart {
+    Ns16550(Ns16550),
+    Pl011(Pl011),
+}
+
+impl Uart {
+    fn new(dev: &UartDevice) -> Self {
+        let base_vaddr = phys_to_virt(dev.mmio.paddr).as_usize();
+        match dev.model {
+            UartModel::Ns16550 => {
+                Self::Ns16550(Ns16550::new(base_vaddr, dev.reg_shift, dev.reg_io_width))
+            }
+            UartModel::Pl011 => Self::Pl011(Pl011::new(base_vaddr)),
+        }
+    }
+
+    fn ops(&mut self) -> &mut dyn SerialDriverOps {
+        match self {
+            Self::Ns16550(uart) => uart,
+            Self::Pl011(uart) => uart,
+        }
+    }
+}
+
+struct SerialPort {
+    uart: Uart,
+    rx_buf: RxRingBuffer<RX_BUF_SIZE>,
+}
+
+impl SerialPort {
+    /// Moves the bytes in the receive FIFO to the receive buffer.
+    ///
+    /// The bytes are dropped if the buffer is full. They can not be logged
+    /// here, as the console may be locked.
+    fn drain_fifo(&mut self) {
+        while let Some(c) = self.uart.ops().getchar() {
+            self.rx_buf.push(c);
+        }
+    }
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_PORT: SpinNoIrq<Option<SerialPort>> = SpinNoIrq::new(None);
+
+static PORTS: [SpinNoIrq<Option<SerialPort>>; MAX_SERIAL_PORTS] = [NO_PORT; MAX_SERIAL_PORTS];
+
+const RX_HANDLERS: [IrqHandler; MAX_SERIAL_PORTS] = [
+    rx_handler::<0>,
+    rx_handler::<1>,
+    rx_handler::<2>,
+    rx_handler::<3>,
+];
+
+fn rx_handler<const PORT: usize>() {
+    with_port(PORT, |port| {
+        if port.uart.ops().ack_interrupt() {
+            port.drain_fifo();
+        }
+    });
+}
+
+fn with_port<R>(port: usize, f: impl FnOnce(&mut SerialPort) -> R) -> Option<R> {
+    PORTS.get(port)?.lock().as_mut().map(f)
+}
+
+/// The number of serial ports.
+pub fn num_ports() -> usize {
+    crate::dtb::uarts().len()
+}
+
+/// Writes a byte to `port`, spinning while its transmit FIFO is full.
+///
+/// It is ignored if the port does not exist.
+pub fn putchar(port: usize, c: u8) {
+    with_port(port, |port| port.uart.ops().putchar(c));
+}
+
+/// Writes bytes to `port`, spinning while its transmit FIFO is full.
+///
+/// It is ignored if the port does not exist.
+pub fn write_bytes(port: usize, bytes: &[u8]) {
+    with_port(port, |port| {
+        for c in bytes {
+            port.uart.ops().putchar(*c);
+        }
+    });
+}
+
+/// Reads a received byte from `port` without blocking.
+///
+/// Returns `None` if nothing has been received, or the port does not exist.
+pub fn getchar(port: usize) -> Option<u8> {
+    let mut c = 0;
+    (read_bytes(port, core::slice::from_mut(&mut c)) > 0).then_some(c)
+}
+
+/// Reads the bytes received by `port` into `buf` without blocking.
+///
+/// Returns the number of bytes read, which is 0 if nothing has been received,
+/// or the port does not exist.
+pub fn read_bytes(port: usize, buf: &mut [u8]) -> usize {
+    with_port(port, |port| {
+        // the bytes not taken by the interrupt handler yet are after the
+        // buffered ones
+        port.drain_fifo();
+        port.rx_buf.read(buf)
+    })
+    .unwrap_or(0)
+}
+
+/// Initializes the UARTs with their receive interrupts masked, so that the
+/// console can be used.
+///
+/// It must be called after the DTB is parsed.
+#[allow(dead_code)]
+pub(crate) fn init_early() {
+    for (i, dev) in crate::dtb::uarts().iter().enumerate() {
+        let mut uart = Uart::new(dev);
+        uart.ops().init();
+        *PORTS[i].lock() = Some(SerialPort {
+            uart,
+            rx_buf: RxRingBuffer::new(),
+        });
+    }
+}
+
+/// Registers the interrupt handlers, and enables the receive interrupts of
+/// the UARTs.
+///
+/// It must be called after the interrupt controller is initialized.
+#[allow(dead_code)]
+pub(crate) fn init_irq() {
+    for (i, dev) in crate::dtb::uarts().iter().enumerate() {
+        if let Some(irq) = dev.mmio.irq {
+            // the port must not be locked here, as failures are logged
+            if crate::irq::register_handler(irq, RX_HANDLERS[i]) {
+                with_port(i, |port| port.uart.ops().set_rx_interrupt(true));
+            }
+        }
+    }
+}
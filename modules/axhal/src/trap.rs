@@ -1,9 +1,20 @@
 use crate_interface::{call_interface, def_interface};
+use memory_addr::VirtAddr;
+
+#[doc(no_inline)]
+pub use page_table_entry::MappingFlags;
 
 #[def_interface]
 pub trait TrapHandler {
     fn handle_irq(irq_num: usize);
-    // more e.g.: handle_page_fault();
+
+    /// Handles a page fault at `vaddr` caused by an access of `access_flags`
+    /// (one of `READ`, `WRITE` and `EXECUTE`), from the user mode if
+    /// `is_user` is true.
+    ///
+    /// Returns `true` if the page fault is resolved, and the faulting
+    /// instruction will be retried. The trap handler panics otherwise.
+    fn handle_page_fault(vaddr: VirtAddr, access_flags: MappingFlags, is_user: bool) -> bool;
 }
 
 /// Call the external IRQ handler.
@@ -11,3 +22,13 @@ pub trait TrapHandler {
 pub(crate) fn handle_irq_extern(irq_num: usize) {
     call_interface!(TrapHandler::handle_irq, irq_num);
 }
+
+/// Call the external page fault handler.
+#[allow(dead_code)]
+pub(crate) fn handle_page_fault_extern(
+    vaddr: VirtAddr,
+    access_flags: MappingFlags,
+    is_user: bool,
+) -> bool {
+    call_interface!(TrapHandler::handle_page_fault, vaddr, access_flags, is_user)
+}
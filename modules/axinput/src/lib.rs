@@ -0,0 +1,65 @@
+//! Input devices (e.g., keyboards and mice) of ArceOS.
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+#[doc(no_inline)]
+pub use driver_input::{EventType, InputEvent};
+
+use alloc::vec::Vec;
+use axdriver::{AxDevice, AxInputDevice};
+use axsync::Mutex;
+use lazy_init::LazyInit;
+
+static INPUT_DEVICES: LazyInit<Vec<Mutex<AxInputDevice>>> = LazyInit::new();
+
+/// Initializes the input subsystem with the devices found by [`axdriver`].
+///
+/// Events of all devices are merged.
+pub fn init_input(input_devs: Vec<AxDevice<AxInputDevice>>) {
+    info!("Initialize input subsystem...");
+
+    info!("number of input devices: {}", input_devs.len());
+    for dev in &input_devs {
+        info!("  use input device: {:?}", dev.device_name());
+    }
+    INPUT_DEVICES.init_by(
+        input_devs
+            .into_iter()
+            .map(|dev| Mutex::new(dev.into_inner()))
+            .collect(),
+    );
+}
+
+/// The number of input devices, 0 if the subsystem is not initialized.
+pub fn num_devices() -> usize {
+    INPUT_DEVICES.try_get().map_or(0, Vec::len)
+}
+
+/// Takes the next pending event of any device, or returns `None` if there is
+/// none.
+///
+/// Events of the same device are returned in order. Returns the index of the
+/// device along with the event.
+pub fn read_event() -> Option<(usize, InputEvent)> {
+    INPUT_DEVICES
+        .try_get()?
+        .iter()
+        .enumerate()
+        .find_map(|(i, dev)| dev.lock().read_event().map(|e| (i, e)))
+}
+
+/// Returns whether the device at `dev_idx` may generate events of
+/// `event_type`, e.g., [`EventType::Key`] for keyboards and
+/// [`EventType::Relative`] for mice.
+pub fn has_event_type(dev_idx: usize, event_type: EventType) -> bool {
+    INPUT_DEVICES
+        .try_get()
+        .and_then(|devs| devs.get(dev_idx))
+        .map_or(false, |dev| {
+            dev.lock().has_event_type(event_type).unwrap_or(false)
+        })
+}
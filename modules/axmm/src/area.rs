@@ -0,0 +1,472 @@
+use alloc::{sync::Arc, vec::Vec};
+use core::fmt;
+
+use axalloc::{global_allocator, GlobalPage};
+use axerrno::{AxError, AxResult};
+use axhal::mem::{phys_to_virt, virt_to_phys, PhysAddr, VirtAddr, PAGE_SIZE_4K};
+use axhal::paging::{MappingFlags, PageSize, PageTable};
+
+use crate::paging_err_to_ax_err;
+
+/// A file that can be mapped by [`Backend::File`].
+pub trait MmapFile: Send + Sync {
+    /// Reads the data of the file starting at `offset` into `buf`.
+    ///
+    /// Returns the number of bytes read, which is less than the length of
+    /// `buf` only if the end of the file is reached.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize>;
+}
+
+/// Where the pages of a [`MemoryArea`] come from.
+#[derive(Clone)]
+pub enum Backend {
+    /// Anonymous memory, filled with zeros.
+    Alloc,
+    /// A private copy of the file data starting at `offset`, the part beyond
+    /// the end of the file is filled with zeros.
+    ///
+    /// Writes to the pages are not carried to the file. The file is read when
+    /// the area is mapped, never in the page fault handler.
+    File {
+        file: Arc<dyn MmapFile>,
+        offset: u64,
+    },
+    /// Physically contiguous pages allocated in advance, starting at `offset`
+    /// bytes of `pages`.
+    ///
+    /// They are mapped with huge pages as far as the alignment allows, e.g.
+    /// if `pages` is allocated by [`GlobalPage::alloc_huge`]. The pages are
+    /// freed when the last area using them is unmapped.
+    Contiguous {
+        pages: Arc<GlobalPage>,
+        offset: usize,
+    },
+}
+
+impl Backend {
+    /// The backend of the part starting `off` bytes after the beginning.
+    fn offset_by(&self, off: usize) -> Self {
+        match self {
+            Self::Alloc => Self::Alloc,
+            Self::File { file, offset } => Self::File {
+                file: file.clone(),
+                offset: offset + off as u64,
+            },
+            Self::Contiguous { pages, offset } => Self::Contiguous {
+                pages: pages.clone(),
+                offset: offset + off,
+            },
+        }
+    }
+
+    /// The physical address of the page `off` bytes after the beginning, if
+    /// the backend is [`Backend::Contiguous`].
+    fn contiguous_paddr(&self, off: usize) -> Option<PhysAddr> {
+        match self {
+            Self::Contiguous { pages, offset } => {
+                Some(pages.start_paddr(virt_to_phys) + *offset + off)
+            }
+            _ => None,
+        }
+    }
+
+    /// The alignment of the start address to map `size` bytes of the backend,
+    /// so the largest possible huge pages can be used.
+    pub(crate) fn map_align(&self, size: usize) -> usize {
+        let paddr = match self.contiguous_paddr(0) {
+            Some(paddr) => paddr,
+            None => return PAGE_SIZE_4K,
+        };
+        [PageSize::Size1G, PageSize::Size2M]
+            .into_iter()
+            .find(|&page_size| paddr.is_aligned(page_size) && size >= page_size as usize)
+            .map_or(PAGE_SIZE_4K, |page_size| page_size as usize)
+    }
+}
+
+impl fmt::Debug for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Alloc => f.write_str("Alloc"),
+            Self::File { offset, .. } => f.debug_struct("File").field("offset", offset).finish(),
+            Self::Contiguous { pages, offset } => f
+                .debug_struct("Contiguous")
+                .field("start", &pages.start_vaddr())
+                .field("size", &pages.size())
+                .field("offset", offset)
+                .finish(),
+        }
+    }
+}
+
+/// A contiguous range of virtual memory with the same mapping flags and
+/// backend, a.k.a. VMA.
+///
+/// The pages of the area are allocated on the first access, or in advance if
+/// the area is populated. They may be shared copy-on-write with other areas.
+pub struct MemoryArea {
+    start: VirtAddr,
+    size: usize,
+    flags: MappingFlags,
+    backend: Backend,
+}
+
+impl MemoryArea {
+    pub(crate) const fn new(
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        backend: Backend,
+    ) -> Self {
+        Self {
+            start,
+            size,
+            flags,
+            backend,
+        }
+    }
+
+    /// The start address of the area.
+    pub const fn start(&self) -> VirtAddr {
+        self.start
+    }
+
+    /// The end address (exclusive) of the area.
+    pub fn end(&self) -> VirtAddr {
+        self.start + self.size
+    }
+
+    /// The size of the area in bytes.
+    pub const fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The mapping flags of the area.
+    pub const fn flags(&self) -> MappingFlags {
+        self.flags
+    }
+
+    /// Where the pages of the area come from.
+    pub const fn backend(&self) -> &Backend {
+        &self.backend
+    }
+
+    /// Whether the area contains `vaddr`.
+    pub fn contains(&self, vaddr: VirtAddr) -> bool {
+        self.start <= vaddr && vaddr < self.end()
+    }
+
+    pub(crate) fn set_flags(&mut self, flags: MappingFlags) {
+        self.flags = flags;
+    }
+
+    /// Splits the area at `pos`, keeps `[start, pos)` in `self` and returns
+    /// `[pos, end)`.
+    pub(crate) fn split_off(&mut self, pos: VirtAddr) -> Self {
+        assert!(self.start < pos && pos < self.end());
+        let off = pos.as_usize() - self.start.as_usize();
+        let right = Self::new(
+            pos,
+            self.size - off,
+            self.flags,
+            self.backend.offset_by(off),
+        );
+        self.size = off;
+        right
+    }
+
+    /// A new area at `new_start` with the same flags and backend as the part
+    /// `[start, end)` of `self`, e.g. to share its pages.
+    pub(crate) fn clone_part(&self, start: VirtAddr, end: VirtAddr, new_start: VirtAddr) -> Self {
+        debug_assert!(self.start <= start && start < end && end <= self.end());
+        let off = start.as_usize() - self.start.as_usize();
+        Self::new(
+            new_start,
+            end.as_usize() - start.as_usize(),
+            self.flags,
+            self.backend.offset_by(off),
+        )
+    }
+
+    /// Allocates the page at `vaddr`, fills it from the backend, and maps it.
+    pub(crate) fn populate_page(&self, pt: &mut PageTable, vaddr: VirtAddr) -> AxResult {
+        debug_assert!(self.contains(vaddr) && vaddr.is_aligned_4k());
+        let off = vaddr.as_usize() - self.start.as_usize();
+        if let Some(paddr) = self.backend.contiguous_paddr(off) {
+            return pt
+                .map(vaddr, paddr, PageSize::Size4K, self.flags)
+                .map_err(paging_err_to_ax_err);
+        }
+        let frame = alloc_frame()?;
+        if let Backend::File { file, offset } = &self.backend {
+            let buf = unsafe {
+                core::slice::from_raw_parts_mut(phys_to_virt(frame).as_mut_ptr(), PAGE_SIZE_4K)
+            };
+            if let Err(e) = read_full(file.as_ref(), offset + off as u64, buf) {
+                dealloc_frame(frame);
+                return Err(e);
+            }
+        }
+        pt.map(vaddr, frame, PageSize::Size4K, self.flags)
+            .map_err(|e| {
+                dealloc_frame(frame);
+                paging_err_to_ax_err(e)
+            })
+    }
+
+    /// Maps the pages read from the file in advance by [`FilePages::read`],
+    /// which must cover the whole area.
+    ///
+    /// The pages are freed if it fails.
+    pub(crate) fn map_file_pages(&self, pt: &mut PageTable, mut pages: FilePages) -> AxResult {
+        debug_assert_eq!(pages.size(), self.size);
+        let res = pages.0.iter().enumerate().try_for_each(|(i, &frame)| {
+            pt.map(
+                self.start + i * PAGE_SIZE_4K,
+                frame,
+                PageSize::Size4K,
+                self.flags,
+            )
+            .map_err(|e| (i, e))
+        });
+        match res {
+            Ok(_) => {
+                pages.0.clear();
+                Ok(())
+            }
+            Err((mapped, e)) => {
+                // the mapped ones are freed here, the others when dropped
+                pages.0.drain(..mapped);
+                self.unmap_pages(pt);
+                Err(paging_err_to_ax_err(e))
+            }
+        }
+    }
+
+    /// Allocates and maps all the pages of the area.
+    ///
+    /// The pages mapped before the failure are freed if it fails.
+    pub(crate) fn populate(&self, pt: &mut PageTable) -> AxResult {
+        if let Some(paddr) = self.backend.contiguous_paddr(0) {
+            return pt
+                .map_region(self.start, paddr, self.size, self.flags, true)
+                .map_err(|e| {
+                    self.unmap_pages(pt);
+                    paging_err_to_ax_err(e)
+                });
+        }
+        let mut vaddr = self.start;
+        while vaddr < self.end() {
+            if let Err(e) = self.populate_page(pt, vaddr) {
+                self.unmap_pages(pt);
+                return Err(e);
+            }
+            vaddr += PAGE_SIZE_4K;
+        }
+        Ok(())
+    }
+
+    /// Makes a private copy of the copy-on-write page at `vaddr` mapped to
+    /// `frame`, and maps the copy writable. The copy is not needed if no
+    /// other mapping shares the page.
+    pub(crate) fn copy_on_write(
+        &self,
+        pt: &mut PageTable,
+        vaddr: VirtAddr,
+        frame: PhysAddr,
+    ) -> AxResult {
+        debug_assert!(self.contains(vaddr) && vaddr.is_aligned_4k());
+        if global_allocator().page_ref_count(phys_to_virt(frame).as_usize()) == 1 {
+            return pt
+                .protect(vaddr, self.flags)
+                .map(|_| ())
+                .map_err(paging_err_to_ax_err);
+        }
+        let new_frame = alloc_frame()?;
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                phys_to_virt(frame).as_ptr(),
+                phys_to_virt(new_frame).as_mut_ptr(),
+                PAGE_SIZE_4K,
+            )
+        };
+        let res = pt
+            .unmap(vaddr)
+            .and_then(|_| pt.map(vaddr, new_frame, PageSize::Size4K, self.flags));
+        if let Err(e) = res {
+            dealloc_frame(new_frame);
+            return Err(paging_err_to_ax_err(e));
+        }
+        dealloc_frame(frame);
+        Ok(())
+    }
+
+    /// Unmaps the pages of the area that have been allocated, and frees them.
+    ///
+    /// A huge page partly in the area is unmapped as a whole, and the part
+    /// out of the area will be mapped again with 4K pages on demand.
+    pub(crate) fn unmap_pages(&self, pt: &mut PageTable) {
+        let is_contiguous = matches!(self.backend, Backend::Contiguous { .. });
+        let mut vaddr = self.start;
+        while vaddr < self.end() {
+            if let Ok((frame, _)) = pt.unmap(vaddr) {
+                if !is_contiguous {
+                    dealloc_frame(frame);
+                }
+            }
+            vaddr += PAGE_SIZE_4K;
+        }
+    }
+
+    /// Unmaps the pages of the area that have been allocated like
+    /// [`unmap_pages`](Self::unmap_pages), but does not free them.
+    ///
+    /// Returns the detached pages, to be mapped back by
+    /// [`attach_pages`](Self::attach_pages) or freed by
+    /// [`free_pages`](Self::free_pages).
+    pub(crate) fn detach_pages(&self, pt: &mut PageTable) -> Vec<DetachedPage> {
+        let mut pages = Vec::new();
+        let mut vaddr = self.start;
+        while vaddr < self.end() {
+            let mut step = PAGE_SIZE_4K;
+            if let Ok((_, flags, page_size)) = pt.query(vaddr) {
+                let page = vaddr.align_down(page_size);
+                if let Ok((frame, _)) = pt.unmap(vaddr) {
+                    pages.push(DetachedPage {
+                        vaddr: page,
+                        frame,
+                        flags,
+                        page_size,
+                    });
+                }
+                step = page.as_usize() + page_size as usize - vaddr.as_usize();
+            }
+            vaddr += step;
+        }
+        pages
+    }
+
+    /// Maps the pages detached by [`detach_pages`](Self::detach_pages) back.
+    pub(crate) fn attach_pages(&self, pt: &mut PageTable, pages: &[DetachedPage]) {
+        for page in pages {
+            if let Err(e) = pt.map(page.vaddr, page.frame, page.page_size, page.flags) {
+                warn!("failed to map back the page at {:#x}: {:?}", page.vaddr, e);
+            }
+        }
+    }
+
+    /// Frees the pages detached by [`detach_pages`](Self::detach_pages).
+    pub(crate) fn free_pages(&self, pages: Vec<DetachedPage>) {
+        if !matches!(self.backend, Backend::Contiguous { .. }) {
+            for page in pages {
+                dealloc_frame(page.frame);
+            }
+        }
+    }
+
+    /// Updates the mapping flags of the pages of the area that have been
+    /// allocated. The copy-on-write pages are kept read-only.
+    ///
+    /// A huge page partly in the area is unmapped instead, and will be mapped
+    /// again with 4K pages on demand.
+    pub(crate) fn protect_pages(&self, pt: &mut PageTable) {
+        let cow_flags = (self.flags - MappingFlags::WRITE) | MappingFlags::COW;
+        let mut vaddr = self.start;
+        while vaddr < self.end() {
+            let mut step = PAGE_SIZE_4K;
+            if let Ok((_, flags, page_size)) = pt.query(vaddr) {
+                let page = vaddr.align_down(page_size);
+                if page < self.start || page + page_size as usize > self.end() {
+                    pt.unmap(vaddr).ok();
+                } else {
+                    let flags = if flags.contains(MappingFlags::COW) {
+                        cow_flags
+                    } else {
+                        self.flags
+                    };
+                    pt.protect(vaddr, flags).ok();
+                    step = page.as_usize() + page_size as usize - vaddr.as_usize();
+                }
+            }
+            vaddr += step;
+        }
+    }
+}
+
+/// The pages of a file read in advance, to be mapped by
+/// [`MemoryArea::map_file_pages`]. The pages not mapped are freed when it is
+/// dropped.
+pub(crate) struct FilePages(Vec<PhysAddr>);
+
+impl FilePages {
+    /// The size of the pages in bytes.
+    pub(crate) fn size(&self) -> usize {
+        self.0.len() * PAGE_SIZE_4K
+    }
+
+    /// Allocates the pages for `size` bytes of `file` starting at `offset`,
+    /// and reads the file into them.
+    pub(crate) fn read(file: &dyn MmapFile, offset: u64, size: usize) -> AxResult<Self> {
+        let mut pages = Self(Vec::with_capacity(size / PAGE_SIZE_4K));
+        for off in (0..size).step_by(PAGE_SIZE_4K) {
+            let frame = alloc_frame()?;
+            pages.0.push(frame);
+            let buf = unsafe {
+                core::slice::from_raw_parts_mut(phys_to_virt(frame).as_mut_ptr(), PAGE_SIZE_4K)
+            };
+            read_full(file, offset + off as u64, buf)?;
+        }
+        Ok(pages)
+    }
+}
+
+impl Drop for FilePages {
+    fn drop(&mut self) {
+        for &frame in &self.0 {
+            dealloc_frame(frame);
+        }
+    }
+}
+
+/// A page unmapped by [`MemoryArea::detach_pages`] but not freed yet.
+pub(crate) struct DetachedPage {
+    vaddr: VirtAddr,
+    frame: PhysAddr,
+    flags: MappingFlags,
+    page_size: PageSize,
+}
+
+impl fmt::Debug for MemoryArea {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MemoryArea")
+            .field("range", &(self.start..self.end()))
+            .field("flags", &self.flags)
+            .field("backend", &self.backend)
+            .finish()
+    }
+}
+
+fn alloc_frame() -> AxResult<PhysAddr> {
+    let vaddr = global_allocator()
+        .alloc_pages(1, PAGE_SIZE_4K)
+        .map_err(|_| AxError::NoMemory)?;
+    unsafe { core::ptr::write_bytes(vaddr as *mut u8, 0, PAGE_SIZE_4K) };
+    Ok(virt_to_phys(vaddr.into()))
+}
+
+/// Drops a reference to `frame`, which is freed if it is not shared.
+fn dealloc_frame(frame: PhysAddr) {
+    global_allocator().page_ref_dec(phys_to_virt(frame).as_usize());
+}
+
+/// Reads the file until `buf` is full or the end of the file is reached.
+fn read_full(file: &dyn MmapFile, offset: u64, buf: &mut [u8]) -> AxResult {
+    let mut read = 0;
+    while read < buf.len() {
+        match file.read_at(offset + read as u64, &mut buf[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,486 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::fmt;
+
+use axalloc::global_allocator;
+use axerrno::{ax_err, AxResult};
+use axhal::mem::{phys_to_virt, PhysAddr, VirtAddr, PAGE_SIZE_4K};
+use axhal::paging::{MappingFlags, PageTable};
+
+use crate::area::{Backend, FilePages, MemoryArea};
+use crate::paging_err_to_ax_err;
+
+/// A virtual address space, with the [`MemoryArea`]s mapped in a range of it
+/// and the page table.
+pub struct AddrSpace {
+    base: VirtAddr,
+    end: VirtAddr,
+    areas: BTreeMap<VirtAddr, MemoryArea>,
+    pt: PageTable,
+}
+
+impl AddrSpace {
+    /// Creates a new address space without any mapping, whose memory areas
+    /// can be mapped in `[base, base + size)`.
+    pub fn new_empty(base: VirtAddr, size: usize) -> AxResult<Self> {
+        Ok(Self {
+            base,
+            end: base + size,
+            areas: BTreeMap::new(),
+            pt: PageTable::try_new().map_err(paging_err_to_ax_err)?,
+        })
+    }
+
+    /// The start address of the range of memory areas.
+    pub const fn base(&self) -> VirtAddr {
+        self.base
+    }
+
+    /// The end address (exclusive) of the range of memory areas.
+    pub const fn end(&self) -> VirtAddr {
+        self.end
+    }
+
+    /// The size of the range of memory areas.
+    pub const fn size(&self) -> usize {
+        self.end.as_usize() - self.base.as_usize()
+    }
+
+    /// The page table of the address space.
+    pub const fn page_table(&self) -> &PageTable {
+        &self.pt
+    }
+
+    /// The physical address of the root page table.
+    pub const fn page_table_root(&self) -> PhysAddr {
+        self.pt.root_paddr()
+    }
+
+    /// Whether `[start, start + size)` is in the range of memory areas.
+    pub fn contains_range(&self, start: VirtAddr, size: usize) -> bool {
+        match start.as_usize().checked_add(size) {
+            Some(end) => self.base <= start && end <= self.end.as_usize(),
+            None => false,
+        }
+    }
+
+    /// Iterates over the memory areas in ascending order of address.
+    pub fn areas(&self) -> impl Iterator<Item = &MemoryArea> {
+        self.areas.values()
+    }
+
+    /// Finds the memory area that contains `vaddr`.
+    pub fn find_area(&self, vaddr: VirtAddr) -> Option<&MemoryArea> {
+        self.areas
+            .range(..=vaddr)
+            .next_back()
+            .map(|(_, area)| area)
+            .filter(|area| area.contains(vaddr))
+    }
+
+    /// Finds a free range of `size` bytes for a new memory area, whose start
+    /// address is aligned to `align` (at least 4K).
+    ///
+    /// The lowest free range at or after `hint` is preferred, and the lowest
+    /// one in the whole address space is returned if there is not any.
+    pub fn find_free_area(&self, hint: VirtAddr, size: usize, align: usize) -> Option<VirtAddr> {
+        let align = align.max(PAGE_SIZE_4K);
+        let hint = hint.align_up(align);
+        if hint > self.base {
+            if let Some(start) = self.find_free_area_from(hint, size, align) {
+                return Some(start);
+            }
+        }
+        self.find_free_area_from(self.base.align_up(align), size, align)
+    }
+
+    fn find_free_area_from(
+        &self,
+        mut start: VirtAddr,
+        size: usize,
+        align: usize,
+    ) -> Option<VirtAddr> {
+        // skip the areas that end before `start`, except the one containing it
+        let first = self
+            .areas
+            .range(..=start)
+            .next_back()
+            .map_or(start, |(&key, _)| key);
+        for area in self.areas.range(first..).map(|(_, area)| area) {
+            if area.end() <= start {
+                continue;
+            }
+            if start.as_usize().checked_add(size)? <= area.start().as_usize() {
+                return Some(start);
+            }
+            start = area.end().align_up(align);
+        }
+        self.contains_range(start, size).then_some(start)
+    }
+
+    fn overlaps(&self, start: VirtAddr, end: VirtAddr) -> bool {
+        self.areas
+            .range(..end)
+            .next_back()
+            .map_or(false, |(_, area)| area.end() > start)
+    }
+
+    fn check_range(&self, start: VirtAddr, size: usize) -> AxResult {
+        if !start.is_aligned_4k() || !memory_addr::is_aligned_4k(size) || size == 0 {
+            return ax_err!(InvalidInput, "address or size not aligned");
+        }
+        if !self.contains_range(start, size) {
+            return ax_err!(InvalidInput, "address out of range");
+        }
+        Ok(())
+    }
+
+    /// Adds a linear mapping of `[start_paddr, start_paddr + size)` at
+    /// `start_vaddr`, e.g. the kernel image and the physical memory.
+    ///
+    /// It is not a memory area: it is mapped with huge pages when possible,
+    /// and it can not be unmapped. It must be outside the range of memory
+    /// areas.
+    pub fn map_linear(
+        &mut self,
+        start_vaddr: VirtAddr,
+        start_paddr: PhysAddr,
+        size: usize,
+        flags: MappingFlags,
+    ) -> AxResult {
+        if start_vaddr < self.end && self.base < start_vaddr + size {
+            return ax_err!(InvalidInput, "linear mapping overlaps memory areas");
+        }
+        self.pt
+            .map_region(start_vaddr, start_paddr, size, flags, true)
+            .map_err(paging_err_to_ax_err)
+    }
+
+    /// Adds a memory area `[start, start + size)` with the mapping `flags`
+    /// and the pages from `backend`.
+    ///
+    /// The pages are allocated on the first access, through
+    /// [`AddrSpace::handle_page_fault`], unless `populate` is true. The pages
+    /// of [`Backend::Contiguous`] are mapped with huge pages only if they are
+    /// populated, and the ones of [`Backend::File`] must be populated, since
+    /// the file is not read in the page fault handler.
+    ///
+    /// Returns [`AxError::AlreadyExists`](axerrno::AxError::AlreadyExists) if the range overlaps another
+    /// memory area.
+    pub fn map(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        backend: Backend,
+        populate: bool,
+    ) -> AxResult {
+        self.map_with(start, size, flags, backend, false, |area, pt| {
+            populate_if(area, pt, populate)
+        })
+    }
+
+    /// Adds a memory area like [`AddrSpace::map`], but replaces the memory
+    /// areas in the range instead of failing.
+    ///
+    /// The old areas are kept untouched if it fails, e.g. when populating the
+    /// new area runs out of memory.
+    pub fn map_fixed(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        backend: Backend,
+        populate: bool,
+    ) -> AxResult {
+        self.map_with(start, size, flags, backend, true, |area, pt| {
+            populate_if(area, pt, populate)
+        })
+    }
+
+    /// Adds a [`Backend::File`] memory area like [`AddrSpace::map`] or
+    /// [`AddrSpace::map_fixed`], with the pages read from the file in
+    /// advance, e.g. without holding the lock of the address space.
+    ///
+    /// The pages are freed if it fails.
+    pub(crate) fn map_file(
+        &mut self,
+        start: VirtAddr,
+        flags: MappingFlags,
+        backend: Backend,
+        pages: FilePages,
+        fixed: bool,
+    ) -> AxResult {
+        let size = pages.size();
+        self.map_with(start, size, flags, backend, fixed, |area, pt| {
+            area.map_file_pages(pt, pages)
+        })
+    }
+
+    /// Adds a memory area, whose pages are mapped by `fill`, replacing the
+    /// memory areas in the range if `fixed` is true.
+    fn map_with<F>(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        backend: Backend,
+        fixed: bool,
+        fill: F,
+    ) -> AxResult
+    where
+        F: FnOnce(&MemoryArea, &mut PageTable) -> AxResult,
+    {
+        self.check_range(start, size)?;
+        if !fixed && self.overlaps(start, start + size) {
+            return ax_err!(AlreadyExists, "range already mapped");
+        }
+        check_backend_size(&backend, size)?;
+        let area = MemoryArea::new(start, size, flags, backend);
+        if !fixed {
+            debug!("map {:?}", area);
+            fill(&area, &mut self.pt)?;
+            self.areas.insert(start, area);
+            return Ok(());
+        }
+        debug!("map fixed {:?}", area);
+        let old_areas = self.take_range(start, start + size);
+        let old_pages: Vec<_> = old_areas
+            .iter()
+            .map(|old| old.detach_pages(&mut self.pt))
+            .collect();
+        let res = fill(&area, &mut self.pt);
+        for (old, pages) in old_areas.into_iter().zip(old_pages) {
+            if res.is_ok() {
+                old.free_pages(pages);
+            } else {
+                old.attach_pages(&mut self.pt, &pages);
+                self.areas.insert(old.start(), old);
+            }
+        }
+        if res.is_ok() {
+            self.areas.insert(start, area);
+        }
+        axhal::arch::flush_tlb(None);
+        res
+    }
+
+    /// Removes the memory areas in `[start, start + size)`, and frees their
+    /// pages. The areas partly in the range are shrunk or split.
+    ///
+    /// It is not an error if nothing is mapped in the range.
+    pub fn unmap(&mut self, start: VirtAddr, size: usize) -> AxResult {
+        self.check_range(start, size)?;
+        debug!("unmap [{:#x}, {:#x})", start, start + size);
+        for area in self.take_range(start, start + size) {
+            area.unmap_pages(&mut self.pt);
+        }
+        axhal::arch::flush_tlb(None);
+        Ok(())
+    }
+
+    /// Changes the mapping flags of `[start, start + size)` to `flags`. The
+    /// areas partly in the range are split.
+    ///
+    /// Returns [`AxError::NoMemory`](axerrno::AxError::NoMemory) if any page in the range is not in a
+    /// memory area, like `mprotect` in Linux.
+    pub fn protect(&mut self, start: VirtAddr, size: usize, flags: MappingFlags) -> AxResult {
+        self.check_range(start, size)?;
+        if !self.is_fully_mapped(start, start + size) {
+            return ax_err!(NoMemory, "range not fully mapped");
+        }
+        debug!("protect [{:#x}, {:#x}) {:?}", start, start + size, flags);
+        for mut area in self.take_range(start, start + size) {
+            area.set_flags(flags);
+            area.protect_pages(&mut self.pt);
+            self.areas.insert(area.start(), area);
+        }
+        axhal::arch::flush_tlb(None);
+        Ok(())
+    }
+
+    /// Maps a copy-on-write copy of `[src, src + size)` at `dst`, like
+    /// copying the memory without copying the pages in advance.
+    ///
+    /// The pages already allocated are shared by both ranges, and are copied
+    /// on the first write to either of them. The other pages are allocated
+    /// separately from the same backend on the first access.
+    ///
+    /// Returns [`AxError::NoMemory`](axerrno::AxError::NoMemory) if any page in the source range is not in
+    /// a memory area, or [`AxError::AlreadyExists`](axerrno::AxError::AlreadyExists) if the destination range
+    /// overlaps a memory area. [`Backend::Contiguous`] areas are not supported.
+    /// Nothing is mapped at `dst` if it fails.
+    pub fn share_cow(&mut self, src: VirtAddr, dst: VirtAddr, size: usize) -> AxResult {
+        self.check_range(src, size)?;
+        self.check_range(dst, size)?;
+        if !self.is_fully_mapped(src, src + size) {
+            return ax_err!(NoMemory, "source range not fully mapped");
+        }
+        if self.overlaps(dst, dst + size) {
+            return ax_err!(AlreadyExists, "range already mapped");
+        }
+        let src_end = src + size;
+        let mut src_areas = self
+            .areas
+            .range(..src_end)
+            .rev()
+            .take_while(|(_, area)| area.end() > src)
+            .map(|(_, area)| area);
+        if src_areas.any(|area| matches!(area.backend(), Backend::Contiguous { .. })) {
+            return ax_err!(Unsupported, "contiguous pages can not be copy-on-write");
+        }
+        debug!("share_cow [{:#x}, {:#x}) -> {:#x}", src, src + size, dst);
+        let copies: Vec<MemoryArea> = self
+            .areas
+            .range(..src_end)
+            .rev()
+            .take_while(|(_, area)| area.end() > src)
+            .map(|(_, area)| {
+                let start = area.start().max(src);
+                let end = area.end().min(src_end);
+                area.clone_part(start, end, dst + (start.as_usize() - src.as_usize()))
+            })
+            .collect();
+        let res = self.pt.share_region(src, dst, size, true, |frame| {
+            global_allocator().page_ref_inc(phys_to_virt(frame).as_usize())
+        });
+        for area in copies {
+            if res.is_ok() {
+                self.areas.insert(area.start(), area);
+            } else {
+                // drop the pages shared before the failure, the source pages
+                // are copied on write only if still shared
+                area.unmap_pages(&mut self.pt);
+            }
+        }
+        axhal::arch::flush_tlb(None);
+        res.map_err(paging_err_to_ax_err)
+    }
+
+    /// Handles a page fault at `vaddr` with the access type `access_flags`,
+    /// by allocating the page, or making a private copy of the copy-on-write
+    /// page on write, if the memory area containing it allows the access.
+    ///
+    /// Returns `false` if the fault is not caused by a page not allocated
+    /// yet or a copy-on-write page, i.e. it is an invalid access. The pages
+    /// of [`Backend::File`] are never allocated here, since reading the file
+    /// may block.
+    pub fn handle_page_fault(&mut self, vaddr: VirtAddr, access_flags: MappingFlags) -> bool {
+        let area = match self.areas.range(..=vaddr).next_back() {
+            Some((_, area)) if area.contains(vaddr) && area.flags().contains(access_flags) => area,
+            _ => return false,
+        };
+        let page = vaddr.align_down_4k();
+        let res = match self.pt.query(page) {
+            Ok((frame, flags, _))
+                if flags.contains(MappingFlags::COW)
+                    && access_flags.contains(MappingFlags::WRITE) =>
+            {
+                area.copy_on_write(&mut self.pt, page, frame)
+            }
+            Ok(_) => return false,
+            Err(_) if matches!(area.backend(), Backend::File { .. }) => {
+                warn!("file page at {:#x} not populated", page);
+                return false;
+            }
+            Err(_) => area.populate_page(&mut self.pt, page),
+        };
+        match res {
+            Ok(_) => {
+                axhal::arch::flush_tlb(Some(page));
+                true
+            }
+            Err(e) => {
+                warn!("failed to handle page fault at {:#x}: {:?}", vaddr, e);
+                false
+            }
+        }
+    }
+
+    /// Whether every page in `[start, end)` is in a memory area.
+    fn is_fully_mapped(&self, start: VirtAddr, end: VirtAddr) -> bool {
+        let mut next = match self.find_area(start) {
+            Some(area) => area.start(),
+            None => return false,
+        };
+        for area in self.areas.range(next..).map(|(_, area)| area) {
+            if area.start() != next {
+                return false;
+            }
+            next = area.end();
+            if next >= end {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Removes the parts of the memory areas in `[start, end)` and returns
+    /// them. The parts out of the range are kept.
+    fn take_range(&mut self, start: VirtAddr, end: VirtAddr) -> Vec<MemoryArea> {
+        let keys: Vec<VirtAddr> = self
+            .areas
+            .range(..end)
+            .rev()
+            .take_while(|(_, area)| area.end() > start)
+            .map(|(&key, _)| key)
+            .collect();
+        let mut taken = Vec::with_capacity(keys.len());
+        for key in keys.into_iter().rev() {
+            let mut area = self.areas.remove(&key).unwrap();
+            if area.end() > end {
+                let right = area.split_off(end);
+                self.areas.insert(right.start(), right);
+            }
+            if area.start() < start {
+                let middle = area.split_off(start);
+                self.areas.insert(area.start(), area);
+                area = middle;
+            }
+            taken.push(area);
+        }
+        taken
+    }
+}
+
+/// Populates `area` if `populate` is true, which is required by
+/// [`Backend::File`].
+fn populate_if(area: &MemoryArea, pt: &mut PageTable, populate: bool) -> AxResult {
+    if populate {
+        area.populate(pt)
+    } else if matches!(area.backend(), Backend::File { .. }) {
+        ax_err!(InvalidInput, "file mappings must be populated")
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks that [`Backend::Contiguous`] has enough pages for `size` bytes.
+fn check_backend_size(backend: &Backend, size: usize) -> AxResult {
+    if let Backend::Contiguous { pages, offset } = backend {
+        if offset
+            .checked_add(size)
+            .map_or(true, |end| end > pages.size())
+        {
+            return ax_err!(InvalidInput, "contiguous pages too small");
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Debug for AddrSpace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AddrSpace")
+            .field("range", &(self.base..self.end))
+            .field("page_table_root", &self.pt.root_paddr())
+            .field("areas", &self.areas.values().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Drop for AddrSpace {
+    fn drop(&mut self) {
+        for area in self.areas.values() {
+            area.unmap_pages(&mut self.pt);
+        }
+    }
+}
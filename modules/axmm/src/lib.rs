@@ -0,0 +1,201 @@
+//! [ArceOS](https://github.com/rcore-os/arceos) virtual memory management.
+//!
+//! The kernel address space ([`AddrSpace`]) contains the linear mappings of
+//! the physical memory, and a range of memory areas ([`MemoryArea`]) that are
+//! mapped and unmapped at runtime by [`mmap`] and [`munmap`], in
+//! `[axconfig::MMAP_BASE, axconfig::MMAP_BASE + axconfig::MMAP_SIZE)`.
+//!
+//! The pages of a memory area are allocated from the global allocator page
+//! by page on the first access, when the page fault is forwarded here by
+//! [`handle_page_fault`], so large buffers do not need contiguous physical
+//! memory. The file mappings are read in advance instead, as the file can not
+//! be read in the page fault handler.
+//!
+//! It is only supported on riscv64 and aarch64, whose trap handlers forward
+//! the page faults.
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+mod area;
+mod aspace;
+
+use alloc::vec::Vec;
+use axerrno::{ax_err, AxError, AxResult};
+use axhal::mem::{memory_regions, phys_to_virt, PhysAddr, VirtAddr, PAGE_SIZE_4K};
+use axhal::paging::PagingError;
+use axsync::spin::SpinNoIrq;
+use lazy_init::LazyInit;
+
+use self::area::FilePages;
+
+pub use self::area::{Backend, MemoryArea, MmapFile};
+pub use self::aspace::AddrSpace;
+
+#[doc(no_inline)]
+pub use axalloc::GlobalPage;
+#[doc(no_inline)]
+pub use axhal::paging::MappingFlags;
+
+/// It is locked in the page fault handler, so a spinlock with IRQs disabled
+/// is used, and the lock holders must not block.
+static KERNEL_ASPACE: LazyInit<SpinNoIrq<AddrSpace>> = LazyInit::new();
+static KERNEL_PAGE_TABLE_ROOT: LazyInit<PhysAddr> = LazyInit::new();
+
+/// Returns the kernel address space.
+pub fn kernel_aspace() -> &'static SpinNoIrq<AddrSpace> {
+    &KERNEL_ASPACE
+}
+
+/// Maps `size` bytes of the pages from `backend` into the kernel address
+/// space with the mapping `flags`.
+///
+/// If `fixed` is true, they are mapped at `addr` exactly, replacing the
+/// existing mappings there, which are kept if it fails. Otherwise `addr` is a hint, and a free range
+/// at or after it is preferred.
+///
+/// The pages are allocated lazily, on the first access to each of them,
+/// except [`Backend::Contiguous`], which is mapped at once with huge pages as
+/// far as possible, so the start address is aligned to the huge page size if
+/// `fixed` is false, and [`Backend::File`], which is read at once before
+/// locking the kernel address space, as the file can not be read in the page
+/// fault handler.
+///
+/// Returns the start address of the mapping.
+pub fn mmap(
+    addr: VirtAddr,
+    size: usize,
+    flags: MappingFlags,
+    fixed: bool,
+    backend: Backend,
+) -> AxResult<VirtAddr> {
+    if size == 0 || size > axconfig::MMAP_SIZE {
+        return ax_err!(InvalidInput, "invalid mmap size");
+    }
+    let size = memory_addr::align_up_4k(size);
+    // reading the file may block, so it can not be done with the lock held
+    let file_pages = match &backend {
+        Backend::File { file, offset } => Some(FilePages::read(file.as_ref(), *offset, size)?),
+        _ => None,
+    };
+    let mut aspace = KERNEL_ASPACE.lock();
+    let start = if fixed {
+        addr
+    } else {
+        let align = backend.map_align(size);
+        aspace
+            .find_free_area(addr, size, align)
+            .ok_or(AxError::NoMemory)?
+    };
+    let populate = matches!(backend, Backend::Contiguous { .. });
+    match file_pages {
+        Some(pages) => aspace.map_file(start, flags, backend, pages, fixed)?,
+        None if fixed => aspace.map_fixed(start, size, flags, backend, populate)?,
+        None => aspace.map(start, size, flags, backend, populate)?,
+    }
+    Ok(start)
+}
+
+/// Unmaps `[addr, addr + size)` in the kernel address space, and frees the
+/// pages in it.
+pub fn munmap(addr: VirtAddr, size: usize) -> AxResult {
+    if size == 0 || size > axconfig::MMAP_SIZE {
+        return ax_err!(InvalidInput, "invalid munmap size");
+    }
+    KERNEL_ASPACE
+        .lock()
+        .unmap(addr, memory_addr::align_up_4k(size))
+}
+
+/// Changes the mapping flags of `[addr, addr + size)` in the kernel address
+/// space to `flags`.
+pub fn mprotect(addr: VirtAddr, size: usize, flags: MappingFlags) -> AxResult {
+    if size == 0 || size > axconfig::MMAP_SIZE {
+        return ax_err!(InvalidInput, "invalid mprotect size");
+    }
+    KERNEL_ASPACE
+        .lock()
+        .protect(addr, memory_addr::align_up_4k(size), flags)
+}
+
+/// Maps a copy-on-write snapshot of `[addr, addr + size)` in the kernel
+/// address space at a free range, see [`AddrSpace::share_cow`].
+///
+/// The memory is copied page by page only when the snapshot or the original
+/// is written, so it is cheap to take a snapshot of a large buffer.
+///
+/// Returns the start address of the snapshot.
+pub fn snapshot(addr: VirtAddr, size: usize) -> AxResult<VirtAddr> {
+    if size == 0 || size > axconfig::MMAP_SIZE {
+        return ax_err!(InvalidInput, "invalid snapshot size");
+    }
+    let size = memory_addr::align_up_4k(size);
+    let mut aspace = KERNEL_ASPACE.lock();
+    let start = aspace
+        .find_free_area(addr, size, PAGE_SIZE_4K)
+        .ok_or(AxError::NoMemory)?;
+    aspace.share_cow(addr, start, size)?;
+    Ok(start)
+}
+
+/// Handles a page fault at `vaddr` of the kernel address space, see
+/// [`AddrSpace::handle_page_fault`].
+pub fn handle_page_fault(vaddr: VirtAddr, access_flags: MappingFlags) -> bool {
+    KERNEL_ASPACE.is_init() && KERNEL_ASPACE.lock().handle_page_fault(vaddr, access_flags)
+}
+
+/// Merges the adjacent memory regions with the same mapping flags, e.g. the
+/// writable sections of the kernel image and the free memory after them, so
+/// they can be mapped with more huge pages to reduce TLB misses.
+fn merged_memory_regions() -> Vec<(PhysAddr, usize, MappingFlags)> {
+    let mut regions: Vec<_> = memory_regions().collect();
+    regions.sort_unstable_by_key(|r| r.paddr);
+    let mut merged: Vec<(PhysAddr, usize, MappingFlags)> = Vec::with_capacity(regions.len());
+    for r in regions {
+        let flags = MappingFlags::from(r.flags);
+        match merged.last_mut() {
+            Some((paddr, size, last_flags))
+                if (*paddr + *size).align_up_4k() == r.paddr
+                    && last_flags.bits() == flags.bits() =>
+            {
+                *size = r.paddr.as_usize() + r.size - paddr.as_usize();
+            }
+            _ => merged.push((r.paddr, r.size, flags)),
+        }
+    }
+    merged
+}
+
+fn new_kernel_aspace() -> AxResult<AddrSpace> {
+    let mut aspace = AddrSpace::new_empty(axconfig::MMAP_BASE.into(), axconfig::MMAP_SIZE)?;
+    for (paddr, size, flags) in merged_memory_regions() {
+        aspace.map_linear(phys_to_virt(paddr), paddr, size, flags)?;
+    }
+    Ok(aspace)
+}
+
+/// Creates the kernel address space, and switches the primary CPU to it.
+pub fn init_memory_management() {
+    let kernel_aspace = new_kernel_aspace().expect("failed to initialize kernel address space");
+    let root = kernel_aspace.page_table_root();
+    unsafe { axhal::arch::write_page_table_root(root) };
+    KERNEL_PAGE_TABLE_ROOT.init_by(root);
+    KERNEL_ASPACE.init_by(SpinNoIrq::new(kernel_aspace));
+}
+
+/// Switches a secondary CPU to the kernel address space.
+pub fn init_memory_management_secondary() {
+    unsafe { axhal::arch::write_page_table_root(*KERNEL_PAGE_TABLE_ROOT) };
+}
+
+const fn paging_err_to_ax_err(e: PagingError) -> AxError {
+    match e {
+        PagingError::NoMemory => AxError::NoMemory,
+        PagingError::NotAligned => AxError::InvalidInput,
+        PagingError::NotMapped => AxError::NotFound,
+        PagingError::AlreadyMapped | PagingError::MappedToHugePage => AxError::AlreadyExists,
+    }
+}
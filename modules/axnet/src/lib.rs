@@ -12,20 +12,25 @@ cfg_if::cfg_if! {
     }
 }
 
-pub use self::net_impl::TcpSocket;
+pub use self::net_impl::{poll_interfaces, SocketWaiter};
+pub use self::net_impl::{IcmpSocket, RawSocket, TcpSocket};
 pub use smoltcp::wire::{IpAddress as IpAddr, IpEndpoint as SocketAddr, Ipv4Address as Ipv4Addr};
 
-use axdriver::NetDevices;
-use driver_common::{BaseDriverOps, DeviceType};
+use alloc::vec::Vec;
+use axdriver::{AxDevice, AxNetDevice};
+use driver_common::BaseDriverOps;
 
-pub fn init_network(net_devs: NetDevices) {
+/// Initializes the network subsystem with the NICs found by [`axdriver`].
+///
+/// Only the first NIC is used (as `eth0`). If there is none, only the
+/// loopback interface is available.
+pub fn init_network(net_devs: Vec<AxDevice<AxNetDevice>>) {
     info!("Initialize network subsystem...");
 
     info!("number of NICs: {}", net_devs.len());
-    axdriver::net_devices_enumerate!((i, dev) in net_devs {
-        assert_eq!(dev.device_type(), DeviceType::Net);
+    for (i, dev) in net_devs.iter().enumerate() {
         info!("  NIC {}: {:?}", i, dev.device_name());
-    });
+    }
 
     net_impl::init(net_devs);
 }
@@ -0,0 +1,182 @@
+use alloc::{sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::task::Waker;
+use core::time::Duration;
+
+use axerrno::{ax_err, AxError, AxResult};
+use axio::PollState;
+use smoltcp::socket::icmp::{self, BindError, SendError};
+
+use super::{block_on, duration_to_nanos, nanos_to_duration, route_iface};
+use super::{IfaceId, SocketHandle, SocketSetWrapper, SocketWaiter, PACKET_BUF_LEN, SOCKET_SET};
+use crate::IpAddr;
+
+/// An ICMP socket, to send and receive ICMP packets such as echo requests and
+/// replies.
+///
+/// The data sent and received are whole ICMP packets, starting from the ICMP
+/// header. The checksum of the outgoing packets is filled by the stack.
+///
+/// It receives the packets from all interfaces, and sends each packet through
+/// the interface routed by its destination.
+pub struct IcmpSocket {
+    handles: Vec<SocketHandle>, // one for each interface
+    waiter: Arc<SocketWaiter>,
+    nonblock: AtomicBool,
+    read_timeout: AtomicU64, // in nanoseconds, 0 means no timeout
+}
+
+impl IcmpSocket {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        let handles = IfaceId::all()
+            .map(|iface| SOCKET_SET.add(iface, SocketSetWrapper::new_icmp_socket()))
+            .collect();
+        Self {
+            handles,
+            waiter: SocketWaiter::new(),
+            nonblock: AtomicBool::new(false),
+            read_timeout: AtomicU64::new(0),
+        }
+    }
+
+    /// Binds this socket to the ICMP identifier `ident`, only the echo
+    /// replies with the same identifier and the ICMP errors caused by the
+    /// packets sent with it will be received.
+    pub fn bind(&self, ident: u16) -> AxResult {
+        self.handles.iter().try_for_each(|&handle| {
+            SOCKET_SET.with_socket_mut::<icmp::Socket, _, _>(handle, |socket| {
+                socket
+                    .bind(icmp::Endpoint::Ident(ident))
+                    .or_else(|e| match e {
+                        BindError::InvalidState => ax_err!(AlreadyExists, "socket bind() failed"),
+                        BindError::Unaddressable => ax_err!(InvalidInput, "socket bind() failed"),
+                    })
+            })
+        })
+    }
+
+    /// Returns whether this socket is in non-blocking mode.
+    pub fn is_nonblocking(&self) -> bool {
+        self.nonblock.load(Ordering::Acquire)
+    }
+
+    /// Moves this socket into or out of non-blocking mode.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> AxResult {
+        self.nonblock.store(nonblocking, Ordering::Release);
+        Ok(())
+    }
+
+    /// Returns the read timeout of this socket.
+    pub fn read_timeout(&self) -> AxResult<Option<Duration>> {
+        Ok(nanos_to_duration(self.read_timeout.load(Ordering::Acquire)))
+    }
+
+    /// Sets the read timeout of this socket, used by `recv_from`.
+    ///
+    /// If the timeout expires, [`AxError::TimedOut`] is returned. `None` means
+    /// blocking infinitely, and a zero duration is an invalid argument.
+    pub fn set_read_timeout(&self, dur: Option<Duration>) -> AxResult {
+        self.read_timeout
+            .store(duration_to_nanos(dur)?, Ordering::Release);
+        Ok(())
+    }
+
+    /// Sends an ICMP packet to the host `addr`.
+    pub fn send_to(&self, buf: &[u8], addr: IpAddr) -> AxResult<usize> {
+        if buf.len() > PACKET_BUF_LEN {
+            return ax_err!(InvalidInput, "socket send_to() failed: packet too large");
+        }
+        let iface = route_iface(&addr);
+        let handle = *self.handles.iter().find(|h| h.iface == iface).unwrap();
+        let n = self.block_on(None, || {
+            SOCKET_SET.with_socket_mut::<icmp::Socket, _, _>(handle, |socket| {
+                match socket.send_slice(buf, addr) {
+                    Ok(()) => Ok(buf.len()),
+                    Err(SendError::BufferFull) => Err(AxError::Again),
+                    Err(SendError::Unaddressable) => {
+                        ax_err!(InvalidInput, "socket send_to() failed")
+                    }
+                }
+            })
+        })?;
+        SOCKET_SET.poll_interfaces();
+        Ok(n)
+    }
+
+    /// Receives an ICMP packet, returns the number of bytes read and the
+    /// source host.
+    ///
+    /// If `buf` is too small, the excess bytes of the packet are discarded.
+    pub fn recv_from(&self, buf: &mut [u8]) -> AxResult<(usize, IpAddr)> {
+        let timeout = self.read_timeout()?;
+        self.block_on(timeout, || {
+            self.handles
+                .iter()
+                .find_map(|&handle| {
+                    SOCKET_SET.with_socket_mut::<icmp::Socket, _, _>(handle, |socket| {
+                        socket.recv_slice(buf).ok()
+                    })
+                })
+                .ok_or(AxError::Again)
+        })
+    }
+
+    /// Returns the readiness of this socket without blocking.
+    ///
+    /// It is writable only if it can send to any interface.
+    pub fn poll(&self) -> AxResult<PollState> {
+        let mut state = PollState {
+            readable: false,
+            writable: true,
+            hangup: false,
+        };
+        for &handle in &self.handles {
+            SOCKET_SET.with_socket::<icmp::Socket, _, _>(handle, |socket| {
+                state.readable |= socket.can_recv();
+                state.writable &= socket.can_send();
+            });
+        }
+        Ok(state)
+    }
+
+    /// Wakes up `waiter` when the state of this socket changes.
+    ///
+    /// The subscription is one-shot, it must be renewed after every wakeup.
+    pub fn subscribe(&self, waiter: &Arc<SocketWaiter>) {
+        self.waiter.add_observer(waiter);
+        self.register_waker(&self.waiter.waker());
+    }
+
+    /// Registers `waker` to the underlying smoltcp sockets of all interfaces.
+    fn register_waker(&self, waker: &Waker) {
+        for &handle in &self.handles {
+            SOCKET_SET.with_socket_mut::<icmp::Socket, _, _>(handle, |socket| {
+                socket.register_recv_waker(waker);
+                socket.register_send_waker(waker);
+            });
+        }
+    }
+
+    fn block_on<F, T>(&self, timeout: Option<Duration>, f: F) -> AxResult<T>
+    where
+        F: FnMut() -> AxResult<T>,
+    {
+        let nonblock = self.is_nonblocking();
+        block_on(
+            &self.waiter,
+            |w| self.register_waker(w),
+            nonblock,
+            timeout,
+            f,
+        )
+    }
+}
+
+impl Drop for IcmpSocket {
+    fn drop(&mut self) {
+        for &handle in &self.handles {
+            SOCKET_SET.remove(handle);
+        }
+    }
+}
@@ -1,24 +1,31 @@
 use alloc::{boxed::Box, collections::VecDeque};
 use core::ops::DerefMut;
+use core::task::Waker;
 
 use axerrno::{ax_err, AxError, AxResult};
 use axsync::Mutex;
-use smoltcp::iface::SocketHandle;
 use smoltcp::socket::tcp::{self, State};
 
-use super::{SocketSetWrapper, LISTEN_QUEUE_SIZE, SOCKET_SET};
+use super::{IfaceId, SocketHandle, SocketSetWrapper, LISTEN_QUEUE_SIZE, SOCKET_SET};
 use crate::SocketAddr;
 
 const PORT_NUM: usize = 65536;
 
 struct ListenTableEntry {
     syn_queue: VecDeque<SocketHandle>,
+    rx_buf_len: usize,
+    tx_buf_len: usize,
+    /// Woken up when any connection in the SYN queue changes its state.
+    waker: Option<Waker>,
 }
 
 impl ListenTableEntry {
-    pub fn new() -> Self {
+    pub fn new(rx_buf_len: usize, tx_buf_len: usize) -> Self {
         Self {
             syn_queue: VecDeque::with_capacity(LISTEN_QUEUE_SIZE),
+            rx_buf_len,
+            tx_buf_len,
+            waker: None,
         }
     }
 }
@@ -51,13 +58,13 @@ impl ListenTable {
         self.tcp[port as usize].lock().is_none()
     }
 
-    pub fn listen(&self, port: u16) -> AxResult {
+    pub fn listen(&self, port: u16, rx_buf_len: usize, tx_buf_len: usize) -> AxResult {
         if port == 0 {
             return ax_err!(InvalidInput, "socket listen() failed");
         }
         let mut entry = self.tcp[port as usize].lock();
         if entry.is_none() {
-            *entry = Some(Box::new(ListenTableEntry::new()));
+            *entry = Some(Box::new(ListenTableEntry::new(rx_buf_len, tx_buf_len)));
             Ok(())
         } else {
             ax_err!(AlreadyExists, "socket listen() failed")
@@ -69,6 +76,32 @@ impl ListenTable {
         *self.tcp[port as usize].lock() = None;
     }
 
+    /// Returns whether there is a connection ready to be accepted on `port`.
+    pub fn can_accept(&self, port: u16) -> AxResult<bool> {
+        if let Some(entry) = self.tcp[port as usize].lock().as_ref() {
+            Ok(entry.syn_queue.iter().any(|&handle| {
+                SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| {
+                    !matches!(socket.state(), State::Listen | State::SynReceived)
+                })
+            }))
+        } else {
+            ax_err!(InvalidInput, "socket poll() failed: not listen")
+        }
+    }
+
+    /// Registers `waker` to be woken up when a connection on `port` may be
+    /// ready to be accepted.
+    pub fn register_waker(&self, port: u16, waker: &Waker) {
+        if let Some(entry) = self.tcp[port as usize].lock().deref_mut() {
+            for &handle in &entry.syn_queue {
+                SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+                    socket.register_recv_waker(waker)
+                });
+            }
+            entry.waker = Some(waker.clone());
+        }
+    }
+
     pub fn accept(&self, port: u16) -> AxResult<(SocketHandle, Option<SocketAddr>)> {
         fn get_socket_info(handle: SocketHandle) -> (bool, Option<SocketAddr>) {
             let (connected, peer_addr) =
@@ -127,16 +160,21 @@ impl ListenTable {
         }
     }
 
-    pub fn incoming_tcp_packet(&self, src: SocketAddr, dst: SocketAddr) {
+    /// Prepares a socket for the SYN packet received by the interface
+    /// `iface`, the connection goes through the same interface.
+    pub fn incoming_tcp_packet(&self, iface: IfaceId, src: SocketAddr, dst: SocketAddr) {
         if let Some(entry) = self.tcp[dst.port as usize].lock().deref_mut() {
             if entry.syn_queue.len() >= LISTEN_QUEUE_SIZE {
                 // SYN queue is full, drop the packet
                 warn!("SYN queue overflow!");
                 return;
             }
-            let mut socket = SocketSetWrapper::new_tcp_socket();
+            let mut socket = SocketSetWrapper::new_tcp_socket(entry.rx_buf_len, entry.tx_buf_len);
             if socket.listen(dst).is_ok() {
-                let handle = SOCKET_SET.add(socket);
+                if let Some(waker) = &entry.waker {
+                    socket.register_recv_waker(waker);
+                }
+                let handle = SOCKET_SET.add(iface, socket);
                 debug!(
                     "socket {}: prepare for connection {} -> {}",
                     handle, src, dst
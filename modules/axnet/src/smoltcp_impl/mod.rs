@@ -1,69 +1,189 @@
+mod icmp;
 mod listen_table;
+mod raw;
 mod tcp;
+mod waiter;
 
-use alloc::{collections::VecDeque, vec};
+use alloc::{collections::VecDeque, sync::Arc, vec, vec::Vec};
 use core::cell::RefCell;
+use core::fmt;
 use core::ops::DerefMut;
-
-use axdriver::NetDevices;
-use axhal::time::{current_time_nanos, NANOS_PER_MICROS};
+#[cfg(feature = "multitask")]
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::Waker;
+use core::time::Duration;
+
+use axdriver::{AxDevice, AxNetDevice};
+use axerrno::{ax_err, AxError, AxResult};
+use axhal::time::{current_time, current_time_nanos, NANOS_PER_MICROS};
 use axsync::Mutex;
-use driver_common::DevError;
-use driver_net::{NetBuffer, NetDriverOps};
+use driver_common::{BaseDriverOps, DevError};
+use driver_net::{loopback::LoopbackDev, ChecksumOffload, NetBuffer, NetDriverOps};
 use lazy_init::LazyInit;
-use smoltcp::iface::{Config, Interface, SocketHandle, SocketSet};
-use smoltcp::phy::{Device, DeviceCapabilities, Medium, RxToken, TxToken};
+use smoltcp::iface::{Config, Interface, SocketSet};
+use smoltcp::phy::{Checksum, ChecksumCapabilities, Device, DeviceCapabilities, Medium};
+use smoltcp::phy::{RxToken, TxToken};
 use smoltcp::socket::{self, AnySocket};
 use smoltcp::time::Instant;
-use smoltcp::wire::{EthernetAddress, HardwareAddress, IpAddress, IpCidr};
+use smoltcp::wire::{EthernetAddress, EthernetFrame, HardwareAddress, IpAddress, IpCidr};
+use smoltcp::wire::{IpProtocol, IpVersion, Ipv4Packet};
 
 use self::listen_table::ListenTable;
 
+pub use self::icmp::IcmpSocket;
+pub use self::raw::RawSocket;
 pub use self::tcp::TcpSocket;
+pub use self::waiter::SocketWaiter;
 
 const IP: IpAddress = IpAddress::v4(10, 0, 2, 15); // QEMU user networking default IP
 const GATEWAY: IpAddress = IpAddress::v4(10, 0, 2, 2); // QEMU user networking gateway
 const IP_PREFIX: u8 = 24;
 
+const LOOPBACK_IP: IpAddress = IpAddress::v4(127, 0, 0, 1);
+const LOOPBACK_IP_PREFIX: u8 = 8;
+
 const RANDOM_SEED: u64 = 0xA2CE_05A2_CE05_A2CE;
 
+// default buffer sizes of TCP sockets
 const TCP_RX_BUF_LEN: usize = 4096;
 const TCP_TX_BUF_LEN: usize = 4096;
 
+// buffer sizes of ICMP and raw sockets, in bytes and in packets
+const PACKET_BUF_LEN: usize = 4096;
+const PACKET_META_NUM: usize = 16;
+
 const RX_BUF_QUEUE_SIZE: usize = 64;
 const LISTEN_QUEUE_SIZE: usize = 512;
 
+/// The maximum time to block before polling the NIC that has no interrupts.
+const NO_IRQ_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
 static LISTEN_TABLE: LazyInit<ListenTable> = LazyInit::new();
 static SOCKET_SET: LazyInit<SocketSetWrapper> = LazyInit::new();
-static ETH0: LazyInit<InterfaceWrapper<axdriver::VirtIoNetDev>> = LazyInit::new();
+static LOOPBACK: LazyInit<InterfaceWrapper<LoopbackDev>> = LazyInit::new();
+static ETH0: LazyInit<InterfaceWrapper<AxNetDevice>> = LazyInit::new();
+
+/// Woken up by the NIC interrupts, or when the interfaces must be polled
+/// earlier than [`NEXT_POLL_NANOS`].
+static POLL_WAITER: SocketWaiter = SocketWaiter::new_const();
+/// When the poll task polls the interfaces next time, in nanoseconds.
+/// `u64::MAX` means it sleeps until the next wakeup.
+#[cfg(feature = "multitask")]
+static NEXT_POLL_NANOS: AtomicU64 = AtomicU64::new(u64::MAX);
+
+/// The network interfaces.
+///
+/// Each interface has its own socket set, and it only sends out the packets
+/// of the sockets in its set. So the packets to the loopback addresses are
+/// never sent through the NIC, and vice versa.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IfaceId {
+    Loopback = 0,
+    Eth0 = 1,
+}
+
+const NUM_IFACES: usize = 2;
+
+/// A handle of a socket in the socket set of an interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SocketHandle {
+    iface: IfaceId,
+    inner: smoltcp::iface::SocketHandle,
+}
 
-struct SocketSetWrapper<'a>(Mutex<SocketSet<'a>>);
+struct SocketSetWrapper<'a>([Mutex<SocketSet<'a>>; NUM_IFACES]);
 
 struct DeviceWrapper<D: NetDriverOps> {
     inner: RefCell<D>, // use `RefCell` is enough since it's wrapped in `Mutex` in `InterfaceWrapper`.
     rx_buf_queue: VecDeque<D::RxBuffer>,
+    medium: Medium,
 }
 
 struct InterfaceWrapper<D: NetDriverOps> {
+    id: IfaceId,
     name: &'static str,
     ether_addr: Option<EthernetAddress>,
     dev: Mutex<DeviceWrapper<D>>,
     iface: Mutex<Interface>,
+    irq_num: LazyInit<usize>,
+}
+
+impl IfaceId {
+    /// Returns the interfaces that have been created.
+    pub fn all() -> impl Iterator<Item = Self> {
+        [Self::Loopback, Self::Eth0]
+            .into_iter()
+            .filter(|&id| id == Self::Loopback || ETH0.is_init())
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Loopback => LOOPBACK.name(),
+            Self::Eth0 => ETH0.name(),
+        }
+    }
+
+    fn iface(self) -> &'static Mutex<Interface> {
+        match self {
+            Self::Loopback => &LOOPBACK.iface,
+            Self::Eth0 => &ETH0.iface,
+        }
+    }
+}
+
+impl fmt::Display for SocketHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.iface.name(), self.inner)
+    }
 }
 
 impl<'a> SocketSetWrapper<'a> {
     fn new() -> Self {
-        Self(Mutex::new(SocketSet::new(vec![])))
+        Self([
+            Mutex::new(SocketSet::new(vec![])),
+            Mutex::new(SocketSet::new(vec![])),
+        ])
     }
 
-    pub fn new_tcp_socket() -> socket::tcp::Socket<'a> {
-        let tcp_rx_buffer = socket::tcp::SocketBuffer::new(vec![0; TCP_RX_BUF_LEN]);
-        let tcp_tx_buffer = socket::tcp::SocketBuffer::new(vec![0; TCP_TX_BUF_LEN]);
+    fn sockets(&self, iface: IfaceId) -> &Mutex<SocketSet<'a>> {
+        &self.0[iface as usize]
+    }
+
+    pub fn new_tcp_socket(rx_buf_len: usize, tx_buf_len: usize) -> socket::tcp::Socket<'a> {
+        let tcp_rx_buffer = socket::tcp::SocketBuffer::new(vec![0; rx_buf_len]);
+        let tcp_tx_buffer = socket::tcp::SocketBuffer::new(vec![0; tx_buf_len]);
         socket::tcp::Socket::new(tcp_rx_buffer, tcp_tx_buffer)
     }
 
-    pub fn add<T: AnySocket<'a>>(&self, socket: T) -> SocketHandle {
-        let handle = self.0.lock().add(socket);
+    pub fn new_icmp_socket() -> socket::icmp::Socket<'a> {
+        let icmp_rx_buffer = socket::icmp::PacketBuffer::new(
+            vec![socket::icmp::PacketMetadata::EMPTY; PACKET_META_NUM],
+            vec![0; PACKET_BUF_LEN],
+        );
+        let icmp_tx_buffer = socket::icmp::PacketBuffer::new(
+            vec![socket::icmp::PacketMetadata::EMPTY; PACKET_META_NUM],
+            vec![0; PACKET_BUF_LEN],
+        );
+        socket::icmp::Socket::new(icmp_rx_buffer, icmp_tx_buffer)
+    }
+
+    pub fn new_raw_socket(protocol: IpProtocol) -> socket::raw::Socket<'a> {
+        let raw_rx_buffer = socket::raw::PacketBuffer::new(
+            vec![socket::raw::PacketMetadata::EMPTY; PACKET_META_NUM],
+            vec![0; PACKET_BUF_LEN],
+        );
+        let raw_tx_buffer = socket::raw::PacketBuffer::new(
+            vec![socket::raw::PacketMetadata::EMPTY; PACKET_META_NUM],
+            vec![0; PACKET_BUF_LEN],
+        );
+        socket::raw::Socket::new(IpVersion::Ipv4, protocol, raw_rx_buffer, raw_tx_buffer)
+    }
+
+    /// Adds a socket to the socket set of the interface `iface`, it will send
+    /// packets through that interface.
+    pub fn add<T: AnySocket<'a>>(&self, iface: IfaceId, socket: T) -> SocketHandle {
+        let inner = self.sockets(iface).lock().add(socket);
+        let handle = SocketHandle { iface, inner };
         debug!("socket {}: created", handle);
         handle
     }
@@ -72,8 +192,8 @@ impl<'a> SocketSetWrapper<'a> {
     where
         F: FnOnce(&T) -> R,
     {
-        let set = self.0.lock();
-        let socket = set.get(handle);
+        let set = self.sockets(handle.iface).lock();
+        let socket = set.get(handle.inner);
         f(socket)
     }
 
@@ -81,34 +201,68 @@ impl<'a> SocketSetWrapper<'a> {
     where
         F: FnOnce(&mut T) -> R,
     {
-        let mut set = self.0.lock();
-        let socket = set.get_mut(handle);
+        let mut set = self.sockets(handle.iface).lock();
+        let socket = set.get_mut(handle.inner);
         f(socket)
     }
 
+    /// Polls all interfaces, the tasks blocked on the sockets whose states
+    /// changed are woken up by smoltcp.
     pub fn poll_interfaces(&self) {
-        ETH0.poll(&self.0);
+        self.poll_all();
+        // the socket operations may set new timers (e.g. retransmission)
+        #[cfg(feature = "multitask")]
+        if deadline_nanos(self.poll_delay()) < NEXT_POLL_NANOS.load(Ordering::Acquire) {
+            POLL_WAITER.notify();
+        }
+    }
+
+    fn poll_all(&self) {
+        LOOPBACK.poll(self.sockets(IfaceId::Loopback));
+        if let Some(eth0) = ETH0.try_get() {
+            eth0.poll(self.sockets(IfaceId::Eth0));
+        }
+    }
+
+    /// Returns how long to wait before the next poll.
+    ///
+    /// `None` means there are no pending timers, the interfaces need not be
+    /// polled until the next network event.
+    pub fn poll_delay(&self) -> Option<Duration> {
+        let lo_delay = LOOPBACK.poll_delay(&self.sockets(IfaceId::Loopback).lock());
+        let eth0_delay = ETH0
+            .try_get()
+            .and_then(|eth0| eth0.poll_delay(&self.sockets(IfaceId::Eth0).lock()));
+        [lo_delay, eth0_delay].into_iter().flatten().min()
     }
 
     pub fn remove(&self, handle: SocketHandle) {
-        self.0.lock().remove(handle);
+        self.sockets(handle.iface).lock().remove(handle.inner);
         debug!("socket {}: destroyed", handle);
     }
 }
 
 impl<D: NetDriverOps> InterfaceWrapper<D> {
-    fn new(name: &'static str, dev: D, ether_addr: Option<EthernetAddress>) -> Self {
+    fn new(id: IfaceId, name: &'static str, dev: D, ether_addr: Option<EthernetAddress>) -> Self {
         let mut config = Config::new();
         config.random_seed = RANDOM_SEED;
         config.hardware_addr = ether_addr.map(HardwareAddress::Ethernet);
 
-        let mut dev = DeviceWrapper::new(dev);
+        // interfaces without an ethernet address carry raw IP packets
+        let medium = if ether_addr.is_some() {
+            Medium::Ethernet
+        } else {
+            Medium::Ip
+        };
+        let mut dev = DeviceWrapper::new(dev, medium);
         let iface = Mutex::new(Interface::new(config, &mut dev));
         Self {
+            id,
             name,
             ether_addr,
             dev: Mutex::new(dev),
             iface,
+            irq_num: LazyInit::new(),
         }
     }
 
@@ -127,6 +281,16 @@ impl<D: NetDriverOps> InterfaceWrapper<D> {
         });
     }
 
+    /// Registers `handler` for the NIC interrupt `irq_num`, the interface will
+    /// not be polled periodically if succeeded.
+    ///
+    /// The handler may run immediately, so the interface must be ready.
+    pub fn setup_irq(&self, irq_num: usize, handler: axhal::irq::IrqHandler) {
+        if axhal::irq::register_handler(irq_num, handler) {
+            self.irq_num.init_by(irq_num);
+        }
+    }
+
     pub fn setup_gateway(&self, gateway: IpAddress) {
         let mut iface = self.iface.lock();
         match gateway {
@@ -134,25 +298,50 @@ impl<D: NetDriverOps> InterfaceWrapper<D> {
         };
     }
 
+    /// Polls the interface with the sockets in its socket set.
     pub fn poll(&self, sockets: &Mutex<SocketSet>) {
         let mut dev = self.dev.lock();
+        if let Some(&irq_num) = self.irq_num.try_get() {
+            // the IRQ was masked by the handler until we get here
+            dev.inner.borrow_mut().ack_interrupt();
+            axhal::irq::set_enable(irq_num, true);
+        }
+        let (id, medium) = (self.id, dev.medium);
         dev.poll(|buf| {
-            snoop_tcp_packet(buf).ok(); // preprocess TCP packets
+            snoop_tcp_packet(id, buf, medium).ok(); // preprocess TCP packets
         });
 
-        let timestamp =
-            Instant::from_micros_const((current_time_nanos() / NANOS_PER_MICROS) as i64);
-        let mut iface = self.iface.lock();
         let mut sockets = sockets.lock();
-        iface.poll(timestamp, dev.deref_mut(), &mut sockets);
+        let mut iface = self.iface.lock();
+        iface.poll(current_instant(), dev.deref_mut(), &mut sockets);
+        // notify the NIC of all packets sent and buffers recycled during polling
+        if let Err(err) = dev.inner.borrow_mut().flush() {
+            warn!("{}: flush failed: {:?}", self.name, err);
+        }
+    }
+
+    /// Returns the time to wait before the next poll of this interface.
+    pub fn poll_delay(&self, sockets: &SocketSet) -> Option<Duration> {
+        let delay = self
+            .iface
+            .lock()
+            .poll_delay(current_instant(), sockets)
+            .map(|d| Duration::from_micros(d.total_micros()));
+        if !self.irq_num.is_init() && self.ether_addr.is_some() {
+            // the NIC has no interrupts, we must poll it by ourselves
+            Some(delay.map_or(NO_IRQ_POLL_INTERVAL, |d| d.min(NO_IRQ_POLL_INTERVAL)))
+        } else {
+            delay
+        }
     }
 }
 
 impl<D: NetDriverOps> DeviceWrapper<D> {
-    fn new(inner: D) -> Self {
+    fn new(inner: D, medium: Medium) -> Self {
         Self {
             inner: RefCell::new(inner),
             rx_buf_queue: VecDeque::with_capacity(RX_BUF_QUEUE_SIZE),
+            medium,
         }
     }
 
@@ -193,14 +382,27 @@ impl<D: NetDriverOps> Device for DeviceWrapper<D> {
     }
 
     fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
-        Some(AxNetTxToken(&self.inner))
+        let can_send = {
+            let mut dev = self.inner.borrow_mut();
+            dev.can_send() || (dev.reclaim_tx_buffers() > 0 && dev.can_send())
+        };
+        if can_send {
+            Some(AxNetTxToken(&self.inner))
+        } else {
+            None
+        }
     }
 
     fn capabilities(&self) -> DeviceCapabilities {
+        let dev_caps = self.inner.borrow().capabilities();
         let mut caps = DeviceCapabilities::default();
-        caps.max_transmission_unit = 1536;
-        caps.max_burst_size = Some(1);
-        caps.medium = Medium::Ethernet;
+        caps.max_transmission_unit = match self.medium {
+            Medium::Ethernet => dev_caps.mtu + EthernetFrame::<&[u8]>::header_len(),
+            _ => dev_caps.mtu,
+        };
+        caps.max_burst_size = dev_caps.max_burst;
+        caps.checksum = checksum_caps(dev_caps.rx_checksum, dev_caps.tx_checksum);
+        caps.medium = self.medium;
         caps
     }
 }
@@ -220,7 +422,9 @@ impl<'a, D: NetDriverOps> RxToken for AxNetRxToken<'a, D> {
             rx_buf.packet()
         );
         let result = f(rx_buf.packet_mut());
-        self.0.borrow_mut().recycle_rx_buffer(rx_buf).unwrap();
+        if let Err(err) = self.0.borrow_mut().recycle_rx_buffer(rx_buf) {
+            warn!("recycle_rx_buffer failed: {:?}", err);
+        }
         result
     }
 }
@@ -231,20 +435,67 @@ impl<'a, D: NetDriverOps> TxToken for AxNetTxToken<'a, D> {
         F: FnOnce(&mut [u8]) -> R,
     {
         let mut dev = self.0.borrow_mut();
-        let mut tx_buf = dev.new_tx_buffer(len).unwrap();
+        let mut tx_buf = match dev.new_tx_buffer(len) {
+            Ok(tx_buf) => tx_buf,
+            Err(err) => {
+                // drop the packet as if it was lost, the protocols will retry
+                warn!("new_tx_buffer failed: {:?}", err);
+                return f(&mut vec![0; len]);
+            }
+        };
         let result = f(tx_buf.packet_mut());
         trace!("SEND {} bytes: {:02X?}", len, tx_buf.packet());
-        dev.send(tx_buf).unwrap();
+        if let Err(err) = dev.transmit(tx_buf) {
+            warn!("transmit failed: {:?}", err);
+        }
         result
     }
 }
 
-fn snoop_tcp_packet(buf: &[u8]) -> Result<(), smoltcp::wire::Error> {
+/// Converts the checksum offloads of a NIC to what the stack needs to do.
+fn checksum_caps(rx: ChecksumOffload, tx: ChecksumOffload) -> ChecksumCapabilities {
+    let checksum = |rx_offloaded, tx_offloaded| match (rx_offloaded, tx_offloaded) {
+        (false, false) => Checksum::Both,
+        (false, true) => Checksum::Rx,
+        (true, false) => Checksum::Tx,
+        (true, true) => Checksum::None,
+    };
+    let mut caps = ChecksumCapabilities::default();
+    caps.ipv4 = checksum(rx.ipv4, tx.ipv4);
+    caps.tcp = checksum(rx.tcp, tx.tcp);
+    caps.udp = checksum(rx.udp, tx.udp);
+    caps.icmpv4 = checksum(rx.icmp, tx.icmp);
+    caps
+}
+
+fn current_instant() -> Instant {
+    Instant::from_micros_const((current_time_nanos() / NANOS_PER_MICROS) as i64)
+}
+
+fn ipv4_packet_of(buf: &[u8], medium: Medium) -> Result<Ipv4Packet<&[u8]>, smoltcp::wire::Error> {
+    use smoltcp::wire::EthernetProtocol;
+
+    match medium {
+        Medium::Ethernet => {
+            let ether_frame = EthernetFrame::new_checked(buf)?;
+            if ether_frame.ethertype() != EthernetProtocol::Ipv4 {
+                return Err(smoltcp::wire::Error);
+            }
+            Ipv4Packet::new_checked(&buf[EthernetFrame::<&[u8]>::header_len()..])
+        }
+        _ => Ipv4Packet::new_checked(buf),
+    }
+}
+
+fn snoop_tcp_packet(
+    iface: IfaceId,
+    buf: &[u8],
+    medium: Medium,
+) -> Result<(), smoltcp::wire::Error> {
     use crate::SocketAddr;
-    use smoltcp::wire::{EthernetFrame, IpProtocol, Ipv4Packet, TcpPacket};
+    use smoltcp::wire::TcpPacket;
 
-    let ether_frame = EthernetFrame::new_checked(buf)?;
-    let ipv4_packet = Ipv4Packet::new_checked(ether_frame.payload())?;
+    let ipv4_packet = ipv4_packet_of(buf, medium)?;
 
     if ipv4_packet.next_header() == IpProtocol::Tcp {
         let tcp_packet = TcpPacket::new_checked(ipv4_packet.payload())?;
@@ -253,22 +504,163 @@ fn snoop_tcp_packet(buf: &[u8]) -> Result<(), smoltcp::wire::Error> {
         let is_first = tcp_packet.syn() && !tcp_packet.ack();
         if is_first {
             // create a socket for the first incoming TCP packet, as the later accept() returns.
-            LISTEN_TABLE.incoming_tcp_packet(src_addr, dst_addr);
+            LISTEN_TABLE.incoming_tcp_packet(iface, src_addr, dst_addr);
         }
     }
     Ok(())
 }
 
-pub(crate) fn init(net_devs: NetDevices) {
-    let dev = net_devs.0;
+/// Returns the interface which the packets to `addr` should be sent through.
+///
+/// If there is no NIC, all packets go to the loopback interface, and those to
+/// non-loopback addresses are dropped there.
+fn route_iface(addr: &IpAddress) -> IfaceId {
+    match addr {
+        IpAddress::Ipv4(v4) if v4.is_loopback() => IfaceId::Loopback,
+        _ if ETH0.is_init() => IfaceId::Eth0,
+        _ => IfaceId::Loopback,
+    }
+}
+
+/// Polls the interfaces and calls `f` repeatedly until it returns anything
+/// other than [`AxError::Again`].
+///
+/// Before each call, `register_waker` is called to register the waker of
+/// `waiter` to the smoltcp sockets. Between two calls, the current task sleeps
+/// on `waiter` until smoltcp reports that the socket state has changed. If
+/// `timeout` expires first, returns [`AxError::TimedOut`]. If `nonblock` is
+/// true, `f` is called only once.
+fn block_on<R, F, T>(
+    waiter: &Arc<SocketWaiter>,
+    register_waker: R,
+    nonblock: bool,
+    timeout: Option<Duration>,
+    mut f: F,
+) -> AxResult<T>
+where
+    R: Fn(&Waker),
+    F: FnMut() -> AxResult<T>,
+{
+    if nonblock {
+        SOCKET_SET.poll_interfaces();
+        return f();
+    }
+
+    let waker = waiter.waker();
+    let deadline = timeout.map(|dur| current_time() + dur);
+    loop {
+        let token = waiter.prepare();
+        SOCKET_SET.poll_interfaces();
+        register_waker(&waker);
+        match f() {
+            Err(AxError::Again) => {
+                // the timers of the sockets are handled by the poll task
+                #[cfg(feature = "multitask")]
+                let mut delay = None;
+                #[cfg(not(feature = "multitask"))]
+                let mut delay = SOCKET_SET.poll_delay();
+                if let Some(deadline) = deadline {
+                    let now = current_time();
+                    if now >= deadline {
+                        return ax_err!(TimedOut, "socket operation timed out");
+                    }
+                    let remain = deadline - now;
+                    delay = Some(delay.map_or(remain, |d: Duration| d.min(remain)));
+                }
+                waiter.wait(token, delay);
+            }
+            res => return res,
+        }
+    }
+}
+
+/// Converts a timeout option to nanoseconds, 0 means `None`.
+fn duration_to_nanos(dur: Option<Duration>) -> AxResult<u64> {
+    match dur {
+        Some(dur) if dur.is_zero() => ax_err!(InvalidInput, "zero duration is not allowed"),
+        Some(dur) => Ok(dur.as_nanos() as u64),
+        None => Ok(0),
+    }
+}
+
+fn nanos_to_duration(nanos: u64) -> Option<Duration> {
+    if nanos == 0 {
+        None
+    } else {
+        Some(Duration::from_nanos(nanos))
+    }
+}
+
+/// Polls all network interfaces, to send out and receive packets, and to
+/// update the socket states.
+pub fn poll_interfaces() {
+    SOCKET_SET.poll_interfaces();
+}
+
+/// Converts the delay before the next poll to the deadline in nanoseconds.
+#[cfg(feature = "multitask")]
+fn deadline_nanos(delay: Option<Duration>) -> u64 {
+    delay.map_or(u64::MAX, |d| (current_time() + d).as_nanos() as u64)
+}
+
+/// Polls the interfaces when the NIC interrupts or the socket timers expire,
+/// so that the blocked tasks are woken up without polling by themselves.
+#[cfg(feature = "multitask")]
+fn poll_task() {
+    loop {
+        let token = POLL_WAITER.prepare();
+        SOCKET_SET.poll_all();
+        NEXT_POLL_NANOS.store(deadline_nanos(SOCKET_SET.poll_delay()), Ordering::Release);
+        // a timer may have been set before the store without notifying us
+        let delay = SOCKET_SET.poll_delay();
+        NEXT_POLL_NANOS.fetch_min(deadline_nanos(delay), Ordering::AcqRel);
+        POLL_WAITER.wait(token, delay);
+    }
+}
+
+fn eth0_irq_handler() {
+    if let Some(&irq_num) = ETH0.try_get().and_then(|eth0| eth0.irq_num.try_get()) {
+        // mask the IRQ until the interface is polled and the NIC is acknowledged
+        axhal::irq::set_enable(irq_num, false);
+    }
+    POLL_WAITER.notify();
+}
+
+pub(crate) fn init(net_devs: Vec<AxDevice<AxNetDevice>>) {
+    let lo = InterfaceWrapper::new(IfaceId::Loopback, "lo", LoopbackDev::new(), None);
+    lo.setup_ip_addr(LOOPBACK_IP, LOOPBACK_IP_PREFIX);
+    LOOPBACK.init_by(lo);
+
+    info!("created net interface {:?}:", LOOPBACK.name());
+    info!("  ip:       {}/{}", LOOPBACK_IP, LOOPBACK_IP_PREFIX);
+
+    SOCKET_SET.init_by(SocketSetWrapper::new());
+    LISTEN_TABLE.init_by(ListenTable::new());
+    #[cfg(feature = "multitask")]
+    axtask::spawn(poll_task);
+
+    let mut net_devs = net_devs.into_iter();
+    let Some(dev) = net_devs.next() else {
+        warn!("no NIC found, only the loopback interface is available");
+        return;
+    };
+    for dev in net_devs {
+        warn!(
+            "NIC {:?} is ignored, only one NIC is supported",
+            dev.device_name()
+        );
+    }
+
+    let irq_num = dev.irq();
+    let dev = dev.into_inner();
     let ether_addr = EthernetAddress(dev.mac_address().0);
-    let eth0 = InterfaceWrapper::new("eth0", dev, Some(ether_addr));
+    let eth0 = InterfaceWrapper::new(IfaceId::Eth0, "eth0", dev, Some(ether_addr));
     eth0.setup_ip_addr(IP, IP_PREFIX);
     eth0.setup_gateway(GATEWAY);
-
     ETH0.init_by(eth0);
-    SOCKET_SET.init_by(SocketSetWrapper::new());
-    LISTEN_TABLE.init_by(ListenTable::new());
+    if let Some(irq_num) = irq_num {
+        ETH0.setup_irq(irq_num, eth0_irq_handler);
+    }
 
     info!("created net interface {:?}:", ETH0.name());
     if let Some(ether_addr) = ETH0.ethernet_address() {
@@ -276,4 +668,7 @@ pub(crate) fn init(net_devs: NetDevices) {
     }
     info!("  ip:       {}/{}", IP, IP_PREFIX);
     info!("  gateway:  {}", GATEWAY);
+    if let Some(irq_num) = ETH0.irq_num.try_get() {
+        info!("  irq:      {}", irq_num);
+    }
 }
@@ -0,0 +1,167 @@
+use alloc::{sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::task::Waker;
+use core::time::Duration;
+
+use axerrno::{ax_err, ax_err_type, AxError, AxResult};
+use axio::PollState;
+use smoltcp::socket::raw;
+use smoltcp::wire::{IpProtocol, Ipv4Packet};
+
+use super::{block_on, duration_to_nanos, nanos_to_duration, route_iface};
+use super::{IfaceId, SocketHandle, SocketSetWrapper, SocketWaiter, PACKET_BUF_LEN, SOCKET_SET};
+
+/// A raw IPv4 socket, to send and receive the IP packets of a specific
+/// protocol.
+///
+/// The data sent and received are whole IP packets, starting from the IP
+/// header. The checksum of the outgoing IP headers is filled by the stack.
+///
+/// It receives the packets from all interfaces, and sends each packet through
+/// the interface routed by its destination.
+pub struct RawSocket {
+    handles: Vec<SocketHandle>, // one for each interface
+    waiter: Arc<SocketWaiter>,
+    nonblock: AtomicBool,
+    read_timeout: AtomicU64, // in nanoseconds, 0 means no timeout
+}
+
+impl RawSocket {
+    /// Creates a raw socket for the IP protocol number `protocol` (e.g., 1 for
+    /// ICMP, 17 for UDP).
+    pub fn new(protocol: u8) -> Self {
+        let protocol = IpProtocol::from(protocol);
+        let handles = IfaceId::all()
+            .map(|iface| SOCKET_SET.add(iface, SocketSetWrapper::new_raw_socket(protocol)))
+            .collect();
+        Self {
+            handles,
+            waiter: SocketWaiter::new(),
+            nonblock: AtomicBool::new(false),
+            read_timeout: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns whether this socket is in non-blocking mode.
+    pub fn is_nonblocking(&self) -> bool {
+        self.nonblock.load(Ordering::Acquire)
+    }
+
+    /// Moves this socket into or out of non-blocking mode.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> AxResult {
+        self.nonblock.store(nonblocking, Ordering::Release);
+        Ok(())
+    }
+
+    /// Returns the read timeout of this socket.
+    pub fn read_timeout(&self) -> AxResult<Option<Duration>> {
+        Ok(nanos_to_duration(self.read_timeout.load(Ordering::Acquire)))
+    }
+
+    /// Sets the read timeout of this socket, used by `recv`.
+    ///
+    /// If the timeout expires, [`AxError::TimedOut`] is returned. `None` means
+    /// blocking infinitely, and a zero duration is an invalid argument.
+    pub fn set_read_timeout(&self, dur: Option<Duration>) -> AxResult {
+        self.read_timeout
+            .store(duration_to_nanos(dur)?, Ordering::Release);
+        Ok(())
+    }
+
+    /// Sends an IP packet, which is routed by its destination address.
+    pub fn send(&self, packet: &[u8]) -> AxResult<usize> {
+        if packet.len() > PACKET_BUF_LEN {
+            return ax_err!(InvalidInput, "socket send() failed: packet too large");
+        }
+        let dst_addr = Ipv4Packet::new_checked(packet)
+            .map_err(|_| ax_err_type!(InvalidInput, "socket send() failed: invalid packet"))?
+            .dst_addr();
+        let iface = route_iface(&dst_addr.into());
+        let handle = *self.handles.iter().find(|h| h.iface == iface).unwrap();
+        let n = self.block_on(None, || {
+            SOCKET_SET.with_socket_mut::<raw::Socket, _, _>(handle, |socket| {
+                socket
+                    .send_slice(packet)
+                    .map(|_| packet.len())
+                    .map_err(|_| AxError::Again) // tx buffer is full
+            })
+        })?;
+        SOCKET_SET.poll_interfaces();
+        Ok(n)
+    }
+
+    /// Receives an IP packet, returns the number of bytes read.
+    ///
+    /// If `buf` is too small, the excess bytes of the packet are discarded.
+    pub fn recv(&self, buf: &mut [u8]) -> AxResult<usize> {
+        let timeout = self.read_timeout()?;
+        self.block_on(timeout, || {
+            self.handles
+                .iter()
+                .find_map(|&handle| {
+                    SOCKET_SET.with_socket_mut::<raw::Socket, _, _>(handle, |socket| {
+                        socket.recv_slice(buf).ok()
+                    })
+                })
+                .ok_or(AxError::Again)
+        })
+    }
+
+    /// Returns the readiness of this socket without blocking.
+    ///
+    /// It is writable only if it can send to any interface.
+    pub fn poll(&self) -> AxResult<PollState> {
+        let mut state = PollState {
+            readable: false,
+            writable: true,
+            hangup: false,
+        };
+        for &handle in &self.handles {
+            SOCKET_SET.with_socket::<raw::Socket, _, _>(handle, |socket| {
+                state.readable |= socket.can_recv();
+                state.writable &= socket.can_send();
+            });
+        }
+        Ok(state)
+    }
+
+    /// Wakes up `waiter` when the state of this socket changes.
+    ///
+    /// The subscription is one-shot, it must be renewed after every wakeup.
+    pub fn subscribe(&self, waiter: &Arc<SocketWaiter>) {
+        self.waiter.add_observer(waiter);
+        self.register_waker(&self.waiter.waker());
+    }
+
+    /// Registers `waker` to the underlying smoltcp sockets of all interfaces.
+    fn register_waker(&self, waker: &Waker) {
+        for &handle in &self.handles {
+            SOCKET_SET.with_socket_mut::<raw::Socket, _, _>(handle, |socket| {
+                socket.register_recv_waker(waker);
+                socket.register_send_waker(waker);
+            });
+        }
+    }
+
+    fn block_on<F, T>(&self, timeout: Option<Duration>, f: F) -> AxResult<T>
+    where
+        F: FnMut() -> AxResult<T>,
+    {
+        let nonblock = self.is_nonblocking();
+        block_on(
+            &self.waiter,
+            |w| self.register_waker(w),
+            nonblock,
+            timeout,
+            f,
+        )
+    }
+}
+
+impl Drop for RawSocket {
+    fn drop(&mut self) {
+        for &handle in &self.handles {
+            SOCKET_SET.remove(handle);
+        }
+    }
+}
@@ -1,27 +1,55 @@
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::task::Waker;
+use core::time::Duration;
+
 use axerrno::{ax_err, ax_err_type, AxError, AxResult};
+use axio::PollState;
 use axsync::Mutex;
-use smoltcp::iface::SocketHandle;
 use smoltcp::socket::tcp::{self, ConnectError, RecvError, State};
 use smoltcp::wire::IpAddress;
 
-use super::{SocketSetWrapper, ETH0, LISTEN_TABLE, SOCKET_SET};
+use super::{block_on, duration_to_nanos, nanos_to_duration, route_iface};
+use super::{IfaceId, SocketHandle, SocketSetWrapper, SocketWaiter, LISTEN_TABLE, SOCKET_SET};
+use super::{TCP_RX_BUF_LEN, TCP_TX_BUF_LEN};
 use crate::SocketAddr;
 
 pub struct TcpSocket {
     handle: Option<SocketHandle>, // `None` if is listening
     local_addr: Option<SocketAddr>,
     peer_addr: Option<SocketAddr>,
+    waiter: Arc<SocketWaiter>,
+    nonblock: AtomicBool,
+    read_timeout: AtomicU64,  // in nanoseconds, 0 means no timeout
+    write_timeout: AtomicU64, // in nanoseconds, 0 means no timeout
+    nodelay: AtomicBool,
+    keepalive: AtomicU64, // in nanoseconds, 0 means disabled
+    rx_buf_len: usize,
+    tx_buf_len: usize,
 }
 
 impl TcpSocket {
     #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
-        let socket = SocketSetWrapper::new_tcp_socket();
-        let handle = Some(SOCKET_SET.add(socket));
+        // not routed yet, it does not send anything until connected
+        let socket = SocketSetWrapper::new_tcp_socket(TCP_RX_BUF_LEN, TCP_TX_BUF_LEN);
+        let handle = Some(SOCKET_SET.add(IfaceId::Loopback, socket));
+        Self::from_handle(handle, TCP_RX_BUF_LEN, TCP_TX_BUF_LEN)
+    }
+
+    fn from_handle(handle: Option<SocketHandle>, rx_buf_len: usize, tx_buf_len: usize) -> Self {
         Self {
             handle,
             local_addr: None,
             peer_addr: None,
+            waiter: SocketWaiter::new(),
+            nonblock: AtomicBool::new(false),
+            read_timeout: AtomicU64::new(0),
+            write_timeout: AtomicU64::new(0),
+            nodelay: AtomicBool::new(false),
+            keepalive: AtomicU64::new(0),
+            rx_buf_len,
+            tx_buf_len,
         }
     }
 
@@ -37,6 +65,115 @@ impl TcpSocket {
         self.peer_addr.ok_or(AxError::NotConnected)
     }
 
+    /// Returns whether this socket is in non-blocking mode.
+    pub fn is_nonblocking(&self) -> bool {
+        self.nonblock.load(Ordering::Acquire)
+    }
+
+    /// Moves this socket into or out of non-blocking mode.
+    ///
+    /// In non-blocking mode, `connect`, `accept`, `send` and `recv` return
+    /// [`AxError::Again`] immediately if the operation cannot complete.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> AxResult {
+        self.nonblock.store(nonblocking, Ordering::Release);
+        Ok(())
+    }
+
+    /// Returns the read timeout of this socket.
+    pub fn read_timeout(&self) -> AxResult<Option<Duration>> {
+        Ok(nanos_to_duration(self.read_timeout.load(Ordering::Acquire)))
+    }
+
+    /// Sets the read timeout of this socket, used by `recv` and `accept`.
+    ///
+    /// If the timeout expires, [`AxError::TimedOut`] is returned. `None` means
+    /// blocking infinitely, and a zero duration is an invalid argument.
+    pub fn set_read_timeout(&self, dur: Option<Duration>) -> AxResult {
+        self.read_timeout
+            .store(duration_to_nanos(dur)?, Ordering::Release);
+        Ok(())
+    }
+
+    /// Returns the write timeout of this socket.
+    pub fn write_timeout(&self) -> AxResult<Option<Duration>> {
+        Ok(nanos_to_duration(
+            self.write_timeout.load(Ordering::Acquire),
+        ))
+    }
+
+    /// Sets the write timeout of this socket, used by `send` and `connect`.
+    ///
+    /// If the timeout expires, [`AxError::TimedOut`] is returned. `None` means
+    /// blocking infinitely, and a zero duration is an invalid argument.
+    pub fn set_write_timeout(&self, dur: Option<Duration>) -> AxResult {
+        self.write_timeout
+            .store(duration_to_nanos(dur)?, Ordering::Release);
+        Ok(())
+    }
+
+    /// Returns whether the Nagle's algorithm is disabled (`TCP_NODELAY`).
+    pub fn nodelay(&self) -> AxResult<bool> {
+        Ok(self.nodelay.load(Ordering::Acquire))
+    }
+
+    /// Disables or enables the Nagle's algorithm (`TCP_NODELAY`).
+    ///
+    /// It is inherited by the sockets returned by `accept`.
+    pub fn set_nodelay(&self, nodelay: bool) -> AxResult {
+        self.nodelay.store(nodelay, Ordering::Release);
+        self.apply_options();
+        Ok(())
+    }
+
+    /// Returns the keep-alive interval of this socket.
+    pub fn keepalive(&self) -> AxResult<Option<Duration>> {
+        Ok(nanos_to_duration(self.keepalive.load(Ordering::Acquire)))
+    }
+
+    /// Sets the interval of sending keep-alive packets when the connection is
+    /// idle, `None` disables keep-alive.
+    ///
+    /// It is inherited by the sockets returned by `accept`.
+    pub fn set_keepalive(&self, interval: Option<Duration>) -> AxResult {
+        self.keepalive
+            .store(duration_to_nanos(interval)?, Ordering::Release);
+        self.apply_options();
+        Ok(())
+    }
+
+    /// Returns the size of the receive buffer in bytes.
+    pub fn recv_buffer_size(&self) -> usize {
+        self.rx_buf_len
+    }
+
+    /// Returns the size of the send buffer in bytes.
+    pub fn send_buffer_size(&self) -> usize {
+        self.tx_buf_len
+    }
+
+    /// Sets the size of the receive buffer in bytes.
+    ///
+    /// It must be called before `connect` or `listen`, and it is inherited by
+    /// the sockets returned by `accept`.
+    pub fn set_recv_buffer_size(&mut self, size: usize) -> AxResult {
+        self.resize_buffers(size, self.tx_buf_len)
+    }
+
+    /// Sets the size of the send buffer in bytes.
+    ///
+    /// It must be called before `connect` or `listen`, and it is inherited by
+    /// the sockets returned by `accept`.
+    pub fn set_send_buffer_size(&mut self, size: usize) -> AxResult {
+        self.resize_buffers(self.rx_buf_len, size)
+    }
+
+    /// Connects to `addr`.
+    ///
+    /// In non-blocking mode, it returns [`AxError::Again`] if the connection
+    /// is in progress, and it can be called again to check whether the
+    /// connection is established. [`AxError::AlreadyExists`] is returned only
+    /// if the socket is already connected. If the write timeout expires, the
+    /// connection attempt is aborted.
     pub fn connect(&mut self, addr: SocketAddr) -> AxResult {
         let handle = if self.is_listening() {
             return ax_err!(AlreadyExists, "socket connect() failed: already connected");
@@ -44,13 +181,55 @@ impl TcpSocket {
             self.handle.unwrap()
         };
 
+        let state = SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| socket.state());
+        let handle = match state {
+            State::Closed => self.start_connect(handle, addr)?,
+            State::SynSent => handle, // still in progress, wait for it again
+            _ => return ax_err!(AlreadyExists, "socket connect() failed: already connected"),
+        };
+
+        let timeout = self.write_timeout()?;
+        self.block_on(timeout, || {
+            let (state, may_recv) = SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| {
+                (socket.state(), socket.may_recv())
+            });
+            if may_recv || state == State::Established {
+                Ok(())
+            } else if state == State::SynSent {
+                Err(AxError::Again)
+            } else {
+                ax_err!(ConnectionRefused, "socket connect() failed")
+            }
+        })
+        .map_err(|e| {
+            if e == AxError::TimedOut {
+                SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| socket.abort());
+            }
+            if e != AxError::Again {
+                self.local_addr = None;
+                self.peer_addr = None;
+            }
+            e
+        })
+    }
+
+    /// Sends the SYN packet to `addr`, returns the handle of the connecting
+    /// socket.
+    fn start_connect(&mut self, handle: SocketHandle, addr: SocketAddr) -> AxResult<SocketHandle> {
         // TODO: check host unreachable
+        let iface = route_iface(&addr.addr);
+        let handle = if handle.iface != iface {
+            // move to the socket set of the interface to send packets through
+            self.recreate(iface, self.rx_buf_len, self.tx_buf_len)?
+        } else {
+            handle
+        };
         let local_port = get_ephemeral_port()?;
-        let iface = &ETH0.iface;
+        self.apply_options();
         let (local_addr, peer_addr) =
             SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
                 socket
-                    .connect(iface.lock().context(), addr, local_port)
+                    .connect(iface.iface().lock().context(), addr, local_port)
                     .or_else(|e| match e {
                         ConnectError::InvalidState => {
                             ax_err!(AlreadyExists, "socket connect() failed")
@@ -62,21 +241,11 @@ impl TcpSocket {
                 Ok((socket.local_endpoint(), socket.remote_endpoint()))
             })?;
 
-        loop {
-            SOCKET_SET.poll_interfaces();
-            let (state, may_recv) = SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| {
-                (socket.state(), socket.may_recv())
-            });
-            if may_recv || state == State::Established {
-                self.local_addr = local_addr;
-                self.peer_addr = peer_addr;
-                return Ok(());
-            } else if state == State::SynSent {
-                axtask::yield_now();
-            } else {
-                return ax_err!(ConnectionRefused, "socket connect() failed");
-            }
-        }
+        // set the addresses here, as a non-blocking connect returns before
+        // the connection is established.
+        self.local_addr = local_addr;
+        self.peer_addr = peer_addr;
+        Ok(handle)
     }
 
     pub fn bind(&mut self, addr: SocketAddr) -> AxResult {
@@ -107,7 +276,7 @@ impl TcpSocket {
             port
         };
 
-        LISTEN_TABLE.listen(local_port)?;
+        LISTEN_TABLE.listen(local_port, self.rx_buf_len, self.tx_buf_len)?;
         debug!("socket listening on {}", self.local_addr.unwrap());
         let handle = self.handle.take().unwrap(); // should not connect/send/recv any more
         SOCKET_SET.remove(handle);
@@ -124,21 +293,16 @@ impl TcpSocket {
             .ok_or_else(|| ax_err_type!(InvalidInput, "socket accept() failed: no address bound"))?
             .port;
 
-        loop {
-            SOCKET_SET.poll_interfaces();
-            match LISTEN_TABLE.accept(local_port) {
-                Ok((handle, peer_addr)) => {
-                    debug!("socket accepted a new connection {}", peer_addr.unwrap());
-                    return Ok(TcpSocket {
-                        handle: Some(handle),
-                        local_addr: self.local_addr,
-                        peer_addr,
-                    });
-                }
-                Err(AxError::Again) => axtask::yield_now(),
-                Err(e) => return Err(e),
-            }
-        }
+        let timeout = self.read_timeout()?;
+        let (handle, peer_addr) = self.block_on(timeout, || LISTEN_TABLE.accept(local_port))?;
+        debug!("socket accepted a new connection {}", peer_addr.unwrap());
+
+        let mut socket = TcpSocket::from_handle(Some(handle), self.rx_buf_len, self.tx_buf_len);
+        socket.local_addr = self.local_addr;
+        socket.peer_addr = peer_addr;
+        socket.set_nodelay(self.nodelay()?)?;
+        socket.set_keepalive(self.keepalive()?)?;
+        Ok(socket)
     }
 
     pub fn shutdown(&self) -> AxResult {
@@ -162,9 +326,9 @@ impl TcpSocket {
         let handle = self
             .handle
             .ok_or_else(|| ax_err_type!(NotConnected, "socket recv() failed"))?;
-        loop {
-            SOCKET_SET.poll_interfaces();
-            match SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+        let timeout = self.read_timeout()?;
+        let n = self.block_on(timeout, || {
+            SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
                 if !socket.is_open() {
                     // not connected
                     ax_err!(NotConnected, "socket recv() failed")
@@ -183,24 +347,19 @@ impl TcpSocket {
                     // no more data
                     Err(AxError::Again)
                 }
-            }) {
-                Ok(n) => {
-                    SOCKET_SET.poll_interfaces();
-                    return Ok(n);
-                }
-                Err(AxError::Again) => axtask::yield_now(),
-                Err(e) => return Err(e),
-            }
-        }
+            })
+        })?;
+        SOCKET_SET.poll_interfaces();
+        Ok(n)
     }
 
     pub fn send(&self, buf: &[u8]) -> AxResult<usize> {
         let handle = self
             .handle
             .ok_or_else(|| ax_err_type!(NotConnected, "socket send() failed"))?;
-        loop {
-            SOCKET_SET.poll_interfaces();
-            match SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+        let timeout = self.write_timeout()?;
+        let n = self.block_on(timeout, || {
+            SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
                 if !socket.is_open() || !socket.may_send() {
                     // not connected
                     ax_err!(NotConnected, "socket send() failed")
@@ -215,15 +374,132 @@ impl TcpSocket {
                     // tx buffer is full
                     Err(AxError::Again)
                 }
-            }) {
-                Ok(n) => {
-                    SOCKET_SET.poll_interfaces();
-                    return Ok(n);
-                }
-                Err(AxError::Again) => axtask::yield_now(),
-                Err(e) => return Err(e),
+            })
+        })?;
+        SOCKET_SET.poll_interfaces();
+        Ok(n)
+    }
+
+    /// Returns the readiness of this socket without blocking.
+    ///
+    /// A listening socket is readable if there is a connection ready to be
+    /// accepted. A connecting socket is neither readable nor writable. The
+    /// interfaces are not polled here, call [`poll_interfaces`] first to get
+    /// the latest state.
+    ///
+    /// [`poll_interfaces`]: crate::poll_interfaces
+    pub fn poll(&self) -> AxResult<PollState> {
+        let handle = match self.handle {
+            Some(handle) => handle,
+            None => {
+                let local_port = self
+                    .local_addr
+                    .ok_or_else(|| ax_err_type!(InvalidInput, "socket poll() failed"))?
+                    .port;
+                return Ok(PollState {
+                    readable: LISTEN_TABLE.can_accept(local_port)?,
+                    ..Default::default()
+                });
             }
+        };
+        Ok(
+            SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| match socket.state() {
+                State::SynSent | State::SynReceived => PollState::default(),
+                _ => PollState {
+                    // the end of stream is also readable, `recv` returns 0
+                    readable: socket.can_recv() || !socket.may_recv(),
+                    writable: socket.can_send(),
+                    hangup: !socket.may_recv() && !socket.may_send(),
+                },
+            }),
+        )
+    }
+
+    /// Wakes up `waiter` when the state of this socket changes.
+    ///
+    /// The subscription is one-shot, it must be renewed after every wakeup.
+    pub fn subscribe(&self, waiter: &Arc<SocketWaiter>) {
+        self.waiter.add_observer(waiter);
+        self.register_waker(&self.waiter.waker());
+    }
+
+    /// Registers `waker` to the underlying smoltcp socket, or to the
+    /// connections in the SYN queue if listening.
+    fn register_waker(&self, waker: &Waker) {
+        if let Some(handle) = self.handle {
+            SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+                socket.register_recv_waker(waker);
+                socket.register_send_waker(waker);
+            });
+        } else if let Some(local_addr) = self.local_addr {
+            LISTEN_TABLE.register_waker(local_addr.port, waker);
+        }
+    }
+
+    /// Applies the socket options to the underlying smoltcp socket.
+    fn apply_options(&self) {
+        if let Some(handle) = self.handle {
+            let nodelay = self.nodelay.load(Ordering::Acquire);
+            let keepalive = nanos_to_duration(self.keepalive.load(Ordering::Acquire));
+            SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+                socket.set_nagle_enabled(!nodelay);
+                socket.set_keep_alive(
+                    keepalive.map(|d| smoltcp::time::Duration::from_micros(d.as_micros() as u64)),
+                );
+            });
+        }
+    }
+
+    /// Replaces the underlying smoltcp socket with one has the new buffer sizes.
+    fn resize_buffers(&mut self, rx_buf_len: usize, tx_buf_len: usize) -> AxResult {
+        if rx_buf_len == 0 || tx_buf_len == 0 {
+            return ax_err!(InvalidInput, "socket buffer size cannot be zero");
         }
+        let handle = self
+            .handle
+            .ok_or_else(|| ax_err_type!(InvalidInput, "socket is already listening"))?;
+        self.recreate(handle.iface, rx_buf_len, tx_buf_len)?;
+        Ok(())
+    }
+
+    /// Replaces the underlying smoltcp socket, which must not be connected,
+    /// with a new one in the socket set of `iface`.
+    fn recreate(
+        &mut self,
+        iface: IfaceId,
+        rx_buf_len: usize,
+        tx_buf_len: usize,
+    ) -> AxResult<SocketHandle> {
+        let handle = self
+            .handle
+            .ok_or_else(|| ax_err_type!(InvalidInput, "socket is already listening"))?;
+        let state = SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| socket.state());
+        if state != State::Closed {
+            return ax_err!(InvalidInput, "socket is already connected");
+        }
+
+        SOCKET_SET.remove(handle);
+        let socket = SocketSetWrapper::new_tcp_socket(rx_buf_len, tx_buf_len);
+        let handle = SOCKET_SET.add(iface, socket);
+        self.handle = Some(handle);
+        self.rx_buf_len = rx_buf_len;
+        self.tx_buf_len = tx_buf_len;
+        self.apply_options();
+        Ok(handle)
+    }
+
+    fn block_on<F, T>(&self, timeout: Option<Duration>, f: F) -> AxResult<T>
+    where
+        F: FnMut() -> AxResult<T>,
+    {
+        let nonblock = self.is_nonblocking();
+        block_on(
+            &self.waiter,
+            |w| self.register_waker(w),
+            nonblock,
+            timeout,
+            f,
+        )
     }
 }
 
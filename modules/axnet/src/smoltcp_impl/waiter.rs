@@ -0,0 +1,105 @@
+use alloc::{sync::Arc, task::Wake, vec::Vec};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::Waker;
+use core::time::Duration;
+
+use axsync::spin::SpinNoIrq;
+
+/// A wait queue for blocking until the state of a socket changes.
+///
+/// Every socket owns one, and registers it as the waker of the underlying
+/// smoltcp sockets, so only the tasks blocked on the sockets that smoltcp
+/// reports ready or changed are woken up. A task that waits on several
+/// sockets at once can create its own, and subscribe it to their waiters.
+pub struct SocketWaiter {
+    #[cfg(feature = "multitask")]
+    wq: axtask::WaitQueue,
+    /// Increased on every wakeup.
+    seq: AtomicUsize,
+    /// Other waiters to wake up along with this one, cleared on wakeup.
+    observers: SpinNoIrq<Vec<Arc<SocketWaiter>>>,
+}
+
+impl SocketWaiter {
+    pub(crate) const fn new_const() -> Self {
+        Self {
+            #[cfg(feature = "multitask")]
+            wq: axtask::WaitQueue::new(),
+            seq: AtomicUsize::new(0),
+            observers: SpinNoIrq::new(Vec::new()),
+        }
+    }
+
+    /// Creates a new waiter.
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::new_const())
+    }
+
+    /// Returns a waker that wakes up this waiter.
+    pub fn waker(self: &Arc<Self>) -> Waker {
+        Waker::from(self.clone())
+    }
+
+    /// Starts listening for wakeups, returns a token for [`wait`](Self::wait).
+    ///
+    /// It must be called before checking the socket state, so that the
+    /// wakeups occurred between the checking and the blocking will not be
+    /// missed.
+    pub fn prepare(&self) -> usize {
+        self.seq.load(Ordering::Acquire)
+    }
+
+    /// Blocks the current task until this waiter is woken up after the
+    /// [`prepare`](Self::prepare) that returned `token`, or the `timeout`
+    /// expires.
+    pub fn wait(&self, token: usize, timeout: Option<Duration>) {
+        let woken = || self.seq.load(Ordering::Acquire) != token;
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "multitask")] {
+                if let Some(dur) = timeout {
+                    self.wq.wait_timeout_until(dur, woken);
+                } else {
+                    self.wq.wait_until(woken);
+                }
+            } else {
+                // No other tasks, just wait for the next IRQ (NIC or timer).
+                let _ = timeout;
+                if !woken() {
+                    axtask::yield_now();
+                }
+            }
+        }
+    }
+
+    /// Wakes up `observer` along with this waiter on the next wakeup.
+    pub fn add_observer(&self, observer: &Arc<SocketWaiter>) {
+        let mut observers = self.observers.lock();
+        // drop the observers that no one waits on anymore
+        observers.retain(|o| Arc::strong_count(o) > 1);
+        if !observers.iter().any(|o| Arc::ptr_eq(o, observer)) {
+            observers.push(observer.clone());
+        }
+    }
+
+    /// Wakes up the tasks blocked on this waiter and the observers, can be
+    /// called in the IRQ context.
+    pub(crate) fn notify(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        #[cfg(feature = "multitask")]
+        self.wq.notify_all(false);
+        let observers = core::mem::take(&mut *self.observers.lock());
+        for observer in observers {
+            observer.notify();
+        }
+    }
+}
+
+impl Wake for SocketWaiter {
+    fn wake(self: Arc<Self>) {
+        self.notify();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.notify();
+    }
+}
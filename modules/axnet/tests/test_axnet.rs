@@ -0,0 +1,116 @@
+use axerrno::{AxError, AxResult};
+use axnet::{IpAddr, SocketAddr, SocketWaiter, TcpSocket};
+
+const LOOPBACK_IP: IpAddr = IpAddr::v4(127, 0, 0, 1);
+
+/// Calls `f` until it returns anything other than `Again`, the interfaces are
+/// polled between two calls.
+fn poll_until<T>(mut f: impl FnMut() -> AxResult<T>) -> AxResult<T> {
+    for _ in 0..100 {
+        match f() {
+            Err(AxError::Again) => axnet::poll_interfaces(),
+            res => return res,
+        }
+    }
+    panic!("the operation is not completed after 100 polls");
+}
+
+fn test_tcp_echo() -> AxResult {
+    let addr = SocketAddr::new(LOOPBACK_IP, 5555);
+    println!("TCP echo via {}:", addr);
+
+    let mut listener = TcpSocket::new();
+    listener.set_nonblocking(true)?;
+    listener.bind(addr)?;
+    listener.listen()?;
+
+    let mut client = TcpSocket::new();
+    client.set_nonblocking(true)?;
+    assert_eq!(client.connect(addr), Err(AxError::Again));
+    assert_eq!(client.peer_addr(), Ok(addr));
+
+    // the time never goes on the host, so the ACK of the handshake is delayed
+    // until the client sends some data.
+    assert_eq!(poll_until(|| client.send(b"hello"))?, 5);
+    let server = poll_until(|| listener.accept())?;
+    assert_eq!(server.peer_addr(), client.local_addr());
+    assert_eq!(client.connect(addr), Err(AxError::AlreadyExists));
+
+    let mut buf = [0; 16];
+    assert_eq!(poll_until(|| server.recv(&mut buf))?, 5);
+    assert_eq!(&buf[..5], b"hello");
+    assert_eq!(poll_until(|| server.send(&buf[..5]))?, 5);
+    assert_eq!(poll_until(|| client.recv(&mut buf))?, 5);
+    assert_eq!(&buf[..5], b"hello");
+
+    // the end of stream
+    client.shutdown()?;
+    assert_eq!(poll_until(|| server.recv(&mut buf))?, 0);
+    assert!(server.poll()?.readable);
+
+    println!("test_tcp_echo() OK!");
+    Ok(())
+}
+
+fn test_tcp_refused() -> AxResult {
+    let addr = SocketAddr::new(LOOPBACK_IP, 5556);
+    println!("TCP connect to {} without listeners:", addr);
+
+    let mut client = TcpSocket::new();
+    client.set_nonblocking(true)?;
+    assert_eq!(client.connect(addr), Err(AxError::Again));
+    poll_until(|| match client.poll()?.hangup {
+        true => Ok(()),
+        false => Err(AxError::Again),
+    })?;
+    assert!(client.send(b"hello").is_err());
+
+    println!("test_tcp_refused() OK!");
+    Ok(())
+}
+
+fn test_socket_waiter() -> AxResult {
+    let addr = SocketAddr::new(LOOPBACK_IP, 5557);
+    let idle_addr = SocketAddr::new(LOOPBACK_IP, 5558);
+    println!("TCP connect to {} while {} is idle:", addr, idle_addr);
+
+    let mut listener = TcpSocket::new();
+    listener.bind(addr)?;
+    listener.listen()?;
+    let mut idle_listener = TcpSocket::new();
+    idle_listener.bind(idle_addr)?;
+    idle_listener.listen()?;
+
+    let waiter = SocketWaiter::new();
+    let idle_waiter = SocketWaiter::new();
+    let token = waiter.prepare();
+    let idle_token = idle_waiter.prepare();
+    listener.subscribe(&waiter);
+    idle_listener.subscribe(&idle_waiter);
+
+    let mut client = TcpSocket::new();
+    client.set_nonblocking(true)?;
+    assert_eq!(client.connect(addr), Err(AxError::Again));
+    for _ in 0..10 {
+        axnet::poll_interfaces();
+    }
+
+    // only the waiter of the listener that received the connection is woken up
+    assert_ne!(waiter.prepare(), token);
+    assert_eq!(idle_waiter.prepare(), idle_token);
+
+    println!("test_socket_waiter() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_axnet() {
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+
+    // no NIC, only the loopback interface
+    axnet::init_network(Vec::new());
+
+    test_tcp_echo().expect("test_tcp_echo() failed");
+    test_tcp_refused().expect("test_tcp_refused() failed");
+    test_socket_waiter().expect("test_socket_waiter() failed");
+}
@@ -0,0 +1,48 @@
+//! Hardware random number generators (entropy sources) of ArceOS.
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use axdriver::{AxDevice, AxRngDevice};
+use axsync::Mutex;
+use lazy_init::LazyInit;
+
+static MAIN_RNG: LazyInit<Mutex<AxRngDevice>> = LazyInit::new();
+
+/// Initializes the entropy source with the devices found by [`axdriver`].
+///
+/// Only the first device is used.
+pub fn init_rng(rng_devs: Vec<AxDevice<AxRngDevice>>) {
+    info!("Initialize RNG subsystem...");
+
+    info!("number of RNGs: {}", rng_devs.len());
+    let Some(dev) = rng_devs.into_iter().next() else {
+        warn!("no RNG device found");
+        return;
+    };
+    info!("  use RNG device: {:?}", dev.device_name());
+    MAIN_RNG.init_by(Mutex::new(dev.into_inner()));
+}
+
+/// Whether a hardware entropy source is available.
+pub fn available() -> bool {
+    MAIN_RNG.is_init()
+}
+
+/// Fills `buf` with random bytes from the hardware entropy source.
+///
+/// Returns the number of bytes filled, which is 0 if there is no device or it
+/// fails.
+pub fn fill_bytes(buf: &mut [u8]) -> usize {
+    let Some(rng) = MAIN_RNG.try_get() else {
+        return 0;
+    };
+    rng.lock().fill_bytes(buf).unwrap_or_else(|e| {
+        warn!("failed to read from the RNG device: {:?}", e);
+        0
+    })
+}
@@ -70,8 +70,13 @@ use core::sync::atomic::{AtomicUsize, Ordering};
 
 static INITED_CPUS: AtomicUsize = AtomicUsize::new(0);
 
+/// The number of CPUs to use, at most [`axconfig::SMP`].
+fn cpu_num() -> usize {
+    axhal::dtb::cpu_num().min(axconfig::SMP)
+}
+
 fn is_init_ok() -> bool {
-    INITED_CPUS.load(Ordering::Acquire) == axconfig::SMP
+    INITED_CPUS.load(Ordering::Acquire) == cpu_num()
 }
 
 #[cfg_attr(not(test), no_mangle)]
@@ -96,6 +101,11 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
     axlog::set_max_level(option_env!("LOG").unwrap_or("")); // no effect if set `log-level-*` features
     info!("Logging is enabled.");
     info!("Primary CPU {} started, dtb = {:#x}.", cpu_id, dtb);
+    info!(
+        "Found {} CPUs, using {} of them.",
+        axhal::dtb::cpu_num(),
+        cpu_num()
+    );
 
     info!("Found physcial memory regions:");
     for r in axhal::mem::memory_regions() {
@@ -117,25 +127,47 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
     #[cfg(feature = "paging")]
     {
         info!("Initialize kernel page table...");
-        remap_kernel_memory().expect("remap kernel memoy failed");
+        axmm::init_memory_management();
     }
 
     #[cfg(feature = "multitask")]
     axtask::init_scheduler();
 
-    #[cfg(any(feature = "fs", feature = "net", feature = "display"))]
+    #[cfg(any(
+        feature = "fs",
+        feature = "net",
+        feature = "display",
+        feature = "char",
+        feature = "rng",
+        feature = "input"
+    ))]
     {
         #[allow(unused_variables)]
         let all_devices = axdriver::init_drivers();
 
         #[cfg(feature = "fs")]
-        axfs::init_filesystems(all_devices.block.0);
+        match all_devices.block.into_iter().next() {
+            Some(dev) => {
+                let irq_num = dev.irq();
+                axfs::init_filesystems(dev.into_inner(), irq_num)
+            }
+            None => warn!("no block device found, skip initializing filesystems"),
+        }
 
         #[cfg(feature = "net")]
         axnet::init_network(all_devices.net);
 
         #[cfg(feature = "display")]
         axdisplay::init_display(all_devices.display);
+
+        #[cfg(feature = "char")]
+        axchar::init_char_devices(all_devices.char);
+
+        #[cfg(feature = "rng")]
+        axrng::init_rng(all_devices.rng);
+
+        #[cfg(feature = "input")]
+        axinput::init_input(all_devices.input);
     }
 
     info!("Initialize interrupt handlers...");
@@ -153,6 +185,12 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
 
     unsafe { main() };
 
+    #[cfg(feature = "alloc-debug")]
+    {
+        warn!("Live heap allocations at exit:");
+        axalloc::global_allocator().dump_live_allocations();
+    }
+
     axtask::exit(0)
 }
 
@@ -182,32 +220,6 @@ fn init_allocator() {
     }
 }
 
-#[cfg(feature = "paging")]
-fn remap_kernel_memory() -> Result<(), axhal::paging::PagingError> {
-    use axhal::mem::{memory_regions, phys_to_virt};
-    use axhal::paging::PageTable;
-    use lazy_init::LazyInit;
-
-    static KERNEL_PAGE_TABLE: LazyInit<PageTable> = LazyInit::new();
-
-    if axhal::cpu::this_cpu_is_bsp() {
-        let mut kernel_page_table = PageTable::try_new()?;
-        for r in memory_regions() {
-            kernel_page_table.map_region(
-                phys_to_virt(r.paddr),
-                r.paddr,
-                r.size,
-                r.flags.into(),
-                true,
-            )?;
-        }
-        KERNEL_PAGE_TABLE.init_by(kernel_page_table);
-    }
-
-    unsafe { axhal::arch::write_page_table_root(KERNEL_PAGE_TABLE.root_paddr()) };
-    Ok(())
-}
-
 fn init_interrupt() {
     use axhal::time::TIMER_IRQ_NUM;
 
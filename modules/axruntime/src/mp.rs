@@ -11,7 +11,7 @@ extern "C" {
 pub fn start_secondary_cpus(primary_cpu_id: usize) {
     let entry = virt_to_phys(VirtAddr::from(_start_secondary as usize));
     let mut logic_cpu_id = 0;
-    for i in 0..SMP {
+    for i in 0..super::cpu_num() {
         if i != primary_cpu_id {
             let stack_top = virt_to_phys(VirtAddr::from(unsafe {
                 SECONDARY_BOOT_STACK[logic_cpu_id].as_ptr_range().end as usize
@@ -29,7 +29,7 @@ pub extern "C" fn rust_main_secondary(cpu_id: usize) -> ! {
     info!("Secondary CPU {} started.", cpu_id);
 
     #[cfg(feature = "paging")]
-    super::remap_kernel_memory().unwrap();
+    axmm::init_memory_management_secondary();
 
     #[cfg(feature = "multitask")]
     axtask::init_scheduler_secondary();
@@ -1,3 +1,6 @@
+use axhal::mem::VirtAddr;
+use axhal::trap::MappingFlags;
+
 struct TrapHandlerImpl;
 
 #[crate_interface::impl_interface]
@@ -7,4 +10,20 @@ impl axhal::trap::TrapHandler for TrapHandlerImpl {
         axhal::irq::dispatch_irq(irq_num);
         drop(guard); // rescheduling may occur when preemption is re-enabled.
     }
+
+    fn handle_page_fault(vaddr: VirtAddr, access_flags: MappingFlags, is_user: bool) -> bool {
+        if is_user {
+            // there is no user address space yet
+            return false;
+        }
+        #[cfg(feature = "paging")]
+        {
+            axmm::handle_page_fault(vaddr, access_flags)
+        }
+        #[cfg(not(feature = "paging"))]
+        {
+            let _ = (vaddr, access_flags);
+            false
+        }
+    }
 }
@@ -1,16 +1,30 @@
 use alloc::{boxed::Box, sync::Arc};
 use core::ops::Deref;
-use core::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 use core::{alloc::Layout, cell::UnsafeCell, fmt, ptr::NonNull};
 
 #[cfg(feature = "preempt")]
 use core::sync::atomic::AtomicUsize;
 
+use allocator::{BaseAllocator, BitmapIdAllocator, IdAllocator};
 use axhal::arch::TaskContext;
 use memory_addr::{align_up_4k, VirtAddr};
+use spinlock::SpinNoIrq;
 
 use crate::{AxTask, AxTaskRef};
 
+/// The maximum number of tasks alive at the same time.
+const MAX_TASKS: usize = 0x1_0000;
+
+/// The IDs of the alive tasks.
+static TASK_IDS: SpinNoIrq<BitmapIdAllocator<{ MAX_TASKS / 64 }>> =
+    SpinNoIrq::new(BitmapIdAllocator::new());
+
+/// A unique ID among the alive tasks.
+///
+/// The IDs are allocated in ascending order and wrap around at the maximum,
+/// skipping the ones still in use. So the ID of a dropped task is reused only
+/// after the other available IDs are used, but it can be reused eventually.
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub struct TaskId(u64);
 
@@ -46,8 +60,12 @@ pub struct TaskInner {
 
 impl TaskId {
     fn new() -> Self {
-        static ID_COUNTER: AtomicU64 = AtomicU64::new(1);
-        Self(ID_COUNTER.fetch_add(1, Ordering::Relaxed))
+        let mut ids = TASK_IDS.lock();
+        if ids.size() == 0 {
+            ids.init(1, MAX_TASKS - 1); // ID 0 is not used
+        }
+        let id = ids.alloc_next_id().expect("too many tasks");
+        Self(id as u64)
     }
 
     pub const fn as_u64(&self) -> u64 {
@@ -243,6 +261,7 @@ impl fmt::Debug for TaskInner {
 impl Drop for TaskInner {
     fn drop(&mut self) {
         debug!("task drop: {}", self.id_name());
+        TASK_IDS.lock().dealloc_id(self.id.0 as usize, 1);
     }
 }
 
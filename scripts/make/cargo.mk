@@ -15,6 +15,21 @@ endif
 features-$(FS) += libax/fs
 features-$(NET) += libax/net
 features-$(GRAPHIC) += libax/display
+features-$(CHAR) += libax/char
+features-$(RNG) += libax/rng
+features-$(INPUT) += libax/input
+
+ifeq ($(BUS),pci)
+  features-y += libax/bus-pci
+endif
+
+ifeq ($(NVME),y)
+  features-y += libax/nvme
+endif
+
+ifeq ($(E1000),y)
+  features-y += libax/e1000
+endif
 
 default_features := y
 
@@ -32,6 +47,12 @@ else ifeq ($(APP_LANG),rust)
   endif
 endif
 
+ifneq ($(filter alloc-debug libax/alloc-debug,$(features-y)),)
+  # The frame pointers are used to find the callers of the heap allocations
+  RUSTFLAGS += -Cforce-frame-pointers=yes
+  export RUSTFLAGS
+endif
+
 build_args-release := --release
 build_args-c := --crate-type staticlib
 build_args-rust :=
@@ -12,20 +12,50 @@ qemu_args-aarch64 := \
   -machine virt \
   -kernel $(OUT_BIN)
 
-qemu_args-y := -m 128M -smp $(SMP) $(qemu_args-$(ARCH))
+qemu_args-y := -m $(MEM) -smp $(SMP) $(qemu_args-$(ARCH))
+
+ifeq ($(BUS), pci)
+  vdev-suffix := pci
+else
+  vdev-suffix := device
+endif
+
+ifeq ($(NVME), y)
+  blk-dev := nvme,serial=arceos
+else
+  blk-dev := virtio-blk-$(vdev-suffix)
+endif
+
+ifeq ($(E1000), y)
+  net-dev := e1000
+else
+  net-dev := virtio-net-$(vdev-suffix)
+endif
 
 qemu_args-$(FS) += \
-  -device virtio-blk-device,drive=disk0 \
+  -device $(blk-dev),drive=disk0 \
   -drive id=disk0,if=none,format=raw,file=$(DISK_IMG)
 
 qemu_args-$(NET) += \
-  -device virtio-net-device,netdev=net0 \
+  -device $(net-dev),netdev=net0 \
   -netdev user,id=net0,hostfwd=tcp::5555-:5555
 
 qemu_args-$(GRAPHIC) += \
-  -device virtio-gpu-device \
+  -device virtio-gpu-$(vdev-suffix) \
   -serial mon:stdio
 
+qemu_args-$(CHAR) += \
+  -device virtio-serial-$(vdev-suffix) \
+  -device virtconsole,chardev=vcon0 \
+  -chardev pty,id=vcon0
+
+qemu_args-$(RNG) += \
+  -device virtio-rng-$(vdev-suffix)
+
+qemu_args-$(INPUT) += \
+  -device virtio-keyboard-$(vdev-suffix) \
+  -device virtio-mouse-$(vdev-suffix)
+
 ifeq ($(GRAPHIC), n)
   qemu_args-y += -nographic
 endif
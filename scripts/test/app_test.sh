@@ -115,6 +115,7 @@ if [ -z "$1" ]; then
         "apps/net/httpclient"
         "apps/c/helloworld"
         "apps/c/memtest"
+        "apps/c/socket"
         "apps/c/sqlite3"
     )
 else
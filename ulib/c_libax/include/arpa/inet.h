@@ -0,0 +1,6 @@
+#ifndef __ARPA_INET_H__
+#define __ARPA_INET_H__
+
+#include <netinet/in.h>
+
+#endif // __ARPA_INET_H__
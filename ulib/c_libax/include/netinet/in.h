@@ -0,0 +1,36 @@
+#ifndef __NETINET_IN_H__
+#define __NETINET_IN_H__
+
+#include <stdint.h>
+#include <sys/socket.h>
+
+typedef uint16_t in_port_t;
+typedef uint32_t in_addr_t;
+
+struct in_addr {
+    in_addr_t s_addr; /* in network byte order */
+};
+
+struct sockaddr_in {
+    sa_family_t sin_family;  /* AF_INET */
+    in_port_t sin_port;      /* in network byte order */
+    struct in_addr sin_addr; /* internet address */
+    uint8_t sin_zero[8];
+};
+
+#define IPPROTO_IP   0
+#define IPPROTO_ICMP 1
+#define IPPROTO_TCP  6
+#define IPPROTO_UDP  17
+
+/* in host byte order, convert them with htonl() */
+#define INADDR_ANY       ((in_addr_t)0x00000000)
+#define INADDR_BROADCAST ((in_addr_t)0xffffffff)
+#define INADDR_LOOPBACK  ((in_addr_t)0x7f000001)
+
+uint32_t htonl(uint32_t hostlong);
+uint16_t htons(uint16_t hostshort);
+uint32_t ntohl(uint32_t netlong);
+uint16_t ntohs(uint16_t netshort);
+
+#endif // __NETINET_IN_H__
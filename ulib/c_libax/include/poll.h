@@ -0,0 +1,21 @@
+#ifndef __POLL_H__
+#define __POLL_H__
+
+#define POLLIN   0x001
+#define POLLPRI  0x002
+#define POLLOUT  0x004
+#define POLLERR  0x008
+#define POLLHUP  0x010
+#define POLLNVAL 0x020
+
+typedef unsigned long nfds_t;
+
+struct pollfd {
+    int fd;        /* file descriptor */
+    short events;  /* requested events */
+    short revents; /* returned events */
+};
+
+int poll(struct pollfd *fds, nfds_t nfds, int timeout);
+
+#endif // __POLL_H__
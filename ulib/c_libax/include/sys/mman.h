@@ -42,8 +42,12 @@
 #define MREMAP_FIXED     2
 #define MREMAP_DONTUNMAP 4
 
+#ifdef AX_CONFIG_PAGING
 void *mmap(void *addr, size_t len, int prot, int flags, int fildes, off_t off);
 int munmap(void *addr, size_t length);
+int mprotect(void *addr, size_t len, int prot);
+#endif
+
 void *mremap(void *old_address, size_t old_size, size_t new_size, int flags,
              ... /* void *new_address */);
 
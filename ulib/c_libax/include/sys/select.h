@@ -0,0 +1,27 @@
+#ifndef __SYS_SELECT_H__
+#define __SYS_SELECT_H__
+
+#include <stddef.h>
+#include <sys/time.h>
+#include <sys/types.h>
+
+#define FD_SETSIZE 1024
+#define __NFDBITS  (8 * sizeof(unsigned long))
+
+typedef struct {
+    unsigned long fds_bits[FD_SETSIZE / __NFDBITS];
+} fd_set;
+
+#define FD_ZERO(s)                                                   \
+    do {                                                             \
+        for (size_t __i = 0; __i < FD_SETSIZE / __NFDBITS; __i++)    \
+            (s)->fds_bits[__i] = 0;                                  \
+    } while (0)
+#define FD_SET(d, s)   ((s)->fds_bits[(d) / __NFDBITS] |= (1UL << ((d) % __NFDBITS)))
+#define FD_CLR(d, s)   ((s)->fds_bits[(d) / __NFDBITS] &= ~(1UL << ((d) % __NFDBITS)))
+#define FD_ISSET(d, s) (!!((s)->fds_bits[(d) / __NFDBITS] & (1UL << ((d) % __NFDBITS))))
+
+int select(int nfds, fd_set *readfds, fd_set *writefds, fd_set *exceptfds,
+           struct timeval *timeout);
+
+#endif // __SYS_SELECT_H__
@@ -0,0 +1,42 @@
+#ifndef __SYS_SOCKET_H__
+#define __SYS_SOCKET_H__
+
+#include <stddef.h>
+#include <stdint.h>
+
+typedef uint32_t socklen_t;
+typedef uint16_t sa_family_t;
+
+struct sockaddr {
+    sa_family_t sa_family; /* address family */
+    char sa_data[14];      /* socket address */
+};
+
+#define AF_UNSPEC 0
+#define AF_INET   2
+#define PF_UNSPEC AF_UNSPEC
+#define PF_INET   AF_INET
+
+#define SOCK_STREAM 1
+#define SOCK_DGRAM  2
+#define SOCK_RAW    3
+
+#define SHUT_RD   0
+#define SHUT_WR   1
+#define SHUT_RDWR 2
+
+#ifdef AX_CONFIG_NET
+int socket(int domain, int type, int protocol);
+int bind(int fd, const struct sockaddr *addr, socklen_t addrlen);
+int connect(int fd, const struct sockaddr *addr, socklen_t addrlen);
+int listen(int fd, int backlog);
+int accept(int fd, struct sockaddr *addr, socklen_t *addrlen);
+
+ssize_t send(int fd, const void *buf, size_t len, int flags);
+ssize_t recv(int fd, void *buf, size_t len, int flags);
+
+int shutdown(int fd, int how);
+int getsockname(int fd, struct sockaddr *addr, socklen_t *addrlen);
+#endif
+
+#endif // __SYS_SOCKET_H__
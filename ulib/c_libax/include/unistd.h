@@ -7,13 +7,16 @@
 
 #define _SC_PAGESIZE 30
 
-#ifdef AX_CONFIG_FS
+#if defined(AX_CONFIG_FS) || defined(AX_CONFIG_NET)
 int close(int fd);
-off_t lseek(int fd, off_t offset, int whence);
-int fsync(int fd);
 
 ssize_t read(int fd, void *buf, size_t count);
 ssize_t write(int fd, const void *buf, size_t count);
+#endif
+
+#ifdef AX_CONFIG_FS
+off_t lseek(int fd, off_t offset, int whence);
+int fsync(int fd);
 
 int fchown(int fd, uid_t owner, gid_t group);
 
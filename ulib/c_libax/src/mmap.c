@@ -1,21 +1,30 @@
+#include <libax.h>
 #include <stddef.h>
 #include <stdio.h>
+#include <sys/mman.h>
 #include <sys/types.h>
 
-// TODO:
+#ifdef AX_CONFIG_PAGING
+
 void *mmap(void *addr, size_t len, int prot, int flags, int fildes, off_t off)
 {
-    unimplemented();
-    return NULL;
+    void *ret = ax_mmap(addr, len, prot, flags, fildes, off);
+    // errors are returned as `-errno` like the system call
+    return (unsigned long)ret >= -4095UL ? MAP_FAILED : ret;
 }
 
-// TODO:
 int munmap(void *addr, size_t length)
 {
-    unimplemented();
-    return 0;
+    return ax_munmap(addr, length);
 }
 
+int mprotect(void *addr, size_t len, int prot)
+{
+    return ax_mprotect(addr, len, prot);
+}
+
+#endif
+
 // TODO:
 void *mremap(void *old_address, size_t old_size, size_t new_size, int flags,
              ... /* void *new_address */)
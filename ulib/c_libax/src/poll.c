@@ -0,0 +1,7 @@
+#include <libax.h>
+#include <poll.h>
+
+int poll(struct pollfd *fds, nfds_t nfds, int timeout)
+{
+    return ax_poll(fds, nfds, timeout);
+}
@@ -0,0 +1,8 @@
+#include <libax.h>
+#include <sys/select.h>
+
+int select(int nfds, fd_set *readfds, fd_set *writefds, fd_set *exceptfds,
+           struct timeval *timeout)
+{
+    return ax_select(nfds, readfds, writefds, exceptfds, timeout);
+}
@@ -0,0 +1,81 @@
+#include <libax.h>
+#include <netinet/in.h>
+#include <stdint.h>
+#include <sys/socket.h>
+
+#if __BYTE_ORDER__ == __ORDER_LITTLE_ENDIAN__
+#define __bswap16(x) __builtin_bswap16(x)
+#define __bswap32(x) __builtin_bswap32(x)
+#else
+#define __bswap16(x) (x)
+#define __bswap32(x) (x)
+#endif
+
+uint32_t htonl(uint32_t hostlong)
+{
+    return __bswap32(hostlong);
+}
+
+uint16_t htons(uint16_t hostshort)
+{
+    return __bswap16(hostshort);
+}
+
+uint32_t ntohl(uint32_t netlong)
+{
+    return __bswap32(netlong);
+}
+
+uint16_t ntohs(uint16_t netshort)
+{
+    return __bswap16(netshort);
+}
+
+#ifdef AX_CONFIG_NET
+
+int socket(int domain, int type, int protocol)
+{
+    return ax_socket(domain, type, protocol);
+}
+
+int bind(int fd, const struct sockaddr *addr, socklen_t addrlen)
+{
+    return ax_bind(fd, addr, addrlen);
+}
+
+int connect(int fd, const struct sockaddr *addr, socklen_t addrlen)
+{
+    return ax_connect(fd, addr, addrlen);
+}
+
+int listen(int fd, int backlog)
+{
+    return ax_listen(fd, backlog);
+}
+
+int accept(int fd, struct sockaddr *addr, socklen_t *addrlen)
+{
+    return ax_accept(fd, addr, addrlen);
+}
+
+ssize_t send(int fd, const void *buf, size_t len, int flags)
+{
+    return ax_send(fd, buf, len, flags);
+}
+
+ssize_t recv(int fd, void *buf, size_t len, int flags)
+{
+    return ax_recv(fd, buf, len, flags);
+}
+
+int shutdown(int fd, int how)
+{
+    return ax_shutdown(fd, how);
+}
+
+int getsockname(int fd, struct sockaddr *addr, socklen_t *addrlen)
+{
+    return ax_getsockname(fd, addr, addrlen);
+}
+
+#endif
@@ -31,6 +31,25 @@ long int sysconf(int name)
     return 0;
 }
 
+#if defined(AX_CONFIG_FS) || defined(AX_CONFIG_NET)
+
+int close(int fd)
+{
+    return ax_close(fd);
+}
+
+ssize_t read(int fd, void *buf, size_t count)
+{
+    return ax_read(fd, buf, count);
+}
+
+ssize_t write(int fd, const void *buf, size_t count)
+{
+    return ax_write(fd, buf, count);
+}
+
+#endif
+
 #ifdef AX_CONFIG_FS
 
 off_t lseek(int fd, off_t offset, int whence)
@@ -45,11 +64,6 @@ int fsync(int fd)
     return 0;
 }
 
-int close(int fd)
-{
-    return ax_close(fd);
-}
-
 // TODO:
 int access(const char *pathname, int mode)
 {
@@ -84,16 +98,6 @@ int ftruncate(int fd, off_t length)
     return 0;
 }
 
-ssize_t read(int fd, void *buf, size_t count)
-{
-    return ax_read(fd, buf, count);
-}
-
-ssize_t write(int fd, const void *buf, size_t count)
-{
-    return ax_write(fd, buf, count);
-}
-
 // TODO:
 int unlink(const char *pathname)
 {
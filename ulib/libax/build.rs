@@ -8,7 +8,13 @@ fn main() {
             cpp_compat: true,
             no_includes: false,
             usize_is_size_t: true,
-            sys_includes: vec!["sys/types.h".into(), "sys/stat.h".into()],
+            sys_includes: vec![
+                "sys/types.h".into(),
+                "sys/stat.h".into(),
+                "sys/select.h".into(),
+                "sys/socket.h".into(),
+                "poll.h".into(),
+            ],
             header: Some("/* Generated by cbindgen and build.rs, DO NOT edit! */".into()),
             ..Default::default()
         };
@@ -16,6 +22,18 @@ fn main() {
             .export
             .rename
             .insert("stat".into(), "struct stat".into());
+        config
+            .export
+            .rename
+            .insert("pollfd".into(), "struct pollfd".into());
+        config
+            .export
+            .rename
+            .insert("timeval".into(), "struct timeval".into());
+        config
+            .export
+            .rename
+            .insert("sockaddr".into(), "struct sockaddr".into());
 
         cbindgen::generate_with_config(crate_dir, config)
             .expect("Unable to generate rust->c bindings")
@@ -26,8 +44,20 @@ fn main() {
         println!("cargo:rerun-if-changed={in_file}");
 
         let include_dir = crate_dir.join("../c_libax/include");
-        let allow_types = ["stat", "size_t", "ssize_t", "off_t", "mode_t", "O_*"];
-        let allow_vars = ["O_.*"];
+        let allow_types = [
+            "stat", "size_t", "ssize_t", "off_t", "mode_t", "O_*", "pollfd", "nfds_t", "fd_set",
+            "timeval", "sock.*", "in_addr",
+        ];
+        let allow_vars = [
+            "O_.*",
+            "POLL.*",
+            "FD_SETSIZE",
+            "PROT_.*",
+            "MAP_.*",
+            "AF_.*",
+            "SOCK_.*",
+            "IPPROTO_.*",
+        ];
 
         let mut builder = bindgen::Builder::default()
             .header(in_file)
@@ -1,3 +1,8 @@
 #include <fcntl.h>
+#include <netinet/in.h>
+#include <poll.h>
 #include <stddef.h>
+#include <sys/mman.h>
+#include <sys/select.h>
+#include <sys/socket.h>
 #include <sys/stat.h>
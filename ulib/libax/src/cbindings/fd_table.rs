@@ -0,0 +1,177 @@
+use alloc::sync::Arc;
+use allocator::{BaseAllocator, BitmapIdAllocator, IdAllocator};
+use axerrno::{LinuxError, LinuxResult};
+use core::ffi::{c_int, c_void};
+
+use super::ctypes;
+use crate::debug;
+use crate::io::{self, PollState, PollWaiter, Pollable};
+use crate::sync::Mutex;
+
+#[cfg(feature = "fs")]
+use crate::{fs::File, io::prelude::*};
+#[cfg(feature = "net")]
+use axnet::TcpSocket;
+
+const FD_LIMIT: usize = 256;
+const FD_NONE: Option<FdObject> = None;
+
+/// File Descriptor Table
+static FD_TABLE: Mutex<FdTable> = Mutex::new(FdTable::new());
+
+/// An object opened by C code, indicated by a file descriptor.
+#[derive(Clone)]
+pub(super) enum FdObject {
+    #[cfg(feature = "fs")]
+    File(Arc<Mutex<File>>),
+    #[cfg(feature = "net")]
+    Socket(Arc<Mutex<TcpSocket>>),
+}
+
+impl FdObject {
+    fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        match self {
+            #[cfg(feature = "fs")]
+            Self::File(file) => Ok(file.lock().read(buf)?),
+            #[cfg(feature = "net")]
+            Self::Socket(socket) => Ok(socket.lock().recv(buf)?),
+        }
+    }
+
+    fn write(&self, buf: &[u8]) -> LinuxResult<usize> {
+        match self {
+            #[cfg(feature = "fs")]
+            Self::File(file) => Ok(file.lock().write(buf)?),
+            #[cfg(feature = "net")]
+            Self::Socket(socket) => Ok(socket.lock().send(buf)?),
+        }
+    }
+}
+
+impl Pollable for FdObject {
+    fn poll(&self) -> io::Result<PollState> {
+        match self {
+            #[cfg(feature = "fs")]
+            Self::File(file) => file.lock().poll(),
+            #[cfg(feature = "net")]
+            Self::Socket(socket) => socket.lock().poll(),
+        }
+    }
+
+    fn register_waiter(&self, _waiter: &PollWaiter) -> bool {
+        match self {
+            #[cfg(feature = "fs")]
+            Self::File(file) => file.lock().register_waiter(_waiter),
+            #[cfg(feature = "net")]
+            Self::Socket(socket) => {
+                socket.lock().subscribe(&_waiter.inner);
+                true
+            }
+        }
+    }
+}
+
+/// The files and sockets opened by C code, indexed by the file descriptors.
+///
+/// The lowest available fd is always used for a new object, and the fds `0`,
+/// `1` and `2` are kept for stdin, stdout and stderr.
+struct FdTable {
+    fds: BitmapIdAllocator<{ FD_LIMIT / 64 }>,
+    objects: [Option<FdObject>; FD_LIMIT],
+}
+
+impl FdTable {
+    const fn new() -> Self {
+        Self {
+            fds: BitmapIdAllocator::new(),
+            objects: [FD_NONE; FD_LIMIT],
+        }
+    }
+
+    fn get(&self, fd: usize) -> Option<&FdObject> {
+        self.objects.get(fd)?.as_ref()
+    }
+
+    /// Adds a new object and returns its fd, or `None` if the table is full.
+    fn add(&mut self, obj: FdObject) -> Option<usize> {
+        if self.fds.size() == 0 {
+            self.fds.init(0, FD_LIMIT);
+            for fd in 0..3 {
+                self.fds.alloc_fixed_id(fd).ok()?;
+            }
+        }
+        let fd = self.fds.alloc_id(1, 1).ok()?;
+        self.objects[fd] = Some(obj);
+        Some(fd)
+    }
+
+    /// Removes the object indicated by `fd`, so the fd can be reused.
+    fn remove(&mut self, fd: usize) -> Option<FdObject> {
+        let obj = self.objects.get_mut(fd)?.take()?;
+        self.fds.dealloc_id(fd, 1);
+        Some(obj)
+    }
+}
+
+/// Get the object indicated by `fd` from `FD_TABLE`.
+pub(super) fn get_fd_object(fd: c_int) -> LinuxResult<FdObject> {
+    FD_TABLE
+        .lock()
+        .get(fd as usize)
+        .cloned()
+        .ok_or(LinuxError::EBADF)
+}
+
+/// Add a new object into `FD_TABLE` and return its fd.
+///
+/// Return `ENFILE` if the table overflows.
+pub(super) fn add_fd_object(obj: FdObject) -> LinuxResult<c_int> {
+    let fd = FD_TABLE.lock().add(obj).ok_or(LinuxError::ENFILE)?;
+    Ok(fd as c_int)
+}
+
+/// Close a file or a socket by `fd`.
+#[no_mangle]
+pub unsafe extern "C" fn ax_close(fd: c_int) -> c_int {
+    debug!("ax_close <= {}", fd);
+    if (0..3).contains(&fd) {
+        return 0; // stdin, stdout, stderr
+    }
+    ax_call_body!(ax_close, {
+        FD_TABLE
+            .lock()
+            .remove(fd as usize)
+            .ok_or(LinuxError::EBADF)?;
+        Ok(0)
+    })
+}
+
+/// Read data from the file or the socket indicated by `fd`.
+///
+/// Return the read size if success.
+#[no_mangle]
+pub unsafe extern "C" fn ax_read(fd: c_int, buf: *mut c_void, count: usize) -> ctypes::ssize_t {
+    debug!("ax_read <= {} {:#x} {}", fd, buf as usize, count);
+    ax_call_body!(ax_read, {
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let dst = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, count) };
+        get_fd_object(fd)?.read(dst)
+    })
+}
+
+/// Write data to the file or the socket indicated by `fd`.
+///
+/// Return the written size if success.
+#[no_mangle]
+pub unsafe extern "C" fn ax_write(fd: c_int, buf: *const c_void, count: usize) -> ctypes::ssize_t {
+    debug!("ax_write <= {} {:#x} {}", fd, buf as usize, count);
+    ax_call_body!(ax_write, {
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let src = unsafe { core::slice::from_raw_parts(buf as *const u8, count) };
+        get_fd_object(fd)?.write(src)
+    })
+}
@@ -1,39 +1,21 @@
 use alloc::sync::Arc;
 use axerrno::{LinuxError, LinuxResult};
-use core::ffi::{c_char, c_int, c_void};
+use core::ffi::{c_char, c_int};
 
+use super::fd_table::{add_fd_object, get_fd_object, FdObject};
 use super::{ctypes, utils::char_ptr_to_str};
 use crate::debug;
 use crate::fs::{File, OpenOptions};
 use crate::io::{self, prelude::*, SeekFrom};
 use crate::sync::Mutex;
 
-const FILE_LIMIT: usize = 256;
-const FD_NONE: Option<Arc<Mutex<File>>> = None;
-
-/// File Descriptor Table
-static FD_TABLE: Mutex<[Option<Arc<Mutex<File>>>; FILE_LIMIT]> = Mutex::new([FD_NONE; FILE_LIMIT]);
-
-/// Get the [`File`] structure from `FD_TABLE` by `fd`.
-fn get_file_by_fd(fd: c_int) -> LinuxResult<Arc<Mutex<File>>> {
-    FD_TABLE
-        .lock()
-        .get(fd as usize)
-        .and_then(|f| f.as_ref())
-        .cloned()
-        .ok_or(LinuxError::EBADF)
-}
-
-/// Add a new file into `FD_TABLE` and return its fd.
-fn add_new_fd(file: File) -> Option<usize> {
-    let mut fd_table = FD_TABLE.lock();
-    for fd in 3..FILE_LIMIT {
-        if fd_table[fd].is_none() {
-            fd_table[fd] = Some(Arc::new(Mutex::new(file)));
-            return Some(fd);
-        }
+/// Get the [`File`] structure from the file descriptor table by `fd`.
+pub(super) fn get_file_by_fd(fd: c_int) -> LinuxResult<Arc<Mutex<File>>> {
+    match get_fd_object(fd)? {
+        FdObject::File(file) => Ok(file),
+        #[allow(unreachable_patterns)]
+        _ => Err(LinuxError::EBADF),
     }
-    None
 }
 
 /// Convert open flags to [`OpenOptions`].
@@ -75,24 +57,7 @@ pub unsafe extern "C" fn ax_open(
     ax_call_body!(ax_open, {
         let options = flags_to_options(flags, mode);
         let file = options.open(filename?)?;
-        add_new_fd(file).ok_or(LinuxError::ENFILE)
-    })
-}
-
-/// Close a file by `fd`.
-#[no_mangle]
-pub unsafe extern "C" fn ax_close(fd: c_int) -> c_int {
-    debug!("ax_close <= {}", fd);
-    if (0..2).contains(&fd) {
-        return 0; // stdin, stdout, stderr
-    }
-    ax_call_body!(ax_close, {
-        FD_TABLE
-            .lock()
-            .get_mut(fd as usize)
-            .and_then(|file| file.take())
-            .ok_or(LinuxError::EBADF)?;
-        Ok(0)
+        add_fd_object(FdObject::File(Arc::new(Mutex::new(file))))
     })
 }
 
@@ -119,40 +84,6 @@ pub unsafe extern "C" fn ax_lseek(
     })
 }
 
-/// Read data from the file indicated by `fd`.
-///
-/// Return the read size if success.
-#[no_mangle]
-pub unsafe extern "C" fn ax_read(fd: c_int, buf: *mut c_void, count: usize) -> ctypes::ssize_t {
-    debug!("ax_read <= {} {:#x} {}", fd, buf as usize, count);
-    ax_call_body!(ax_read, {
-        if buf.is_null() {
-            return Err(LinuxError::EFAULT);
-        }
-        let dst = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, count) };
-        let file = get_file_by_fd(fd)?;
-        let len = file.lock().read(dst)?;
-        Ok(len)
-    })
-}
-
-/// Write data to the file indicated by `fd`.
-///
-/// Return the written size if success.
-#[no_mangle]
-pub unsafe extern "C" fn ax_write(fd: c_int, buf: *const c_void, count: usize) -> ctypes::ssize_t {
-    debug!("ax_write <= {} {:#x} {}", fd, buf as usize, count);
-    ax_call_body!(ax_write, {
-        if buf.is_null() {
-            return Err(LinuxError::EFAULT);
-        }
-        let src = unsafe { core::slice::from_raw_parts(buf as *const u8, count) };
-        let file = get_file_by_fd(fd)?;
-        let len = file.lock().write(src)?;
-        Ok(len)
-    })
-}
-
 fn stat_file(file: &File) -> io::Result<ctypes::stat> {
     let metadata = file.metadata()?;
     let metadata = metadata.raw_metadata();
@@ -0,0 +1,199 @@
+use axerrno::{LinuxError, LinuxResult};
+use core::ffi::{c_int, c_ulong};
+use core::time::Duration;
+
+use super::ctypes;
+use crate::debug;
+use crate::io::{self, PollState, PollWaiter, Pollable};
+
+/// Register `waiter` to `source`, then get the readiness of `source`.
+fn poll_source(source: &dyn Pollable, waiter: &PollWaiter) -> io::Result<PollState> {
+    waiter.register(source);
+    source.poll()
+}
+
+/// Register `waiter` to the file or the socket indicated by `fd`, then get
+/// its readiness.
+///
+/// The standard I/O (0, 1 and 2), the opened files and the sockets are
+/// supported. Return `None` if `fd` is not a valid file descriptor.
+fn poll_fd(fd: c_int, waiter: &PollWaiter) -> Option<io::Result<PollState>> {
+    match fd {
+        0 => Some(poll_source(&io::stdin(), waiter)),
+        1 | 2 => Some(poll_source(&io::stdout(), waiter)),
+        #[cfg(any(feature = "fs", feature = "net"))]
+        _ => super::fd_table::get_fd_object(fd)
+            .ok()
+            .map(|obj| poll_source(&obj, waiter)),
+        #[cfg(not(any(feature = "fs", feature = "net")))]
+        _ => None,
+    }
+}
+
+/// Whether `fd` can be passed to [`poll_fd`].
+fn is_valid_fd(fd: c_int) -> bool {
+    match fd {
+        0..=2 => true,
+        #[cfg(any(feature = "fs", feature = "net"))]
+        _ => super::fd_table::get_fd_object(fd).is_ok(),
+        #[cfg(not(any(feature = "fs", feature = "net")))]
+        _ => false,
+    }
+}
+
+const NFDBITS: usize = c_ulong::BITS as usize;
+
+fn fd_isset(set: &ctypes::fd_set, fd: usize) -> bool {
+    set.fds_bits[fd / NFDBITS] & (1 << (fd % NFDBITS)) != 0
+}
+
+fn fd_set(set: &mut ctypes::fd_set, fd: usize) {
+    set.fds_bits[fd / NFDBITS] |= 1 << (fd % NFDBITS);
+}
+
+/// Wait for some of the file descriptors in `fds` to become ready.
+///
+/// `timeout` is in milliseconds, a negative value means an infinite timeout.
+/// Return the number of ready file descriptors, or 0 if timed out.
+///
+/// The standard I/O, the opened files and the sockets can be polled, other
+/// file descriptors are reported with `POLLNVAL`.
+#[no_mangle]
+pub unsafe extern "C" fn ax_poll(
+    fds: *mut ctypes::pollfd,
+    nfds: ctypes::nfds_t,
+    timeout: c_int,
+) -> c_int {
+    debug!("ax_poll <= {:#x} {} {}", fds as usize, nfds, timeout);
+    ax_call_body!(ax_poll, {
+        let fds: &mut [ctypes::pollfd] = if nfds == 0 {
+            &mut [] // just sleep until timeout
+        } else if fds.is_null() {
+            return Err(LinuxError::EFAULT);
+        } else {
+            unsafe { core::slice::from_raw_parts_mut(fds, nfds as usize) }
+        };
+        let timeout = if timeout < 0 {
+            None
+        } else {
+            Some(Duration::from_millis(timeout as u64))
+        };
+
+        let count = io::wait_events(timeout, |waiter| {
+            let mut count = 0;
+            for pfd in fds.iter_mut() {
+                pfd.revents = 0;
+                if pfd.fd < 0 {
+                    continue; // ignored
+                }
+                let events = pfd.events as u32;
+                let revents = match poll_fd(pfd.fd, waiter) {
+                    Some(state) => {
+                        let state = state?;
+                        let mut revents = 0;
+                        if state.readable && events & ctypes::POLLIN != 0 {
+                            revents |= ctypes::POLLIN;
+                        }
+                        if state.writable && events & ctypes::POLLOUT != 0 {
+                            revents |= ctypes::POLLOUT;
+                        }
+                        if state.hangup {
+                            revents |= ctypes::POLLHUP;
+                        }
+                        revents
+                    }
+                    None => ctypes::POLLNVAL,
+                };
+                if revents != 0 {
+                    pfd.revents = revents as _;
+                    count += 1;
+                }
+            }
+            Ok(count)
+        })?;
+        Ok(count)
+    })
+}
+
+/// Wait for some of the file descriptors in `readfds` to become readable, or
+/// some in `writefds` to become writable.
+///
+/// The sets are modified in place to indicate which file descriptors are
+/// ready. `exceptfds` is always cleared as no exceptional conditions are
+/// supported. Return the total number of ready file descriptors in all sets.
+///
+/// The standard I/O, the opened files and the sockets can be selected, other
+/// file descriptors fail with `EBADF`.
+#[no_mangle]
+pub unsafe extern "C" fn ax_select(
+    nfds: c_int,
+    readfds: *mut ctypes::fd_set,
+    writefds: *mut ctypes::fd_set,
+    exceptfds: *mut ctypes::fd_set,
+    timeout: *mut ctypes::timeval,
+) -> c_int {
+    debug!(
+        "ax_select <= {} {:#x} {:#x} {:#x} {:#x}",
+        nfds, readfds as usize, writefds as usize, exceptfds as usize, timeout as usize
+    );
+    ax_call_body!(ax_select, {
+        if nfds < 0 || nfds as usize > ctypes::FD_SETSIZE as usize {
+            return Err(LinuxError::EINVAL);
+        }
+        let nfds = nfds as usize;
+        let timeout = if timeout.is_null() {
+            None
+        } else {
+            let tv = unsafe { *timeout };
+            if tv.tv_sec < 0 || tv.tv_usec < 0 {
+                return Err(LinuxError::EINVAL);
+            }
+            Some(Duration::from_secs(tv.tv_sec as u64) + Duration::from_micros(tv.tv_usec as u64))
+        };
+
+        let read_in = unsafe { readfds.as_ref() }.copied().unwrap_or_default();
+        let write_in = unsafe { writefds.as_ref() }.copied().unwrap_or_default();
+        for fd in 0..nfds {
+            if (fd_isset(&read_in, fd) || fd_isset(&write_in, fd)) && !is_valid_fd(fd as _) {
+                return Err(LinuxError::EBADF);
+            }
+        }
+
+        let mut read_out = ctypes::fd_set::default();
+        let mut write_out = ctypes::fd_set::default();
+        let count = io::wait_events(timeout, |waiter| {
+            let mut count = 0;
+            read_out = Default::default();
+            write_out = Default::default();
+            for fd in 0..nfds {
+                let (read, write) = (fd_isset(&read_in, fd), fd_isset(&write_in, fd));
+                if !read && !write {
+                    continue;
+                }
+                let state = poll_fd(fd as _, waiter).unwrap_or_else(|| Ok(PollState::default()))?;
+                if read && (state.readable || state.hangup) {
+                    fd_set(&mut read_out, fd);
+                    count += 1;
+                }
+                if write && state.writable {
+                    fd_set(&mut write_out, fd);
+                    count += 1;
+                }
+            }
+            Ok(count)
+        })?;
+
+        unsafe {
+            if let Some(readfds) = readfds.as_mut() {
+                *readfds = read_out;
+            }
+            if let Some(writefds) = writefds.as_mut() {
+                *writefds = write_out;
+            }
+            if let Some(exceptfds) = exceptfds.as_mut() {
+                *exceptfds = Default::default();
+            }
+        }
+        Ok(count)
+    })
+}
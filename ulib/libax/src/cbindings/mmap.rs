@@ -0,0 +1,133 @@
+use axerrno::{LinuxError, LinuxResult};
+use core::ffi::{c_int, c_void};
+
+use super::ctypes;
+use crate::debug;
+use crate::mem::{Backend, MappingFlags};
+
+/// Convert `PROT_*` to [`MappingFlags`].
+///
+/// Write-only pages are not supported by all architectures, so `PROT_WRITE`
+/// implies `PROT_READ`.
+fn prot_to_flags(prot: c_int) -> MappingFlags {
+    let prot = prot as u32;
+    let mut flags = MappingFlags::empty();
+    if prot & ctypes::PROT_READ != 0 {
+        flags |= MappingFlags::READ;
+    }
+    if prot & ctypes::PROT_WRITE != 0 {
+        flags |= MappingFlags::READ | MappingFlags::WRITE;
+    }
+    if prot & ctypes::PROT_EXEC != 0 {
+        flags |= MappingFlags::EXECUTE;
+    }
+    flags
+}
+
+#[cfg(feature = "fs")]
+mod file {
+    use alloc::sync::Arc;
+    use axerrno::AxResult;
+
+    use crate::fs::File;
+    use crate::io::{Read, Seek, SeekFrom};
+    use crate::mem::MmapFile;
+    use crate::sync::Mutex;
+
+    /// A file in the file descriptor table mapped by `mmap`.
+    pub struct MappedFile(pub Arc<Mutex<File>>);
+
+    impl MmapFile for MappedFile {
+        fn read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize> {
+            // the file position is shared with `read` and `write`, keep it
+            let mut file = self.0.lock();
+            let pos = file.seek(SeekFrom::Current(0))?;
+            file.seek(SeekFrom::Start(offset))?;
+            let ret = file.read(buf);
+            file.seek(SeekFrom::Start(pos))?;
+            ret
+        }
+    }
+}
+
+/// Map the file indicated by `fd` or anonymous memory into memory.
+///
+/// Return the start address of the mapping if success, or `-errno` cast to a
+/// pointer like the Linux system call.
+#[no_mangle]
+pub unsafe extern "C" fn ax_mmap(
+    addr: *mut c_void,
+    len: usize,
+    prot: c_int,
+    flags: c_int,
+    fd: c_int,
+    off: ctypes::off_t,
+) -> *mut c_void {
+    debug!(
+        "ax_mmap <= {:#x} {:#x} {:#x} {:#x} {} {:#x}",
+        addr as usize, len, prot, flags, fd, off
+    );
+    ax_call_body!(ax_mmap, {
+        let map_flags = flags as u32;
+        let map_type = map_flags & ctypes::MAP_TYPE;
+        if map_type != ctypes::MAP_SHARED && map_type != ctypes::MAP_PRIVATE {
+            return Err(LinuxError::EINVAL);
+        }
+        let prot = prot_to_flags(prot);
+        let backend = if map_flags & ctypes::MAP_ANONYMOUS != 0 {
+            // there is only one address space, shared anonymous memory is the
+            // same as private one
+            Backend::Alloc
+        } else {
+            if off < 0 || off as usize % axhal::mem::PAGE_SIZE_4K != 0 {
+                return Err(LinuxError::EINVAL);
+            }
+            // the changes can not be written back to the file
+            if map_type == ctypes::MAP_SHARED && prot.contains(MappingFlags::WRITE) {
+                return Err(LinuxError::ENODEV);
+            }
+            file_backend(fd, off as u64)?
+        };
+        let fixed = map_flags & ctypes::MAP_FIXED != 0;
+        let start = crate::mem::mmap(addr as *mut u8, len, prot, fixed, backend)?;
+        Ok(start)
+    })
+}
+
+#[cfg(feature = "fs")]
+fn file_backend(fd: c_int, offset: u64) -> LinuxResult<Backend> {
+    let file = super::fs::get_file_by_fd(fd)?;
+    Ok(Backend::File {
+        file: alloc::sync::Arc::new(file::MappedFile(file)),
+        offset,
+    })
+}
+
+#[cfg(not(feature = "fs"))]
+fn file_backend(_fd: c_int, _offset: u64) -> LinuxResult<Backend> {
+    Err(LinuxError::EBADF)
+}
+
+/// Unmap the memory starting at `addr`.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn ax_munmap(addr: *mut c_void, len: usize) -> c_int {
+    debug!("ax_munmap <= {:#x} {:#x}", addr as usize, len);
+    ax_call_body!(ax_munmap, {
+        crate::mem::munmap(addr as *mut u8, len)?;
+        Ok(0)
+    })
+}
+
+/// Set the protection of the memory starting at `addr`.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn ax_mprotect(addr: *mut c_void, len: usize, prot: c_int) -> c_int {
+    debug!("ax_mprotect <= {:#x} {:#x} {:#x}", addr as usize, len, prot);
+    ax_call_body!(ax_mprotect, {
+        crate::mem::mprotect(addr as *mut u8, len, prot_to_flags(prot))?;
+        Ok(0)
+    })
+}
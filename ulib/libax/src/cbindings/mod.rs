@@ -5,10 +5,17 @@
 #[macro_use]
 mod utils;
 
+#[cfg(any(feature = "fs", feature = "net"))]
+mod fd_table;
 #[cfg(feature = "fs")]
 mod fs;
+mod io_mpx;
 #[cfg(feature = "alloc")]
 mod malloc;
+#[cfg(feature = "paging")]
+mod mmap;
+#[cfg(feature = "net")]
+mod socket;
 
 /// cbindgen:ignore
 #[rustfmt::skip]
@@ -50,7 +57,19 @@ pub unsafe extern "C" fn ax_panic() -> ! {
 #[cfg(feature = "alloc")]
 pub use self::malloc::{ax_free, ax_malloc};
 
+#[cfg(feature = "paging")]
+pub use self::mmap::{ax_mmap, ax_mprotect, ax_munmap};
+
+pub use self::io_mpx::{ax_poll, ax_select};
+
+#[cfg(any(feature = "fs", feature = "net"))]
+pub use self::fd_table::{ax_close, ax_read, ax_write};
+
 #[cfg(feature = "fs")]
-pub use self::fs::{
-    ax_close, ax_fstat, ax_getcwd, ax_lseek, ax_lstat, ax_open, ax_read, ax_stat, ax_write,
+pub use self::fs::{ax_fstat, ax_getcwd, ax_lseek, ax_lstat, ax_open, ax_stat};
+
+#[cfg(feature = "net")]
+pub use self::socket::{
+    ax_accept, ax_bind, ax_connect, ax_getsockname, ax_listen, ax_recv, ax_send, ax_shutdown,
+    ax_socket,
 };
@@ -0,0 +1,238 @@
+use alloc::sync::Arc;
+use axerrno::{LinuxError, LinuxResult};
+use core::ffi::{c_int, c_void};
+use core::mem::size_of;
+
+use axnet::{IpAddr, Ipv4Addr, SocketAddr, TcpSocket};
+
+use super::ctypes;
+use super::fd_table::{add_fd_object, get_fd_object, FdObject};
+use crate::debug;
+use crate::sync::Mutex;
+
+/// Get the socket indicated by `fd`, return `ENOTSOCK` if it is not a socket.
+fn get_socket_by_fd(fd: c_int) -> LinuxResult<Arc<Mutex<TcpSocket>>> {
+    match get_fd_object(fd)? {
+        FdObject::Socket(socket) => Ok(socket),
+        #[allow(unreachable_patterns)]
+        _ => Err(LinuxError::ENOTSOCK),
+    }
+}
+
+/// Convert a C `sockaddr_in` to [`SocketAddr`].
+fn from_sockaddr(
+    addr: *const ctypes::sockaddr,
+    addrlen: ctypes::socklen_t,
+) -> LinuxResult<SocketAddr> {
+    if addr.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    if (addrlen as usize) < size_of::<ctypes::sockaddr_in>() {
+        return Err(LinuxError::EINVAL);
+    }
+    let addr = unsafe { *(addr as *const ctypes::sockaddr_in) };
+    if addr.sin_family as u32 != ctypes::AF_INET {
+        return Err(LinuxError::EAFNOSUPPORT);
+    }
+    // both are in network byte order
+    let ip = Ipv4Addr::from_bytes(&addr.sin_addr.s_addr.to_ne_bytes());
+    Ok(SocketAddr::new(
+        IpAddr::Ipv4(ip),
+        u16::from_be(addr.sin_port),
+    ))
+}
+
+/// Write [`SocketAddr`] to a C `sockaddr_in`, truncated to `*addrlen` bytes.
+///
+/// `*addrlen` is set to the size of `sockaddr_in`.
+fn into_sockaddr(
+    sockaddr: SocketAddr,
+    addr: *mut ctypes::sockaddr,
+    addrlen: *mut ctypes::socklen_t,
+) -> LinuxResult {
+    if addr.is_null() {
+        return Ok(()); // the address is not needed
+    }
+    if addrlen.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    let IpAddr::Ipv4(ip) = sockaddr.addr;
+    let sockaddr_in = ctypes::sockaddr_in {
+        sin_family: ctypes::AF_INET as _,
+        sin_port: sockaddr.port.to_be(),
+        sin_addr: ctypes::in_addr {
+            s_addr: u32::from_ne_bytes(ip.0),
+        },
+        ..Default::default()
+    };
+    let len = size_of::<ctypes::sockaddr_in>();
+    unsafe {
+        let copy_len = len.min(*addrlen as usize);
+        core::ptr::copy_nonoverlapping(
+            &sockaddr_in as *const _ as *const u8,
+            addr as *mut u8,
+            copy_len,
+        );
+        *addrlen = len as _;
+    }
+    Ok(())
+}
+
+/// Create a socket and insert it into the file descriptor table.
+///
+/// Only the TCP sockets (`AF_INET` and `SOCK_STREAM`) are supported.
+///
+/// Return its file descriptor.
+#[no_mangle]
+pub unsafe extern "C" fn ax_socket(domain: c_int, socktype: c_int, protocol: c_int) -> c_int {
+    debug!("ax_socket <= {} {} {}", domain, socktype, protocol);
+    ax_call_body!(ax_socket, {
+        if domain as u32 != ctypes::AF_INET {
+            return Err(LinuxError::EAFNOSUPPORT);
+        }
+        if socktype as u32 != ctypes::SOCK_STREAM
+            || (protocol != 0 && protocol as u32 != ctypes::IPPROTO_TCP)
+        {
+            return Err(LinuxError::EPROTONOSUPPORT);
+        }
+        let socket = Arc::new(Mutex::new(TcpSocket::new()));
+        add_fd_object(FdObject::Socket(socket))
+    })
+}
+
+/// Bind an address to the socket indicated by `fd`.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn ax_bind(
+    fd: c_int,
+    addr: *const ctypes::sockaddr,
+    addrlen: ctypes::socklen_t,
+) -> c_int {
+    debug!("ax_bind <= {} {:#x} {}", fd, addr as usize, addrlen);
+    ax_call_body!(ax_bind, {
+        let addr = from_sockaddr(addr, addrlen)?;
+        get_socket_by_fd(fd)?.lock().bind(addr)?;
+        Ok(0)
+    })
+}
+
+/// Connect the socket indicated by `fd` to the address `addr`.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn ax_connect(
+    fd: c_int,
+    addr: *const ctypes::sockaddr,
+    addrlen: ctypes::socklen_t,
+) -> c_int {
+    debug!("ax_connect <= {} {:#x} {}", fd, addr as usize, addrlen);
+    ax_call_body!(ax_connect, {
+        let addr = from_sockaddr(addr, addrlen)?;
+        get_socket_by_fd(fd)?.lock().connect(addr)?;
+        Ok(0)
+    })
+}
+
+/// Listen for connections on the socket indicated by `fd`.
+///
+/// `backlog` is ignored. Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn ax_listen(fd: c_int, backlog: c_int) -> c_int {
+    debug!("ax_listen <= {} {}", fd, backlog);
+    ax_call_body!(ax_listen, {
+        get_socket_by_fd(fd)?.lock().listen()?;
+        Ok(0)
+    })
+}
+
+/// Accept a connection on the listening socket indicated by `fd`, and write
+/// the address of the peer to `addr` if it is not null.
+///
+/// Return the file descriptor of the new connected socket.
+#[no_mangle]
+pub unsafe extern "C" fn ax_accept(
+    fd: c_int,
+    addr: *mut ctypes::sockaddr,
+    addrlen: *mut ctypes::socklen_t,
+) -> c_int {
+    debug!("ax_accept <= {} {:#x}", fd, addr as usize);
+    ax_call_body!(ax_accept, {
+        let socket = get_socket_by_fd(fd)?.lock().accept()?;
+        into_sockaddr(socket.peer_addr()?, addr, addrlen)?;
+        add_fd_object(FdObject::Socket(Arc::new(Mutex::new(socket))))
+    })
+}
+
+/// Send data through the connected socket indicated by `fd`.
+///
+/// `flags` is ignored. Return the sent size if success.
+#[no_mangle]
+pub unsafe extern "C" fn ax_send(
+    fd: c_int,
+    buf: *const c_void,
+    len: usize,
+    flags: c_int,
+) -> ctypes::ssize_t {
+    debug!("ax_send <= {} {:#x} {} {}", fd, buf as usize, len, flags);
+    ax_call_body!(ax_send, {
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let src = unsafe { core::slice::from_raw_parts(buf as *const u8, len) };
+        Ok(get_socket_by_fd(fd)?.lock().send(src)?)
+    })
+}
+
+/// Receive data from the connected socket indicated by `fd`.
+///
+/// `flags` is ignored. Return the received size if success, or 0 if the
+/// peer has closed the connection.
+#[no_mangle]
+pub unsafe extern "C" fn ax_recv(
+    fd: c_int,
+    buf: *mut c_void,
+    len: usize,
+    flags: c_int,
+) -> ctypes::ssize_t {
+    debug!("ax_recv <= {} {:#x} {} {}", fd, buf as usize, len, flags);
+    ax_call_body!(ax_recv, {
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let dst = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, len) };
+        Ok(get_socket_by_fd(fd)?.lock().recv(dst)?)
+    })
+}
+
+/// Shut down the connection of the socket indicated by `fd`.
+///
+/// Both directions are shut down whatever `how` is. Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn ax_shutdown(fd: c_int, how: c_int) -> c_int {
+    debug!("ax_shutdown <= {} {}", fd, how);
+    ax_call_body!(ax_shutdown, {
+        get_socket_by_fd(fd)?.lock().shutdown()?;
+        Ok(0)
+    })
+}
+
+/// Get the local address of the socket indicated by `fd`.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn ax_getsockname(
+    fd: c_int,
+    addr: *mut ctypes::sockaddr,
+    addrlen: *mut ctypes::socklen_t,
+) -> c_int {
+    debug!("ax_getsockname <= {} {:#x}", fd, addr as usize);
+    ax_call_body!(ax_getsockname, {
+        if addr.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let local_addr = get_socket_by_fd(fd)?.lock().local_addr()?;
+        into_sockaddr(local_addr, addr, addrlen)?;
+        Ok(0)
+    })
+}
@@ -1,15 +1,36 @@
 //! Graphics manipulation operations.
+//!
+//! The framebuffer is taken by [`framebuffer`], and after drawing on it, the
+//! changed part returned by [`Framebuffer::take_damage`] is flushed to the
+//! screen by [`framebuffer_flush_rect`].
+//!
+//! The framebuffer also implements the `DrawTarget` trait of
+//! [embedded-graphics](https://docs.rs/embedded-graphics), with `Rgb888`
+//! colors.
 
-use axdisplay;
+use crate::io;
 
-pub use axdisplay::DisplayInfo;
+pub use axdisplay::{Color, DisplayInfo, Framebuffer, PixelFormat, Rect};
 
 /// Returns the framebuffer information.
 pub fn framebuffer_info() -> DisplayInfo {
     axdisplay::framebuffer_info()
 }
 
-/// Flushes the framebuffer.
-pub fn framebuffer_flush() -> isize {
+/// Takes the framebuffer.
+///
+/// Returns `None` if there is no display, or the framebuffer has already
+/// been taken.
+pub fn framebuffer() -> Option<Framebuffer<'static>> {
+    axdisplay::framebuffer()
+}
+
+/// Flushes the whole framebuffer to the screen.
+pub fn framebuffer_flush() -> io::Result {
     axdisplay::framebuffer_flush()
 }
+
+/// Flushes the pixels in `rect` of the framebuffer to the screen.
+pub fn framebuffer_flush_rect(rect: Rect) -> io::Result {
+    axdisplay::framebuffer_flush_rect(rect)
+}
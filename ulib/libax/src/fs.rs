@@ -1,5 +1,18 @@
 //! Filesystem manipulation operations.
 
+use crate::io::{PollState, Pollable, Result};
+
 pub use axfs::api::{canonicalize, metadata, read, read_to_string, remove_file, write};
 pub use axfs::api::{create_dir, create_dir_all, read_dir, remove_dir};
 pub use axfs::api::{DirEntry, File, FileType, Metadata, OpenOptions, Permissions, ReadDir};
+
+/// Regular files are always ready to be read or written, like on Linux.
+impl Pollable for File {
+    fn poll(&self) -> Result<PollState> {
+        Ok(PollState {
+            readable: true,
+            writable: true,
+            hangup: false,
+        })
+    }
+}
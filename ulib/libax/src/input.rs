@@ -0,0 +1,21 @@
+//! Input events from keyboards, mice and other input devices.
+
+pub use axinput::{EventType, InputEvent};
+
+/// The number of input devices.
+pub fn num_devices() -> usize {
+    axinput::num_devices()
+}
+
+/// Takes the next pending event of any device, along with the index of the
+/// device, or returns `None` if there is none.
+pub fn read_event() -> Option<(usize, InputEvent)> {
+    axinput::read_event()
+}
+
+/// Returns whether the device at `dev_idx` may generate events of
+/// `event_type`, e.g., [`EventType::Key`] for keyboards and
+/// [`EventType::Relative`] for mice.
+pub fn has_event_type(dev_idx: usize, event_type: EventType) -> bool {
+    axinput::has_event_type(dev_idx, event_type)
+}
@@ -0,0 +1,55 @@
+//! Character devices other than the console, e.g., virtio-console ports.
+
+use crate::io::{prelude::*, Result};
+
+/// A handle to a character device port.
+pub struct CharDevice {
+    port: usize,
+}
+
+impl CharDevice {
+    /// The port number of the device.
+    pub fn port(&self) -> usize {
+        self.port
+    }
+}
+
+impl Read for CharDevice {
+    // Block until at least one byte is read.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        loop {
+            let read_len = axchar::read(self.port, buf)?;
+            if buf.is_empty() || read_len > 0 {
+                return Ok(read_len);
+            }
+            crate::task::yield_now();
+        }
+    }
+}
+
+impl Write for CharDevice {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        axchar::write(self.port, buf)
+    }
+    fn flush(&mut self) -> Result {
+        Ok(())
+    }
+}
+
+/// The number of character device ports.
+pub fn num_char_devices() -> usize {
+    axchar::num_ports()
+}
+
+/// Opens the character device at `port`, or returns `None` if it does not
+/// exist.
+///
+/// Port 0 is also used as an alternate console, which shares the input and
+/// output with [`stdin`](super::stdin) and [`stdout`](super::stdout).
+pub fn char_device(port: usize) -> Option<CharDevice> {
+    if port < axchar::num_ports() {
+        Some(CharDevice { port })
+    } else {
+        None
+    }
+}
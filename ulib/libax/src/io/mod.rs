@@ -1,8 +1,20 @@
 //! Traits, helpers, and type definitions for core I/O functionality.
 
+mod poll;
 mod stdio;
 
+#[cfg(feature = "char")]
+mod chardev;
+
 pub use axio::prelude;
 pub use axio::{BufRead, BufReader, Error, Read, Result, Seek, SeekFrom, Write};
 
+pub use self::poll::{poll, PollFd, PollState, PollWaiter, Pollable};
+pub(crate) use self::poll::wait_events;
 pub use self::stdio::{stdin, stdout, Stdin, Stdout, __print_impl};
+
+#[cfg(feature = "alloc")]
+pub use self::poll::select;
+
+#[cfg(feature = "char")]
+pub use self::chardev::{char_device, num_char_devices, CharDevice};
@@ -0,0 +1,225 @@
+//! Waiting for readiness on multiple I/O objects at once.
+
+use core::cell::Cell;
+use core::time::Duration;
+
+use super::Result;
+
+pub use axio::PollState;
+
+/// How often to check the I/O objects that cannot notify their readiness
+/// (e.g., the console).
+const BUSY_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// I/O objects whose readiness can be queried without blocking.
+pub trait Pollable {
+    /// Returns the current readiness of this object.
+    fn poll(&self) -> Result<PollState>;
+
+    /// Registers `waiter` to be woken up when the readiness of this object
+    /// changes, returns `false` if this object cannot notify its readiness.
+    ///
+    /// The registration is one-shot, it is renewed before every check.
+    fn register_waiter(&self, _waiter: &PollWaiter) -> bool {
+        false
+    }
+}
+
+/// Wakes up the task blocked in [`poll`] or [`select`] when the readiness of
+/// the objects it waits on changes.
+pub struct PollWaiter {
+    #[cfg(feature = "net")]
+    pub(crate) inner: alloc::sync::Arc<axnet::SocketWaiter>,
+    /// Whether some objects to check cannot notify their readiness.
+    busy_poll: Cell<bool>,
+}
+
+impl PollWaiter {
+    fn new() -> Self {
+        Self {
+            #[cfg(feature = "net")]
+            inner: axnet::SocketWaiter::new(),
+            busy_poll: Cell::new(false),
+        }
+    }
+
+    /// Registers this waiter to `source` before checking its readiness.
+    ///
+    /// If `source` cannot notify its readiness, the waiting task will check
+    /// it periodically.
+    pub(crate) fn register(&self, source: &dyn Pollable) {
+        if !source.register_waiter(self) {
+            self.busy_poll.set(true);
+        }
+    }
+}
+
+/// An I/O object to be polled, with the events of interest and the returned
+/// events.
+pub struct PollFd<'a> {
+    /// The object to poll.
+    pub source: &'a dyn Pollable,
+    /// The events of interest, `hangup` is ignored as it is always reported.
+    pub events: PollState,
+    /// The events occurred, filled by [`poll`].
+    pub revents: PollState,
+}
+
+impl<'a> PollFd<'a> {
+    /// Creates a new `PollFd` waiting for `source` to be readable and/or
+    /// writable.
+    pub fn new(source: &'a dyn Pollable, readable: bool, writable: bool) -> Self {
+        Self {
+            source,
+            events: PollState {
+                readable,
+                writable,
+                hangup: false,
+            },
+            revents: PollState::default(),
+        }
+    }
+
+    fn update(&mut self, waiter: &PollWaiter) -> Result<bool> {
+        waiter.register(self.source);
+        let state = self.source.poll()?;
+        self.revents = PollState {
+            readable: self.events.readable && state.readable,
+            writable: self.events.writable && state.writable,
+            hangup: state.hangup,
+        };
+        Ok(self.revents != PollState::default())
+    }
+}
+
+/// Waits for some of the objects in `fds` to become ready, like `poll(2)`.
+///
+/// The events occurred are stored in [`PollFd::revents`], and the number of
+/// ready objects is returned. If `timeout` expires first, returns 0. A zero
+/// `timeout` checks the objects and returns immediately, and `None` blocks
+/// infinitely.
+pub fn poll(fds: &mut [PollFd], timeout: Option<Duration>) -> Result<usize> {
+    wait_events(timeout, |waiter| {
+        let mut count = 0;
+        for fd in fds.iter_mut() {
+            if fd.update(waiter)? {
+                count += 1;
+            }
+        }
+        Ok(count)
+    })
+}
+
+/// Waits for some of the objects in `readfds` to become readable, or some of
+/// the objects in `writefds` to become writable, like `select(2)`.
+///
+/// On return, the objects that are not ready are cleared (set to `None`) from
+/// both sets, and the total number of ready objects is returned. If `timeout`
+/// expires first, returns 0 with both sets cleared. A hangup object is
+/// reported as readable.
+#[cfg(feature = "alloc")]
+pub fn select<'a>(
+    readfds: &mut [Option<&'a dyn Pollable>],
+    writefds: &mut [Option<&'a dyn Pollable>],
+    timeout: Option<Duration>,
+) -> Result<usize> {
+    fn is_ready(fd: &dyn Pollable, write: bool, waiter: &PollWaiter) -> Result<bool> {
+        waiter.register(fd);
+        let state = fd.poll()?;
+        Ok(if write {
+            state.writable
+        } else {
+            state.readable || state.hangup
+        })
+    }
+
+    // the readiness of the objects in the last round, with which the sets
+    // are updated, so they agree with the returned count
+    let mut ready = alloc::vec![false; readfds.len() + writefds.len()];
+    let count = wait_events(timeout, |waiter| {
+        let fds = readfds.iter().map(|fd| (fd, false));
+        let fds = fds.chain(writefds.iter().map(|fd| (fd, true)));
+        for ((fd, write), ready) in fds.zip(ready.iter_mut()) {
+            *ready = match fd {
+                Some(fd) => is_ready(*fd, write, waiter)?,
+                None => false,
+            };
+        }
+        Ok(ready.iter().filter(|&&ready| ready).count())
+    })?;
+
+    let fds = readfds.iter_mut().chain(writefds.iter_mut());
+    for (fd, ready) in fds.zip(ready) {
+        if !ready {
+            *fd = None;
+        }
+    }
+    Ok(count)
+}
+
+/// Calls `f` repeatedly until it returns a non-zero count of ready objects,
+/// or `timeout` expires.
+///
+/// `f` registers the given waiter to the objects before checking them.
+/// Between two calls, the current task sleeps until the waiter is woken up.
+/// If some objects cannot notify their readiness, it sleeps at most
+/// [`BUSY_POLL_INTERVAL`].
+pub(crate) fn wait_events<F>(timeout: Option<Duration>, mut f: F) -> Result<usize>
+where
+    F: FnMut(&PollWaiter) -> Result<usize>,
+{
+    let deadline = timeout.map(|dur| axhal::time::current_time() + dur);
+    let waiter = PollWaiter::new();
+    loop {
+        #[cfg(feature = "net")]
+        let token = {
+            let token = waiter.inner.prepare();
+            axnet::poll_interfaces();
+            token
+        };
+        waiter.busy_poll.set(false);
+        let count = f(&waiter)?;
+        if count > 0 {
+            return Ok(count);
+        }
+
+        let mut delay = if waiter.busy_poll.get() {
+            Some(BUSY_POLL_INTERVAL)
+        } else {
+            None
+        };
+        if let Some(deadline) = deadline {
+            let now = axhal::time::current_time();
+            if now >= deadline {
+                return Ok(0);
+            }
+            let remain = deadline - now;
+            delay = Some(delay.map_or(remain, |d| d.min(remain)));
+        }
+
+        #[cfg(feature = "net")]
+        waiter.inner.wait(token, delay);
+        #[cfg(all(not(feature = "net"), feature = "multitask"))]
+        match delay {
+            Some(dur) => crate::task::sleep(dur),
+            None => crate::task::yield_now(),
+        }
+        #[cfg(all(not(feature = "net"), not(feature = "multitask")))]
+        {
+            let _ = delay;
+            crate::task::yield_now(); // wait for the next IRQ
+        }
+    }
+}
+
+#[cfg(feature = "fs")]
+impl Pollable for crate::fs::File {
+    /// Regular files are always ready.
+    fn poll(&self) -> Result<PollState> {
+        Ok(PollState {
+            readable: true,
+            writable: true,
+            hangup: false,
+        })
+    }
+}
@@ -1,7 +1,7 @@
 #[cfg(feature = "alloc")]
 use alloc::string::String;
 
-use crate::io::{prelude::*, BufReader, Result};
+use crate::io::{prelude::*, BufReader, PollState, Pollable, Result};
 use crate::sync::Mutex;
 
 struct StdinRaw;
@@ -17,9 +17,22 @@ pub struct Stdout {
     inner: &'static Mutex<StdoutRaw>,
 }
 
+/// The port of the character device used as an alternate console.
+#[cfg(feature = "char")]
+const ALT_CONSOLE_PORT: usize = 0;
+
 impl StdinRaw {
     fn getchar() -> Option<u8> {
-        axhal::console::getchar().map(|c| if c == b'\r' { b'\n' } else { c })
+        let c = axhal::console::getchar();
+        #[cfg(feature = "char")]
+        let c = c.or_else(|| {
+            let mut c = 0;
+            match axchar::read(ALT_CONSOLE_PORT, core::slice::from_mut(&mut c)) {
+                Ok(1) => Some(c),
+                _ => None,
+            }
+        });
+        c.map(|c| if c == b'\r' { b'\n' } else { c })
     }
 }
 
@@ -42,6 +55,11 @@ impl Read for StdinRaw {
 impl Write for StdoutRaw {
     fn write(&mut self, buf: &[u8]) -> Result<usize> {
         axhal::console::write_bytes(buf);
+        #[cfg(feature = "char")]
+        if axchar::num_ports() > ALT_CONSOLE_PORT {
+            // the output is mirrored, ignore errors of the alternate console
+            axchar::write(ALT_CONSOLE_PORT, buf).ok();
+        }
         Ok(buf.len())
     }
     fn flush(&mut self) -> Result {
@@ -84,6 +102,26 @@ impl Write for Stdout {
     }
 }
 
+impl Pollable for Stdin {
+    fn poll(&self) -> Result<PollState> {
+        Ok(PollState {
+            readable: self.inner.lock().has_data_left()?,
+            writable: false,
+            hangup: false,
+        })
+    }
+}
+
+impl Pollable for Stdout {
+    fn poll(&self) -> Result<PollState> {
+        Ok(PollState {
+            readable: false,
+            writable: true,
+            hangup: false,
+        })
+    }
+}
+
 /// Constructs a new handle to the standard input of the current process.
 pub fn stdin() -> Stdin {
     static INSTANCE: Mutex<BufReader<StdinRaw>> = Mutex::new(BufReader::new(StdinRaw));
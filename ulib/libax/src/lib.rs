@@ -24,6 +24,9 @@ pub mod sync;
 pub mod task;
 pub mod time;
 
+#[cfg(feature = "alloc")]
+pub mod mem;
+
 #[cfg(feature = "fs")]
 pub mod fs;
 
@@ -33,5 +36,8 @@ pub mod net;
 #[cfg(feature = "display")]
 pub mod display;
 
+#[cfg(feature = "input")]
+pub mod input;
+
 #[cfg(feature = "cbindings")]
 pub mod cbindings;
@@ -0,0 +1,46 @@
+//! Virtual memory mappings.
+//!
+//! The pages mapped by [`mmap`] are allocated one by one on the first access,
+//! instead of being contiguous physical memory like the heap, so it suits
+//! large buffers.
+
+use crate::io;
+
+pub use axmm::{Backend, GlobalPage, MappingFlags, MmapFile};
+
+/// Maps `len` bytes of the pages from `backend` with the mapping `flags`.
+///
+/// If `fixed` is true, they are mapped at `addr` exactly, replacing the
+/// existing mappings there. Otherwise `addr` is a hint (`0` for none).
+///
+/// Returns the start address of the mapping.
+pub fn mmap(
+    addr: *mut u8,
+    len: usize,
+    flags: MappingFlags,
+    fixed: bool,
+    backend: Backend,
+) -> io::Result<*mut u8> {
+    axmm::mmap((addr as usize).into(), len, flags, fixed, backend).map(|va| va.as_mut_ptr())
+}
+
+/// Unmaps `len` bytes starting at `addr`, which must be page-aligned.
+pub fn munmap(addr: *mut u8, len: usize) -> io::Result {
+    axmm::munmap((addr as usize).into(), len)
+}
+
+/// Changes the mapping flags of `len` bytes starting at `addr`, which must be
+/// page-aligned.
+pub fn mprotect(addr: *mut u8, len: usize, flags: MappingFlags) -> io::Result {
+    axmm::mprotect((addr as usize).into(), len, flags)
+}
+
+/// Maps a copy-on-write snapshot of `len` bytes starting at `addr`, which must
+/// be page-aligned and mapped by [`mmap`].
+///
+/// The pages are copied only when the snapshot or the original is written.
+/// Returns the start address of the snapshot, which can be unmapped by
+/// [`munmap`].
+pub fn snapshot(addr: *mut u8, len: usize) -> io::Result<*mut u8> {
+    axmm::snapshot((addr as usize).into(), len).map(|va| va.as_mut_ptr())
+}
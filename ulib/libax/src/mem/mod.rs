@@ -0,0 +1,37 @@
+//! Memory management: heap statistics and virtual memory mappings.
+
+#[cfg(feature = "paging")]
+mod mapping;
+
+#[cfg(feature = "paging")]
+pub use self::mapping::*;
+
+pub use axalloc::{AllocStats, SizeClassStats};
+
+#[cfg(feature = "alloc-debug")]
+pub use axalloc::{ALLOC_POISON, FREE_POISON};
+
+/// Returns a snapshot of the heap allocator statistics, including the usage
+/// of each size class, the peak usage, failed allocations and fragmentation.
+pub fn stats() -> AllocStats {
+    axalloc::global_stats()
+}
+
+/// Prints the live heap allocations with their sizes and the addresses of
+/// the allocation calls, and returns the number of them.
+///
+/// They are also printed when the main function returns.
+#[cfg(feature = "alloc-debug")]
+pub fn dump_live_allocations() -> usize {
+    axalloc::global_allocator().dump_live_allocations()
+}
+
+/// Returns the address of the allocation call of the live heap allocation
+/// at `ptr`, or `None` if it is not allocated.
+///
+/// The address is 0 if the kernel is built without the frame pointers, which
+/// are only forced by `make` for the `alloc-debug` builds.
+#[cfg(feature = "alloc-debug")]
+pub fn allocation_caller(ptr: *const u8) -> Option<usize> {
+    axalloc::global_allocator().allocation_caller(ptr as usize)
+}
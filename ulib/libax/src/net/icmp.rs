@@ -0,0 +1,68 @@
+use core::time::Duration;
+
+use crate::io::{self, PollState, PollWaiter, Pollable};
+
+use axnet::IpAddr;
+
+/// An ICMP socket, to send and receive ICMP packets (e.g., echo requests and
+/// replies).
+///
+/// The data sent and received are whole ICMP packets, including the ICMP
+/// header. The checksum of the outgoing packets is filled automatically.
+pub struct IcmpSocket {
+    socket: axnet::IcmpSocket,
+}
+
+impl IcmpSocket {
+    /// Creates an ICMP socket bound to the identifier `ident`.
+    ///
+    /// Only the echo replies with the same identifier, and the ICMP errors
+    /// caused by the packets sent from this socket are received.
+    pub fn bind(ident: u16) -> io::Result<Self> {
+        let socket = axnet::IcmpSocket::new();
+        socket.bind(ident)?;
+        Ok(Self { socket })
+    }
+
+    /// Sends an ICMP packet to the given address, returns the number of bytes
+    /// sent.
+    pub fn send_to(&self, buf: &[u8], addr: IpAddr) -> io::Result<usize> {
+        self.socket.send_to(buf, addr)
+    }
+
+    /// Receives an ICMP packet, returns the number of bytes read and the
+    /// address of the sender.
+    pub fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, IpAddr)> {
+        self.socket.recv_from(buf)
+    }
+
+    /// Moves this socket into or out of non-blocking mode.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result {
+        self.socket.set_nonblocking(nonblocking)
+    }
+
+    /// Sets the read timeout to the timeout specified.
+    ///
+    /// If the value specified is `None`, then [`recv_from`](Self::recv_from)
+    /// will block indefinitely. An error is returned if the zero duration is
+    /// passed.
+    pub fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result {
+        self.socket.set_read_timeout(dur)
+    }
+
+    /// Returns the read timeout of this socket.
+    pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        self.socket.read_timeout()
+    }
+}
+
+impl Pollable for IcmpSocket {
+    fn poll(&self) -> io::Result<PollState> {
+        self.socket.poll()
+    }
+
+    fn register_waiter(&self, waiter: &PollWaiter) -> bool {
+        self.socket.subscribe(&waiter.inner);
+        true
+    }
+}
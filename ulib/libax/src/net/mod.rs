@@ -1,6 +1,10 @@
 //! Networking primitives for TCP/UDP communication.
 
+mod icmp;
+mod raw;
 mod tcp;
 
+pub use self::icmp::IcmpSocket;
+pub use self::raw::RawSocket;
 pub use self::tcp::{TcpListener, TcpStream};
 pub use axnet::{IpAddr, Ipv4Addr, SocketAddr};
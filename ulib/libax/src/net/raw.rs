@@ -0,0 +1,61 @@
+use core::time::Duration;
+
+use crate::io::{self, PollState, PollWaiter, Pollable};
+
+/// A raw IPv4 socket, to send and receive the IP packets of a specific
+/// protocol.
+///
+/// The data sent and received are whole IP packets, including the IP header.
+/// The checksum of the outgoing IP headers is filled automatically.
+pub struct RawSocket {
+    socket: axnet::RawSocket,
+}
+
+impl RawSocket {
+    /// Creates a raw socket for the IP protocol number `protocol` (e.g., 1 for
+    /// ICMP).
+    pub fn new(protocol: u8) -> io::Result<Self> {
+        Ok(Self {
+            socket: axnet::RawSocket::new(protocol),
+        })
+    }
+
+    /// Sends an IP packet, returns the number of bytes sent.
+    pub fn send(&self, packet: &[u8]) -> io::Result<usize> {
+        self.socket.send(packet)
+    }
+
+    /// Receives an IP packet, returns the number of bytes read.
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.socket.recv(buf)
+    }
+
+    /// Moves this socket into or out of non-blocking mode.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result {
+        self.socket.set_nonblocking(nonblocking)
+    }
+
+    /// Sets the read timeout to the timeout specified.
+    ///
+    /// If the value specified is `None`, then [`recv`](Self::recv) will block
+    /// indefinitely. An error is returned if the zero duration is passed.
+    pub fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result {
+        self.socket.set_read_timeout(dur)
+    }
+
+    /// Returns the read timeout of this socket.
+    pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        self.socket.read_timeout()
+    }
+}
+
+impl Pollable for RawSocket {
+    fn poll(&self) -> io::Result<PollState> {
+        self.socket.poll()
+    }
+
+    fn register_waiter(&self, waiter: &PollWaiter) -> bool {
+        self.socket.subscribe(&waiter.inner);
+        true
+    }
+}
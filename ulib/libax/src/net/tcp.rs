@@ -1,4 +1,6 @@
-use crate::io::{self, prelude::*};
+use core::time::Duration;
+
+use crate::io::{self, prelude::*, PollState, PollWaiter, Pollable};
 
 use axnet::{SocketAddr, TcpSocket};
 
@@ -20,6 +22,20 @@ impl TcpStream {
         Ok(Self { socket })
     }
 
+    /// Opens a TCP connection to a remote host, with the given sizes of the
+    /// receive and send buffers.
+    pub fn connect_with_buffer_size(
+        addr: SocketAddr,
+        recv_buf_size: usize,
+        send_buf_size: usize,
+    ) -> io::Result<Self> {
+        let mut socket = TcpSocket::new();
+        socket.set_recv_buffer_size(recv_buf_size)?;
+        socket.set_send_buffer_size(send_buf_size)?;
+        socket.connect(addr)?;
+        Ok(Self { socket })
+    }
+
     /// Returns the socket address of the local half of this TCP connection.
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
         self.socket.local_addr()
@@ -34,6 +50,73 @@ impl TcpStream {
     pub fn shutdown(&self) -> io::Result {
         self.socket.shutdown()
     }
+
+    /// Moves this TCP stream into or out of non-blocking mode.
+    ///
+    /// In non-blocking mode, reads and writes return the `Again` error if the
+    /// operation cannot complete immediately.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result {
+        self.socket.set_nonblocking(nonblocking)
+    }
+
+    /// Sets the read timeout to the timeout specified.
+    ///
+    /// If the value specified is `None`, then read calls will block
+    /// indefinitely. An error is returned if the zero duration is passed.
+    pub fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result {
+        self.socket.set_read_timeout(dur)
+    }
+
+    /// Returns the read timeout of this socket.
+    pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        self.socket.read_timeout()
+    }
+
+    /// Sets the write timeout to the timeout specified.
+    ///
+    /// If the value specified is `None`, then write calls will block
+    /// indefinitely. An error is returned if the zero duration is passed.
+    pub fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result {
+        self.socket.set_write_timeout(dur)
+    }
+
+    /// Returns the write timeout of this socket.
+    pub fn write_timeout(&self) -> io::Result<Option<Duration>> {
+        self.socket.write_timeout()
+    }
+
+    /// Sets the value of the `TCP_NODELAY` option on this socket.
+    ///
+    /// If set, this option disables the Nagle algorithm.
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result {
+        self.socket.set_nodelay(nodelay)
+    }
+
+    /// Gets the value of the `TCP_NODELAY` option on this socket.
+    pub fn nodelay(&self) -> io::Result<bool> {
+        self.socket.nodelay()
+    }
+
+    /// Sets the interval of TCP keep-alive packets, `None` disables
+    /// keep-alive.
+    pub fn set_keepalive(&self, interval: Option<Duration>) -> io::Result {
+        self.socket.set_keepalive(interval)
+    }
+
+    /// Returns the interval of TCP keep-alive packets.
+    pub fn keepalive(&self) -> io::Result<Option<Duration>> {
+        self.socket.keepalive()
+    }
+
+    /// Returns the size of the receive buffer in bytes.
+    pub fn recv_buffer_size(&self) -> usize {
+        self.socket.recv_buffer_size()
+    }
+
+    /// Returns the size of the send buffer in bytes.
+    pub fn send_buffer_size(&self) -> usize {
+        self.socket.send_buffer_size()
+    }
 }
 
 impl Read for TcpStream {
@@ -52,6 +135,17 @@ impl Write for TcpStream {
     }
 }
 
+impl Pollable for TcpStream {
+    fn poll(&self) -> io::Result<PollState> {
+        self.socket.poll()
+    }
+
+    fn register_waiter(&self, waiter: &PollWaiter) -> bool {
+        self.socket.subscribe(&waiter.inner);
+        true
+    }
+}
+
 impl TcpListener {
     /// Creates a new `TcpListener` which will be bound to the specified
     /// address.
@@ -62,6 +156,22 @@ impl TcpListener {
         Ok(Self { socket })
     }
 
+    /// Creates a new `TcpListener` which will be bound to the specified
+    /// address, and the accepted streams will have the given sizes of the
+    /// receive and send buffers.
+    pub fn bind_with_buffer_size(
+        addr: SocketAddr,
+        recv_buf_size: usize,
+        send_buf_size: usize,
+    ) -> io::Result<Self> {
+        let mut socket = TcpSocket::new();
+        socket.set_recv_buffer_size(recv_buf_size)?;
+        socket.set_send_buffer_size(send_buf_size)?;
+        socket.bind(addr)?;
+        socket.listen()?;
+        Ok(Self { socket })
+    }
+
     /// Returns the local socket address of this listener.
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
         self.socket.local_addr()
@@ -77,4 +187,34 @@ impl TcpListener {
         let addr = socket.peer_addr()?;
         Ok((TcpStream { socket }, addr))
     }
+
+    /// Moves this TCP listener into or out of non-blocking mode.
+    ///
+    /// In non-blocking mode, [`accept`](Self::accept) returns the `Again`
+    /// error if there is no pending connection. The accepted streams are
+    /// always in blocking mode.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result {
+        self.socket.set_nonblocking(nonblocking)
+    }
+
+    /// Sets the value of the `TCP_NODELAY` option of the accepted streams.
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result {
+        self.socket.set_nodelay(nodelay)
+    }
+
+    /// Sets the keep-alive interval of the accepted streams.
+    pub fn set_keepalive(&self, interval: Option<Duration>) -> io::Result {
+        self.socket.set_keepalive(interval)
+    }
+}
+
+impl Pollable for TcpListener {
+    fn poll(&self) -> io::Result<PollState> {
+        self.socket.poll()
+    }
+
+    fn register_waiter(&self, waiter: &PollWaiter) -> bool {
+        self.socket.subscribe(&waiter.inner);
+        true
+    }
 }
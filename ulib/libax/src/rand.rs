@@ -1,17 +1,52 @@
 //! Random number generator.
+//!
+//! With the `rng` feature, a hardware entropy source (e.g., virtio-rng) is
+//! used to seed the pseudo random generator, and can be read directly by
+//! [`fill_bytes`].
 
 use core::sync::atomic::{AtomicU64, Ordering::SeqCst};
 
 static SEED: AtomicU64 = AtomicU64::new(0xa2ce_a2ce);
 
+#[cfg(feature = "rng")]
+static SEEDED: core::sync::atomic::AtomicBool = core::sync::atomic::AtomicBool::new(false);
+
 /// Sets the seed for the random number generator.
 pub fn srand(seed: u32) {
+    #[cfg(feature = "rng")]
+    SEEDED.store(true, SeqCst);
     SEED.store(seed.wrapping_sub(1) as u64, SeqCst);
 }
 
 /// Returns a 32-bit unsigned pseudo random interger.
+///
+/// If [`srand`] is never called, the generator is seeded from the hardware
+/// entropy source on the first call when it is available.
 pub fn rand_u32() -> u32 {
+    #[cfg(feature = "rng")]
+    if !SEEDED.swap(true, SeqCst) {
+        let mut seed = [0; 8];
+        if axrng::fill_bytes(&mut seed) == seed.len() {
+            SEED.store(u64::from_ne_bytes(seed), SeqCst);
+        }
+    }
     let new_seed = SEED.load(SeqCst).wrapping_mul(6364136223846793005) + 1;
     SEED.store(new_seed, SeqCst);
     (new_seed >> 33) as u32
 }
+
+/// Fills `buf` with random bytes.
+///
+/// The bytes come from the hardware entropy source if it is available,
+/// otherwise (or if the device cannot provide enough bytes) from
+/// [`rand_u32`].
+pub fn fill_bytes(buf: &mut [u8]) {
+    #[cfg(feature = "rng")]
+    let buf = {
+        let filled = axrng::fill_bytes(buf);
+        &mut buf[filled..]
+    };
+    for chunk in buf.chunks_mut(4) {
+        chunk.copy_from_slice(&rand_u32().to_ne_bytes()[..chunk.len()]);
+    }
+}